@@ -0,0 +1,217 @@
+//! Benchmarks of detection, decoding and preprocessing.
+//!
+//! Run them with `cargo bench --features test-util`. The test images are
+//! rendered from a fixed set of payloads, so they are identical on every
+//! machine, and no image files are needed.
+
+#[macro_use]
+extern crate criterion;
+extern crate quirs;
+
+use std::slice;
+use criterion::{ Criterion, BenchmarkId, BatchSize, Throughput, black_box };
+use quirs::{ Decoder, Encoder, ImageBuf, Vec2D };
+use quirs::info::EccLevel;
+use quirs::testutil::{ rasterize, Raster };
+use quirs::testutil::{ rgb_to_luma, rgb_to_luma_scalar, binarize, binarize_scalar };
+use quirs::transform::Preprocessed;
+
+/// The payloads of the test codes, from a small version to a large one.
+const PAYLOADS: [&str; 3] = [
+    "https://example.com/",
+    "WIFI:T:WPA;S:quirs-benchmark;P:correct horse battery staple;H:false;;",
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+     tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim \
+     veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea \
+     commodo consequat. Duis aute irure dolor in reprehenderit in voluptate.",
+];
+
+/// The sizes of the frames in which a single code is detected.
+const FRAME_SIZES: [(usize, usize); 4] = [(320, 240), (640, 480), (1280, 720), (1920, 1080)];
+
+/// The numbers of codes in a frame of `CROWDED_FRAME_SIZE`.
+const CODES_PER_FRAME: [usize; 4] = [1, 2, 4, 8];
+
+/// The size of the frames with several codes.
+const CROWDED_FRAME_SIZE: (usize, usize) = (1280, 720);
+
+/// The size of the frames which are preprocessed.
+const PREPROCESSED_FRAME_SIZE: (usize, usize) = (1920, 1080);
+
+/// Renders the code of the `index`th payload with 3 pixels per module and
+/// a little noise.
+fn code_image(index: usize) -> ImageBuf {
+    let code = Encoder::new(EccLevel::M)
+        .encode(PAYLOADS[index % PAYLOADS.len()].as_bytes())
+        .expect("can't encode payload");
+    let raster = Raster { scale: 3.0, noise: 24, seed: index as u64 + 1, ..Raster::default() };
+
+    rasterize(&code, &raster).expect("can't rasterize code")
+}
+
+/// Pastes `codes` onto a light gray frame, side by side and wrapping to the
+/// next row as needed. Codes which don't fit are left out.
+fn frame(size: (usize, usize), codes: &[ImageBuf]) -> ImageBuf {
+    let (width, height) = size;
+    let mut data = vec![200; width * height];
+    let (mut left, mut top, mut row_height) = (0, 0, 0);
+
+    for code in codes {
+        if left + code.width() > width {
+            left = 0;
+            top += row_height;
+            row_height = 0;
+        }
+
+        if left + code.width() > width || top + code.height() > height {
+            continue;
+        }
+
+        for (y, row) in code.data().chunks(code.width()).enumerate() {
+            let start = (top + y) * width + left;
+            data[start..start + row.len()].copy_from_slice(row);
+        }
+
+        left += code.width();
+        row_height = row_height.max(code.height());
+    }
+
+    ImageBuf::new(data, Vec2D { x: width, y: height }).expect("can't create frame")
+}
+
+/// A frame of the given size with deterministic, busy content.
+fn noise_frame(size: (usize, usize), bytes_per_pixel: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+
+    (0..size.0 * size.1 * bytes_per_pixel)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// Detection and decoding of a single code, by frame size.
+fn frame_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame_size");
+    let mut decoder = Decoder::new().expect("can't create decoder");
+    let code = code_image(1);
+
+    for &size in &FRAME_SIZES {
+        let image = frame(size, slice::from_ref(&code));
+        let id = BenchmarkId::from_parameter(format!("{}x{}", size.0, size.1));
+
+        group.throughput(Throughput::Elements((size.0 * size.1) as u64));
+        group.bench_with_input(id, &image, |b, image| b.iter(|| {
+            decoder
+                .decode_image(&image.as_image())
+                .expect("can't process image")
+                .decoded()
+                .count()
+        }));
+    }
+
+    group.finish();
+}
+
+/// Detection and decoding of a frame, by the number of codes in it.
+fn codes_per_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codes_per_frame");
+    let mut decoder = Decoder::new().expect("can't create decoder");
+
+    for &count in &CODES_PER_FRAME {
+        let codes: Vec<ImageBuf> = (0..count).map(code_image).collect();
+        let image = frame(CROWDED_FRAME_SIZE, &codes);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &image, |b, image| b.iter(|| {
+            decoder
+                .decode_image(&image.as_image())
+                .expect("can't process image")
+                .decoded()
+                .count()
+        }));
+    }
+
+    group.finish();
+}
+
+/// Decoding of an already extracted code, by the length of its payload.
+fn decode_code(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_code");
+
+    for payload in &PAYLOADS {
+        let code = Encoder::new(EccLevel::M)
+            .encode(payload.as_bytes())
+            .and_then(|code| code.to_qr_code())
+            .expect("can't encode payload");
+
+        group.bench_with_input(BenchmarkId::from_parameter(payload.len()), &code, |b, code| {
+            b.iter(|| code.decode())
+        });
+    }
+
+    group.finish();
+}
+
+/// The conversions and transformations applied to frames before detection.
+fn preprocessing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("preprocessing");
+    let (width, height) = PREPROCESSED_FRAME_SIZE;
+    let size = Vec2D { x: width, y: height };
+    let rgb = noise_frame(PREPROCESSED_FRAME_SIZE, 3);
+    let gray = ImageBuf::new(noise_frame(PREPROCESSED_FRAME_SIZE, 1), size).expect("can't create frame");
+
+    group.throughput(Throughput::Elements((width * height) as u64));
+    group.bench_function("from_rgb", |b| b.iter(|| {
+        ImageBuf::from_rgb(black_box(&rgb), size, 3 * width)
+    }));
+    group.bench_function("from_nv12", |b| b.iter(|| {
+        ImageBuf::from_nv12(black_box(gray.data()), size, width)
+    }));
+    group.bench_function("binarize", |b| b.iter(|| {
+        let mut image = gray.clone();
+        image.binarize(black_box(128));
+        image
+    }));
+    group.bench_function("downscale_2x", |b| b.iter(|| {
+        Preprocessed::new(gray.clone()).downscale(2)
+    }));
+    group.bench_function("rotate_cw", |b| b.iter(|| {
+        Preprocessed::new(gray.clone()).rotate_cw()
+    }));
+    group.finish();
+}
+
+/// The vectorized per-pixel loops against their scalar counterparts.
+fn simd(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simd");
+    let (width, height) = PREPROCESSED_FRAME_SIZE;
+    let rgb = noise_frame(PREPROCESSED_FRAME_SIZE, 3);
+    let gray = noise_frame(PREPROCESSED_FRAME_SIZE, 1);
+    let mut luma = vec![0; width * height];
+
+    group.throughput(Throughput::Elements((width * height) as u64));
+    group.bench_function("rgb_to_luma/scalar", |b| b.iter(|| {
+        rgb_to_luma_scalar(black_box(&rgb), &mut luma)
+    }));
+    group.bench_function("rgb_to_luma/vectorized", |b| b.iter(|| {
+        rgb_to_luma(black_box(&rgb), &mut luma)
+    }));
+    group.bench_function("binarize/scalar", |b| b.iter_batched_ref(
+        || gray.clone(),
+        |pixels| binarize_scalar(pixels, black_box(128)),
+        BatchSize::LargeInput,
+    ));
+    group.bench_function("binarize/vectorized", |b| b.iter_batched_ref(
+        || gray.clone(),
+        |pixels| binarize(pixels, black_box(128)),
+        BatchSize::LargeInput,
+    ));
+    group.finish();
+}
+
+criterion_group!(benches, frame_size, codes_per_frame, decode_code, preprocessing, simd);
+criterion_main!(benches);
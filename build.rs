@@ -1,25 +1,51 @@
-use std::fs;
+extern crate cc;
+
 use std::env;
-use std::process::Command;
+
+/// The sources of the `quirc` library.
+const SOURCES: &[&str] = &[
+    "quirc/lib/decode.c",
+    "quirc/lib/identify.c",
+    "quirc/lib/quirc.c",
+    "quirc/lib/version_db.c",
+];
+
+/// The sources of the functions reaching into the internals of `quirc`.
+const SHIMS: &[&str] = &[
+    "shim/debug.c",
+    "shim/image.c",
+    "shim/memory.c",
+];
 
 fn main() {
+    let target = env::var("TARGET")
+        .expect("missing TARGET env var");
+
+    let mut build = cc::Build::new();
 
-    Command::new("make")
-        .args(&["libquirc.a", "-C", "quirc/"])
-        .status()
-        .expect("couldn't build quirc C library");
+    build
+        .files(SOURCES)
+        .files(SHIMS)
+        .include("quirc/lib")
+        .warnings(false);
 
-    let out_dir = env::var("OUT_DIR")
-        .expect("missing OUT_DIR env var");
+    // There is no C library on this target. The handful of functions
+    // `quirc` needs are declared by these headers, and implemented in
+    // `src/wasm_libc.rs`.
+    if target == "wasm32-unknown-unknown" {
+        build
+            .include("wasm/include")
+            .flag("-ffreestanding");
+    }
 
-    fs::copy("quirc/libquirc.a", out_dir.clone() + "/libquirc.a")
-        .expect("couldn't copy libquirc.a to OUT_DIR");
+    build.compile("quirc");
 
-    Command::new("make")
-        .args(&["clean", "-C", "quirc/"])
-        .status()
-        .expect("couldn't make clean");
+    // The ZBar backend uses the system library.
+    if env::var_os("CARGO_FEATURE_ZBAR").is_some() {
+        println!("cargo:rustc-link-lib=zbar");
+    }
 
-    println!("cargo:rustc-link-lib=static=quirc");
-    println!("cargo:rustc-link-search=native={}", out_dir);
+    for source in SOURCES.iter().chain(SHIMS) {
+        println!("cargo:rerun-if-changed={}", source);
+    }
 }
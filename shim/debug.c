@@ -0,0 +1,61 @@
+/*
+ * Access to the internal state of a `quirc` decoder, which its public API
+ * doesn't expose, for debugging detection from Rust.
+ */
+
+#include "quirc_internal.h"
+
+/*
+ * Copies the pixel buffer as it was left by the last call to quirc_end()
+ * into `out`, which must hold `q->w * q->h` bytes. After thresholding, each
+ * pixel is QUIRC_PIXEL_WHITE (0), QUIRC_PIXEL_BLACK (1), or the label of
+ * the region it was assigned to while looking for finder patterns (at
+ * least QUIRC_PIXEL_REGION), capped at 255 if labels are 16 bits wide.
+ */
+void quirs_debug_pixels(const struct quirc *q, uint8_t *out)
+{
+	int i;
+
+	for (i = 0; i < q->w * q->h; i++) {
+		quirc_pixel_t pixel = q->pixels[i];
+
+		out[i] = pixel > 255 ? 255 : (uint8_t)pixel;
+	}
+}
+
+/*
+ * Returns the number of regions (connected components of dark pixels)
+ * which were examined as candidate finder patterns in the last image.
+ */
+int quirs_debug_num_regions(const struct quirc *q)
+{
+	return q->num_regions - QUIRC_PIXEL_REGION;
+}
+
+/*
+ * Returns the number of capstones (finder patterns) found in the last
+ * image, which are grouped into grids (codes) by threes.
+ */
+int quirs_debug_num_capstones(const struct quirc *q)
+{
+	return q->num_capstones;
+}
+
+/*
+ * Copies the corners and the center of the capstone at `index`, which
+ * must be less than quirs_debug_num_capstones(), and returns the index of
+ * the grid it belongs to, or -1 if it couldn't be grouped into any.
+ */
+int quirs_debug_capstone(const struct quirc *q, int index,
+			 struct quirc_point *corners, struct quirc_point *center)
+{
+	const struct quirc_capstone *cap = &q->capstones[index];
+	int i;
+
+	for (i = 0; i < 4; i++)
+		corners[i] = cap->corners[i];
+
+	*center = cap->center;
+
+	return cap->qr_grid;
+}
@@ -0,0 +1,24 @@
+/*
+ * Replacing the image buffer of a `quirc` decoder, so that images can be
+ * thresholded in a buffer owned by Rust, without copying them first.
+ */
+
+#include "quirc_internal.h"
+
+/*
+ * Makes `image`, which must hold `q->w * q->h` bytes, the image buffer of
+ * `q`, and returns the previous one, which the caller must install again
+ * before `q` is resized or destroyed, since `quirc` frees it then. If the
+ * pixel buffer aliased the previous image buffer, it aliases the new one.
+ */
+uint8_t *quirs_swap_image(struct quirc *q, uint8_t *image)
+{
+	uint8_t *previous = q->image;
+
+	if ((uint8_t *)q->pixels == previous)
+		q->pixels = (quirc_pixel_t *)image;
+
+	q->image = image;
+
+	return previous;
+}
@@ -0,0 +1,25 @@
+/*
+ * Accounting for the memory allocated by a `quirc` decoder, which its
+ * public API doesn't expose, so that growth can be monitored from Rust.
+ */
+
+#include "quirc_internal.h"
+
+/*
+ * Returns the number of bytes allocated by `q`: the decoder object itself,
+ * its image buffer, its pixel buffer unless it aliases the image buffer,
+ * and the work area of the flood fill. The image buffer is counted even
+ * while it is replaced by `quirs_swap_image()`, since it is still held.
+ */
+size_t quirs_memory_usage(const struct quirc *q)
+{
+	size_t area = (size_t)q->w * (size_t)q->h;
+	size_t total = sizeof(*q) + area;
+
+	if (!QUIRC_PIXEL_ALIAS_IMAGE)
+		total += area * sizeof(quirc_pixel_t);
+
+	total += q->num_flood_fill_vars * sizeof(*q->flood_fill_vars);
+
+	return total;
+}
@@ -0,0 +1,234 @@
+//! Drawing the results of a scan onto a copy of the scanned image.
+//!
+//! `Report::annotate()` outlines every detected code with a polygon through
+//! its corners, and labels it with its index in the report and the
+//! beginning of its payload, or the reason why it couldn't be decoded.
+//! Decoded and failed codes are drawn in different colors, so that it is
+//! obvious at a glance whether a code was missed altogether, detected but
+//! not decoded, or decoded. Codes which couldn't even be extracted have no
+//! corners, and are not drawn.
+//!
+//! Labels are drawn in a built-in 5 × 7 pixel font covering printable
+//! ASCII; any other character is drawn as `?`.
+
+use geom::{ Image, Vec2D };
+use report::{ Report, Outcome };
+use render::{ Rgb, RgbImage };
+use error::Result;
+
+/// The width of a glyph of the font, in pixels.
+const GLYPH_WIDTH: usize = 5;
+
+/// The height of a glyph of the font, in pixels.
+const GLYPH_HEIGHT: usize = 7;
+
+/// The glyphs of the printable ASCII characters, from `' '` to `'~'`, as
+/// columns from left to right, the least significant bit being the top row.
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00],
+    [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e],
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e],
+    [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41],
+    [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c],
+    [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c],
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x10, 0x08, 0x08, 0x10, 0x08],
+];
+
+/// Parameters of annotating an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnnotateOptions {
+    /// The color of the outlines and labels of decoded codes.
+    /// Defaults to green.
+    pub decoded: Rgb,
+    /// The color of the outlines and labels of codes which couldn't be
+    /// decoded. Defaults to red.
+    pub failed: Rgb,
+    /// The color of the text of labels. Defaults to white.
+    pub text: Rgb,
+    /// The width of outlines, in pixels. Defaults to 2.
+    pub line_width: usize,
+    /// The size of each pixel of the font, in pixels. Defaults to 2.
+    pub text_scale: usize,
+    /// The number of characters of the payload (or of the error message)
+    /// shown in labels, beyond which it is truncated. Defaults to 32.
+    pub max_label_len: usize,
+}
+
+impl Default for AnnotateOptions {
+    fn default() -> Self {
+        AnnotateOptions {
+            decoded: Rgb { r: 0x00, g: 0xa0, b: 0x00 },
+            failed: Rgb { r: 0xd0, g: 0x00, b: 0x00 },
+            text: Rgb::WHITE,
+            line_width: 2,
+            text_scale: 2,
+            max_label_len: 32,
+        }
+    }
+}
+
+impl Report {
+    /// Draws the codes in the report onto a color copy of `image`, which
+    /// should be the image the report was made of. See the module
+    /// documentation for details.
+    pub fn annotate(&self, image: &Image, options: &AnnotateOptions) -> Result<RgbImage> {
+        let mut annotated = RgbImage::from_gray(image)?;
+
+        for (index, outcome) in self.codes.iter().enumerate() {
+            let (corners, color, detail) = match *outcome {
+                Outcome::Decoded(ref code) => {
+                    (code.corners, options.decoded, String::from_utf8_lossy(&code.payload).into_owned())
+                }
+                Outcome::Failed { corners: Some(corners), ref error } => {
+                    (corners, options.failed, error.to_string())
+                }
+                Outcome::Failed { corners: None, .. } => continue,
+            };
+
+            for (i, &from) in corners.iter().enumerate() {
+                draw_line(&mut annotated, from, corners[(i + 1) % 4], color, options.line_width);
+            }
+
+            let label = format!("#{} {}", index, truncate(&detail, options.max_label_len));
+            draw_label(&mut annotated, &corners, &label, color, options);
+        }
+
+        Ok(annotated)
+    }
+}
+
+/// Returns the first `max_len` characters of `text`, followed by an
+/// ellipsis if it is longer.
+fn truncate(text: &str, max_len: usize) -> String {
+    let mut chars = text.chars();
+    let mut truncated: String = chars.by_ref().take(max_len).collect();
+
+    if chars.next().is_some() {
+        truncated.push_str("...");
+    }
+
+    truncated
+}
+
+/// Draws a straight line of the given width from `from` to `to`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap, cast_sign_loss))]
+fn draw_line(image: &mut RgbImage, from: Vec2D, to: Vec2D, color: Rgb, width: usize) {
+    // One pixel per step along the longer axis, interpolating the other.
+    let dx = to.x as isize - from.x as isize;
+    let dy = to.y as isize - from.y as isize;
+    let steps = dx.abs().max(dy.abs());
+    let offset = width / 2;
+
+    for step in 0..=steps {
+        let (x, y) = if steps == 0 {
+            (from.x as isize, from.y as isize)
+        } else {
+            (from.x as isize + dx * step / steps, from.y as isize + dy * step / steps)
+        };
+
+        // Pixels left of or above the image wrap around to huge values,
+        // which `fill_rect()` ignores like any other pixel off the image.
+        let left = (x as usize).wrapping_sub(offset);
+        let top = (y as usize).wrapping_sub(offset);
+
+        fill_rect(image, Vec2D { x: left, y: top }, Vec2D { x: width, y: width }, color);
+    }
+}
+
+/// Fills the rectangle with the given top left corner and size. Pixels
+/// off the image are ignored.
+fn fill_rect(image: &mut RgbImage, origin: Vec2D, size: Vec2D, color: Rgb) {
+    for y in 0..size.y {
+        for x in 0..size.x {
+            image.set(origin.x.wrapping_add(x), origin.y.wrapping_add(y), color);
+        }
+    }
+}
+
+/// Draws `label` on a filled box, just above the code with the given
+/// corners if there is room there, or just inside it otherwise.
+fn draw_label(image: &mut RgbImage, corners: &[Vec2D; 4], label: &str, color: Rgb, options: &AnnotateOptions) {
+    let scale = options.text_scale.max(1);
+    let padding = scale;
+    let box_size = Vec2D {
+        x: label.chars().count() * (GLYPH_WIDTH + 1) * scale + padding,
+        y: GLYPH_HEIGHT * scale + 2 * padding,
+    };
+    let left = corners.iter().map(|corner| corner.x).min().unwrap_or(0);
+    let top = corners.iter().map(|corner| corner.y).min().unwrap_or(0);
+    // Keep the label on the image if the code is near the right edge.
+    let x = left.min(image.width().saturating_sub(box_size.x));
+    let y = top.checked_sub(box_size.y + options.line_width).unwrap_or(top);
+
+    fill_rect(image, Vec2D { x, y }, box_size, color);
+
+    for (i, c) in label.chars().enumerate() {
+        let origin = Vec2D {
+            x: x + padding + i * (GLYPH_WIDTH + 1) * scale,
+            y: y + padding,
+        };
+
+        draw_glyph(image, origin, c, options.text, scale);
+    }
+}
+
+/// Draws a character of the font with its top left corner at `origin`,
+/// each pixel of the glyph being a `scale × scale` square.
+fn draw_glyph(image: &mut RgbImage, origin: Vec2D, c: char, color: Rgb, scale: usize) {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+
+    for (column, &bits) in FONT[index].iter().enumerate() {
+        for row in (0..GLYPH_HEIGHT).filter(|&row| bits >> row & 1 != 0) {
+            let pixel = Vec2D {
+                x: origin.x + column * scale,
+                y: origin.y + row * scale,
+            };
+
+            fill_rect(image, pixel, Vec2D { x: scale, y: scale }, color);
+        }
+    }
+}
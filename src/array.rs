@@ -0,0 +1,104 @@
+//! Interoperability with `ndarray`, for scientific and computer vision
+//! pipelines which keep their frames in 2-dimensional arrays.
+//!
+//! Arrays are indexed as `[row, column]`, i.e. their shape is
+//! `(height, width)`, like that of NumPy and OpenCV images. Corners of codes
+//! are returned as `4 × 2` arrays of `[x, y]` rows, clockwise from the top
+//! left corner.
+
+use std::convert::TryFrom;
+use std::vec::Vec;
+use ndarray::{ Array2, ArrayView2 };
+use decoder::{ Decoder, CodeError };
+use geom::{ Image, ImageBuf, Vec2D, QrCode };
+use info::Info;
+use error::{ Error, Result };
+
+/// The corners of a code as returned by `QrCode::corner_array()`, along
+/// with the information decoded from it, or the reason why it couldn't be.
+pub type Decoded = ::std::result::Result<(Array2<f64>, Info), CodeError>;
+
+/// Borrows the pixels of an array in standard (row-major, contiguous)
+/// layout without copying them. Other views, e.g. transposed ones or
+/// slices of columns, are rejected with `Error::InvalidImage`; convert
+/// those into an `ImageBuf` instead, or use `Decoder::decode_array()`.
+impl<'a> TryFrom<ArrayView2<'a, u8>> for Image<'a> {
+    type Error = Error;
+
+    fn try_from(array: ArrayView2<'a, u8>) -> Result<Self> {
+        let size = array_size(&array);
+
+        match array.to_slice() {
+            Some(data) => Image::new(data, size),
+            None => Err(Error::InvalidImage),
+        }
+    }
+}
+
+/// Copies the pixels of an array in any memory layout.
+impl<'a> TryFrom<ArrayView2<'a, u8>> for ImageBuf {
+    type Error = Error;
+
+    fn try_from(array: ArrayView2<'a, u8>) -> Result<Self> {
+        let size = array_size(&array);
+
+        ImageBuf::new(array.iter().cloned().collect(), size)
+    }
+}
+
+impl QrCode {
+    /// The four corners of the QR code as a `4 × 2` array of `[x, y]`
+    /// rows, clockwise from the top left one.
+    pub fn corner_array(&self) -> Array2<f64> {
+        from_corners(&self.corners())
+    }
+}
+
+impl Decoder {
+    /// Decodes the QR codes in an array of shape `(height, width)`,
+    /// returning the corners of every code along with the decoded
+    /// information, or the reason why it couldn't be decoded.
+    ///
+    /// Arrays in standard layout are read directly, while others are
+    /// copied into the internal buffer of the decoder row by row, so no
+    /// intermediate image is allocated either way.
+    pub fn decode_array(
+        &mut self,
+        array: ArrayView2<u8>,
+    ) -> Result<Vec<Decoded>> {
+        let codes = match Image::try_from(array.view()) {
+            Ok(image) => self.decode_image(&image)?,
+            Err(_) => {
+                let mut buf = self.begin(array_size(&array))?;
+
+                for (dst, src) in buf.iter_mut().zip(array.iter()) {
+                    *dst = *src;
+                }
+
+                buf.end()
+            }
+        };
+
+        Ok(codes
+            .decoded()
+            .map(|result| result.map(|(code, info)| (code.corner_array(), info)))
+            .collect())
+    }
+}
+
+/// Converts corners to a `4 × 2` array of `[x, y]` rows.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+pub fn from_corners(corners: &[Vec2D; 4]) -> Array2<f64> {
+    Array2::from_shape_fn((4, 2), |(i, j)| {
+        let corner = corners[i];
+
+        if j == 0 { corner.x as f64 } else { corner.y as f64 }
+    })
+}
+
+/// Returns the width and the height of an array of shape `(height, width)`.
+fn array_size(array: &ArrayView2<u8>) -> Vec2D {
+    let (height, width) = array.dim();
+
+    Vec2D { x: width, y: height }
+}
@@ -0,0 +1,106 @@
+//! Decoding images without blocking an asynchronous executor.
+//!
+//! Detecting and decoding codes is CPU-bound, so it mustn't run on the
+//! threads of an executor which drive other tasks. `decode_image_async()`
+//! runs it on the blocking thread pool of the current `tokio` runtime if the
+//! `tokio` feature is enabled and a runtime is available, and on a thread of
+//! its own otherwise. The returned future is executor-agnostic either way.
+
+use std::thread;
+use std::pin::Pin;
+use std::future::Future;
+use std::panic::{ self, AssertUnwindSafe };
+use std::sync::{ Arc, Mutex, PoisonError };
+use std::task::{ Context, Poll, Waker };
+use decoder::Decoder;
+use geom::{ ImageBuf, QrCode };
+use info::Info;
+use error::{ Error, DecodingErrorKind, Result };
+
+/// The codes found in an image, along with the information decoded from them.
+pub type DecodedCodes = Vec<(QrCode, Info)>;
+
+/// The state shared between a `DecodeFuture` and the thread doing the work.
+#[derive(Debug, Default)]
+struct Shared {
+    /// The result, once the work is done and until it is taken.
+    result: Option<Result<DecodedCodes>>,
+    /// The waker of the task which last polled the future.
+    waker: Option<Waker>,
+}
+
+/// A future resolving to the codes decoded from an image, obtained from
+/// `decode_image_async()`.
+#[derive(Debug)]
+pub struct DecodeFuture {
+    /// The state shared with the thread doing the work.
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Future for DecodeFuture {
+    type Output = Result<DecodedCodes>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Detects the codes in `image` and decodes them on a blocking thread,
+/// with a decoder of its own. Codes which can't be decoded are omitted.
+///
+/// The future fails if no decoder could be created, if the image couldn't
+/// be fed to it, or with `DecodingErrorKind::Unknown` if decoding panicked.
+pub fn decode_image_async(image: ImageBuf) -> DecodeFuture {
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let task_shared = Arc::clone(&shared);
+
+    spawn_blocking(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| decode(&image)))
+            .unwrap_or(Err(Error::DecodingFailed(DecodingErrorKind::Unknown)));
+        let mut state = task_shared.lock().unwrap_or_else(PoisonError::into_inner);
+
+        state.result = Some(result);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+
+    DecodeFuture { shared }
+}
+
+/// Detects and decodes the codes in `image`.
+fn decode(image: &ImageBuf) -> Result<DecodedCodes> {
+    let mut decoder = Decoder::new()?;
+    let codes = decoder
+        .decode_image(&image.as_image())?
+        .decoded()
+        .filter_map(|code| code.ok())
+        .collect();
+
+    Ok(codes)
+}
+
+/// Runs `work` on the blocking thread pool of the current `tokio` runtime,
+/// or on a new thread if there's no runtime.
+#[cfg(feature = "tokio")]
+fn spawn_blocking<F: FnOnce() + Send + 'static>(work: F) {
+    match ::tokio::runtime::Handle::try_current() {
+        Ok(runtime) => drop(runtime.spawn_blocking(work)),
+        Err(_) => drop(thread::spawn(work)),
+    }
+}
+
+/// Runs `work` on a new thread.
+#[cfg(not(feature = "tokio"))]
+fn spawn_blocking<F: FnOnce() + Send + 'static>(work: F) {
+    drop(thread::spawn(work));
+}
@@ -0,0 +1,352 @@
+//! Interchangeable implementations of detection and decoding.
+//!
+//! A `Backend` finds the codes in an image, extracts their modules, and
+//! decodes them. `Decoder`, which wraps the `quirc` C library, is always
+//! available. With the `rqrr` feature, `RqrrBackend` detects codes with the
+//! pure-Rust `rqrr` crate instead, and decodes them with the Rust reader of
+//! the bitstream in this crate (see `decode_in_rust()`), so that it never
+//! calls into C.
+//!
+//! Codes are extracted as `QrCode`s, so codes detected by one backend can
+//! be decoded by another, which helps telling whether detection or decoding
+//! is to blame when the backends disagree. With the `zbar` feature,
+//! `zbar::ZbarBackend` adds the ZBar library, which only reports payloads.
+//! `CompareDecoder` runs two backends on the same images and reports where
+//! they disagree. `by_name()` selects a backend at runtime, e.g. from a
+//! command line option.
+
+use std::vec::Vec;
+use std::boxed::Box;
+use geom::{ Image, QrCode, Vec2D };
+use info::Info;
+use decoder::Decoder;
+use mask::polygon_contains;
+use bitstream;
+use error::{ Error, Result };
+#[cfg(feature = "rqrr")]
+use rqrr::{ BitGrid, Grid, PreparedImage };
+#[cfg(feature = "rqrr")]
+use quirc_sys::{ quirc_code, quirc_point, QUIRC_MAX_BITMAP };
+#[cfg(feature = "rqrr")]
+use util::{ usize_to_int, checked_mul };
+#[cfg(feature = "rqrr")]
+use error::DecodingErrorKind;
+#[cfg(feature = "zbar")]
+use zbar::ZbarBackend;
+
+/// A detector and decoder of QR codes.
+pub trait Backend: Send {
+    /// A short name identifying the backend, as accepted by `by_name()`.
+    fn name(&self) -> &'static str;
+
+    /// Detects the codes in `image`, replacing the ones detected in the
+    /// previous image, and returns their number.
+    fn detect(&mut self, image: &Image) -> Result<usize>;
+
+    /// Extracts the code at `index` among the ones detected in the last
+    /// image. Fails with `Error::InvalidArgument` if `index` is out of bounds.
+    fn extract(&self, index: usize) -> Result<QrCode>;
+
+    /// Decodes a code, which may have been extracted by another backend.
+    fn decode(&self, code: &QrCode) -> Result<Info>;
+
+    /// Detects, extracts and decodes every code in `image`, in the order
+    /// of detection.
+    fn scan(&mut self, image: &Image) -> Result<Vec<Result<(QrCode, Info)>>> {
+        let count = self.detect(image)?;
+
+        Ok((0..count).map(|index| {
+            let code = self.extract(index)?;
+            let info = self.decode(&code)?;
+            Ok((code, info))
+        }).collect())
+    }
+
+    /// Detects and decodes every code in `image`, reporting only where it
+    /// is and what it contains, for comparison with other backends. Codes
+    /// which can't be extracted are left out.
+    ///
+    /// Backends which decode codes without exposing their modules, and
+    /// therefore can't implement `extract()`, override this method.
+    fn detections(&mut self, image: &Image) -> Result<Vec<Detection>> {
+        let count = self.detect(image)?;
+        let detections = (0..count)
+            .filter_map(|index| self.extract(index).ok())
+            .map(|code| Detection {
+                corners: code.corners(),
+                payload: self.decode(&code).map(|info| info.payload().to_vec()),
+            })
+            .collect();
+
+        Ok(detections)
+    }
+}
+
+impl<T: Backend + ?Sized> Backend for Box<T> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn detect(&mut self, image: &Image) -> Result<usize> {
+        (**self).detect(image)
+    }
+
+    fn extract(&self, index: usize) -> Result<QrCode> {
+        (**self).extract(index)
+    }
+
+    fn decode(&self, code: &QrCode) -> Result<Info> {
+        (**self).decode(code)
+    }
+
+    fn scan(&mut self, image: &Image) -> Result<Vec<Result<(QrCode, Info)>>> {
+        (**self).scan(image)
+    }
+
+    fn detections(&mut self, image: &Image) -> Result<Vec<Detection>> {
+        (**self).detections(image)
+    }
+}
+
+/// A code found by a backend, reduced to what every backend can report.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    /// The corners of the code, clockwise from the top left one.
+    pub corners: [Vec2D; 4],
+    /// The payload of the code, or the reason why it couldn't be decoded.
+    pub payload: Result<Vec<u8>>,
+}
+
+impl Detection {
+    /// Returns whether `other` was found at the same place as this code,
+    /// i.e. whether the center of either lies within the other.
+    pub fn overlaps(&self, other: &Detection) -> bool {
+        polygon_contains(&self.corners, other.center()) ||
+        polygon_contains(&other.corners, self.center())
+    }
+
+    /// Returns whether the payloads of two codes are the same, or both
+    /// codes failed to decode.
+    pub fn same_payload(&self, other: &Detection) -> bool {
+        match (&self.payload, &other.payload) {
+            (Ok(payload), Ok(other_payload)) => payload == other_payload,
+            (Err(_), Err(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The mean of the corners.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn center(&self) -> (f64, f64) {
+        let (x, y) = self.corners.iter().fold((0, 0), |(x, y), corner| (x + corner.x, y + corner.y));
+        (x as f64 / 4.0, y as f64 / 4.0)
+    }
+}
+
+/// Runs two backends on the same images, and reports the codes which only
+/// one of them found, or which they decoded differently.
+#[derive(Debug)]
+pub struct CompareDecoder<A, B> {
+    /// The backend whose results are considered the reference.
+    first: A,
+    /// The backend compared against the first one.
+    second: B,
+}
+
+impl<A: Backend, B: Backend> CompareDecoder<A, B> {
+    /// Compares `second` against `first`.
+    pub fn new(first: A, second: B) -> Self {
+        CompareDecoder { first, second }
+    }
+
+    /// Returns the backends, consuming the comparer.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+
+    /// Scans `image` with both backends, and pairs up the codes they
+    /// found at the same place.
+    pub fn compare(&mut self, image: &Image) -> Result<Comparison> {
+        let first = self.first.detections(image)?;
+        let mut second = self.second.detections(image)?;
+        let mut comparison = Comparison {
+            first_name: self.first.name(),
+            second_name: self.second.name(),
+            agreed: Vec::new(),
+            disagreements: Vec::new(),
+        };
+
+        for detection in first {
+            match second.iter().position(|other| other.overlaps(&detection)) {
+                Some(index) => {
+                    let other = second.remove(index);
+
+                    if detection.same_payload(&other) {
+                        comparison.agreed.push(detection);
+                    } else {
+                        comparison.disagreements.push(Disagreement::Payload(detection, other));
+                    }
+                }
+                None => comparison.disagreements.push(Disagreement::OnlyFirst(detection)),
+            }
+        }
+
+        comparison.disagreements.extend(second.into_iter().map(Disagreement::OnlySecond));
+
+        Ok(comparison)
+    }
+}
+
+/// The results of scanning an image with two backends.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// The name of the first backend.
+    pub first_name: &'static str,
+    /// The name of the second backend.
+    pub second_name: &'static str,
+    /// The codes which both backends found and decoded the same way, as
+    /// reported by the first one.
+    pub agreed: Vec<Detection>,
+    /// The codes on which the backends disagree.
+    pub disagreements: Vec<Disagreement>,
+}
+
+impl Comparison {
+    /// Returns whether the backends agree on every code.
+    pub fn is_agreement(&self) -> bool {
+        self.disagreements.is_empty()
+    }
+}
+
+/// A code on which two backends disagree.
+#[derive(Debug, Clone)]
+pub enum Disagreement {
+    /// Only the first backend found the code.
+    OnlyFirst(Detection),
+    /// Only the second backend found the code.
+    OnlySecond(Detection),
+    /// Both backends found the code, but only one of them could decode it,
+    /// or their payloads differ. The detections are those of the first and
+    /// the second backend, respectively.
+    Payload(Detection, Detection),
+}
+
+impl Backend for Decoder {
+    fn name(&self) -> &'static str {
+        "quirc"
+    }
+
+    fn detect(&mut self, image: &Image) -> Result<usize> {
+        self.decode_image(image)?;
+        Ok(self.count())
+    }
+
+    fn extract(&self, index: usize) -> Result<QrCode> {
+        Decoder::extract(self, index)
+    }
+
+    fn decode(&self, code: &QrCode) -> Result<Info> {
+        code.decode()
+    }
+}
+
+/// Creates the backend called `name`: `"quirc"`, or `"rqrr"` and `"zbar"`
+/// with the features of the same names. Fails with `Error::InvalidArgument`
+/// for any other name.
+pub fn by_name(name: &str) -> Result<Box<dyn Backend>> {
+    match name {
+        "quirc" => Ok(Box::new(Decoder::new()?)),
+        #[cfg(feature = "rqrr")]
+        "rqrr" => Ok(Box::new(RqrrBackend::new())),
+        #[cfg(feature = "zbar")]
+        "zbar" => Ok(Box::new(ZbarBackend::new()?)),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+/// Decodes a code without `quirc`: reads the format information, unmasks
+/// and error-corrects the codewords, and decodes the segments in Rust.
+///
+/// The result is the same as that of `QrCode::decode()` for well-formed
+/// codes, but the errors of damaged codes may be of different kinds.
+pub fn decode_in_rust(code: &QrCode) -> Result<Info> {
+    let codewords = bitstream::read_codewords(code)?;
+    let payload = bitstream::decode_payload(&codewords.data, codewords.version)?;
+
+    Info::from_codewords(&codewords, &payload)
+}
+
+/// A backend detecting codes with the `rqrr` crate and decoding them with
+/// `decode_in_rust()`, entirely in Rust.
+#[cfg(feature = "rqrr")]
+#[derive(Debug, Clone, Default)]
+pub struct RqrrBackend {
+    /// The codes detected in the last image, or the reasons why they can't
+    /// be represented as a `QrCode`.
+    codes: Vec<Result<QrCode>>,
+}
+
+#[cfg(feature = "rqrr")]
+impl RqrrBackend {
+    /// Creates a backend which hasn't detected any codes yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "rqrr")]
+impl Backend for RqrrBackend {
+    fn name(&self) -> &'static str {
+        "rqrr"
+    }
+
+    fn detect(&mut self, image: &Image) -> Result<usize> {
+        let mut prepared = PreparedImage::prepare_from_greyscale(
+            image.width(),
+            image.height(),
+            |x, y| image.row(y)[x],
+        );
+
+        self.codes = prepared.detect_grids().iter().map(grid_to_code).collect();
+
+        Ok(self.codes.len())
+    }
+
+    fn extract(&self, index: usize) -> Result<QrCode> {
+        self.codes.get(index).map_or(Err(Error::InvalidArgument), |&code| code)
+    }
+
+    fn decode(&self, code: &QrCode) -> Result<Info> {
+        decode_in_rust(code)
+    }
+}
+
+/// Converts a grid detected by `rqrr` into a `QrCode`. Fails if a corner
+/// was extrapolated outside the image, which `quirc` never reports.
+#[cfg(feature = "rqrr")]
+fn grid_to_code<G: BitGrid>(grid: &Grid<G>) -> Result<QrCode> {
+    let size = grid.grid.size();
+
+    if checked_mul(size, size)? > QUIRC_MAX_BITMAP * 8 {
+        return Err(Error::DecodingFailed(DecodingErrorKind::InvalidGridSize));
+    }
+
+    let mut raw = quirc_code::default();
+
+    for (corner, point) in raw.corners.iter_mut().zip(&grid.bounds) {
+        *corner = quirc_point { x: point.x, y: point.y };
+    }
+
+    raw.size = usize_to_int(size)?;
+
+    for y in 0..size {
+        for x in 0..size {
+            if grid.grid.bit(y, x) {
+                let i = y * size + x;
+                raw.cell_bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+    }
+
+    QrCode::from_raw(raw)
+}
@@ -0,0 +1,164 @@
+//! Scanning many files on a pool of threads.
+
+use std::fs;
+use std::io;
+use std::thread;
+use std::vec;
+use std::iter::Enumerate;
+use std::path::{ Path, PathBuf };
+use std::collections::BTreeMap;
+use std::sync::{ mpsc, Arc, Mutex, PoisonError };
+use quirs::{ Decoder, ImageBuf };
+use quirs::report::Report;
+use quirs::annotate::AnnotateOptions;
+use quirs::Error;
+
+/// The extensions of the files picked up from directories, in lowercase.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "pgm", "pbm", "ppm", "pnm"];
+
+/// The files which are yet to be scanned, along with their indices.
+type Queue = Arc<Mutex<Enumerate<vec::IntoIter<PathBuf>>>>;
+
+/// A scanned file: its index, its path and the results.
+type Scanned = (usize, PathBuf, Result<Report, Error>);
+
+/// Replaces directories among `paths` with the image files within them,
+/// recursively and in sorted order. Files are kept whatever their extension.
+pub fn expand(paths: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths.iter().map(PathBuf::from) {
+        if path.is_dir() {
+            walk(&path, &mut files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Appends the image files within `dir` and its subdirectories to `files`.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if is_image(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the extension of `path` is that of a supported format.
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            IMAGE_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Loads an image and decodes the codes in it. If `annotate_dir` is
+/// given, an annotated copy of the image is saved there, too.
+pub fn scan_file(decoder: &mut Decoder, path: &Path, annotate_dir: Option<&Path>) -> Result<Report, Error> {
+    let image = ImageBuf::load(path)?;
+    let report = Report::scan(decoder, &image.as_image())?;
+
+    if let Some(dir) = annotate_dir {
+        let annotated = report.annotate(&image.as_image(), &AnnotateOptions::default())?;
+        fs::write(annotated_path(dir, path), annotated.to_png()?)?;
+    }
+
+    Ok(report)
+}
+
+/// Returns the path of the annotated copy of `path` in `dir`: the name of
+/// the file without its extension, followed by `.annotated.png`.
+pub fn annotated_path(dir: &Path, path: &Path) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+
+    name.push(".annotated.png");
+    dir.join(name)
+}
+
+/// Scans `files` on up to `jobs` threads, each with its own decoder, and
+/// passes the results to `emit` in the order of `files`. Stops early if
+/// `emit` fails. Annotated copies of the images are saved in `annotate_dir`
+/// if it is given.
+pub fn scan_all<F>(
+    files: Vec<PathBuf>,
+    jobs: usize,
+    annotate_dir: Option<&Path>,
+    mut emit: F,
+) -> Result<(), Error>
+    where F: FnMut(&Path, Result<Report, Error>) -> io::Result<()>
+{
+    let num_workers = jobs.min(files.len()).max(1);
+    let decoders = (0..num_workers)
+        .map(|_| Decoder::new())
+        .collect::<Result<Vec<_>, _>>()?;
+    let queue: Queue = Arc::new(Mutex::new(files.into_iter().enumerate()));
+    let (tx, rx) = mpsc::channel();
+    let workers: Vec<_> = decoders
+        .into_iter()
+        .map(|decoder| {
+            let queue = Arc::clone(&queue);
+            let tx = tx.clone();
+            let annotate_dir = annotate_dir.map(Path::to_path_buf);
+
+            thread::spawn(move || work(decoder, &queue, annotate_dir.as_ref().map(PathBuf::as_path), &tx))
+        })
+        .collect();
+
+    drop(tx);
+
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    let mut emitted = Ok(());
+
+    'receive: for (index, path, result) in rx {
+        pending.insert(index, (path, result));
+
+        while let Some((path, result)) = pending.remove(&next) {
+            emitted = emit(&path, result);
+            next += 1;
+
+            if emitted.is_err() {
+                // Dropping the receiver makes the workers stop.
+                break 'receive;
+            }
+        }
+    }
+
+    for worker in workers {
+        // Workers don't panic unless the decoder does, which is a bug.
+        worker.join().expect("worker thread panicked");
+    }
+
+    emitted.map_err(Error::from)
+}
+
+/// Scans files from `queue` until it is exhausted, or the results are no
+/// longer received.
+fn work(mut decoder: Decoder, queue: &Queue, annotate_dir: Option<&Path>, tx: &mpsc::Sender<Scanned>) {
+    loop {
+        let job = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+        let (index, path) = match job {
+            Some(job) => job,
+            None => return,
+        };
+        let result = scan_file(&mut decoder, &path, annotate_dir);
+
+        if tx.send((index, path, result)).is_err() {
+            return;
+        }
+    }
+}
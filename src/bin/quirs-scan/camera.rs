@@ -0,0 +1,47 @@
+//! Scanning the frames of a Video4Linux camera continuously.
+
+use std::io::{ self, Write };
+use quirs::Vec2D;
+use quirs::Error;
+use quirs::video::{ CameraSource, Scanner };
+use quirs::schema::{ EventKind, ScanEvent };
+use super::{ Format, Options, format_corners };
+
+/// The frame size requested from the camera unless `--size` is given.
+const DEFAULT_SIZE: Vec2D = Vec2D { x: 640, y: 480 };
+
+/// Scans the frames of `device` until capturing fails or the standard
+/// output is closed, and prints each code as it comes into view. A code
+/// is printed again only once it has left the view and come back.
+pub fn scan_camera(device: &str, options: &Options) -> Result<(), Error> {
+    let camera = CameraSource::open(device, options.size.unwrap_or(DEFAULT_SIZE))?;
+    let mut scanner = Scanner::new()?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for frame in camera {
+        let events = scanner.scan(&frame?.as_image())?;
+
+        for event in events.iter().filter(|event| event.kind == EventKind::Appeared) {
+            print_event(&mut out, options, event)?;
+        }
+
+        // Consumers of a pipeline react to each code as soon as it is seen.
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Prints a code which has just appeared, as requested by `options`.
+fn print_event<W: Write>(out: &mut W, options: &Options, event: &ScanEvent) -> io::Result<()> {
+    if options.format == Format::Json {
+        return writeln!(out, "{}", event.to_json());
+    }
+
+    if options.corners {
+        write!(out, "{}\t", format_corners(&event.code.corners))?;
+    }
+
+    writeln!(out, "{}", String::from_utf8_lossy(&event.code.payload))
+}
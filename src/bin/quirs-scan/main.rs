@@ -0,0 +1,404 @@
+//! `quirs-scan`: prints the payloads of the QR codes in image files.
+//!
+//! PNG, JPEG and Netpbm (PBM, PGM, PPM) images are supported, and
+//! directories are searched for them recursively. Files are decoded in
+//! parallel, but reported in order. By default, every decoded payload is
+//! printed on a line of its own, prefixed by the name of the file if there
+//! are several; `--json` and `--csv` print JSON lines and CSV rows instead.
+//! The exit status is 0 on success, 1 if `--fail-if-none` is given and no
+//! code was decoded, and 2 if a file couldn't be read or the arguments are
+//! invalid.
+//!
+//! `--annotate DIR` saves a copy of every image with the detected codes
+//! outlined and labeled in `DIR`, to see what was (or wasn't) found.
+//!
+//! With the `v4l2` feature, `--camera DEVICE` scans the frames of a camera
+//! instead, until it is interrupted, and prints each code once as it comes
+//! into view, like `zbarcam`.
+
+extern crate quirs;
+
+mod batch;
+#[cfg(feature = "v4l2")]
+mod camera;
+
+use std::env;
+use std::fs;
+use std::thread;
+use std::process;
+use std::path::Path;
+use std::io::{ self, Write };
+use quirs::Vec2D;
+use quirs::report::{ Report, Outcome };
+use quirs::Error;
+
+/// The summary of the command line options.
+const USAGE: &str = "\
+usage: quirs-scan [OPTIONS] FILE|DIRECTORY...
+       quirs-scan [OPTIONS] --camera DEVICE
+
+Decodes the QR codes in PNG, JPEG and Netpbm images. Directories are
+searched for images with the usual extensions recursively. With a camera,
+each code is printed once as it comes into view, until interrupted.
+
+options:
+    --json          print one JSON report per file instead of plain payloads
+    --csv           print one CSV row per code (or per file without codes)
+    --corners       print the corners of each code before its payload
+    --fail-if-none  exit with status 1 if no code could be decoded
+    --annotate DIR  save copies of the images with the codes outlined and
+                    labeled in DIR, as NAME.annotated.png
+    -j, --jobs N    decode N files in parallel (default: number of CPUs)
+    --camera DEVICE scan the frames of a Video4Linux camera, e.g. /dev/video0
+    --size WxH      the frame size to request from the camera (default: 640x480)
+    -h, --help      print this help";
+
+/// The exit status if no code was decoded and `--fail-if-none` is given.
+const EXIT_NONE_FOUND: i32 = 1;
+
+/// The exit status if a file couldn't be read, or the usage is invalid.
+const EXIT_FAILURE: i32 = 2;
+
+/// The header of the CSV output.
+const CSV_HEADER: &str = "file,code,status,payload,corners,error";
+
+/// The format of the results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Payloads, one per line.
+    Text,
+    /// A JSON report per file, one per line.
+    Json,
+    /// A CSV row per code, or per file without decoded codes.
+    Csv,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Text
+    }
+}
+
+/// The command line options.
+#[derive(Debug, Default)]
+struct Options {
+    /// The format of the results.
+    format: Format,
+    /// Print the corners of the codes along with the payloads.
+    corners: bool,
+    /// Exit with `EXIT_NONE_FOUND` if no code was decoded.
+    fail_if_none: bool,
+    /// The number of files to decode in parallel; 0 means one per CPU.
+    jobs: usize,
+    /// The directory to save annotated copies of the images in.
+    annotate_dir: Option<String>,
+    /// The images and directories to scan.
+    files: Vec<String>,
+    /// The camera to scan instead of files.
+    camera: Option<String>,
+    /// The frame size to request from the camera, if not the default.
+    #[cfg_attr(not(feature = "v4l2"), allow(dead_code))]
+    size: Option<Vec2D>,
+}
+
+impl Options {
+    /// Parses the command line arguments, without the name of the program.
+    /// Returns `None` if the help was requested.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Self>, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => options.set_format(Format::Json)?,
+                "--csv" => options.set_format(Format::Csv)?,
+                "-j" | "--jobs" => {
+                    options.jobs = args
+                        .next()
+                        .and_then(|jobs| jobs.parse().ok())
+                        .filter(|&jobs| jobs > 0)
+                        .ok_or_else(|| format!("`{}` requires a positive number", arg))?;
+                }
+                "--annotate" => {
+                    options.annotate_dir = Some(args
+                        .next()
+                        .ok_or_else(|| format!("`{}` requires a directory", arg))?);
+                }
+                "--camera" => {
+                    options.camera = Some(args
+                        .next()
+                        .ok_or_else(|| format!("`{}` requires a device", arg))?);
+                }
+                "--size" => {
+                    options.size = Some(args
+                        .next()
+                        .as_ref()
+                        .and_then(|size| parse_size(size))
+                        .ok_or_else(|| format!("`{}` requires a size such as 640x480", arg))?);
+                }
+                "--corners" => options.corners = true,
+                "--fail-if-none" => options.fail_if_none = true,
+                "-h" | "--help" => return Ok(None),
+                "--" => options.files.extend(&mut args),
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("unknown option `{}`", arg));
+                }
+                _ => options.files.push(arg),
+            }
+        }
+
+        match (options.camera.is_some(), options.files.is_empty()) {
+            (false, true) => Err(String::from("no input files")),
+            (true, false) => Err(String::from("`--camera` can't be combined with input files")),
+            (true, true) if options.format == Format::Csv => {
+                Err(String::from("`--csv` can't be combined with `--camera`"))
+            }
+            (true, true) if options.annotate_dir.is_some() => {
+                Err(String::from("`--annotate` can't be combined with `--camera`"))
+            }
+            _ => Ok(Some(options)),
+        }
+    }
+
+    /// Sets the output format, unless another one has already been chosen.
+    fn set_format(&mut self, format: Format) -> Result<(), String> {
+        if self.format != Format::Text && self.format != format {
+            return Err(String::from("`--json` and `--csv` are mutually exclusive"));
+        }
+
+        self.format = format;
+        Ok(())
+    }
+}
+
+fn main() {
+    process::exit(run());
+}
+
+/// Scans the files or the camera given on the command line, and returns
+/// the exit status.
+fn run() -> i32 {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("quirs-scan: {}\n\n{}", message, USAGE);
+            return EXIT_FAILURE;
+        }
+    };
+
+    if let Some(ref device) = options.camera {
+        return run_camera(device, &options);
+    }
+
+    let files = match batch::expand(&options.files) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("quirs-scan: {}", error);
+            return EXIT_FAILURE;
+        }
+    };
+    let annotate_dir = options.annotate_dir.as_ref().map(Path::new);
+
+    if let Some(dir) = annotate_dir {
+        if let Err(error) = fs::create_dir_all(dir) {
+            eprintln!("quirs-scan: {}: {}", dir.display(), error);
+            return EXIT_FAILURE;
+        }
+    }
+
+    let jobs = match options.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    let prefix_file = files.len() > 1 || options.files.iter().any(|f| Path::new(f).is_dir());
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut status = 0;
+    let mut num_decoded = 0;
+
+    if options.format == Format::Csv && writeln!(out, "{}", CSV_HEADER).is_err() {
+        return EXIT_FAILURE;
+    }
+
+    let scanned = batch::scan_all(files, jobs, annotate_dir, |path, result| {
+        let file = path.display().to_string();
+
+        match result {
+            Ok(report) => {
+                num_decoded += report.num_decoded();
+                print_report(&mut out, &options, &file, prefix_file, &report)
+            }
+            Err(error) => {
+                status = EXIT_FAILURE;
+                eprintln!("quirs-scan: {}: {}", file, error);
+                print_error(&mut out, options.format, &file, &error)
+            }
+        }
+    });
+
+    match scanned {
+        // Most likely, the reader of a pipe has gone away.
+        Err(Error::Io(_)) => status,
+        Err(error) => {
+            eprintln!("quirs-scan: {}", error);
+            EXIT_FAILURE
+        }
+        Ok(()) if status == 0 && options.fail_if_none && num_decoded == 0 => EXIT_NONE_FOUND,
+        Ok(()) => status,
+    }
+}
+
+/// Scans the camera `device`, and returns the exit status.
+#[cfg(feature = "v4l2")]
+fn run_camera(device: &str, options: &Options) -> i32 {
+    match camera::scan_camera(device, options) {
+        // The reader of the pipe has gone away, which is how it stops us.
+        Ok(()) | Err(Error::Io(io::ErrorKind::BrokenPipe)) => 0,
+        Err(error) => {
+            eprintln!("quirs-scan: {}: {}", device, error);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Fails, because camera support isn't compiled in.
+#[cfg(not(feature = "v4l2"))]
+fn run_camera(_device: &str, _options: &Options) -> i32 {
+    eprintln!("quirs-scan: `--camera` requires the `v4l2` feature");
+    EXIT_FAILURE
+}
+
+/// Prints the results of scanning `file` as requested by `options`. In
+/// plain text, payloads are prefixed by the file if `prefix_file` is set,
+/// and codes which couldn't be decoded are reported on the standard error.
+fn print_report<W: Write>(
+    out: &mut W,
+    options: &Options,
+    file: &str,
+    prefix_file: bool,
+    report: &Report,
+) -> io::Result<()> {
+    match options.format {
+        Format::Json => writeln!(out, "{{\"file\":{},\"report\":{}}}", json_string(file), report.to_json()),
+        Format::Csv => print_csv_rows(out, file, report),
+        Format::Text => {
+            for (index, outcome) in report.codes.iter().enumerate() {
+                match *outcome {
+                    Outcome::Decoded(ref code) => {
+                        if prefix_file {
+                            write!(out, "{}: ", file)?;
+                        }
+
+                        if options.corners {
+                            write!(out, "{}\t", format_corners(&code.corners))?;
+                        }
+
+                        writeln!(out, "{}", String::from_utf8_lossy(&code.payload))?;
+                    }
+                    Outcome::Failed { ref error, .. } => {
+                        eprintln!("quirs-scan: {}: code #{} couldn't be decoded: {}", file, index, error);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Prints a CSV row for every code in the report, or a single row with the
+/// status `none` if there are no codes.
+fn print_csv_rows<W: Write>(out: &mut W, file: &str, report: &Report) -> io::Result<()> {
+    if report.codes.is_empty() {
+        return writeln!(out, "{},,none,,,", csv_field(file));
+    }
+
+    for (index, outcome) in report.codes.iter().enumerate() {
+        match *outcome {
+            Outcome::Decoded(ref code) => writeln!(
+                out,
+                "{},{},decoded,{},{},",
+                csv_field(file),
+                index,
+                csv_field(&String::from_utf8_lossy(&code.payload)),
+                format_corners(&code.corners),
+            )?,
+            Outcome::Failed { ref corners, ref error } => writeln!(
+                out,
+                "{},{},failed,,{},{}",
+                csv_field(file),
+                index,
+                corners.as_ref().map(format_corners).unwrap_or_default(),
+                csv_field(&error.to_string()),
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the reason why `file` couldn't be scanned, unless the results
+/// are printed as plain text, in which case it only goes to the standard
+/// error.
+fn print_error<W: Write>(out: &mut W, format: Format, file: &str, error: &Error) -> io::Result<()> {
+    let message = error.to_string();
+
+    match format {
+        Format::Json => writeln!(out, "{{\"file\":{},\"error\":{}}}", json_string(file), json_string(&message)),
+        Format::Csv => writeln!(out, "{},,error,,,{}", csv_field(file), csv_field(&message)),
+        Format::Text => Ok(()),
+    }
+}
+
+/// Parses a frame size of the form `WIDTHxHEIGHT`.
+fn parse_size(size: &str) -> Option<Vec2D> {
+    let mut parts = size.splitn(2, 'x');
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+
+    Some(Vec2D { x, y })
+}
+
+/// Formats corners as space-separated `x,y` pairs.
+fn format_corners(corners: &[Vec2D; 4]) -> String {
+    corners
+        .iter()
+        .map(|corner| format!("{},{}", corner.x, corner.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Formats a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+
+    literal.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            control if control < ' ' => literal.push_str(&format!("\\u{:04x}", u32::from(control))),
+            other => literal.push(other),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,478 @@
+//! Reading the data bitstream of a QR code from its modules, in Rust.
+//!
+//! `quirc` only reports the decoded payload, and it stops at the first
+//! segment it doesn't understand, such as a structured append header.
+//! This module re-reads the symbol: it decodes the format information,
+//! removes the data mask, de-interleaves and error-corrects the codeword
+//! blocks, and walks the segments of the resulting bitstream.
+
+use std::vec::Vec;
+use geom::{ QrCode, Vec2D };
+use info::{ EccLevel, DataType, Segment, FormatInfo };
+use sequence::StructuredAppend;
+use error::{ Error, DecodingErrorKind, Result };
+use encoder::data_modules;
+use gf::rs_correct;
+use spec;
+
+/// The largest number of bit errors in the format information which can
+/// be corrected: the minimum distance of the BCH code is 7.
+const MAX_FORMAT_ERRORS: u32 = 3;
+
+/// The error-corrected data codewords of a code, and its parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Codewords {
+    /// The version of the code.
+    pub version: u8,
+    /// The error correction level of the code.
+    pub ecc_level: EccLevel,
+    /// The data mask of the code.
+    pub mask: u8,
+    /// The data codewords, after error correction, in order.
+    pub data: Vec<u8>,
+    /// The number of codewords which were corrected.
+    pub corrected: usize,
+}
+
+/// Returns the version corresponding to the size of a code.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+pub fn version_from_size(size: usize) -> Result<u8> {
+    if size < spec::size(spec::MIN_VERSION) || size % 4 != 1 {
+        Err(Error::DecodingFailed(DecodingErrorKind::InvalidGridSize))
+    } else if size > spec::size(spec::MAX_VERSION) {
+        Err(Error::DecodingFailed(DecodingErrorKind::InvalidVersion))
+    } else {
+        Ok(((size - 17) / 4) as u8)
+    }
+}
+
+/// Reads the data codewords of a code, correcting errors.
+pub fn read_codewords(code: &QrCode) -> Result<Codewords> {
+    let version = version_from_size(code.size())?;
+    let FormatInfo { ecc_level, mask_id: mask, .. } = read_format(code)?;
+    let total = spec::total_codewords(version);
+    let mut raw = vec![0; total];
+
+    for (i, coord) in data_modules(version).into_iter().take(total * 8).enumerate() {
+        let dark = code.bit_at(coord) != spec::mask_bit(mask, coord.x, coord.y);
+        raw[i / 8] |= u8::from(dark) << (7 - i % 8);
+    }
+
+    let (data, corrected) = deinterleave_and_correct(&raw, version, ecc_level)?;
+
+    Ok(Codewords { version, ecc_level, mask, data, corrected })
+}
+
+/// Reads both copies of the format information, and returns the valid
+/// format closest to either of them.
+pub fn read_format(code: &QrCode) -> Result<FormatInfo> {
+    let size = code.size();
+
+    version_from_size(size)?;
+
+    let bit = |x, y| u16::from(code.bit_at(Vec2D { x, y }));
+    let mut first = 0;
+    let mut second = 0;
+
+    for i in 0..15 {
+        let (x, y) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        first |= bit(x, y) << i;
+
+        let (x, y) = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+        second |= bit(x, y) << i;
+    }
+
+    let levels = [EccLevel::L, EccLevel::M, EccLevel::Q, EccLevel::H];
+    let candidates = levels.iter().flat_map(|&ecc_level| (0..8).map(move |mask| (ecc_level, mask)));
+
+    candidates
+        .map(|(ecc_level, mask_id)| {
+            let bits = spec::format_bits(ecc_level, mask_id);
+            let bit_errors = (bits ^ first).count_ones().min((bits ^ second).count_ones());
+            FormatInfo { ecc_level, mask_id, bit_errors }
+        })
+        .filter(|format| format.bit_errors <= MAX_FORMAT_ERRORS)
+        .min_by_key(|format| format.bit_errors)
+        .ok_or(Error::DecodingFailed(DecodingErrorKind::FormatEcc))
+}
+
+/// Splits the interleaved codewords into blocks, corrects each of them,
+/// and concatenates their data codewords. This is the inverse of the
+/// interleaving performed by the encoder. Returns the data codewords and
+/// the total number of corrected codewords.
+fn deinterleave_and_correct(raw: &[u8], version: u8, ecc_level: EccLevel) -> Result<(Vec<u8>, usize)> {
+    let num_blocks = spec::ecc_blocks(version, ecc_level);
+    let ecc_len = spec::ecc_codewords_per_block(version, ecc_level);
+    let num_short_blocks = num_blocks - raw.len() % num_blocks;
+    let short_block_len = raw.len() / num_blocks;
+    let short_data_len = short_block_len - ecc_len;
+
+    // Short blocks are one data codeword shorter than long ones; that
+    // position is skipped while interleaving.
+    let mut blocks = vec![Vec::with_capacity(short_block_len + 1); num_blocks];
+    let mut codewords = raw.iter();
+
+    for i in 0..=short_block_len {
+        for (j, block) in blocks.iter_mut().enumerate() {
+            if i != short_data_len || j >= num_short_blocks {
+                block.extend(codewords.next());
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(spec::data_codewords(version, ecc_level));
+    let mut corrected = 0;
+
+    for mut block in blocks {
+        corrected += rs_correct(&mut block, ecc_len)
+            .ok_or(Error::DecodingFailed(DecodingErrorKind::DataEcc))?;
+
+        let data_len = block.len() - ecc_len;
+        data.extend_from_slice(&block[..data_len]);
+    }
+
+    Ok((data, corrected))
+}
+
+/// The data codewords of a code, as kept in an `Info`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawData {
+    /// The version of the code, which determines the bitstream syntax.
+    version: u8,
+    /// The number of codewords which were corrected.
+    corrected: usize,
+    /// The data codewords.
+    data: Vec<u8>,
+}
+
+impl RawData {
+    /// Copies the data codewords.
+    pub fn new(codewords: &Codewords) -> Self {
+        RawData {
+            version: codewords.version,
+            corrected: codewords.corrected,
+            data: codewords.data.clone(),
+        }
+    }
+
+    /// Returns the version of the code.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the data codewords.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the number of codewords which were corrected.
+    pub fn corrected(&self) -> usize {
+        self.corrected
+    }
+}
+
+/// A cursor over a sequence of bits, most significant bit first.
+#[derive(Debug, Clone, Copy)]
+pub struct BitReader<'a> {
+    /// The bytes containing the bits.
+    data: &'a [u8],
+    /// The index of the next bit.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader at the beginning of `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    /// Returns the index of the next bit.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the number of bits which haven't been read yet.
+    pub fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    /// Reads `len` (at most 32) bits as an unsigned integer.
+    pub fn read(&mut self, len: usize) -> Result<u32> {
+        if len > self.remaining() {
+            return Err(Error::DecodingFailed(DecodingErrorKind::DataUnderflow));
+        }
+
+        let value = (self.pos..self.pos + len).fold(0, |acc, i| {
+            acc << 1 | u32::from((self.data[i / 8] >> (7 - i % 8)) & 1)
+        });
+
+        self.pos += len;
+
+        Ok(value)
+    }
+}
+
+/// The contents of a bitstream, decoded the same way as by `quirc`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Payload {
+    /// The structured append header, if any.
+    pub structured_append: Option<StructuredAppend>,
+    /// The last ECI assignment number, if any.
+    pub eci: Option<u32>,
+    /// The highest-valued data type of the segments.
+    pub data_type: Option<DataType>,
+    /// The data segments, in order.
+    pub segments: Vec<Segment>,
+    /// The concatenated contents of the segments.
+    pub bytes: Vec<u8>,
+    /// The number of bits before the terminator, or in the whole
+    /// bitstream if it has none.
+    pub bits_used: usize,
+}
+
+/// Decodes the segments of the bitstream made up by the data codewords.
+pub fn decode_payload(data: &[u8], version: u8) -> Result<Payload> {
+    let mut reader = BitReader::new(data);
+    let mut payload = Payload {
+        structured_append: None,
+        eci: None,
+        data_type: None,
+        segments: Vec::new(),
+        bytes: Vec::new(),
+        bits_used: data.len() * 8,
+    };
+
+    while reader.remaining() >= 4 {
+        let position = reader.position();
+        let data_type = match reader.read(4)? {
+            0b0000 => { // terminator
+                payload.bits_used = position;
+                break;
+            }
+            0b0001 => DataType::Numeric,
+            0b0010 => DataType::Alphanumeric,
+            0b0100 => DataType::Byte,
+            0b1000 => DataType::Kanji,
+            0b0011 => {
+                payload.structured_append = Some(read_structured_append(&mut reader)?);
+                continue;
+            }
+            0b0111 => {
+                payload.eci = Some(read_eci(&mut reader)?);
+                continue;
+            }
+            // FNC1 in the first position carries no data, in the second
+            // position, it carries an application indicator.
+            0b0101 => continue,
+            0b1001 => {
+                reader.read(8)?;
+                continue;
+            }
+            _ => return Err(Error::DecodingFailed(DecodingErrorKind::UnknownDataType)),
+        };
+
+        let char_count = reader.read(spec::char_count_bits(version, data_type))? as usize;
+        let mut bytes = Vec::new();
+
+        read_segment_data(&mut reader, data_type, char_count, &mut bytes)?;
+        payload.data_type = payload.data_type.max(Some(data_type));
+        payload.bytes.extend_from_slice(&bytes);
+        payload.segments.push(Segment { data_type, char_count, eci: payload.eci, bytes });
+    }
+
+    Ok(payload)
+}
+
+/// Reads the contents of a structured append header after its mode
+/// indicator.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn read_structured_append(reader: &mut BitReader) -> Result<StructuredAppend> {
+    let index = reader.read(4)? as u8;
+    let total = reader.read(4)? as u8 + 1;
+    let parity = reader.read(8)? as u8;
+
+    Ok(StructuredAppend { index, total, parity })
+}
+
+/// Reads an ECI designator after its mode indicator, which is 1, 2 or 3
+/// bytes long, the length being encoded in its leading bits.
+fn read_eci(reader: &mut BitReader) -> Result<u32> {
+    let first = reader.read(8)?;
+
+    if first & 0x80 == 0 {
+        Ok(first)
+    } else if first & 0xc0 == 0x80 {
+        Ok((first & 0x3f) << 8 | reader.read(8)?)
+    } else if first & 0xe0 == 0xc0 {
+        Ok((first & 0x1f) << 16 | reader.read(16)?)
+    } else {
+        Err(Error::DecodingFailed(DecodingErrorKind::UnknownDataType))
+    }
+}
+
+/// Reads the `count` characters of a segment, and appends them to `out`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn read_segment_data(
+    reader: &mut BitReader,
+    data_type: DataType,
+    count: usize,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    /// The characters of the alphanumeric mode, in the order of their values.
+    const ALPHANUMERIC_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+    let overflow = Error::DecodingFailed(DecodingErrorKind::DataOverflow);
+
+    match data_type {
+        DataType::Numeric => {
+            let mut left = count;
+
+            while left > 0 {
+                let digits = left.min(3);
+                let value = reader.read(digits * 3 + 1)?;
+
+                if value >= [0, 10, 100, 1000][digits] {
+                    return Err(overflow);
+                }
+
+                let text = format!("{:0width$}", value, width = digits);
+                out.extend_from_slice(text.as_bytes());
+                left -= digits;
+            }
+        }
+        DataType::Alphanumeric => {
+            let mut left = count;
+
+            while left > 0 {
+                let chars = left.min(2);
+                let value = reader.read(chars * 5 + 1)? as usize;
+
+                if value >= [0, 45, 45 * 45][chars] {
+                    return Err(overflow);
+                }
+
+                if chars == 2 {
+                    out.push(ALPHANUMERIC_CHARSET[value / 45]);
+                }
+
+                out.push(ALPHANUMERIC_CHARSET[value % 45]);
+                left -= chars;
+            }
+        }
+        DataType::Byte => for _ in 0..count {
+            out.push(reader.read(8)? as u8);
+        },
+        DataType::Kanji => for _ in 0..count {
+            let value = reader.read(13)?;
+            let intermediate = ((value / 0xc0) << 8) | (value % 0xc0);
+            let shift_jis = if intermediate + 0x8140 <= 0x9ffc {
+                intermediate + 0x8140
+            } else {
+                intermediate + 0xc140
+            };
+
+            out.push((shift_jis >> 8) as u8);
+            out.push(shift_jis as u8);
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use gf::{ rs_generator, rs_remainder };
+    use super::*;
+
+    /// Packs `(value, len)` fields, most significant bit first, and pads
+    /// the result with zero bytes to `num_bytes`.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn pack(fields: &[(u32, usize)], num_bytes: usize) -> Vec<u8> {
+        let mut bytes = vec![0; num_bytes];
+        let mut pos = 0;
+
+        for &(value, len) in fields {
+            for i in (0..len).rev() {
+                bytes[pos / 8] |= (((value >> i) & 1) as u8) << (7 - pos % 8);
+                pos += 1;
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn short_and_long_blocks_are_deinterleaved() {
+        // Two blocks of 15 data codewords and two of 16.
+        let (version, ecc_level) = (5, EccLevel::Q);
+        let num_blocks = spec::ecc_blocks(version, ecc_level);
+        let ecc_len = spec::ecc_codewords_per_block(version, ecc_level);
+        let data: Vec<u8> = (0..spec::data_codewords(version, ecc_level)).map(|i| i as u8).collect();
+        let blocks: Vec<&[u8]> = vec![&data[..15], &data[15..30], &data[30..46], &data[46..]];
+        let ecc: Vec<Vec<u8>> = blocks
+            .iter()
+            .map(|block| rs_remainder(block, &rs_generator(ecc_len)))
+            .collect();
+
+        assert_eq!(num_blocks, blocks.len());
+
+        // The data codewords are interleaved first, where the short blocks
+        // have run out, the long ones go on; then the ECC codewords.
+        let mut raw = Vec::new();
+
+        for i in 0..16 {
+            raw.extend(blocks.iter().filter_map(|block| block.get(i)));
+        }
+
+        for i in 0..ecc_len {
+            raw.extend(ecc.iter().map(|block| block[i]));
+        }
+
+        assert_eq!(raw.len(), spec::total_codewords(version));
+        let corrected = deinterleave_and_correct(&raw, version, ecc_level).expect("correction failed");
+
+        assert_eq!(corrected, (data.clone(), 0));
+
+        // The last data codeword of a long block, and an ECC codeword of
+        // a short one.
+        raw[61] ^= 0xff;
+        raw[62] ^= 0x01;
+
+        let corrected = deinterleave_and_correct(&raw, version, ecc_level).expect("correction failed");
+
+        assert_eq!(corrected, (data, 2));
+    }
+
+    #[test]
+    fn structured_append_header_is_parsed() {
+        let data = pack(&[
+            (0b0011, 4), (2, 4), (3, 4), (0x5a, 8), // symbol 3 of 4, parity 0x5a
+            (0b0100, 4), (2, 8), (u32::from(b'h'), 8), (u32::from(b'i'), 8),
+            (0b0000, 4),
+        ], 16);
+        let payload = decode_payload(&data, 1).expect("decoding failed");
+
+        assert_eq!(payload.structured_append, Some(StructuredAppend { index: 2, total: 4, parity: 0x5a }));
+        assert_eq!(payload.bytes, b"hi");
+        assert_eq!(payload.segments.len(), 1);
+        assert_eq!(payload.bits_used, 48);
+    }
+
+    #[test]
+    fn truncated_structured_append_header_is_rejected() {
+        let data = pack(&[(0b0011, 4), (2, 4), (3, 4)], 2);
+
+        assert!(matches!(
+            decode_payload(&data, 1),
+            Err(Error::DecodingFailed(DecodingErrorKind::DataUnderflow))
+        ));
+    }
+}
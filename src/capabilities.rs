@@ -0,0 +1,163 @@
+//! Runtime discovery of optional functionality.
+//!
+//! Parts of the crate depend on optional Cargo features. Rather than
+//! disappearing when such a feature is disabled, the affected APIs keep
+//! working in a degraded manner, e.g. single-threaded instead of parallel.
+//! This module reports which features were compiled in, and what the
+//! affected APIs fall back to otherwise, so that integrators can detect
+//! (and log) degraded behavior at runtime instead of being surprised by it.
+
+/// An optional piece of functionality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capability {
+    /// The name of the Cargo feature providing the functionality.
+    pub feature: &'static str,
+    /// Whether the feature was enabled when the crate was compiled.
+    pub available: bool,
+    /// What the functionality is.
+    pub description: &'static str,
+    /// How the affected APIs behave when the feature is disabled.
+    pub fallback: &'static str,
+}
+
+impl Capability {
+    /// Returns `true` if the affected APIs run in their fallback mode.
+    pub fn is_degraded(&self) -> bool {
+        !self.available
+    }
+}
+
+/// Every optional capability of the crate, in alphabetical order of the
+/// name of the corresponding feature.
+const CAPABILITIES: &[Capability] = &[
+    Capability {
+        feature: "bitvec",
+        available: cfg!(feature = "bitvec"),
+        description: "the modules of codes as `bitvec` bit slices",
+        fallback: "modules are only available as booleans or packed bytes",
+    },
+    Capability {
+        feature: "capi",
+        available: cfg!(feature = "capi"),
+        description: "a C API over the decoder, exported from a `cdylib` (`capi`)",
+        fallback: "C code must link against `quirc` directly",
+    },
+    Capability {
+        feature: "encoding_rs",
+        available: cfg!(feature = "encoding_rs"),
+        description: "decoding text in legacy character sets, including Shift JIS",
+        fallback: "only UTF-8, UTF-16, UTF-32, ISO-8859-1 and ASCII are decoded",
+    },
+    Capability {
+        feature: "gstreamer",
+        available: cfg!(feature = "gstreamer"),
+        description: "scanning the frames of a GStreamer pipeline (`video::AppSinkScanner`)",
+        fallback: "frames must be pulled from the pipeline by the application",
+    },
+    Capability {
+        feature: "jpeg-decoder",
+        available: cfg!(feature = "jpeg-decoder"),
+        description: "loading JPEG images (`ImageBuf::load()`)",
+        fallback: "only PNG (with the `png` feature) and Netpbm images are loaded",
+    },
+    Capability {
+        feature: "mobile",
+        available: cfg!(feature = "mobile"),
+        description: "UniFFI bindings for Kotlin and Swift apps (`mobile`)",
+        fallback: "Android and iOS apps must go through the C API",
+    },
+    Capability {
+        feature: "ndarray",
+        available: cfg!(feature = "ndarray"),
+        description: "decoding `ndarray` arrays, and corners as arrays (`array`)",
+        fallback: "arrays must be converted to slices by the application",
+    },
+    Capability {
+        feature: "opencv",
+        available: cfg!(feature = "opencv"),
+        description: "converting OpenCV `Mat`s and drawing outlines onto them (`cv`)",
+        fallback: "frames must be converted to grayscale slices by the application",
+    },
+    Capability {
+        feature: "png",
+        available: cfg!(feature = "png"),
+        description: "loading PNG images (`ImageBuf::load()`)",
+        fallback: "only JPEG (with the `jpeg-decoder` feature) and Netpbm images are loaded",
+    },
+    Capability {
+        feature: "prometheus",
+        available: cfg!(feature = "prometheus"),
+        description: "aggregating decoder metrics for Prometheus (`metrics::PrometheusRecorder`)",
+        fallback: "`Metrics` must be implemented by the application",
+    },
+    Capability {
+        feature: "python",
+        available: cfg!(feature = "python"),
+        description: "Python bindings taking NumPy arrays (`python`)",
+        fallback: "Python code must go through the C API or `quirc` directly",
+    },
+    Capability {
+        feature: "rayon",
+        available: cfg!(feature = "rayon"),
+        description: "parallel decoding of the codes in an image (`Detections::par_decode()`)",
+        fallback: "the codes are decoded sequentially, on the calling thread",
+    },
+    Capability {
+        feature: "rqrr",
+        available: cfg!(feature = "rqrr"),
+        description: "detecting codes in pure Rust with `rqrr` (`backend::RqrrBackend`)",
+        fallback: "codes are detected by the `quirc` C library",
+    },
+    Capability {
+        feature: "serde",
+        available: cfg!(feature = "serde"),
+        description: "serialization of codes, decoded information and errors with Serde",
+        fallback: "results can only be exported through the `schema` module",
+    },
+    Capability {
+        feature: "test-util",
+        available: cfg!(feature = "test-util"),
+        description: "corpus replay and encode/decode round trips for tests (`testutil`)",
+        fallback: "regression harnesses must be written by the application",
+    },
+    Capability {
+        feature: "tokio",
+        available: cfg!(feature = "tokio"),
+        description: "asynchronous decoding on the blocking thread pool of tokio",
+        fallback: "`asynch::decode_image_async()` spawns a thread for every image",
+    },
+    Capability {
+        feature: "tracing",
+        available: cfg!(feature = "tracing"),
+        description: "`tracing` spans around detection, extraction and decoding",
+        fallback: "the decoder must be timed by the application",
+    },
+    Capability {
+        feature: "v4l2",
+        available: cfg!(feature = "v4l2"),
+        description: "capturing frames from Video4Linux devices (`video::CameraSource`)",
+        fallback: "frames must be captured by the application",
+    },
+    Capability {
+        feature: "zbar",
+        available: cfg!(feature = "zbar"),
+        description: "comparing against the ZBar library (`zbar::ZbarBackend`)",
+        fallback: "backends can only be compared with `quirc` and `rqrr`",
+    },
+];
+
+/// Returns every optional capability of this build of the crate.
+pub fn report() -> &'static [Capability] {
+    CAPABILITIES
+}
+
+/// Returns the capabilities that are unavailable in this build.
+pub fn degraded() -> impl Iterator<Item = &'static Capability> {
+    CAPABILITIES.iter().filter(|c| c.is_degraded())
+}
+
+/// Returns whether the named feature was enabled at compile time.
+/// Unknown feature names are reported as unavailable.
+pub fn is_available(feature: &str) -> bool {
+    CAPABILITIES.iter().any(|c| c.feature == feature && c.available)
+}
@@ -0,0 +1,366 @@
+//! A C API over the decoder, for applications not written in Rust.
+//!
+//! Unlike the raw `quirc` API, it copies images with padded rows, honors
+//! exclusion zones and the image size limit, and returns decoded codes
+//! together with their ECI designators in a single call. The functions
+//! are annotated for `cbindgen`, which generates `quirs.h` according to
+//! `cbindgen.toml`, and they are exported once the crate is built as a
+//! `cdylib` or a `staticlib` with the `capi` feature:
+//!
+//! ```shell
+//! cargo rustc --release --features capi --crate-type cdylib
+//! cbindgen --config cbindgen.toml --output quirs.h
+//! ```
+//!
+//! Every function returning a `QuirsStatus` reports failures through it,
+//! including null pointers and panics, which are never unwound into C.
+
+use std::ptr;
+use std::slice;
+use std::os::raw::c_char;
+use std::panic::{ self, AssertUnwindSafe };
+use decoder::Decoder;
+use geom::{ Image, Vec2D };
+use mask::ExclusionZone;
+use schema::CodeRecord;
+use error::Error;
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuirsStatus {
+    /// The call succeeded.
+    QuirsOk = 0,
+    /// A required pointer argument was null.
+    QuirsNullPointer,
+    /// Memory could not be allocated.
+    QuirsAllocFailed,
+    /// The buffer is too small for the dimensions of the image.
+    QuirsSizeMismatch,
+    /// A size is too large to be handled.
+    QuirsIntOverflow,
+    /// The image has more pixels than the decoder accepts.
+    QuirsImageTooLarge,
+    /// An argument is outside of its valid range.
+    QuirsInvalidArgument,
+    /// An internal error occurred.
+    QuirsInternalError,
+}
+
+impl From<Error> for QuirsStatus {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::AllocFailed           => QuirsStatus::QuirsAllocFailed,
+            Error::SizeMismatch { .. }   => QuirsStatus::QuirsSizeMismatch,
+            Error::IntOverflow(_)        => QuirsStatus::QuirsIntOverflow,
+            Error::ImageTooLarge { .. }  => QuirsStatus::QuirsImageTooLarge,
+            Error::InvalidArgument       => QuirsStatus::QuirsInvalidArgument,
+            _                            => QuirsStatus::QuirsInternalError,
+        }
+    }
+}
+
+/// A point in an image, in pixels.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct QuirsPoint {
+    /// The column of the point.
+    pub x: usize,
+    /// The row of the point.
+    pub y: usize,
+}
+
+/// A decoded QR code. The payload is owned by the `QuirsResults` the code
+/// belongs to.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct QuirsCode {
+    /// The corners of the code, clockwise from the top left one.
+    pub corners: [QuirsPoint; 4],
+    /// The version of the code, from 1 to 40.
+    pub version: u8,
+    /// The error correction level: 0 for M, 1 for L, 2 for H, 3 for Q.
+    pub ecc_level: u8,
+    /// The mask ID, from 0 to 7.
+    pub mask_id: u8,
+    /// The highest-valued data type: 1 for numeric, 2 for alphanumeric,
+    /// 4 for bytes and 8 for Kanji.
+    pub data_type: u8,
+    /// The ECI assignment number, or 0 if there is none.
+    pub eci: u32,
+    /// The raw payload. It isn't NUL-terminated.
+    pub payload: *const u8,
+    /// The length of the payload in bytes.
+    pub payload_len: usize,
+}
+
+/// The codes decoded from an image.
+#[derive(Debug)]
+pub struct QuirsResults {
+    /// The codes, whose payloads point into `records`.
+    codes: Vec<QuirsCode>,
+    /// The owners of the payloads, which are only accessed through `codes`.
+    #[allow(dead_code)]
+    records: Vec<CodeRecord>,
+    /// The number of codes which were detected, but couldn't be decoded.
+    failed: usize,
+}
+
+/// Runs `f`, turning a panic into `QuirsStatus::QuirsInternalError`.
+fn guard<F: FnOnce() -> QuirsStatus>(f: F) -> QuirsStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(QuirsStatus::QuirsInternalError)
+}
+
+/// Returns a static, NUL-terminated description of `status`.
+#[no_mangle]
+pub extern "C" fn quirs_status_str(status: QuirsStatus) -> *const c_char {
+    let message: &'static [u8] = match status {
+        QuirsStatus::QuirsOk              => b"success\0",
+        QuirsStatus::QuirsNullPointer     => b"null pointer argument\0",
+        QuirsStatus::QuirsAllocFailed     => b"memory allocation failed\0",
+        QuirsStatus::QuirsSizeMismatch    => b"buffer size doesn't match image dimensions\0",
+        QuirsStatus::QuirsIntOverflow     => b"size is out of range\0",
+        QuirsStatus::QuirsImageTooLarge   => b"image exceeds the maximal area\0",
+        QuirsStatus::QuirsInvalidArgument => b"invalid argument\0",
+        QuirsStatus::QuirsInternalError   => b"internal error\0",
+    };
+
+    message.as_ptr() as *const c_char
+}
+
+/// Creates a decoder, or returns null if memory couldn't be allocated.
+/// It must be freed with `quirs_decoder_free()`.
+#[no_mangle]
+pub extern "C" fn quirs_decoder_new() -> *mut Decoder {
+    panic::catch_unwind(Decoder::new)
+        .ok()
+        .and_then(|decoder| decoder.ok())
+        .map_or(ptr::null_mut(), |decoder| Box::into_raw(Box::new(decoder)))
+}
+
+/// Frees a decoder. Does nothing if `decoder` is null.
+///
+/// # Safety
+///
+/// `decoder` must be null or have been returned by `quirs_decoder_new()`,
+/// and it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_decoder_free(decoder: *mut Decoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Rejects images with more than `limit` pixels, or lifts the limit if
+/// `limit` is 0.
+///
+/// # Safety
+///
+/// `decoder` must be null or a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_decoder_set_max_image_area(
+    decoder: *mut Decoder,
+    limit: usize,
+) -> QuirsStatus {
+    let decoder = match decoder.as_mut() {
+        Some(decoder) => decoder,
+        None => return QuirsStatus::QuirsNullPointer,
+    };
+
+    decoder.config_mut().max_image_area = if limit == 0 { None } else { Some(limit) };
+
+    QuirsStatus::QuirsOk
+}
+
+/// Hides the given rectangle of every subsequent image from the detector.
+///
+/// # Safety
+///
+/// `decoder` must be null or a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_decoder_add_exclusion_rect(
+    decoder: *mut Decoder,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> QuirsStatus {
+    let decoder = match decoder.as_mut() {
+        Some(decoder) => decoder,
+        None => return QuirsStatus::QuirsNullPointer,
+    };
+    let zone = ExclusionZone::Rect {
+        origin: Vec2D { x, y },
+        size: Vec2D { x: width, y: height },
+    };
+
+    guard(|| {
+        decoder.config_mut().exclusion_zones.push(zone);
+        QuirsStatus::QuirsOk
+    })
+}
+
+/// Removes every exclusion zone of the decoder.
+///
+/// # Safety
+///
+/// `decoder` must be null or a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_decoder_clear_exclusion_zones(decoder: *mut Decoder) -> QuirsStatus {
+    match decoder.as_mut() {
+        Some(decoder) => {
+            decoder.config_mut().exclusion_zones.clear();
+            QuirsStatus::QuirsOk
+        }
+        None => QuirsStatus::QuirsNullPointer,
+    }
+}
+
+/// Detects and decodes the QR codes in an 8-bit grayscale image, whose
+/// rows are `stride` bytes apart. On success, `*results` is set to the
+/// decoded codes, which must be freed with `quirs_results_free()`;
+/// otherwise it is set to null.
+///
+/// # Safety
+///
+/// `decoder` must be null or a valid decoder. `data` must be null or
+/// point to at least `stride * (height - 1) + width` readable bytes.
+/// `results` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_decode_gray(
+    decoder: *mut Decoder,
+    data: *const u8,
+    width: usize,
+    height: usize,
+    stride: usize,
+    results: *mut *mut QuirsResults,
+) -> QuirsStatus {
+    if results.is_null() {
+        return QuirsStatus::QuirsNullPointer;
+    }
+
+    *results = ptr::null_mut();
+
+    let decoder = match decoder.as_mut() {
+        Some(decoder) => decoder,
+        None => return QuirsStatus::QuirsNullPointer,
+    };
+
+    if data.is_null() {
+        return QuirsStatus::QuirsNullPointer;
+    }
+
+    let len = match stride.checked_mul(height.saturating_sub(1)).and_then(|n| n.checked_add(width)) {
+        Some(len) if height > 0 => len,
+        Some(_) => 0,
+        None => return QuirsStatus::QuirsIntOverflow,
+    };
+    let bytes = slice::from_raw_parts(data, len);
+
+    guard(|| {
+        let image = match Image::with_stride(bytes, Vec2D { x: width, y: height }, stride) {
+            Ok(image) => image,
+            Err(error) => return error.into(),
+        };
+        let detections = match decoder.decode_image(&image) {
+            Ok(detections) => detections,
+            Err(error) => return error.into(),
+        };
+        let mut records = Vec::new();
+        let mut failed = 0;
+
+        for decoded in detections.decoded() {
+            match decoded {
+                Ok((code, info)) => records.push(CodeRecord::new(&code, &info)),
+                Err(_) => failed += 1,
+            }
+        }
+
+        *results = Box::into_raw(Box::new(QuirsResults::new(records, failed)));
+
+        QuirsStatus::QuirsOk
+    })
+}
+
+impl QuirsResults {
+    /// Lays out the decoded codes for C.
+    fn new(records: Vec<CodeRecord>, failed: usize) -> Self {
+        let codes = records
+            .iter()
+            .map(|record| {
+                let mut corners = [QuirsPoint::default(); 4];
+
+                for (corner, point) in corners.iter_mut().zip(&record.corners) {
+                    corner.x = point.x;
+                    corner.y = point.y;
+                }
+
+                QuirsCode {
+                    corners,
+                    version: record.version,
+                    ecc_level: record.ecc_level as u8,
+                    mask_id: record.mask_id,
+                    data_type: record.data_type as u8,
+                    eci: record.eci,
+                    payload: record.payload.as_ptr(),
+                    payload_len: record.payload.len(),
+                }
+            })
+            .collect();
+
+        // Moving the records doesn't move the heap buffers of the
+        // payloads, so the pointers stay valid.
+        QuirsResults { codes, records, failed }
+    }
+}
+
+/// Returns the number of decoded codes, or 0 if `results` is null.
+///
+/// # Safety
+///
+/// `results` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_results_count(results: *const QuirsResults) -> usize {
+    results.as_ref().map_or(0, |results| results.codes.len())
+}
+
+/// Returns the number of codes which were detected but couldn't be
+/// decoded, or 0 if `results` is null.
+///
+/// # Safety
+///
+/// `results` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_results_failed(results: *const QuirsResults) -> usize {
+    results.as_ref().map_or(0, |results| results.failed)
+}
+
+/// Returns the decoded code at `index`, or null if `results` is null or
+/// `index` is out of bounds. The code lives as long as `results`.
+///
+/// # Safety
+///
+/// `results` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_results_get(
+    results: *const QuirsResults,
+    index: usize,
+) -> *const QuirsCode {
+    results
+        .as_ref()
+        .and_then(|results| results.codes.get(index))
+        .map_or(ptr::null(), |code| code)
+}
+
+/// Frees decoded codes. Does nothing if `results` is null.
+///
+/// # Safety
+///
+/// `results` must be null or have been returned by `quirs_decode_gray()`,
+/// and neither it nor its codes may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn quirs_results_free(results: *mut QuirsResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
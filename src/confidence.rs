@@ -0,0 +1,196 @@
+//! Scoring how reliable a detection is.
+//!
+//! `quirc` reports every code it can decode the same way, whether it was
+//! read from a crisp, frontal image or barely made it through the error
+//! correction. The signals collected here tell these apart, so that
+//! applications can rank detections, or discard doubtful ones:
+//!
+//! * how many modules of the finder and timing patterns were read wrong,
+//! * how many bits of the format information had to be corrected,
+//! * how large the modules appear in the image,
+//! * how much the dark and light modules of the finder patterns differ in
+//!   brightness, if the image is available, and
+//! * how much of the capacity of the error correction was used up, which
+//!   is only known after decoding (see `Info::confidence()`).
+//!
+//! Every signal is a number between 0 (worst) and 1 (best), and scores are
+//! the products of the signals, so a single bad signal makes for a bad score.
+
+use geom::{ Image, QrCode, Vec2D, Vec2Df };
+use info::Info;
+use perspective::sample;
+use spec;
+
+/// Modules at least this large, in pixels, are considered to be read
+/// reliably. Smaller ones are increasingly blurred and aliased.
+const FULL_MODULE_SIZE: f64 = 4.0;
+
+/// The difference between the mean brightness of the light and the dark
+/// modules of the finder patterns considered to be perfect contrast.
+const FULL_CONTRAST: f64 = 128.0;
+
+/// The number of bit errors in the format information which make it
+/// impossible to correct.
+const FORMAT_ERROR_LIMIT: u32 = 4;
+
+/// The signals the confidence in a code is computed from, each between 0
+/// (worst) and 1 (best).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Signals {
+    /// The fraction of the modules of the finder patterns, their
+    /// separators and the timing patterns which were read as expected.
+    pub function_patterns: f64,
+    /// 1 if the format information was read without errors, decreasing
+    /// with each corrected bit, and 0 if it couldn't be corrected.
+    pub format: f64,
+    /// The apparent size of the modules relative to `FULL_MODULE_SIZE`,
+    /// capped at 1.
+    pub module_size: f64,
+    /// The difference between the brightness of the light and the dark
+    /// modules of the finder patterns relative to `FULL_CONTRAST`, capped
+    /// at 1. `None` if the image wasn't available, or the finder patterns
+    /// lie outside of it.
+    pub contrast: Option<f64>,
+}
+
+impl Signals {
+    /// Returns the product of the signals which are available.
+    pub fn score(&self) -> f64 {
+        self.function_patterns * self.format * self.module_size * self.contrast.unwrap_or(1.0)
+    }
+}
+
+impl QrCode {
+    /// Returns the confidence in the code, between 0 and 1, based on the
+    /// code alone. See `confidence_signals()` for its ingredients.
+    pub fn confidence(&self) -> f64 {
+        self.confidence_signals(None).score()
+    }
+
+    /// Returns the confidence in the code, between 0 and 1, taking the
+    /// contrast of its finder patterns in `image` into account, too.
+    /// `image` must be the image the code was detected in.
+    pub fn confidence_in(&self, image: &Image) -> f64 {
+        self.confidence_signals(Some(image)).score()
+    }
+
+    /// Computes the signals the confidence in the code is based on. The
+    /// contrast is only measured if `image` is given.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn confidence_signals(&self, image: Option<&Image>) -> Signals {
+        let format = self.format_info().map_or(0.0, |format| {
+            1.0 - f64::from(format.bit_errors) / f64::from(FORMAT_ERROR_LIMIT)
+        });
+
+        Signals {
+            function_patterns: self.function_pattern_fraction(),
+            format,
+            module_size: (self.module_size_px() / FULL_MODULE_SIZE).min(1.0),
+            contrast: image.and_then(|image| self.finder_contrast(image)),
+        }
+    }
+
+    /// Returns the fraction of the modules of the finder patterns, their
+    /// separators and the timing patterns which have the expected color.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn function_pattern_fraction(&self) -> f64 {
+        let mut total = 0_usize;
+        let mut matching = 0_usize;
+
+        self.for_each_finder_module(true, |coord, dark| {
+            total += 1;
+            matching += usize::from(self.bit_at(coord) == dark);
+        });
+
+        let size = self.size();
+
+        for i in 8..size.saturating_sub(8) {
+            for &coord in &[Vec2D { x: i, y: 6 }, Vec2D { x: 6, y: i }] {
+                total += 1;
+                matching += usize::from(self.bit_at(coord) == (i % 2 == 0));
+            }
+        }
+
+        if total == 0 {
+            0.0
+        } else {
+            matching as f64 / total as f64
+        }
+    }
+
+    /// Returns the difference between the mean brightness of the light and
+    /// the dark modules of the finder patterns in `image`, relative to
+    /// `FULL_CONTRAST`, or `None` if any of them lies outside the image.
+    fn finder_contrast(&self, image: &Image) -> Option<f64> {
+        let mut light = (0.0, 0.0);
+        let mut dark = (0.0, 0.0);
+        let mut inside = true;
+
+        self.for_each_finder_module(false, |coord, is_dark| {
+            let (x, y) = self.grid_to_image(coord);
+
+            match sample(image, Vec2Df { x, y }) {
+                Some(value) => {
+                    let sum = if is_dark { &mut dark } else { &mut light };
+                    sum.0 += value;
+                    sum.1 += 1.0;
+                }
+                None => inside = false,
+            }
+        });
+
+        if !inside || light.1 == 0.0 || dark.1 == 0.0 {
+            return None;
+        }
+
+        let difference = light.0 / light.1 - dark.0 / dark.1;
+
+        Some((difference / FULL_CONTRAST).max(0.0).min(1.0))
+    }
+
+    /// Calls `f` with the coordinates and the expected color of each module
+    /// of the three finder patterns, and of their separators if requested.
+    fn for_each_finder_module<F: FnMut(Vec2D, bool)>(&self, separators: bool, mut f: F) {
+        let size = self.size();
+        let radius = if separators { 4 } else { 3 };
+
+        if size < 2 * radius + 1 {
+            return;
+        }
+
+        for &(cx, cy) in &[(3, 3), (size - 4, 3), (3, size - 4)] {
+            for y in cy.saturating_sub(radius)..(cy + radius + 1).min(size) {
+                for x in cx.saturating_sub(radius)..(cx + radius + 1).min(size) {
+                    let dx = if x > cx { x - cx } else { cx - x };
+                    let dy = if y > cy { y - cy } else { cy - y };
+                    let distance = dx.max(dy);
+
+                    f(Vec2D { x, y }, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+}
+
+impl Info {
+    /// Returns the fraction of the capacity of the error correction which
+    /// was left unused, between 0 and 1: 1 if no codeword had to be
+    /// corrected, and 0 if as many were corrected as possibly could be.
+    ///
+    /// Returns `None` if the number of corrected codewords is unknown,
+    /// i.e. unless the codewords were kept while decoding, e.g. by
+    /// `QrCode::decode_with_codewords()`.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn confidence(&self) -> Option<f64> {
+        let corrected = self.corrected_errors()?;
+        let version = self.version().number();
+        let ecc_level = self.ecc_level();
+        let correctable = spec::ecc_blocks(version, ecc_level) * spec::ecc_codewords_per_block(version, ecc_level) / 2;
+
+        if correctable == 0 {
+            return None;
+        }
+
+        Some((1.0 - corrected as f64 / correctable as f64).max(0.0))
+    }
+}
@@ -0,0 +1,339 @@
+//! Cryptocurrency payment requests: `bitcoin:` URIs (BIP-21) and
+//! `ethereum:` URIs (EIP-681).
+//!
+//! Bitcoin addresses are verified in both the legacy Base58Check and the
+//! SegWit Bech32/Bech32m encodings. Ethereum addresses are verified
+//! against their EIP-55 mixed-case checksum if they have one; ENS names
+//! are accepted as they are.
+
+use super::ParseError;
+use super::hash::{ sha256, keccak256 };
+use super::uri::{ percent_decode, split_query, strip_prefix_ignore_case };
+
+/// The alphabet of the Base58 encoding.
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The alphabet of the Bech32 encoding.
+const BECH32_ALPHABET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The checksum constant of Bech32, used by version 0 witness programs.
+const BECH32_CONST: u32 = 1;
+
+/// The checksum constant of Bech32m, used by version 1+ witness programs.
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// The version bytes of Base58Check addresses on the main and test
+/// networks: pay-to-pubkey-hash and pay-to-script-hash, respectively.
+const BASE58_VERSIONS: &[u8] = &[0x00, 0x05, 0x6f, 0xc4];
+
+/// The human-readable parts of SegWit addresses on the main network,
+/// the test network and the regression test network.
+const SEGWIT_PREFIXES: &[&str] = &["bc", "tb", "bcrt"];
+
+/// The blockchain a payment is requested on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CryptoNetwork {
+    /// Bitcoin (`bitcoin:`).
+    Bitcoin,
+    /// Ethereum or a compatible chain (`ethereum:`).
+    Ethereum,
+}
+
+/// Whether the address of a payment request was verified by a checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressChecksum {
+    /// The address contains a checksum, and it is correct.
+    Valid,
+    /// The address contains no checksum, e.g. it is an all-lowercase
+    /// Ethereum address or an ENS name.
+    Absent,
+}
+
+/// A cryptocurrency payment request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CryptoPayment {
+    /// The blockchain.
+    pub network: CryptoNetwork,
+    /// The address of the recipient, or an ENS name on Ethereum.
+    pub address: String,
+    /// Whether the address was verified by its checksum.
+    pub checksum: AddressChecksum,
+    /// The amount, verbatim: in BTC for Bitcoin (`amount`), and in wei,
+    /// possibly in scientific notation, for Ethereum (`value`).
+    pub amount: Option<String>,
+    /// The name of the recipient (`label`).
+    pub label: Option<String>,
+    /// A note describing the payment (`message`).
+    pub message: Option<String>,
+    /// The ID of the chain (`@chain_id`), on Ethereum.
+    pub chain_id: Option<u64>,
+    /// The name of the contract function to call, on Ethereum, e.g. `transfer`.
+    pub function: Option<String>,
+    /// The other parameters, with their names in lowercase.
+    pub params: Vec<(String, String)>,
+}
+
+impl CryptoPayment {
+    /// Parses a `bitcoin:` or an `ethereum:` URI. The scheme is matched
+    /// case-insensitively. The address is mandatory, and must be valid.
+    pub fn parse(uri: &str) -> Result<Self, ParseError> {
+        if let Some(rest) = strip_prefix_ignore_case(uri, "bitcoin:") {
+            parse_bitcoin(rest)
+        } else if let Some(rest) = strip_prefix_ignore_case(uri, "ethereum:") {
+            parse_ethereum(rest)
+        } else {
+            Err(ParseError::new("cryptocurrency URI", "unsupported scheme"))
+        }
+    }
+}
+
+/// Parses a BIP-21 URI after the scheme.
+fn parse_bitcoin(rest: &str) -> Result<CryptoPayment, ParseError> {
+    let error = |reason| ParseError::new("BIP-21", reason);
+    let (address, query) = split_query(rest);
+    let address = percent_decode(address, false);
+
+    if address.is_empty() {
+        return Err(error("missing address"));
+    }
+
+    if !is_valid_base58_address(&address) && !is_valid_segwit_address(&address) {
+        return Err(error("invalid address"));
+    }
+
+    let mut payment = CryptoPayment {
+        network: CryptoNetwork::Bitcoin,
+        address,
+        checksum: AddressChecksum::Valid,
+        amount: None,
+        label: None,
+        message: None,
+        chain_id: None,
+        function: None,
+        params: Vec::new(),
+    };
+
+    for (key, value) in query {
+        match key.as_str() {
+            "amount" if is_valid_btc_amount(&value) => payment.amount = Some(value),
+            "amount" => return Err(error("invalid amount")),
+            "label" => payment.label = Some(value),
+            "message" => payment.message = Some(value),
+            // Required parameters which aren't understood must be rejected.
+            _ if key.starts_with("req-") => return Err(error("unsupported required parameter")),
+            _ => payment.params.push((key, value)),
+        }
+    }
+
+    Ok(payment)
+}
+
+/// Parses an EIP-681 URI after the scheme.
+fn parse_ethereum(rest: &str) -> Result<CryptoPayment, ParseError> {
+    let error = |reason| ParseError::new("EIP-681", reason);
+    let (path, query) = split_query(rest);
+    let path = strip_prefix_ignore_case(path, "pay-").unwrap_or(path);
+
+    let (target, function) = match path.find('/') {
+        Some(i) => (&path[..i], Some(path[i + 1..].to_owned())),
+        None => (path, None),
+    };
+
+    let (address, chain_id) = match target.find('@') {
+        Some(i) => {
+            let chain_id = target[i + 1..].parse().map_err(|_| error("invalid chain ID"))?;
+            (&target[..i], Some(chain_id))
+        }
+        None => (target, None),
+    };
+
+    let checksum = if address.starts_with("0x") || address.starts_with("0X") {
+        eip55_checksum(&address[2..]).ok_or_else(|| error("invalid address"))?
+    } else if address.contains('.') {
+        AddressChecksum::Absent
+    } else {
+        return Err(error("invalid address"));
+    };
+
+    let mut payment = CryptoPayment {
+        network: CryptoNetwork::Ethereum,
+        address: address.to_owned(),
+        checksum,
+        amount: None,
+        label: None,
+        message: None,
+        chain_id,
+        function: function.filter(|f| !f.is_empty()),
+        params: Vec::new(),
+    };
+
+    for (key, value) in query {
+        match key.as_str() {
+            "value" => payment.amount = Some(value),
+            "label" => payment.label = Some(value),
+            "message" => payment.message = Some(value),
+            _ => payment.params.push((key, value)),
+        }
+    }
+
+    Ok(payment)
+}
+
+/// Checks that the amount is a non-negative decimal number of BTC with
+/// at most 8 fractional digits (i.e. a whole number of satoshis).
+fn is_valid_btc_amount(amount: &str) -> bool {
+    let (whole, fraction) = match amount.find('.') {
+        Some(i) => (&amount[..i], &amount[i + 1..]),
+        None => (amount, ""),
+    };
+
+    !(whole.is_empty() && fraction.is_empty())
+        && fraction.len() <= 8
+        && whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+}
+
+/// Verifies a legacy Base58Check address.
+fn is_valid_base58_address(address: &str) -> bool {
+    let bytes = match base58_decode(address) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+
+    if bytes.len() != 25 || !BASE58_VERSIONS.contains(&bytes[0]) {
+        return false;
+    }
+
+    let (payload, checksum) = bytes.split_at(21);
+
+    sha256(&sha256(payload))[..4] == *checksum
+}
+
+/// Decodes a Base58 string into bytes, big-endian.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    // Leading '1's encode leading zero bytes.
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    let mut result = vec![0; zeros];
+    result.extend(bytes);
+
+    Some(result)
+}
+
+/// Verifies a SegWit address (BIP-173 and BIP-350).
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn is_valid_segwit_address(address: &str) -> bool {
+    let has_lower = address.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = address.bytes().any(|b| b.is_ascii_uppercase());
+
+    if (has_lower && has_upper) || address.len() > 90 {
+        return false;
+    }
+
+    let address = address.to_ascii_lowercase();
+    let separator = match address.rfind('1') {
+        Some(i) => i,
+        None => return false,
+    };
+
+    let (hrp, data) = (&address[..separator], &address[separator + 1..]);
+
+    if !SEGWIT_PREFIXES.contains(&hrp) || data.len() < 7 {
+        return false;
+    }
+
+    let values: Option<Vec<u8>> = data
+        .bytes()
+        .map(|c| BECH32_ALPHABET.iter().position(|&a| a == c).map(|v| v as u8))
+        .collect();
+    let values = match values {
+        Some(values) => values,
+        None => return false,
+    };
+
+    let version = values[0];
+    let program = &values[1..values.len() - 6];
+    let expected_const = if version == 0 { BECH32_CONST } else { BECH32M_CONST };
+
+    if version > 16 || bech32_polymod(hrp, &values) != expected_const {
+        return false;
+    }
+
+    // Convert the 5-bit groups of the witness program to bytes; any
+    // leftover bits must be zero padding of less than a byte.
+    let bits = program.len() * 5;
+    let program_len = bits / 8;
+    let padding_is_zero = program.last().map_or(true, |&last| {
+        let padding_bits = bits % 8;
+        padding_bits < 5 && last & ((1 << padding_bits) - 1) == 0
+    });
+
+    padding_is_zero && match version {
+        0 => program_len == 20 || program_len == 32,
+        _ => 2 <= program_len && program_len <= 40,
+    }
+}
+
+/// Computes the Bech32 checksum polynomial over the human-readable part
+/// and the data, including the checksum itself.
+fn bech32_polymod(hrp: &str, data: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+
+    let expanded = hrp.bytes().map(|b| b >> 5)
+        .chain(Some(0))
+        .chain(hrp.bytes().map(|b| b & 31))
+        .chain(data.iter().cloned());
+
+    expanded.fold(1, |checksum, value| {
+        let top = checksum >> 25;
+        let checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(value);
+
+        GENERATOR.iter().enumerate().fold(checksum, |checksum, (i, &g)| {
+            if (top >> i) & 1 == 1 { checksum ^ g } else { checksum }
+        })
+    })
+}
+
+/// Verifies the EIP-55 checksum of an Ethereum address (without the `0x`
+/// prefix). Single-case addresses have no checksum.
+fn eip55_checksum(hex: &str) -> Option<AddressChecksum> {
+    if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let has_lower = hex.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = hex.bytes().any(|b| b.is_ascii_uppercase());
+
+    if !(has_lower && has_upper) {
+        return Some(AddressChecksum::Absent);
+    }
+
+    let hash = keccak256(hex.to_ascii_lowercase().as_bytes());
+    let valid = hex.bytes().enumerate().all(|(i, b)| {
+        let nibble = if i % 2 == 0 { hash[i / 2] >> 4 } else { hash[i / 2] & 0x0f };
+
+        match b {
+            b'a'..=b'f' => nibble < 8,
+            b'A'..=b'F' => nibble >= 8,
+            _ => true,
+        }
+    });
+
+    if valid { Some(AddressChecksum::Valid) } else { None }
+}
@@ -0,0 +1,200 @@
+//! Merchant-presented payment codes in the EMVCo QR format.
+//!
+//! The payload is a sequence of data objects, each consisting of a
+//! two-digit tag, a two-digit length and the value. Some of the values
+//! (merchant account information, additional data, etc.) are templates,
+//! i.e. nested sequences of data objects. The payload starts with the
+//! payload format indicator `000201`, and ends with a CRC-16 (tag `63`).
+
+use super::ParseError;
+
+/// The tag of the payload format indicator.
+const TAG_FORMAT_INDICATOR: u8 = 0;
+
+/// The tag of the checksum.
+const TAG_CRC: u8 = 63;
+
+/// A data object, possibly a template containing further data objects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmvTlv {
+    /// The tag, from 0 to 99.
+    pub tag: u8,
+    /// The raw value. For templates, this is the encoded nested objects.
+    pub value: String,
+    /// The nested data objects, if this is a template.
+    pub children: Vec<EmvTlv>,
+}
+
+impl EmvTlv {
+    /// Returns the nested data object with the given tag, if any.
+    pub fn child(&self, tag: u8) -> Option<&EmvTlv> {
+        self.children.iter().find(|c| c.tag == tag)
+    }
+}
+
+/// A merchant-presented payment code in the EMVCo format.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmvQr {
+    /// The top-level data objects, in the order of their appearance.
+    objects: Vec<EmvTlv>,
+}
+
+impl EmvQr {
+    /// Parses a payload in the EMVCo format, verifying its structure and
+    /// its CRC-16 checksum, which must be the last data object.
+    pub fn parse(payload: &str) -> Result<Self, ParseError> {
+        let objects = parse_objects(payload, true)?;
+
+        match objects.first() {
+            Some(first) if first.tag == TAG_FORMAT_INDICATOR && first.value == "01" => {}
+            _ => return Err(error("missing payload format indicator")),
+        }
+
+        match objects.last() {
+            Some(last) if last.tag == TAG_CRC && last.value.len() == 4 => {
+                let expected = u16::from_str_radix(&last.value, 16)
+                    .map_err(|_| error("invalid checksum"))?;
+                let covered = &payload[..payload.len() - last.value.len()];
+
+                if crc16(covered.as_bytes()) != expected {
+                    return Err(error("checksum mismatch"));
+                }
+            }
+            _ => return Err(error("missing checksum")),
+        }
+
+        Ok(EmvQr { objects })
+    }
+
+    /// Returns the top-level data objects.
+    pub fn objects(&self) -> &[EmvTlv] {
+        &self.objects
+    }
+
+    /// Returns the top-level data object with the given tag, if any.
+    pub fn get(&self, tag: u8) -> Option<&EmvTlv> {
+        self.objects.iter().find(|o| o.tag == tag)
+    }
+
+    /// Returns the value of the top-level data object with the given tag.
+    fn value(&self, tag: u8) -> Option<&str> {
+        self.get(tag).map(|o| o.value.as_str())
+    }
+
+    /// Returns whether the code is dynamic, i.e. meant for a single
+    /// transaction, rather than static (point of initiation method `12`).
+    pub fn is_dynamic(&self) -> bool {
+        self.value(1) == Some("12")
+    }
+
+    /// Returns the Merchant Category Code (ISO 18245).
+    pub fn merchant_category_code(&self) -> Option<&str> {
+        self.value(52)
+    }
+
+    /// Returns the numeric ISO 4217 code of the transaction currency,
+    /// e.g. `840` for US dollars.
+    pub fn currency(&self) -> Option<&str> {
+        self.value(53)
+    }
+
+    /// Returns the transaction amount as a decimal number, e.g. `9.99`,
+    /// if it is fixed by the code.
+    pub fn amount(&self) -> Option<&str> {
+        self.value(54)
+    }
+
+    /// Returns the ISO 3166-1 alpha-2 code of the country of the merchant.
+    pub fn country_code(&self) -> Option<&str> {
+        self.value(58)
+    }
+
+    /// Returns the name of the merchant.
+    pub fn merchant_name(&self) -> Option<&str> {
+        self.value(59)
+    }
+
+    /// Returns the city of the merchant.
+    pub fn merchant_city(&self) -> Option<&str> {
+        self.value(60)
+    }
+
+    /// Returns the postal code of the merchant.
+    pub fn postal_code(&self) -> Option<&str> {
+        self.value(61)
+    }
+
+    /// Returns the merchant account information templates (tags 26 to 51),
+    /// which identify the payment networks the merchant accepts.
+    pub fn merchant_accounts(&self) -> impl Iterator<Item = &EmvTlv> {
+        self.objects.iter().filter(|o| 26 <= o.tag && o.tag <= 51)
+    }
+
+    /// Returns the additional data field template (tag 62), containing
+    /// e.g. the bill number or the terminal label.
+    pub fn additional_data(&self) -> Option<&EmvTlv> {
+        self.get(62)
+    }
+}
+
+/// Creates a `ParseError` for the EMVCo format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("EMVCo", reason)
+}
+
+/// Returns whether the value of a top-level data object with this tag
+/// is a template.
+fn is_template(tag: u8) -> bool {
+    match tag {
+        26..=51 | 62 | 64 | 80..=99 => true,
+        _ => false,
+    }
+}
+
+/// Parses a sequence of data objects. Templates are only expanded at the
+/// top level, since nested templates don't exist in the format.
+fn parse_objects(mut s: &str, top_level: bool) -> Result<Vec<EmvTlv>, ParseError> {
+    let mut objects = Vec::new();
+
+    while !s.is_empty() {
+        let header = s.get(..4).ok_or_else(|| error("truncated data object"))?;
+
+        if !header.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error("invalid data object header"));
+        }
+
+        let tag: u8 = header[..2].parse().map_err(|_| error("invalid tag"))?;
+        let len: usize = header[2..].parse().map_err(|_| error("invalid length"))?;
+
+        // The length counts characters, not bytes, since merchant names
+        // etc. may be in a language other than English.
+        let rest = &s[4..];
+        let end = rest.char_indices().nth(len).map_or(rest.len(), |(i, _)| i);
+
+        if rest[..end].chars().count() != len {
+            return Err(error("truncated data object"));
+        }
+
+        let value = &rest[..end];
+        let children = if top_level && is_template(tag) {
+            parse_objects(value, false)?
+        } else {
+            Vec::new()
+        };
+
+        objects.push(EmvTlv { tag, value: value.to_owned(), children });
+        s = &rest[end..];
+    }
+
+    Ok(objects)
+}
+
+/// Computes the CRC-16/CCITT-FALSE checksum (polynomial 0x1021,
+/// initial value 0xFFFF) mandated by the format.
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xffff_u16, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 == 0 { crc << 1 } else { (crc << 1) ^ 0x1021 }
+        })
+    })
+}
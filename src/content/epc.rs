@@ -0,0 +1,238 @@
+//! SEPA credit transfers in the EPC069-12 format, a.k.a. "GiroCode".
+//!
+//! The payload consists of up to 12 lines, starting with the service tag
+//! `BCD`, the version, the character set and the identification `SCT`,
+//! followed by the details of the transfer. Trailing optional lines may
+//! be omitted.
+
+use std::str;
+use super::ParseError;
+
+/// The maximal length of the payload in bytes.
+const MAX_PAYLOAD_LEN: usize = 331;
+
+/// The maximal amount in euro cents.
+const MAX_AMOUNT_CENTS: u64 = 999_999_999 * 100 + 99;
+
+/// The reference to the transfer, used by the beneficiary for reconciliation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EpcRemittance {
+    /// A structured creditor reference, e.g. ISO 11649 `RF...`.
+    Reference(String),
+    /// Free-form text.
+    Text(String),
+}
+
+/// A SEPA credit transfer in the EPC069-12 format.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EpcPayment {
+    /// The version of the format, 1 or 2.
+    pub version: u8,
+    /// The BIC of the bank of the beneficiary; optional since version 2.
+    pub bic: Option<String>,
+    /// The name of the beneficiary.
+    pub name: String,
+    /// The IBAN of the account of the beneficiary.
+    pub iban: String,
+    /// The amount in euro cents, if specified.
+    pub amount_cents: Option<u64>,
+    /// The purpose of the transfer, as a four-letter ISO 20022 code.
+    pub purpose: Option<String>,
+    /// The reference to the transfer, if any.
+    pub remittance: Option<EpcRemittance>,
+    /// A message from the beneficiary to the payer.
+    pub information: Option<String>,
+}
+
+impl EpcPayment {
+    /// Parses a payload in the EPC069-12 format, validating the fixed
+    /// fields, the length of every field, and the checksum of the IBAN.
+    ///
+    /// The payload is taken as bytes, since it may be in any of the
+    /// character sets allowed by the format. UTF-8 and ISO 8859-1 are
+    /// decoded; the other ISO 8859 variants are only accepted if the
+    /// payload is pure ASCII.
+    pub fn parse(payload: &[u8]) -> Result<Self, ParseError> {
+        if payload.len() > MAX_PAYLOAD_LEN {
+            return Err(error("payload too long"));
+        }
+
+        let mut lines = payload
+            .split(|&b| b == b'\n')
+            .map(|line| if line.last() == Some(&b'\r') { &line[..line.len() - 1] } else { line });
+
+        if lines.next() != Some(b"BCD") {
+            return Err(error("missing `BCD` service tag"));
+        }
+
+        let version = match lines.next() {
+            Some(b"001") => 1,
+            Some(b"002") => 2,
+            _ => return Err(error("unsupported version")),
+        };
+
+        let charset = match lines.next() {
+            Some(&[digit]) if (b'1'..=b'8').contains(&digit) => digit - b'0',
+            _ => return Err(error("invalid character set")),
+        };
+
+        if lines.next() != Some(b"SCT") {
+            return Err(error("missing `SCT` identification"));
+        }
+
+        let mut fields = Vec::with_capacity(8);
+
+        for line in lines {
+            fields.push(decode(line, charset)?);
+        }
+
+        while fields.last().map_or(false, String::is_empty) {
+            fields.pop();
+        }
+
+        if fields.len() > 8 {
+            return Err(error("too many lines"));
+        }
+
+        fields.resize(8, String::new());
+
+        let mut fields = fields.into_iter().map(|field| {
+            let field = field.trim().to_owned();
+            if field.is_empty() { None } else { Some(field) }
+        });
+        let mut next = || fields.next().unwrap_or_default();
+
+        let bic = next();
+        let name = next().ok_or_else(|| error("missing beneficiary name"))?;
+        let iban = next().ok_or_else(|| error("missing IBAN"))?;
+        let amount = next();
+        let purpose = next();
+        let reference = next();
+        let text = next();
+        let information = next();
+
+        match bic {
+            Some(ref bic) if !is_valid_bic(bic) => return Err(error("invalid BIC")),
+            None if version == 1 => return Err(error("missing BIC")),
+            _ => {}
+        }
+
+        if name.chars().count() > 70 {
+            return Err(error("beneficiary name too long"));
+        }
+
+        if !is_valid_iban(&iban) {
+            return Err(error("invalid IBAN"));
+        }
+
+        let amount_cents = match amount {
+            Some(ref amount) => Some(parse_amount(amount).ok_or_else(|| error("invalid amount"))?),
+            None => None,
+        };
+
+        if let Some(ref purpose) = purpose {
+            if purpose.len() != 4 || !purpose.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(error("invalid purpose code"));
+            }
+        }
+
+        let remittance = match (reference, text) {
+            (Some(_), Some(_)) => return Err(error("both structured and unstructured remittance")),
+            (Some(reference), None) if reference.chars().count() <= 35 => {
+                Some(EpcRemittance::Reference(reference))
+            }
+            (None, Some(text)) if text.chars().count() <= 140 => Some(EpcRemittance::Text(text)),
+            (None, None) => None,
+            _ => return Err(error("remittance information too long")),
+        };
+
+        if information.as_ref().map_or(false, |info| info.chars().count() > 70) {
+            return Err(error("beneficiary to originator information too long"));
+        }
+
+        Ok(EpcPayment {
+            version,
+            bic,
+            name,
+            iban,
+            amount_cents,
+            purpose,
+            remittance,
+            information,
+        })
+    }
+}
+
+/// Creates a `ParseError` for the EPC format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("EPC", reason)
+}
+
+/// Decodes a line in the character set with the given EPC code.
+fn decode(line: &[u8], charset: u8) -> Result<String, ParseError> {
+    match charset {
+        1 => str::from_utf8(line)
+            .map(str::to_owned)
+            .map_err(|_| error("invalid UTF-8")),
+        2 => Ok(line.iter().map(|&b| char::from(b)).collect()),
+        _ if line.is_ascii() => Ok(line.iter().map(|&b| char::from(b)).collect()),
+        _ => Err(error("unsupported non-ASCII text in character set")),
+    }
+}
+
+/// Checks the format of a BIC: 4 letters for the bank, 2 letters for the
+/// country, 2 alphanumeric characters for the location, and optionally
+/// 3 alphanumeric characters for the branch.
+fn is_valid_bic(bic: &str) -> bool {
+    let bytes = bic.as_bytes();
+
+    (bytes.len() == 8 || bytes.len() == 11)
+        && bytes[..6].iter().all(u8::is_ascii_uppercase)
+        && bytes[6..].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// Checks the format of an IBAN and its ISO 7064 MOD 97-10 checksum.
+fn is_valid_iban(iban: &str) -> bool {
+    let bytes = iban.as_bytes();
+
+    if bytes.len() < 15 || bytes.len() > 34
+        || !bytes[..2].iter().all(u8::is_ascii_uppercase)
+        || !bytes[2..4].iter().all(u8::is_ascii_digit)
+        || !bytes.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()) {
+        return false;
+    }
+
+    let rearranged = bytes[4..].iter().chain(&bytes[..4]);
+    let remainder = rearranged.fold(0_u32, |acc, &b| {
+        if b.is_ascii_digit() {
+            (acc * 10 + u32::from(b - b'0')) % 97
+        } else {
+            (acc * 100 + u32::from(b - b'A') + 10) % 97
+        }
+    });
+
+    remainder == 1
+}
+
+/// Parses an amount of the form `EUR123.45` into cents.
+fn parse_amount(amount: &str) -> Option<u64> {
+    let amount = amount.strip_prefix("EUR")?;
+    let (units, cents) = match amount.find('.') {
+        Some(i) => (&amount[..i], &amount[i + 1..]),
+        None => (amount, ""),
+    };
+
+    if units.is_empty() || cents.len() > 2
+        || !units.bytes().chain(cents.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let cents = format!("{:0<2}", cents).parse::<u64>().ok()?;
+    let total = units.parse::<u64>().ok()?.checked_mul(100)?.checked_add(cents)?;
+
+    if total == 0 || total > MAX_AMOUNT_CENTS {
+        None
+    } else {
+        Some(total)
+    }
+}
@@ -0,0 +1,273 @@
+//! Calendar events in the iCalendar format (`BEGIN:VEVENT`).
+//!
+//! The event may stand alone or be wrapped in a `VCALENDAR`. Date-times
+//! are kept in the time zone they are specified in: UTC, a named time zone
+//! (`TZID`), or "floating", i.e. local time wherever the event is observed.
+//! Definitions of custom time zones (`VTIMEZONE`) are not interpreted.
+
+use std::fmt;
+use std::ops::Range;
+use super::ParseError;
+use super::line::{ Property, unfold, unescape };
+
+/// A calendar date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventDate {
+    /// The year, e.g. 2024.
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+}
+
+/// A time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventTimeOfDay {
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 60 (for leap seconds).
+    pub second: u8,
+}
+
+/// The time zone in which a date-time is specified.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EventTimeZone {
+    /// Local time, wherever the event is observed.
+    Floating,
+    /// Coordinated Universal Time (a trailing `Z`).
+    Utc,
+    /// The time zone identified by the `TZID` parameter, usually an IANA
+    /// name such as `Europe/Berlin`.
+    Named(String),
+}
+
+/// The start or end of an event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventTime {
+    /// The date.
+    pub date: EventDate,
+    /// The time of day, or `None` for all-day events (`VALUE=DATE`).
+    pub time: Option<EventTimeOfDay>,
+    /// The time zone of the time of day.
+    pub zone: EventTimeZone,
+}
+
+impl EventTime {
+    /// Parses the value of a `DTSTART` or `DTEND` property.
+    fn parse(property: &Property) -> Option<Self> {
+        let value = property.value.trim();
+        let digits = |range: Range<usize>| -> Option<u16> {
+            let s = value.get(range)?;
+            if s.bytes().all(|b| b.is_ascii_digit()) { s.parse().ok() } else { None }
+        };
+
+        let date = EventDate {
+            year: digits(0..4)?,
+            month: small(digits(4..6)?)?,
+            day: small(digits(6..8)?)?,
+        };
+
+        if date.month < 1 || date.month > 12 || date.day < 1 || date.day > 31 {
+            return None;
+        }
+
+        let (time, utc) = match value.len() {
+            8 => (None, false),
+            15 | 16 if value.as_bytes()[8] == b'T' => {
+                let time = EventTimeOfDay {
+                    hour: small(digits(9..11)?)?,
+                    minute: small(digits(11..13)?)?,
+                    second: small(digits(13..15)?)?,
+                };
+
+                if time.hour > 23 || time.minute > 59 || time.second > 60 {
+                    return None;
+                }
+
+                match value.get(15..) {
+                    Some("") => (Some(time), false),
+                    Some("Z") | Some("z") => (Some(time), true),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        let zone = if utc {
+            EventTimeZone::Utc
+        } else {
+            match property.param("TZID") {
+                Some(tzid) if time.is_some() => EventTimeZone::Named(tzid.to_owned()),
+                _ => EventTimeZone::Floating,
+            }
+        };
+
+        Some(EventTime { date, time, zone })
+    }
+
+    /// Returns whether this denotes a whole day rather than a point in time.
+    pub fn is_all_day(&self) -> bool {
+        self.time.is_none()
+    }
+
+    /// Returns the number of seconds since the Unix epoch, if the
+    /// date-time unambiguously identifies an instant: i.e. if it is in UTC,
+    /// or in a named time zone which is an alias of UTC. Floating times,
+    /// all-day dates and other named time zones would require knowledge of
+    /// the observer's location or of the time zone database, respectively.
+    pub fn unix_timestamp(&self) -> Option<i64> {
+        let is_utc = match self.zone {
+            EventTimeZone::Utc => true,
+            EventTimeZone::Named(ref tzid) => is_utc_alias(tzid),
+            EventTimeZone::Floating => false,
+        };
+
+        match self.time {
+            Some(time) if is_utc => {
+                let days = days_from_civil(self.date);
+                let seconds = i64::from(time.hour) * 3600
+                    + i64::from(time.minute) * 60
+                    + i64::from(time.second);
+                Some(days * 86400 + seconds)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EventTime {
+    /// Formats the date-time as in ISO 8601, followed by the name of the
+    /// time zone in brackets if it is a named one.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let date = self.date;
+        write!(f, "{:04}-{:02}-{:02}", date.year, date.month, date.day)?;
+
+        if let Some(time) = self.time {
+            write!(f, "T{:02}:{:02}:{:02}", time.hour, time.minute, time.second)?;
+
+            match self.zone {
+                EventTimeZone::Floating => {}
+                EventTimeZone::Utc => f.write_str("Z")?,
+                EventTimeZone::Named(ref tzid) => write!(f, "[{}]", tzid)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A calendar event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Event {
+    /// The title of the event (`SUMMARY`).
+    pub summary: Option<String>,
+    /// The start of the event (`DTSTART`).
+    pub start: EventTime,
+    /// The end of the event (`DTEND`), exclusive.
+    pub end: Option<EventTime>,
+    /// Where the event takes place (`LOCATION`).
+    pub location: Option<String>,
+    /// A more detailed description (`DESCRIPTION`).
+    pub description: Option<String>,
+    /// A web page about the event (`URL`).
+    pub url: Option<String>,
+    /// The unique identifier of the event (`UID`).
+    pub uid: Option<String>,
+}
+
+impl Event {
+    /// Parses a `VEVENT`, optionally within a `VCALENDAR`. Only the first
+    /// event is parsed. The start of the event is mandatory.
+    pub fn parse(payload: &str) -> Result<Self, ParseError> {
+        let mut lines = unfold(payload).into_iter().skip_while(|line| {
+            !line.trim().eq_ignore_ascii_case("BEGIN:VEVENT")
+        });
+
+        if lines.next().is_none() {
+            return Err(error("missing `BEGIN:VEVENT`"));
+        }
+
+        let mut summary = None;
+        let mut start = None;
+        let mut end = None;
+        let mut location = None;
+        let mut description = None;
+        let mut url = None;
+        let mut uid = None;
+        let mut depth = 0_usize;
+
+        for line in lines {
+            let property = match Property::parse(&line) {
+                Some(property) => property,
+                None => continue,
+            };
+
+            // Skip nested components, e.g. `VALARM`.
+            match property.name.as_str() {
+                "BEGIN" => depth += 1,
+                "END" if depth == 0 => break,
+                "END" => depth -= 1,
+                _ if depth > 0 => {}
+                "SUMMARY" => summary = Some(unescape(&property.value)),
+                "DTSTART" => {
+                    start = Some(EventTime::parse(&property).ok_or_else(|| error("invalid start"))?);
+                }
+                "DTEND" => {
+                    end = Some(EventTime::parse(&property).ok_or_else(|| error("invalid end"))?);
+                }
+                "LOCATION" => location = Some(unescape(&property.value)),
+                "DESCRIPTION" => description = Some(unescape(&property.value)),
+                "URL" => url = Some(property.value.trim().to_owned()),
+                "UID" => uid = Some(unescape(&property.value)),
+                _ => {}
+            }
+        }
+
+        Ok(Event {
+            summary,
+            start: start.ok_or_else(|| error("missing start"))?,
+            end,
+            location,
+            description,
+            url,
+            uid,
+        })
+    }
+}
+
+/// Creates a `ParseError` for the iCalendar format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("iCalendar", reason)
+}
+
+/// Narrows a value known to be less than 100.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn small(value: u16) -> Option<u8> {
+    if value < 100 { Some(value as u8) } else { None }
+}
+
+/// Returns whether a `TZID` denotes UTC itself.
+fn is_utc_alias(tzid: &str) -> bool {
+    let tzid = tzid.trim_start_matches('/');
+
+    ["UTC", "GMT", "Z", "Etc/UTC", "Etc/GMT", "Etc/Zulu", "Zulu", "Universal"]
+        .iter()
+        .any(|alias| tzid.eq_ignore_ascii_case(alias))
+}
+
+/// Returns the number of days between the Unix epoch and `date` in the
+/// proleptic Gregorian calendar.
+fn days_from_civil(date: EventDate) -> i64 {
+    let month = i64::from(date.month);
+    let year = i64::from(date.year) - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from(date.day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
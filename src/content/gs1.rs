@@ -0,0 +1,376 @@
+//! GS1 element strings, as used for product identification and logistics.
+//!
+//! An element string is a sequence of Application Identifiers (AIs), each
+//! followed by its data. Data of a predefined length simply runs into the
+//! next AI, while variable-length data is terminated by the group separator
+//! character (ASCII `GS`, 0x1D), which stands in for FNC1. The
+//! human-readable form, in which every AI is enclosed in parentheses, e.g.
+//! `(01)09501101530003(17)250101`, is accepted as well.
+
+use std::fmt;
+use super::ParseError;
+
+/// The group separator, which terminates variable-length data.
+const GS: char = '\x1d';
+
+/// Symbology identifiers which may precede the element string, signaling
+/// GS1 data in QR Code, Code 128, Data Matrix and DataBar, respectively.
+const SYMBOLOGY_IDENTIFIERS: &[&str] = &["]Q3", "]C1", "]d2", "]e0"];
+
+/// The syntax of the data belonging to an Application Identifier.
+#[derive(Debug, Clone, Copy)]
+struct AiFormat {
+    /// The leading digits shared by the AIs of this format.
+    prefix: &'static str,
+    /// The number of digits in the AI.
+    ai_len: usize,
+    /// The minimal length of the data.
+    min_len: usize,
+    /// The maximal length of the data.
+    max_len: usize,
+    /// Whether the data consists of digits only.
+    numeric: bool,
+}
+
+/// Shorthand for constructing an `AiFormat`.
+const fn ai(prefix: &'static str, ai_len: usize, min_len: usize, max_len: usize, numeric: bool) -> AiFormat {
+    AiFormat { prefix, ai_len, min_len, max_len, numeric }
+}
+
+/// The formats of the Application Identifiers in common use, per the GS1
+/// General Specifications. More specific prefixes come first.
+const AI_FORMATS: &[AiFormat] = &[
+    ai("00", 2, 18, 18, true),
+    ai("01", 2, 14, 14, true),
+    ai("02", 2, 14, 14, true),
+    ai("03", 2, 14, 14, true),
+    ai("04", 2, 16, 16, true),
+    ai("10", 2, 1, 20, false),
+    ai("11", 2, 6, 6, true),
+    ai("12", 2, 6, 6, true),
+    ai("13", 2, 6, 6, true),
+    ai("15", 2, 6, 6, true),
+    ai("16", 2, 6, 6, true),
+    ai("17", 2, 6, 6, true),
+    ai("20", 2, 2, 2, true),
+    ai("21", 2, 1, 20, false),
+    ai("22", 2, 1, 20, false),
+    ai("235", 3, 1, 28, false),
+    ai("240", 3, 1, 30, false),
+    ai("241", 3, 1, 30, false),
+    ai("242", 3, 1, 6, true),
+    ai("243", 3, 1, 20, false),
+    ai("250", 3, 1, 30, false),
+    ai("251", 3, 1, 30, false),
+    ai("253", 3, 14, 30, false),
+    ai("254", 3, 1, 20, false),
+    ai("255", 3, 14, 25, true),
+    ai("30", 2, 1, 8, true),
+    ai("31", 4, 6, 6, true),
+    ai("32", 4, 6, 6, true),
+    ai("33", 4, 6, 6, true),
+    ai("34", 4, 6, 6, true),
+    ai("35", 4, 6, 6, true),
+    ai("36", 4, 6, 6, true),
+    ai("37", 2, 1, 8, true),
+    ai("390", 4, 1, 15, true),
+    ai("391", 4, 4, 18, true),
+    ai("392", 4, 1, 15, true),
+    ai("393", 4, 4, 18, true),
+    ai("394", 4, 4, 4, true),
+    ai("395", 4, 6, 6, true),
+    ai("400", 3, 1, 30, false),
+    ai("401", 3, 1, 30, false),
+    ai("402", 3, 17, 17, true),
+    ai("403", 3, 1, 30, false),
+    ai("41", 3, 13, 13, true),
+    ai("420", 3, 1, 20, false),
+    ai("421", 3, 4, 12, false),
+    ai("422", 3, 3, 3, true),
+    ai("423", 3, 3, 15, true),
+    ai("424", 3, 3, 3, true),
+    ai("425", 3, 3, 15, true),
+    ai("426", 3, 3, 3, true),
+    ai("427", 3, 1, 3, false),
+    ai("7001", 4, 13, 13, true),
+    ai("7002", 4, 1, 30, false),
+    ai("7003", 4, 10, 10, true),
+    ai("7004", 4, 1, 4, true),
+    ai("7005", 4, 1, 12, false),
+    ai("7006", 4, 6, 6, true),
+    ai("7007", 4, 6, 12, true),
+    ai("7008", 4, 1, 3, false),
+    ai("7009", 4, 1, 10, false),
+    ai("7010", 4, 1, 2, false),
+    ai("702", 4, 1, 30, false),
+    ai("703", 4, 4, 30, false),
+    ai("71", 3, 1, 20, false),
+    ai("8001", 4, 14, 14, true),
+    ai("8002", 4, 1, 20, false),
+    ai("8003", 4, 14, 30, false),
+    ai("8004", 4, 1, 30, false),
+    ai("8005", 4, 6, 6, true),
+    ai("8006", 4, 18, 18, true),
+    ai("8007", 4, 1, 34, false),
+    ai("8008", 4, 8, 12, true),
+    ai("8009", 4, 1, 50, false),
+    ai("8010", 4, 1, 30, false),
+    ai("8011", 4, 1, 12, true),
+    ai("8012", 4, 1, 20, false),
+    ai("8013", 4, 1, 25, false),
+    ai("8017", 4, 18, 18, true),
+    ai("8018", 4, 18, 18, true),
+    ai("8019", 4, 1, 10, true),
+    ai("8020", 4, 1, 25, false),
+    ai("8026", 4, 18, 18, true),
+    ai("811", 4, 1, 70, false),
+    ai("8200", 4, 1, 70, false),
+    ai("90", 2, 1, 30, false),
+    ai("9", 2, 1, 90, false),
+];
+
+/// Looks up the format of the AI at the beginning of `s`.
+fn ai_format(s: &str) -> Option<&'static AiFormat> {
+    AI_FORMATS.iter().find(|format| s.starts_with(format.prefix))
+}
+
+/// A single Application Identifier and its data.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Gs1Element {
+    /// The Application Identifier, e.g. `01` for a GTIN.
+    pub ai: String,
+    /// The data, without the terminating separator.
+    pub value: String,
+}
+
+/// A date of the form `YYMMDD`, as used by e.g. the expiry date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gs1Date {
+    /// The last two digits of the year.
+    pub year: u8,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, or `None` if the date denotes the last day
+    /// of the month, which is encoded as day `00`.
+    pub day: Option<u8>,
+}
+
+impl Gs1Date {
+    /// Parses a date of the form `YYMMDD`.
+    fn parse(s: &str) -> Option<Self> {
+        let bytes = s.as_bytes();
+
+        if bytes.len() != 6 || !bytes.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+
+        let pair = |i: usize| (bytes[i] - b'0') * 10 + (bytes[i + 1] - b'0');
+        let (year, month, day) = (pair(0), pair(2), pair(4));
+
+        if month < 1 || month > 12 || day > 31 {
+            return None;
+        }
+
+        Some(Gs1Date { year, month, day: if day == 0 { None } else { Some(day) } })
+    }
+
+    /// Returns the four-digit year, determined relative to `current_year`
+    /// according to the GS1 rule: the year is taken from the century which
+    /// places it at most 49 years in the past and at most 50 years in the
+    /// future.
+    pub fn full_year(&self, current_year: u16) -> u16 {
+        let century = current_year - current_year % 100;
+        let year = century + u16::from(self.year);
+
+        if year > current_year + 50 {
+            year - 100
+        } else if year + 49 < current_year {
+            year + 100
+        } else {
+            year
+        }
+    }
+}
+
+impl fmt::Display for Gs1Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}{:02}{:02}", self.year, self.month, self.day.unwrap_or(0))
+    }
+}
+
+/// A GS1 element string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Gs1 {
+    /// The elements, in the order of their appearance.
+    elements: Vec<Gs1Element>,
+}
+
+impl Gs1 {
+    /// Parses a GS1 element string, in either the raw form with group
+    /// separators, optionally preceded by a symbology identifier such as
+    /// `]Q3`, or the human-readable form with parenthesized AIs.
+    ///
+    /// Every AI must be known, its data must have a valid length, and
+    /// the check digits of GTINs and SSCCs must be correct.
+    pub fn parse(payload: &str) -> Result<Self, ParseError> {
+        let payload = SYMBOLOGY_IDENTIFIERS
+            .iter()
+            .find(|id| payload.starts_with(*id))
+            .map_or(payload, |id| &payload[id.len()..]);
+        let payload = payload.trim_start_matches(GS);
+
+        let elements = if payload.starts_with('(') {
+            parse_human_readable(payload)?
+        } else {
+            parse_raw(payload)?
+        };
+
+        if elements.is_empty() {
+            return Err(error("no elements"));
+        }
+
+        for element in &elements {
+            if let "00" | "01" | "02" | "03" = element.ai.as_str() {
+                if !has_valid_check_digit(&element.value) {
+                    return Err(error("invalid check digit"));
+                }
+            }
+        }
+
+        Ok(Gs1 { elements })
+    }
+
+    /// Returns the elements, in the order of their appearance.
+    pub fn elements(&self) -> &[Gs1Element] {
+        &self.elements
+    }
+
+    /// Returns the data of the first element with the given AI.
+    pub fn get(&self, ai: &str) -> Option<&str> {
+        self.elements.iter().find(|e| e.ai == ai).map(|e| e.value.as_str())
+    }
+
+    /// Returns the Global Trade Item Number (AI 01).
+    pub fn gtin(&self) -> Option<&str> {
+        self.get("01")
+    }
+
+    /// Returns the Serial Shipping Container Code (AI 00).
+    pub fn sscc(&self) -> Option<&str> {
+        self.get("00")
+    }
+
+    /// Returns the batch or lot number (AI 10).
+    pub fn batch(&self) -> Option<&str> {
+        self.get("10")
+    }
+
+    /// Returns the serial number (AI 21).
+    pub fn serial(&self) -> Option<&str> {
+        self.get("21")
+    }
+
+    /// Returns the production date (AI 11).
+    pub fn production_date(&self) -> Option<Gs1Date> {
+        self.get("11").and_then(Gs1Date::parse)
+    }
+
+    /// Returns the best before date (AI 15).
+    pub fn best_before_date(&self) -> Option<Gs1Date> {
+        self.get("15").and_then(Gs1Date::parse)
+    }
+
+    /// Returns the expiration date (AI 17).
+    pub fn expiry_date(&self) -> Option<Gs1Date> {
+        self.get("17").and_then(Gs1Date::parse)
+    }
+}
+
+/// Creates a `ParseError` for the GS1 format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("GS1", reason)
+}
+
+/// Parses an element string in which variable-length data is terminated
+/// by the group separator.
+fn parse_raw(mut s: &str) -> Result<Vec<Gs1Element>, ParseError> {
+    let mut elements = Vec::new();
+
+    while !s.is_empty() {
+        let format = ai_format(s).ok_or_else(|| error("unknown application identifier"))?;
+
+        if s.len() < format.ai_len || !s.is_char_boundary(format.ai_len) {
+            return Err(error("truncated application identifier"));
+        }
+
+        let (ai, rest) = s.split_at(format.ai_len);
+        let end = if format.min_len == format.max_len {
+            format.max_len.min(rest.len())
+        } else {
+            rest.find(GS).unwrap_or_else(|| rest.len())
+        };
+
+        if !rest.is_char_boundary(end) {
+            return Err(error("invalid data"));
+        }
+
+        let value = &rest[..end];
+        validate(ai, value, format)?;
+        elements.push(Gs1Element { ai: ai.to_owned(), value: value.to_owned() });
+        s = rest[end..].trim_start_matches(GS);
+    }
+
+    Ok(elements)
+}
+
+/// Parses an element string in which every AI is enclosed in parentheses.
+fn parse_human_readable(s: &str) -> Result<Vec<Gs1Element>, ParseError> {
+    let mut elements = Vec::new();
+
+    for part in s.split('(').skip(1) {
+        let close = part.find(')').ok_or_else(|| error("unterminated application identifier"))?;
+        let (ai, value) = (&part[..close], &part[close + 1..]);
+        let format = ai_format(ai)
+            .filter(|format| format.ai_len == ai.len())
+            .ok_or_else(|| error("unknown application identifier"))?;
+
+        validate(ai, value, format)?;
+        elements.push(Gs1Element { ai: ai.to_owned(), value: value.to_owned() });
+    }
+
+    Ok(elements)
+}
+
+/// Checks the AI and its data against the format.
+fn validate(ai: &str, value: &str, format: &AiFormat) -> Result<(), ParseError> {
+    if !ai.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error("non-numeric application identifier"));
+    }
+
+    if value.len() < format.min_len || value.len() > format.max_len {
+        return Err(error("invalid data length"));
+    }
+
+    if format.numeric && !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error("non-numeric data"));
+    }
+
+    // Only the printable characters of ISO 646 are allowed.
+    if !value.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(error("invalid character in data"));
+    }
+
+    Ok(())
+}
+
+/// Verifies the GS1 mod 10 check digit at the end of `digits`.
+fn has_valid_check_digit(digits: &str) -> bool {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| u32::from(b - b'0') * if i % 2 == 1 { 3 } else { 1 })
+        .sum();
+
+    sum % 10 == 0
+}
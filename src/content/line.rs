@@ -0,0 +1,184 @@
+//! The content line syntax shared by vCard (RFC 6350) and iCalendar
+//! (RFC 5545), including the quoted-printable encoding of vCard 2.1.
+
+use super::uri::hex_value;
+
+/// A content line, with its value decoded from quoted-printable if needed,
+/// but not yet unescaped.
+#[derive(Debug)]
+pub struct Property {
+    /// The name of the property in uppercase, without the group.
+    pub name: String,
+    /// The values of the `TYPE` parameters in lowercase, including the
+    /// bare types of vCard 2.1, e.g. `TEL;WORK;VOICE:...`.
+    pub types: Vec<String>,
+    /// The other parameters, with their names in uppercase and their
+    /// values unquoted.
+    pub params: Vec<(String, String)>,
+    /// The raw value.
+    pub value: String,
+}
+
+impl Property {
+    /// Splits a content line into its name, parameters and value.
+    pub fn parse(line: &str) -> Option<Self> {
+        let colon = value_start(line)?;
+        let mut params = line[..colon].split(';');
+        let name = params.next()?;
+        let name = name.rsplit('.').next().unwrap_or(name).trim().to_ascii_uppercase();
+        let mut types = Vec::new();
+        let mut others = Vec::new();
+        let mut quoted_printable = false;
+
+        for param in params {
+            let (key, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => ("TYPE", param),
+            };
+
+            if key.eq_ignore_ascii_case("TYPE") {
+                types.extend(
+                    value.trim_matches('"')
+                        .split(',')
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_ascii_lowercase)
+                );
+            } else if key.eq_ignore_ascii_case("ENCODING") {
+                quoted_printable = value.eq_ignore_ascii_case("QUOTED-PRINTABLE");
+            } else {
+                others.push((key.trim().to_ascii_uppercase(), value.trim_matches('"').to_owned()));
+            }
+        }
+
+        quoted_printable |= types.iter().any(|t| t == "quoted-printable");
+        types.retain(|t| t != "quoted-printable");
+
+        let value = &line[colon + 1..];
+        let value = if quoted_printable {
+            quoted_printable_decode(value)
+        } else {
+            value.to_owned()
+        };
+
+        Some(Property { name, types, params: others, value })
+    }
+
+    /// Returns the value of the named parameter, if present.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Returns the index of the colon separating the name and the parameters
+/// of a content line from its value, skipping colons in quoted parameters.
+pub fn value_start(line: &str) -> Option<usize> {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Splits the payload into logical lines, joining folded lines as well as
+/// quoted-printable soft line breaks. Empty lines are removed.
+pub fn unfold(payload: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut soft_break = false;
+
+    for line in payload.split('\n') {
+        let line = line.trim_end_matches('\r');
+
+        if soft_break {
+            if let Some(last) = lines.last_mut() {
+                last.pop();
+                last.push_str(line);
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+            }
+        } else if !line.is_empty() {
+            lines.push(line.to_owned());
+        }
+
+        soft_break = lines.last().map_or(false, |last| {
+            last.ends_with('=') && value_start(last).map_or(false, |colon| {
+                last[..colon].to_ascii_uppercase().contains("QUOTED-PRINTABLE")
+            })
+        });
+    }
+
+    lines
+}
+
+/// Decodes `=XX` escapes. Malformed escapes are kept verbatim, and invalid
+/// UTF-8 is replaced by U+FFFD, as only UTF-8 payloads are supported.
+pub fn quoted_printable_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'=' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                out.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Splits a structured value at the unescaped semicolons, and unescapes
+/// the components.
+pub fn components(value: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ';' => {
+                result.push(unescape(&value[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    result.push(unescape(&value[start..]));
+    result
+}
+
+/// Replaces `\n` by a line break, and removes the backslash from other
+/// escaped characters, e.g. `\,` or `\;`.
+pub fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => result.push('\n'),
+                Some(escaped) => result.push(escaped),
+                None => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+
+    result
+}
@@ -0,0 +1,332 @@
+//! Parsing of the structured data commonly found in QR code payloads.
+//!
+//! Most QR codes in the wild follow one of a handful of conventions for
+//! encoding URLs, contact details, network credentials, etc. `classify()`
+//! recognizes these, and falls back to plain text (or raw bytes) otherwise.
+
+mod uri;
+mod line;
+mod wifi;
+mod vcard;
+mod epc;
+mod otpauth;
+mod gs1;
+mod event;
+mod emv;
+mod url;
+mod hash;
+mod crypto;
+
+use std::str;
+use std::fmt;
+use std::error;
+use info::Info;
+use self::uri::{ percent_decode, split_query, query_value, strip_prefix_ignore_case };
+
+pub use self::wifi::{ WifiConfig, WifiSecurity };
+pub use self::vcard::{ VCard, VCardName, VCardAddress, VCardValue };
+pub use self::epc::{ EpcPayment, EpcRemittance };
+pub use self::otpauth::{ OtpAuth, OtpKind, OtpAlgorithm };
+pub use self::gs1::{ Gs1, Gs1Element, Gs1Date };
+pub use self::event::{ Event, EventTime, EventDate, EventTimeOfDay, EventTimeZone };
+pub use self::emv::{ EmvQr, EmvTlv };
+pub use self::url::{ Url, UrlOptions, UrlWarning };
+pub use self::crypto::{ CryptoPayment, CryptoNetwork, AddressChecksum };
+
+/// The structured content of a QR code payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    /// An `http` or `https` URL.
+    Url(String),
+    /// Credentials for joining a wireless network (`WIFI:`).
+    WifiConfig(WifiConfig),
+    /// A contact in the vCard format (`BEGIN:VCARD`).
+    VCard(VCard),
+    /// A contact in the MeCard format (`MECARD:`).
+    MeCard(MeCard),
+    /// A SEPA credit transfer in the EPC069-12 format (`BCD`).
+    EpcPayment(EpcPayment),
+    /// A one-time password generator (`otpauth://`).
+    OtpAuth(OtpAuth),
+    /// A GS1 element string, e.g. a GTIN with a batch and expiry date.
+    Gs1(Gs1),
+    /// A calendar event in the iCalendar format (`BEGIN:VEVENT`).
+    Event(Event),
+    /// A merchant-presented payment code in the EMVCo format (`000201...`).
+    EmvQr(EmvQr),
+    /// A cryptocurrency payment request (`bitcoin:` or `ethereum:`).
+    CryptoPayment(CryptoPayment),
+    /// A geographic location (`geo:`).
+    Geo {
+        /// Latitude in degrees.
+        latitude: f64,
+        /// Longitude in degrees.
+        longitude: f64,
+        /// Altitude in meters, if specified.
+        altitude: Option<f64>,
+    },
+    /// A telephone number (`tel:`).
+    Tel(String),
+    /// A text message (`sms:` or `SMSTO:`).
+    Sms {
+        /// The phone number of the recipient.
+        number: String,
+        /// The text of the message, if specified.
+        body: Option<String>,
+    },
+    /// An e-mail message (`mailto:` or `MATMSG:`).
+    Email {
+        /// The address of the recipient.
+        to: String,
+        /// The subject of the message, if specified.
+        subject: Option<String>,
+        /// The text of the message, if specified.
+        body: Option<String>,
+    },
+    /// Any other UTF-8 text.
+    Text(String),
+    /// A payload that isn't valid UTF-8.
+    Binary(Vec<u8>),
+}
+
+/// Contact details in the MeCard format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MeCard {
+    /// The name of the contact, usually in `Last,First` order.
+    pub name: Option<String>,
+    /// Phone numbers.
+    pub phones: Vec<String>,
+    /// E-mail addresses.
+    pub emails: Vec<String>,
+    /// Web sites.
+    pub urls: Vec<String>,
+    /// Postal address.
+    pub address: Option<String>,
+    /// Birthday, usually as `YYYYMMDD`.
+    pub birthday: Option<String>,
+    /// Free-form note.
+    pub note: Option<String>,
+}
+
+/// An error encountered while parsing a payload in a specific format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseError {
+    /// The name of the format, e.g. `WIFI`.
+    format: &'static str,
+    /// What is wrong with the payload.
+    reason: &'static str,
+}
+
+impl ParseError {
+    /// Creates an error describing why a payload isn't valid.
+    fn new(format: &'static str, reason: &'static str) -> Self {
+        ParseError { format, reason }
+    }
+
+    /// Returns the name of the format the payload was supposed to be in.
+    pub fn format(&self) -> &'static str {
+        self.format
+    }
+
+    /// Returns a description of what is wrong with the payload.
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} payload: {}", self.format, self.reason)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Recognizes the structure of a payload.
+pub fn classify(payload: &[u8]) -> Content {
+    // EPC payloads may be in a legacy character set, so they
+    // have to be recognized before decoding the payload as UTF-8.
+    if payload.starts_with(b"BCD\n") || payload.starts_with(b"BCD\r\n") {
+        if let Ok(payment) = EpcPayment::parse(payload) {
+            return Content::EpcPayment(payment);
+        }
+    }
+
+    let text = match str::from_utf8(payload) {
+        Ok(text) => text,
+        Err(_) => return Content::Binary(payload.to_vec()),
+    };
+
+    if strip_prefix_ignore_case(text, "http://").is_some()
+        || strip_prefix_ignore_case(text, "https://").is_some() {
+        Content::Url(text.to_owned())
+    } else if strip_prefix_ignore_case(text, "WIFI:").is_some() {
+        WifiConfig::parse(text)
+            .map(Content::WifiConfig)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if text.starts_with("000201") {
+        EmvQr::parse(text)
+            .map(Content::EmvQr)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if is_gs1_candidate(text) {
+        Gs1::parse(text)
+            .map(Content::Gs1)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if strip_prefix_ignore_case(text, "bitcoin:").is_some()
+        || strip_prefix_ignore_case(text, "ethereum:").is_some() {
+        CryptoPayment::parse(text)
+            .map(Content::CryptoPayment)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if strip_prefix_ignore_case(text, "otpauth://").is_some() {
+        OtpAuth::parse(text)
+            .map(Content::OtpAuth)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if strip_prefix_ignore_case(text, "BEGIN:VEVENT").is_some()
+        || strip_prefix_ignore_case(text, "BEGIN:VCALENDAR").is_some() {
+        Event::parse(text)
+            .map(Content::Event)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if strip_prefix_ignore_case(text, "BEGIN:VCARD").is_some() {
+        VCard::parse(text)
+            .map(Content::VCard)
+            .unwrap_or_else(|_| Content::Text(text.to_owned()))
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "MECARD:") {
+        Content::MeCard(parse_mecard(rest))
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "geo:") {
+        parse_geo(rest).unwrap_or_else(|| Content::Text(text.to_owned()))
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "tel:") {
+        Content::Tel(rest.to_owned())
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "SMSTO:") {
+        let mut parts = rest.splitn(2, ':');
+        let number = parts.next().unwrap_or_default().to_owned();
+        let body = parts.next().map(str::to_owned);
+        Content::Sms { number, body }
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "sms:") {
+        let (number, query) = split_query(rest);
+        let body = query_value(&query, "body").map(str::to_owned);
+        Content::Sms { number: percent_decode(number, false), body }
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "mailto:") {
+        let (to, query) = split_query(rest);
+        Content::Email {
+            to: percent_decode(to, false),
+            subject: query_value(&query, "subject").map(str::to_owned),
+            body: query_value(&query, "body").map(str::to_owned),
+        }
+    } else if let Some(rest) = strip_prefix_ignore_case(text, "MATMSG:") {
+        let mut email = (String::new(), None, None);
+
+        for (key, value) in fields(rest) {
+            match key.as_str() {
+                "TO"   => email.0 = value,
+                "SUB"  => email.1 = Some(value),
+                "BODY" => email.2 = Some(value),
+                _ => {}
+            }
+        }
+
+        Content::Email { to: email.0, subject: email.1, body: email.2 }
+    } else {
+        Content::Text(text.to_owned())
+    }
+}
+
+impl Info {
+    /// Recognizes the structure of the payload. See `content::classify()`.
+    pub fn parse_content(&self) -> Content {
+        classify(self.payload())
+    }
+}
+
+/// Returns whether the payload looks like a GS1 element string: either it
+/// starts with a symbology identifier or an FNC1 (group separator), or it
+/// consists of digits and group separators, or it is in the human-readable
+/// form, starting with a parenthesized AI.
+fn is_gs1_candidate(text: &str) -> bool {
+    let bytes = text.as_bytes();
+
+    text.starts_with("]Q3")
+        || text.starts_with('\x1d')
+        || (bytes.contains(&0x1d) && bytes.first().map_or(false, u8::is_ascii_digit))
+        || (bytes.len() > 4 && bytes[0] == b'(' && bytes[1..3].iter().all(u8::is_ascii_digit))
+}
+
+/// Parses the fields of a `MECARD:` payload after the prefix.
+fn parse_mecard(body: &str) -> MeCard {
+    let mut card = MeCard::default();
+
+    for (key, value) in fields(body) {
+        match key.as_str() {
+            "N"     => card.name = Some(value),
+            "TEL"   => card.phones.push(value),
+            "EMAIL" => card.emails.push(value),
+            "URL"   => card.urls.push(value),
+            "ADR"   => card.address = Some(value),
+            "BDAY"  => card.birthday = Some(value),
+            "NOTE"  => card.note = Some(value),
+            _ => {}
+        }
+    }
+
+    card
+}
+
+/// Parses the coordinates of a `geo:` URI after the scheme.
+fn parse_geo(body: &str) -> Option<Content> {
+    let (coordinates, _) = split_query(body);
+    let coordinates = coordinates.split(';').next().unwrap_or_default();
+    let mut values = coordinates.split(',').map(|c| c.trim().parse::<f64>());
+    let latitude = values.next()?.ok()?;
+    let longitude = values.next()?.ok()?;
+    let altitude = match values.next() {
+        Some(value) => Some(value.ok()?),
+        None => None,
+    };
+
+    if values.next().is_some() || latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+
+    Some(Content::Geo { latitude, longitude, altitude })
+}
+
+/// Splits the body of a MeCard-style payload (`KEY:value;KEY:value;;`)
+/// into its fields. Keys are converted to uppercase, and the characters
+/// `\`, `;`, `,`, `:` and `"` are unescaped in values. Parsing stops at
+/// the first empty field, i.e. at the terminating `;;`.
+fn fields(body: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut chars = body.chars();
+
+    loop {
+        let mut key = String::new();
+        let mut value = String::new();
+        let mut in_value = false;
+        let mut terminated = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => if let Some(escaped) = chars.next() {
+                    if in_value { value.push(escaped) } else { key.push(escaped) }
+                },
+                ':' if !in_value => in_value = true,
+                ';' => {
+                    terminated = true;
+                    break;
+                }
+                c => if in_value { value.push(c) } else { key.push(c) },
+            }
+        }
+
+        if key.is_empty() {
+            break;
+        }
+
+        result.push((key.to_ascii_uppercase(), value));
+
+        if !terminated {
+            break;
+        }
+    }
+
+    result
+}
@@ -0,0 +1,183 @@
+//! Provisioning of one-time password generators via `otpauth://` URIs.
+//!
+//! The format, originally defined by Google Authenticator, is
+//! `otpauth://TYPE/LABEL?PARAMETERS`, where the type is `totp` or `hotp`,
+//! the label is `ISSUER:ACCOUNT` or just `ACCOUNT`, and the parameters
+//! include the Base32-encoded shared secret.
+
+use std::fmt;
+use super::ParseError;
+use super::uri::{ percent_decode, split_query, query_value, strip_prefix_ignore_case };
+
+/// The number of digits in a password if not specified.
+const DEFAULT_DIGITS: u8 = 6;
+
+/// The number of seconds a TOTP password is valid for if not specified.
+const DEFAULT_PERIOD: u32 = 30;
+
+/// The hash function underlying the HMAC used for generating passwords.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OtpAlgorithm {
+    /// SHA-1, the default.
+    #[default]
+    Sha1,
+    /// SHA-256.
+    Sha256,
+    /// SHA-512.
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    /// Returns the value of the `algorithm` parameter for this function.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The kind of the password generator, with its moving factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OtpKind {
+    /// Time-based (RFC 6238).
+    Totp {
+        /// The number of seconds a password is valid for.
+        period: u32,
+    },
+    /// Counter-based (RFC 4226).
+    Hotp {
+        /// The initial value of the counter.
+        counter: u64,
+    },
+}
+
+/// The parameters of a one-time password generator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OtpAuth {
+    /// Time- or counter-based, with the corresponding parameter.
+    pub kind: OtpKind,
+    /// The name of the account, e.g. an e-mail address.
+    pub account: String,
+    /// The provider of the account, from the `issuer` parameter or,
+    /// failing that, from the label.
+    pub issuer: Option<String>,
+    /// The shared secret, decoded from Base32.
+    pub secret: Vec<u8>,
+    /// The hash function used for generating passwords.
+    pub algorithm: OtpAlgorithm,
+    /// The number of digits in a password.
+    pub digits: u8,
+}
+
+impl OtpAuth {
+    /// Parses an `otpauth://` URI. The secret is mandatory, as is the
+    /// counter of HOTP generators; the other parameters have defaults.
+    pub fn parse(uri: &str) -> Result<Self, ParseError> {
+        let rest = strip_prefix_ignore_case(uri, "otpauth://")
+            .ok_or_else(|| error("missing `otpauth://` scheme"))?;
+        let (path, query) = split_query(rest);
+        let slash = path.find('/').ok_or_else(|| error("missing label"))?;
+        let (kind, label) = (&path[..slash], percent_decode(&path[slash + 1..], false));
+
+        let (label_issuer, account) = match label.find(':') {
+            Some(i) => (Some(label[..i].trim()), label[i + 1..].trim()),
+            None => (None, label.trim()),
+        };
+
+        let issuer = query_value(&query, "issuer")
+            .or(label_issuer)
+            .filter(|issuer| !issuer.is_empty())
+            .map(str::to_owned);
+
+        let secret = query_value(&query, "secret").ok_or_else(|| error("missing secret"))?;
+        let secret = base32_decode(secret).ok_or_else(|| error("invalid secret"))?;
+
+        if secret.is_empty() {
+            return Err(error("empty secret"));
+        }
+
+        let algorithm = match query_value(&query, "algorithm") {
+            None => OtpAlgorithm::default(),
+            Some(name) if name.eq_ignore_ascii_case("SHA1") => OtpAlgorithm::Sha1,
+            Some(name) if name.eq_ignore_ascii_case("SHA256") => OtpAlgorithm::Sha256,
+            Some(name) if name.eq_ignore_ascii_case("SHA512") => OtpAlgorithm::Sha512,
+            Some(_) => return Err(error("unsupported algorithm")),
+        };
+
+        let digits = match query_value(&query, "digits") {
+            None => DEFAULT_DIGITS,
+            Some(digits) => match digits.parse() {
+                Ok(digits @ 6..=10) => digits,
+                _ => return Err(error("invalid number of digits")),
+            },
+        };
+
+        let kind = if kind.eq_ignore_ascii_case("totp") {
+            let period = match query_value(&query, "period") {
+                None => DEFAULT_PERIOD,
+                Some(period) => match period.parse() {
+                    Ok(period) if period > 0 => period,
+                    _ => return Err(error("invalid period")),
+                },
+            };
+            OtpKind::Totp { period }
+        } else if kind.eq_ignore_ascii_case("hotp") {
+            let counter = query_value(&query, "counter").ok_or_else(|| error("missing counter"))?;
+            let counter = counter.parse().map_err(|_| error("invalid counter"))?;
+            OtpKind::Hotp { counter }
+        } else {
+            return Err(error("unknown type"));
+        };
+
+        Ok(OtpAuth {
+            kind,
+            account: account.to_owned(),
+            issuer,
+            secret,
+            algorithm,
+            digits,
+        })
+    }
+}
+
+/// Creates a `ParseError` for the `otpauth` format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("otpauth", reason)
+}
+
+/// Decodes the RFC 4648 Base32 alphabet, case-insensitively. Padding is
+/// optional, as it is usually omitted from `otpauth://` URIs, and so are
+/// spaces, which some issuers insert for readability.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+
+    for c in s.trim_end_matches('=').bytes().filter(|&c| c != b' ') {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+
+        buffer = buffer << 5 | u32::from(value);
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(out)
+}
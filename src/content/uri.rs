@@ -0,0 +1,83 @@
+//! Helpers for the URI-like syntaxes used by many payload formats.
+
+/// Decodes `%XX` escapes in `s`, and also `+` as space if `plus_as_space`
+/// (as in `application/x-www-form-urlencoded` query strings). Malformed
+/// escapes are kept verbatim, and invalid UTF-8 is replaced by U+FFFD.
+pub fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                    (Some(hi), Some(lo)) => {
+                        out.push(hi << 4 | lo);
+                        i += 3;
+                        continue;
+                    }
+                    _ => out.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            byte => out.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Returns the value of an ASCII hexadecimal digit.
+pub fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Splits `s` into the part before the first `?` and the decoded
+/// key-value pairs of the query string after it, if any.
+pub fn split_query(s: &str) -> (&str, Vec<(String, String)>) {
+    match s.find('?') {
+        Some(i) => (&s[..i], query_pairs(&s[i + 1..])),
+        None => (s, Vec::new()),
+    }
+}
+
+/// Decodes the `key=value` pairs of a query string separated by `&`.
+/// Keys are converted to lowercase, since they are case-insensitive in
+/// every format in which this is used.
+pub fn query_pairs(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.find('=') {
+                Some(i) => (&pair[..i], &pair[i + 1..]),
+                None => (pair, ""),
+            };
+            (percent_decode(key, true).to_lowercase(), percent_decode(value, true))
+        })
+        .collect()
+}
+
+/// Looks up the value of the first occurrence of `key` among `pairs`.
+pub fn query_value<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+/// Strips `prefix` off the beginning of `s`, ignoring ASCII case.
+pub fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
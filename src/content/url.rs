@@ -0,0 +1,435 @@
+//! Validation of URL payloads, and detection of common phishing tricks.
+//!
+//! Only hierarchical URLs of the form `scheme://authority/path?query#fragment`
+//! are supported. Internationalized host names are decoded from Punycode
+//! for display, and their labels are checked for mixed scripts, which
+//! are a hallmark of homograph attacks (e.g. a Cyrillic `а` in `pаypal.com`).
+
+use std::char;
+use std::str;
+use info::Info;
+use super::ParseError;
+use super::uri::hex_value;
+
+/// The parameters of the Punycode encoding, as used by IDNA (RFC 3492).
+const PUNYCODE_BASE: u32 = 36;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_T_MIN: u32 = 1;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_T_MAX: u32 = 26;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_SKEW: u32 = 38;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_DAMP: u32 = 700;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_INITIAL_BIAS: u32 = 72;
+/// See `PUNYCODE_BASE`.
+const PUNYCODE_INITIAL_N: u32 = 0x80;
+
+/// Determines which URLs are accepted, and whether they are checked for
+/// suspicious patterns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UrlOptions {
+    /// The accepted schemes, in lowercase. Defaults to `http` and `https`.
+    pub schemes: Vec<String>,
+    /// Whether to look for suspicious patterns. Defaults to `true`.
+    pub flag_suspicious: bool,
+}
+
+impl Default for UrlOptions {
+    fn default() -> Self {
+        UrlOptions {
+            schemes: vec!["http".to_owned(), "https".to_owned()],
+            flag_suspicious: true,
+        }
+    }
+}
+
+/// A pattern in a URL which is often used for misleading users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UrlWarning {
+    /// The URL contains user information, which can make another host
+    /// appear to be the real one, e.g. `https://bank.com@evil.example/`.
+    UserInfo,
+    /// The host name contains internationalized (Punycode) labels.
+    Punycode,
+    /// A label of the host name mixes letters from different scripts.
+    MixedScripts,
+    /// The host is a numeric IP address rather than a domain name.
+    IpAddress,
+    /// The host name contains percent-encoded characters.
+    EncodedHost,
+}
+
+impl UrlWarning {
+    /// Returns a short human-readable description of the pattern.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            UrlWarning::UserInfo => "URL contains user information before the host",
+            UrlWarning::Punycode => "host name contains internationalized labels",
+            UrlWarning::MixedScripts => "host name mixes letters from different scripts",
+            UrlWarning::IpAddress => "host is a numeric IP address",
+            UrlWarning::EncodedHost => "host name contains percent-encoded characters",
+        }
+    }
+}
+
+/// A validated URL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Url {
+    /// The URL, verbatim.
+    url: String,
+    /// The scheme, in lowercase.
+    scheme: String,
+    /// The user information before the `@`, if any.
+    user_info: Option<String>,
+    /// The host, verbatim.
+    host: String,
+    /// The host, in lowercase, with Punycode labels decoded.
+    unicode_host: String,
+    /// The port, if specified.
+    port: Option<u16>,
+    /// Everything after the authority: the path, the query and the fragment.
+    path: String,
+    /// The suspicious patterns found.
+    warnings: Vec<UrlWarning>,
+}
+
+impl Url {
+    /// Validates an `http` or `https` URL, and looks for suspicious patterns.
+    pub fn parse(url: &str) -> Result<Self, ParseError> {
+        Self::parse_with(url, &UrlOptions::default())
+    }
+
+    /// Validates a URL with one of the schemes in `options`.
+    pub fn parse_with(url: &str, options: &UrlOptions) -> Result<Self, ParseError> {
+        let url = url.trim();
+
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(error("whitespace or control character"));
+        }
+
+        validate_percent_encoding(url)?;
+
+        let colon = url.find(':').ok_or_else(|| error("missing scheme"))?;
+        let scheme = url[..colon].to_ascii_lowercase();
+        let valid_scheme = scheme.bytes().next().map_or(false, |b| b.is_ascii_alphabetic())
+            && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b));
+
+        if !valid_scheme {
+            return Err(error("invalid scheme"));
+        }
+
+        if !options.schemes.iter().any(|s| s.eq_ignore_ascii_case(&scheme)) {
+            return Err(error("unsupported scheme"));
+        }
+
+        let rest = url[colon + 1..]
+            .strip_prefix("//")
+            .ok_or_else(|| error("missing authority"))?;
+        let authority_end = rest
+            .find(|c| c == '/' || c == '?' || c == '#')
+            .unwrap_or_else(|| rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+
+        let (user_info, host_port) = match authority.rfind('@') {
+            Some(i) => (Some(authority[..i].to_owned()), &authority[i + 1..]),
+            None => (None, authority),
+        };
+
+        let (host, port) = split_port(host_port)?;
+
+        if host.is_empty() {
+            return Err(error("missing host"));
+        }
+
+        let unicode_host = host_to_unicode(host)?;
+
+        let mut parsed = Url {
+            url: url.to_owned(),
+            scheme,
+            user_info,
+            host: host.to_owned(),
+            unicode_host,
+            port,
+            path: path.to_owned(),
+            warnings: Vec::new(),
+        };
+
+        if options.flag_suspicious {
+            parsed.warnings = parsed.find_suspicious_patterns();
+        }
+
+        Ok(parsed)
+    }
+
+    /// Returns the URL, verbatim, without surrounding whitespace.
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the scheme, in lowercase.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Returns the user information before the `@`, if any.
+    pub fn user_info(&self) -> Option<&str> {
+        self.user_info.as_ref().map(String::as_str)
+    }
+
+    /// Returns the host, verbatim. IPv6 addresses include the brackets.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the host in lowercase, with Punycode (`xn--`) labels decoded.
+    /// This is what the user should be shown as the destination.
+    pub fn unicode_host(&self) -> &str {
+        &self.unicode_host
+    }
+
+    /// Returns the port, if specified.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Returns everything after the host and the port: the path, the query
+    /// and the fragment, verbatim.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the suspicious patterns found in the URL. This is empty
+    /// unless the `flag_suspicious` option was set.
+    pub fn warnings(&self) -> &[UrlWarning] {
+        &self.warnings
+    }
+
+    /// Returns whether any suspicious patterns were found.
+    pub fn is_suspicious(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    /// Looks for suspicious patterns in the already-validated URL.
+    fn find_suspicious_patterns(&self) -> Vec<UrlWarning> {
+        let mut warnings = Vec::new();
+
+        if self.user_info.is_some() {
+            warnings.push(UrlWarning::UserInfo);
+        }
+
+        if self.host.split('.').any(is_punycode_label) {
+            warnings.push(UrlWarning::Punycode);
+        }
+
+        if self.unicode_host.split('.').any(has_mixed_scripts) {
+            warnings.push(UrlWarning::MixedScripts);
+        }
+
+        let is_ipv4 = self.host.split('.').count() == 4
+            && self.host.split('.').all(|part| part.parse::<u8>().is_ok());
+
+        if is_ipv4 || self.host.starts_with('[') {
+            warnings.push(UrlWarning::IpAddress);
+        }
+
+        if self.host.contains('%') {
+            warnings.push(UrlWarning::EncodedHost);
+        }
+
+        warnings
+    }
+}
+
+impl Info {
+    /// Validates the payload as an `http` or `https` URL. See `Url::parse()`.
+    pub fn as_url(&self) -> Result<Url, ParseError> {
+        self.as_url_with(&UrlOptions::default())
+    }
+
+    /// Validates the payload as a URL. See `Url::parse_with()`.
+    pub fn as_url_with(&self, options: &UrlOptions) -> Result<Url, ParseError> {
+        let text = str::from_utf8(self.payload()).map_err(|_| error("invalid UTF-8"))?;
+        Url::parse_with(text, options)
+    }
+}
+
+/// Creates a `ParseError` for the URL format.
+fn error(reason: &'static str) -> ParseError {
+    ParseError::new("URL", reason)
+}
+
+/// Checks that every `%` is followed by two hexadecimal digits.
+fn validate_percent_encoding(s: &str) -> Result<(), ParseError> {
+    let bytes = s.as_bytes();
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'%' {
+            let valid = bytes.len() > i + 2
+                && hex_value(bytes[i + 1]).is_some()
+                && hex_value(bytes[i + 2]).is_some();
+
+            if !valid {
+                return Err(error("invalid percent-encoding"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits the port, if any, off the host.
+fn split_port(host_port: &str) -> Result<(&str, Option<u16>), ParseError> {
+    // IPv6 addresses contain colons, so only look for the port after them.
+    let search_from = if host_port.starts_with('[') {
+        host_port.find(']').map(|i| i + 1).ok_or_else(|| error("unterminated IPv6 address"))?
+    } else {
+        0
+    };
+
+    match host_port[search_from..].find(':') {
+        Some(i) => {
+            let (host, port) = host_port.split_at(search_from + i);
+            let port = &port[1..];
+
+            if port.is_empty() {
+                Ok((host, None))
+            } else if port.bytes().all(|b| b.is_ascii_digit()) {
+                port.parse().map(|port| (host, Some(port))).map_err(|_| error("invalid port"))
+            } else {
+                Err(error("invalid port"))
+            }
+        }
+        None => Ok((host_port, None)),
+    }
+}
+
+/// Returns whether a label of a host name is Punycode-encoded.
+fn is_punycode_label(label: &str) -> bool {
+    label.len() > 4 && label.get(..4).map_or(false, |prefix| prefix.eq_ignore_ascii_case("xn--"))
+}
+
+/// Lowercases the host and decodes its Punycode labels.
+fn host_to_unicode(host: &str) -> Result<String, ParseError> {
+    let labels: Result<Vec<String>, ParseError> = host
+        .split('.')
+        .map(|label| {
+            if is_punycode_label(label) {
+                punycode_decode(&label[4..].to_ascii_lowercase())
+                    .ok_or_else(|| error("invalid Punycode label"))
+            } else {
+                Ok(label.to_lowercase())
+            }
+        })
+        .collect();
+
+    labels.map(|labels| labels.join("."))
+}
+
+/// Decodes a Punycode string, without the `xn--` prefix (RFC 3492).
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn punycode_decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(i) => (&input[..i], &input[i + 1..]),
+        None => ("", input),
+    };
+
+    if !basic.is_ascii() {
+        return None;
+    }
+
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = PUNYCODE_INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = PUNYCODE_INITIAL_BIAS;
+    let mut digits = extended.bytes().peekable();
+
+    while digits.peek().is_some() {
+        let old_i = i;
+        let mut weight: u32 = 1;
+        let mut k = PUNYCODE_BASE;
+
+        loop {
+            let digit = match digits.next()? {
+                b @ b'a'..=b'z' => u32::from(b - b'a'),
+                b @ b'0'..=b'9' => u32::from(b - b'0') + 26,
+                _ => return None,
+            };
+
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+
+            let threshold = if k <= bias {
+                PUNYCODE_T_MIN
+            } else if k >= bias + PUNYCODE_T_MAX {
+                PUNYCODE_T_MAX
+            } else {
+                k - bias
+            };
+
+            if digit < threshold {
+                break;
+            }
+
+            weight = weight.checked_mul(PUNYCODE_BASE - threshold)?;
+            k += PUNYCODE_BASE;
+        }
+
+        let length = output.len() as u32 + 1;
+        bias = punycode_adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length)?;
+        i %= length;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+
+    Some(output.into_iter().collect())
+}
+
+/// Adapts the bias after decoding a code point (RFC 3492, section 6.1).
+fn punycode_adapt(delta: u32, length: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / PUNYCODE_DAMP } else { delta / 2 };
+    let mut k = 0;
+
+    delta += delta / length;
+
+    while delta > ((PUNYCODE_BASE - PUNYCODE_T_MIN) * PUNYCODE_T_MAX) / 2 {
+        delta /= PUNYCODE_BASE - PUNYCODE_T_MIN;
+        k += PUNYCODE_BASE;
+    }
+
+    k + (PUNYCODE_BASE - PUNYCODE_T_MIN + 1) * delta / (delta + PUNYCODE_SKEW)
+}
+
+/// The scripts which are commonly confused with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    /// Latin letters, including accented ones.
+    Latin,
+    /// Greek letters.
+    Greek,
+    /// Cyrillic letters.
+    Cyrillic,
+}
+
+/// Returns the script of a letter, or `None` for digits, punctuation,
+/// and letters of scripts which aren't easily confused with Latin.
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x41..=0x5a | 0x61..=0x7a | 0xc0..=0x24f | 0x1e00..=0x1eff => Some(Script::Latin),
+        0x370..=0x3ff | 0x1f00..=0x1fff => Some(Script::Greek),
+        0x400..=0x52f | 0x2de0..=0x2dff | 0xa640..=0xa69f => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Returns whether a label contains letters of more than one of the
+/// commonly confused scripts.
+fn has_mixed_scripts(label: &str) -> bool {
+    let mut scripts = label.chars().filter_map(script);
+
+    match scripts.next() {
+        Some(first) => scripts.any(|s| s != first),
+        None => false,
+    }
+}
@@ -0,0 +1,181 @@
+//! Contact details in the vCard format (versions 2.1, 3.0 and 4.0).
+//!
+//! A vCard is a sequence of content lines of the form
+//! `[group.]NAME;PARAM=value;...:value`, enclosed by `BEGIN:VCARD` and
+//! `END:VCARD`. Long lines may be folded by inserting a line break followed
+//! by a space or a tab, and vCard 2.1 values may be quoted-printable, in
+//! which case a trailing `=` joins the line with the next one.
+
+use super::ParseError;
+use super::uri::strip_prefix_ignore_case;
+use super::line::{ Property, unfold, components, unescape };
+
+/// A value of a property which may be qualified by types, e.g. a `TEL`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VCardValue {
+    /// The value itself.
+    pub value: String,
+    /// The types of the value, e.g. `work` or `cell`, in lowercase.
+    pub types: Vec<String>,
+}
+
+/// The components of the structured name of a contact (`N`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VCardName {
+    /// Family name.
+    pub family: String,
+    /// Given name.
+    pub given: String,
+    /// Additional (middle) names.
+    pub additional: String,
+    /// Honorific prefixes, e.g. `Dr.`
+    pub prefixes: String,
+    /// Honorific suffixes, e.g. `Jr.`
+    pub suffixes: String,
+}
+
+/// The components of a postal address (`ADR`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VCardAddress {
+    /// The types of the address, e.g. `home` or `work`, in lowercase.
+    pub types: Vec<String>,
+    /// Post office box.
+    pub po_box: String,
+    /// Extended address, e.g. apartment or suite number.
+    pub extended: String,
+    /// Street address.
+    pub street: String,
+    /// Locality, e.g. city.
+    pub locality: String,
+    /// Region, e.g. state or province.
+    pub region: String,
+    /// Postal code.
+    pub postal_code: String,
+    /// Country name.
+    pub country: String,
+}
+
+/// Contact details in the vCard format.
+///
+/// Only the commonly used properties are extracted; others are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VCard {
+    /// The version of the format, e.g. `3.0`.
+    pub version: String,
+    /// The formatted name of the contact (`FN`).
+    pub formatted_name: Option<String>,
+    /// The structured name of the contact (`N`).
+    pub name: Option<VCardName>,
+    /// Phone numbers (`TEL`).
+    pub phones: Vec<VCardValue>,
+    /// E-mail addresses (`EMAIL`).
+    pub emails: Vec<VCardValue>,
+    /// The name of the organization, followed by its units (`ORG`).
+    pub org: Vec<String>,
+    /// Job title (`TITLE`).
+    pub title: Option<String>,
+    /// Postal addresses (`ADR`).
+    pub addresses: Vec<VCardAddress>,
+    /// Web sites (`URL`).
+    pub urls: Vec<String>,
+    /// Birthday (`BDAY`), verbatim.
+    pub birthday: Option<String>,
+    /// Free-form note (`NOTE`).
+    pub note: Option<String>,
+}
+
+impl VCard {
+    /// Parses a payload in the vCard format. The payload must start with
+    /// `BEGIN:VCARD` and contain a `VERSION` property; a missing `END:VCARD`
+    /// is tolerated, since it is sometimes cut off in QR codes.
+    pub fn parse(payload: &str) -> Result<Self, ParseError> {
+        let mut lines = unfold(payload).into_iter();
+        let mut card = VCard::default();
+
+        match lines.next() {
+            Some(ref line) if line.trim().eq_ignore_ascii_case("BEGIN:VCARD") => {}
+            _ => return Err(ParseError::new("vCard", "missing `BEGIN:VCARD`")),
+        }
+
+        for line in lines {
+            let property = match Property::parse(&line) {
+                Some(property) => property,
+                None => continue,
+            };
+
+            match property.name.as_str() {
+                "END" => break,
+                "VERSION" => card.version = property.value.trim().to_owned(),
+                "FN" => card.formatted_name = Some(unescape(&property.value)),
+                "N" => {
+                    let mut parts = components(&property.value).into_iter();
+                    let mut next = || parts.next().unwrap_or_default();
+
+                    card.name = Some(VCardName {
+                        family: next(),
+                        given: next(),
+                        additional: next(),
+                        prefixes: next(),
+                        suffixes: next(),
+                    });
+                }
+                "TEL" => {
+                    let value = unescape(&property.value);
+                    let value = match strip_prefix_ignore_case(&value, "tel:") {
+                        Some(number) => number.to_owned(),
+                        None => value,
+                    };
+                    card.phones.push(VCardValue { value, types: property.types });
+                }
+                "EMAIL" => {
+                    let value = unescape(&property.value);
+                    card.emails.push(VCardValue { value, types: property.types });
+                }
+                "ORG" => card.org = components(&property.value),
+                "TITLE" => card.title = Some(unescape(&property.value)),
+                "ADR" => {
+                    let mut parts = components(&property.value).into_iter();
+                    let mut next = || parts.next().unwrap_or_default();
+
+                    card.addresses.push(VCardAddress {
+                        po_box: next(),
+                        extended: next(),
+                        street: next(),
+                        locality: next(),
+                        region: next(),
+                        postal_code: next(),
+                        country: next(),
+                        types: property.types,
+                    });
+                }
+                "URL" => card.urls.push(unescape(&property.value)),
+                "BDAY" => card.birthday = Some(unescape(&property.value)),
+                "NOTE" => card.note = Some(unescape(&property.value)),
+                _ => {}
+            }
+        }
+
+        if card.version.is_empty() {
+            return Err(ParseError::new("vCard", "missing `VERSION`"));
+        }
+
+        Ok(card)
+    }
+
+    /// Returns the name to display for the contact: the formatted name if
+    /// present, or else the given and family names joined by a space.
+    pub fn display_name(&self) -> Option<String> {
+        if let Some(ref name) = self.formatted_name {
+            return Some(name.clone());
+        }
+
+        self.name.as_ref().map(|name| {
+            let parts = [&name.given, &name.family];
+            let parts: Vec<&str> = parts.iter()
+                .map(|part| part.as_str())
+                .filter(|part| !part.is_empty())
+                .collect();
+            parts.join(" ")
+        })
+    }
+}
@@ -0,0 +1,121 @@
+//! Credentials for joining a wireless network, in the `WIFI:` format.
+//!
+//! The format is `WIFI:T:<type>;S:<ssid>;P:<password>;H:<hidden>;;`, where
+//! the fields may come in any order, and the characters `\`, `;`, `,`, `:`
+//! and `"` are escaped by a backslash within the values.
+
+use std::fmt;
+use super::{ fields, ParseError };
+use super::uri::strip_prefix_ignore_case;
+
+/// The authentication type of a wireless network.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WifiSecurity {
+    /// An open network (`nopass`, or no type specified).
+    None,
+    /// WEP.
+    Wep,
+    /// WPA, WPA2 or WPA3 with a pre-shared key.
+    Wpa,
+    /// Any other authentication type, verbatim.
+    Other(String),
+}
+
+impl WifiSecurity {
+    /// Returns the value of the `T` field representing this type.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            WifiSecurity::None => "nopass",
+            WifiSecurity::Wep => "WEP",
+            WifiSecurity::Wpa => "WPA",
+            WifiSecurity::Other(ref other) => other,
+        }
+    }
+}
+
+/// Credentials for joining a wireless network.
+///
+/// The `Display` impl produces a payload in the `WIFI:` format, with all
+/// special characters escaped, so that it can be parsed back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WifiConfig {
+    /// The name of the network.
+    pub ssid: String,
+    /// The password of the network, unless it is open.
+    pub password: Option<String>,
+    /// The authentication type of the network.
+    pub security: WifiSecurity,
+    /// Whether the network doesn't broadcast its SSID.
+    pub hidden: bool,
+}
+
+impl WifiConfig {
+    /// Parses a payload in the `WIFI:` format. The prefix is matched
+    /// case-insensitively. The SSID is mandatory, while all other fields
+    /// are optional. Unknown fields (e.g. the EAP parameters) are ignored.
+    pub fn parse(payload: &str) -> Result<Self, ParseError> {
+        let body = strip_prefix_ignore_case(payload, "WIFI:")
+            .ok_or_else(|| ParseError::new("WIFI", "missing `WIFI:` prefix"))?;
+        let mut ssid = None;
+        let mut config = WifiConfig {
+            ssid: String::new(),
+            password: None,
+            security: WifiSecurity::None,
+            hidden: false,
+        };
+
+        for (key, value) in fields(body) {
+            match key.as_str() {
+                "S" => ssid = Some(value),
+                "P" => config.password = Some(value).filter(|p| !p.is_empty()),
+                "T" => config.security = match value.to_ascii_uppercase().as_str() {
+                    "" | "NOPASS" => WifiSecurity::None,
+                    "WEP" => WifiSecurity::Wep,
+                    "WPA" | "WPA2" | "WPA3" => WifiSecurity::Wpa,
+                    _ => WifiSecurity::Other(value),
+                },
+                "H" => config.hidden = value.eq_ignore_ascii_case("true"),
+                _ => {}
+            }
+        }
+
+        config.ssid = ssid.ok_or_else(|| ParseError::new("WIFI", "missing SSID"))?;
+
+        Ok(config)
+    }
+}
+
+impl fmt::Display for WifiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WIFI:T:{};S:", Escaped(self.security.as_str()))?;
+        write!(f, "{};", Escaped(&self.ssid))?;
+
+        if let Some(ref password) = self.password {
+            write!(f, "P:{};", Escaped(password))?;
+        }
+
+        if self.hidden {
+            f.write_str("H:true;")?;
+        }
+
+        f.write_str(";")
+    }
+}
+
+/// Formats a field value with the special characters escaped.
+#[derive(Debug, Clone, Copy)]
+struct Escaped<'a>(&'a str);
+
+impl<'a> fmt::Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            if let '\\' | ';' | ',' | ':' | '"' = c {
+                f.write_str("\\")?;
+            }
+
+            write!(f, "{}", c)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Interoperability with OpenCV, through the `opencv` crate.
+//!
+//! Frames captured or decoded by OpenCV are converted into an `ImageBuf`,
+//! taking the padding of rows into account, and color frames are converted
+//! to grayscale by OpenCV itself. The outlines of the detected codes can
+//! then be drawn onto the original frame, e.g. for a preview window:
+//!
+//! ```ignore
+//! let image = ImageBuf::try_from(&frame)?;
+//! let codes: Vec<_> = decoder.decode_image(&image.as_image())?.flatten().collect();
+//!
+//! cv::draw_codes(&mut frame, &codes, Scalar::new(0.0, 255.0, 0.0, 0.0), 2)?;
+//! highgui::imshow("quirs", &frame)?;
+//! ```
+
+use std::convert::TryFrom;
+use std::vec::Vec;
+use opencv::prelude::*;
+use opencv::core::{ Mat, Point, Scalar, Vector, CV_8UC1, CV_8UC3, CV_8UC4 };
+use opencv::imgproc::{ self, COLOR_BGR2GRAY, COLOR_BGRA2GRAY, LINE_AA };
+use geom::{ ImageBuf, Vec2D, QrCode };
+use util::{ int_to_usize, checked_mul };
+use error::{ Error, Result };
+
+/// Copies a 2-dimensional `Mat` of type `CV_8UC1` (grayscale), `CV_8UC3`
+/// (BGR) or `CV_8UC4` (BGRA), converting color images to grayscale.
+/// Fails with `Error::InvalidImage` for any other type.
+impl<'a> TryFrom<&'a Mat> for ImageBuf {
+    type Error = Error;
+
+    fn try_from(mat: &'a Mat) -> Result<Self> {
+        if mat.dims() > 2 {
+            return Err(Error::InvalidImage);
+        }
+
+        let conversion = match mat.typ() {
+            CV_8UC1 => return copy_gray(mat),
+            CV_8UC3 => COLOR_BGR2GRAY,
+            CV_8UC4 => COLOR_BGRA2GRAY,
+            _ => return Err(Error::InvalidImage),
+        };
+        let mut gray = Mat::default();
+
+        imgproc::cvt_color_def(mat, &mut gray, conversion).map_err(|_| Error::InvalidImage)?;
+
+        copy_gray(&gray)
+    }
+}
+
+/// Copies a `CV_8UC1` matrix row by row, dropping the padding of rows.
+fn copy_gray(mat: &Mat) -> Result<ImageBuf> {
+    let size = Vec2D {
+        x: int_to_usize(mat.cols())?,
+        y: int_to_usize(mat.rows())?,
+    };
+    let mut data = Vec::with_capacity(checked_mul(size.x, size.y)?);
+
+    for y in 0..mat.rows() {
+        data.extend_from_slice(mat.at_row::<u8>(y).map_err(|_| Error::InvalidImage)?);
+    }
+
+    ImageBuf::new(data, size)
+}
+
+/// Draws the outline of a code with the given corners onto `mat`, in the
+/// given color and with the given line thickness in pixels.
+pub fn draw_outline(
+    mat: &mut Mat,
+    corners: &[Vec2D; 4],
+    color: Scalar,
+    thickness: i32,
+) -> opencv::Result<()> {
+    // Coordinates beyond the range of `int` are off the image anyway,
+    // and OpenCV clips the lines to it.
+    let coordinate = |value: usize| i32::try_from(value).unwrap_or(i32::max_value());
+    let points: Vector<Point> = corners
+        .iter()
+        .map(|corner| Point::new(coordinate(corner.x), coordinate(corner.y)))
+        .collect();
+
+    imgproc::polylines(mat, &points, true, color, thickness, LINE_AA, 0)
+}
+
+/// Draws the outlines of `codes` onto `mat`, which should be the frame
+/// (or a copy of it) the codes were detected in.
+pub fn draw_codes(
+    mat: &mut Mat,
+    codes: &[QrCode],
+    color: Scalar,
+    thickness: i32,
+) -> opencv::Result<()> {
+    for code in codes {
+        draw_outline(mat, &code.corners(), color, thickness)?;
+    }
+
+    Ok(())
+}
@@ -1,36 +1,475 @@
 //! The actual QR code decoder.
 
+use std::fmt;
 use std::ptr;
+use std::mem;
+use std::slice;
 use std::usize;
+use std::ops::{ Deref, DerefMut };
+use std::iter::FusedIterator;
+use std::vec;
+#[cfg(feature = "std")]
+use std::time::Instant;
+#[cfg(feature = "std")]
 use std::ffi::CStr;
-use libc::c_int;
-use geom::{ Image, QrCode };
-use quirc_sys::{ quirc, quirc_version, quirc_new, quirc_destroy };
+#[cfg(feature = "std")]
+use std::error;
+use std::os::raw::c_int;
+use std::vec::Vec;
+use geom::{ Image, ImageBuf, Vec2D, QrCode };
+use info::Info;
+use mask::{ self, ExclusionZone };
+#[cfg(feature = "std")]
+use soak::SoakMonitor;
+#[cfg(feature = "std")]
+use metrics::{ Metrics, Recorder };
+use quirc_sys::{ quirc, quirc_new, quirc_destroy };
+#[cfg(feature = "std")]
+use quirc_sys::quirc_version;
 use quirc_sys::{ quirc_resize, quirc_begin, quirc_end };
 use quirc_sys::{ quirc_code, quirc_count, quirc_extract };
-use util::{ usize_to_int, int_to_usize };
+use quirc_sys::{ quirs_debug_pixels, QUIRC_PIXEL_WHITE, QUIRC_PIXEL_BLACK };
+use quirc_sys::{ quirc_point, quirs_debug_num_regions, quirs_debug_num_capstones, quirs_debug_capstone };
+use quirc_sys::{ quirs_swap_image, quirs_memory_usage };
+use util::{ usize_to_int, int_to_usize, checked_add, checked_mul };
 use error::{ Error, Result };
 
+/// Options controlling how images are fed to a `Decoder`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DecoderConfig {
+    /// Regions of every image that are hidden from the detector.
+    /// They are blanked out while the image is copied into the decoder,
+    /// so the caller's buffer is left untouched, except by
+    /// `Decoder::decode_image_in_place()`, which takes it over anyway.
+    pub exclusion_zones: Vec<ExclusionZone>,
+    /// The largest number of pixels an image may have, including the
+    /// padding added according to `resize_bucket`. Larger images are
+    /// rejected with `Error::ImageTooLarge` before `quirc` allocates any
+    /// memory for them. `None` means no limit.
+    pub max_image_area: Option<usize>,
+    /// Rounds the width and the height of the image buffers of `quirc` up
+    /// to a multiple of this many pixels, and pads images with white, so
+    /// that images whose sizes differ only slightly (e.g. frames of a video
+    /// cropped to a region of interest) don't make the decoder free and
+    /// reallocate its buffers every time. `None`, 0 and 1 mean no rounding.
+    ///
+    /// `Decoder::decode_image_in_place()` pads images within the buffer it
+    /// takes over, which is only reallocated if its capacity is too small.
+    /// `Decoder::begin()` hands out buffers of the exact size of the image.
+    pub resize_bucket: Option<usize>,
+}
+
+impl DecoderConfig {
+    /// Returns the size of the buffers for an image of the given size,
+    /// rounded up as specified by `resize_bucket`.
+    fn bucketed_size(&self, size: Vec2D) -> Result<Vec2D> {
+        let bucket = match self.resize_bucket {
+            Some(bucket) if bucket > 1 => bucket,
+            _ => return Ok(size),
+        };
+        let round_up = |n: usize| -> Result<usize> {
+            checked_mul(checked_add(n, bucket - 1)? / bucket, bucket)
+        };
+
+        Ok(Vec2D { x: round_up(size.x)?, y: round_up(size.y)? })
+    }
+}
+
+/// What the detector found in an image on its way to the QR codes,
+/// returned by `Decoder::diagnostics()`.
+///
+/// Detection proceeds in stages: dark regions are examined for the
+/// 1:1:3:1:1 pattern of finder patterns ("capstones"), capstones are
+/// grouped by threes into grids, and each grid is a code, which may still
+/// fail to decode. So if no code is returned, no capstones means that the
+/// finder patterns weren't recognized at all (look at the thresholded
+/// image, see `Decoder::debug_threshold_image()`), while capstones which
+/// belong to no grid mean that they couldn't be grouped, e.g. because one
+/// of the three is missing, or the perspective is too steep.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Diagnostics {
+    /// The number of regions examined as candidate finder patterns.
+    pub num_regions: usize,
+    /// The finder patterns found, in the order they were found.
+    pub capstones: Vec<Capstone>,
+    /// The number of grids formed by the capstones, which is the number
+    /// of codes detected.
+    pub num_grids: usize,
+}
+
+impl Diagnostics {
+    /// Returns the capstones which couldn't be grouped into any grid.
+    pub fn ungrouped_capstones(&self) -> impl Iterator<Item = &Capstone> {
+        self.capstones.iter().filter(|capstone| capstone.grid.is_none())
+    }
+}
+
+/// A finder pattern found by the detector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capstone {
+    /// The corners of the outer ring of the pattern.
+    pub corners: [Vec2D; 4],
+    /// The center of the pattern.
+    pub center: Vec2D,
+    /// The index of the grid (i.e. of the detected code) which the pattern
+    /// is part of, if any.
+    pub grid: Option<usize>,
+}
+
 /// A QR code decoder.
-#[derive(Debug)]
+///
+/// A decoder is `Send`, so it can be moved to another thread, or shared
+/// between threads behind a `Mutex`. It isn't `Sync`: detecting codes in
+/// an image mutates its internal state, which must not be read meanwhile.
 pub struct Decoder {
     /// Opaque handle to the `quirc` decoder object.
     inner: *mut quirc,
+    /// The options in effect for subsequent calls to `decode_image()`.
+    config: DecoderConfig,
+    /// The size of the image being processed.
+    size: Vec2D,
+    /// The size of the image buffer currently allocated by `quirc`, which
+    /// is larger than `size` if the image is padded, or `None` if none was
+    /// allocated yet, or the last allocation failed.
+    buffer_size: Option<Vec2D>,
+    /// Whether the image buffer holds the thresholded pixels of the last
+    /// processed image, rather than the input to the next one.
+    thresholded: bool,
+    /// The pixels taken over by `decode_image_in_place()`. While they are
+    /// installed as the image buffer of `quirc`, `quirc_image` holds the
+    /// buffer they replaced.
+    image_buf: Vec<u8>,
+    /// The image buffer allocated by `quirc` itself, while it is replaced
+    /// by `image_buf`.
+    quirc_image: Option<*mut u8>,
+    /// Monitors memory usage and latency, if enabled.
+    #[cfg(feature = "std")]
+    soak: Option<SoakMonitor>,
+    /// Receives the statistics of every image, if attached.
+    #[cfg(feature = "std")]
+    metrics: Option<Recorder>,
+}
+
+/// Shows the length of the pixels taken over by `decode_image_in_place()`
+/// instead of their contents, which are as large as the image.
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Decoder");
+
+        debug
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .field("size", &self.size)
+            .field("buffer_size", &self.buffer_size)
+            .field("thresholded", &self.thresholded)
+            .field("image_buf_len", &self.image_buf.len())
+            .field("quirc_image", &self.quirc_image);
+
+        #[cfg(feature = "std")]
+        debug
+            .field("soak", &self.soak)
+            .field("metrics", &self.metrics);
+
+        debug.finish()
+    }
 }
 
 impl Decoder {
-    /// Attempts to create a `Decoder`.
+    /// Attempts to create a `Decoder` with the default configuration.
     pub fn new() -> Result<Self> {
+        Self::with_config(DecoderConfig::default())
+    }
+
+    /// Attempts to create a `Decoder` with the specified configuration.
+    pub fn with_config(config: DecoderConfig) -> Result<Self> {
         let inner = unsafe { quirc_new() };
 
         if inner.is_null() {
             Err(Error::AllocFailed)
         } else {
-            Ok(Decoder { inner })
+            Ok(Decoder {
+                inner,
+                config,
+                size: Vec2D::default(),
+                buffer_size: None,
+                thresholded: false,
+                image_buf: Vec::new(),
+                quirc_image: None,
+                #[cfg(feature = "std")]
+                soak: None,
+                #[cfg(feature = "std")]
+                metrics: None,
+            })
+        }
+    }
+
+    /// Returns the current configuration of the decoder.
+    pub fn config(&self) -> &DecoderConfig {
+        &self.config
+    }
+
+    /// Returns the configuration of the decoder for modification.
+    pub fn config_mut(&mut self) -> &mut DecoderConfig {
+        &mut self.config
+    }
+
+    /// Rejects images with more than `limit` pixels from now on, bounding
+    /// the memory `quirc` allocates. See `DecoderConfig::max_image_area`.
+    pub fn set_max_image_area(&mut self, limit: usize) {
+        self.config.max_image_area = Some(limit);
+    }
+
+    /// Rounds the size of the image buffers up to a multiple of `bucket`
+    /// pixels from now on. See `DecoderConfig::resize_bucket`.
+    pub fn set_resize_bucket(&mut self, bucket: usize) {
+        self.config.resize_bucket = Some(bucket);
+    }
+
+    /// Returns the number of bytes allocated for the decoder: by `quirc`,
+    /// for its image and pixel buffers, which are sized according to the
+    /// last image, and for the work area of its flood fill, as well as the
+    /// pixels taken over by `decode_image_in_place()`.
+    pub fn buffer_bytes(&self) -> usize {
+        let quirc_bytes = unsafe { quirs_memory_usage(self.inner) };
+
+        quirc_bytes + self.image_buf.capacity()
+    }
+
+    /// Returns the number of QR codes detected in the last image.
+    pub fn count(&self) -> usize {
+        int_to_usize(self.raw_count()).expect("quirc_count() was negative")
+    }
+
+    /// Extracts the QR code at `index` among the ones detected in the last
+    /// image, in the order in which `Iter` yields them. This allows holding
+    /// on to the results of detection without borrowing the decoder.
+    ///
+    /// Fails with `Error::InvalidArgument` if `index >= self.count()`.
+    pub fn extract(&self, index: usize) -> Result<QrCode> {
+        if index < self.count() {
+            self.extract_raw(usize_to_int(index)?)
+        } else {
+            Err(Error::InvalidArgument)
+        }
+    }
+
+    /// Extracts the QR codes detected in the last image into `codes`, in
+    /// the order of detection, replacing its previous contents. The codes
+    /// already in `codes` are overwritten in place, so passing the same
+    /// vector for every image, e.g. in a video loop, neither allocates nor
+    /// copies the codes once it is large enough. Codes which can't be
+    /// extracted are skipped.
+    pub fn extract_into(&self, codes: &mut Vec<QrCode>) {
+        let mut len = 0;
+
+        for index in 0..self.raw_count() {
+            if let Some(code) = codes.get_mut(len) {
+                unsafe {
+                    quirc_extract(self.inner, index, code.as_raw_mut());
+                }
+
+                if code.validate().is_ok() {
+                    len += 1;
+                }
+            } else if let Ok(code) = self.extract_raw(index) {
+                codes.push(code);
+                len += 1;
+            }
+        }
+
+        codes.truncate(len);
+    }
+
+    /// Returns the number of codes detected in the last image, as reported
+    /// by `quirc`.
+    fn raw_count(&self) -> c_int {
+        let count = unsafe {
+            quirc_count(self.inner)
+        };
+
+        assert!(count >= 0, "quirc_count() was negative");
+
+        count
+    }
+
+    /// Extracts the code at `index`, which must be in bounds.
+    fn extract_raw(&self, index: c_int) -> Result<QrCode> {
+        assert!(index >= 0, "current index was negative");
+
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!("extract", index).entered();
+
+        // This is not `mem::uninitialized` because `quirc_extract()`
+        // returns without writing anything to the `quirc_code` out argument
+        // if the index is OOB. Although we have a bounds check, I have
+        // trust issues with underlying C libraries, so this remains a 0.
+        let mut raw = quirc_code::default();
+
+        unsafe {
+            quirc_extract(self.inner, index, &mut raw);
+        }
+
+        QrCode::from_raw(raw)
+    }
+
+    /// Returns the last processed image as the detector saw it after
+    /// thresholding: light pixels are white (255), dark pixels are black
+    /// (0), and dark pixels which were examined as part of a candidate
+    /// finder pattern are gray (128). Comparing it with the input shows
+    /// whether preprocessing (lighting, contrast, blur) is to blame for a
+    /// code which isn't detected.
+    ///
+    /// Returns `None` if no image has been processed since the decoder was
+    /// created, or since a buffer was obtained from `begin()`.
+    pub fn debug_threshold_image(&self) -> Option<ImageBuf> {
+        if !self.thresholded {
+            return None;
+        }
+
+        let mut data = vec![0; self.buffer_size.map_or(0, |size| size.x * size.y)];
+
+        unsafe {
+            quirs_debug_pixels(self.inner, data.as_mut_ptr());
+        }
+
+        for pixel in &mut data {
+            *pixel = match *pixel {
+                QUIRC_PIXEL_WHITE => 0xff,
+                QUIRC_PIXEL_BLACK => 0x00,
+                _ => 0x80,
+            };
+        }
+
+        // Crop the padding, if any.
+        let stride = self.buffer_size.map_or(0, |size| size.x);
+
+        if stride > self.size.x {
+            data = data
+                .chunks(stride)
+                .take(self.size.y)
+                .flat_map(|row| &row[..self.size.x])
+                .cloned()
+                .collect();
+        }
+
+        ImageBuf::new(data, self.size).ok()
+    }
+
+    /// Returns the intermediate results of detection in the last processed
+    /// image, which explain why fewer codes were detected than expected.
+    /// See `Diagnostics` for how to interpret them.
+    ///
+    /// Returns `None` if no image has been processed since the decoder was
+    /// created, or since a buffer was obtained from `begin()`.
+    pub fn diagnostics(&self) -> Option<Diagnostics> {
+        if !self.thresholded {
+            return None;
+        }
+
+        let (num_regions, num_capstones) = unsafe {
+            (quirs_debug_num_regions(self.inner), quirs_debug_num_capstones(self.inner))
+        };
+        let capstones = (0..num_capstones)
+            .map(|index| {
+                let mut corners = [quirc_point::default(); 4];
+                let mut center = quirc_point::default();
+                let grid = unsafe {
+                    quirs_debug_capstone(self.inner, index, corners.as_mut_ptr(), &mut center)
+                };
+
+                Ok(Capstone {
+                    corners: [
+                        Vec2D::from_raw(corners[0])?,
+                        Vec2D::from_raw(corners[1])?,
+                        Vec2D::from_raw(corners[2])?,
+                        Vec2D::from_raw(corners[3])?,
+                    ],
+                    center: Vec2D::from_raw(center)?,
+                    // Negative if the capstone belongs to no grid.
+                    grid: int_to_usize(grid).ok(),
+                })
+            })
+            .collect::<Result<_>>()
+            .ok()?;
+
+        Some(Diagnostics {
+            num_regions: int_to_usize(num_regions).ok()?,
+            capstones,
+            num_grids: self.count(),
+        })
+    }
+
+    /// Attaches a monitor, which is then fed the buffer size and the
+    /// detection latency of every subsequent image. Replaces and returns
+    /// the previously attached monitor, if any.
+    #[cfg(feature = "std")]
+    pub fn set_soak_monitor(&mut self, monitor: SoakMonitor) -> Option<SoakMonitor> {
+        self.soak.replace(monitor)
+    }
+
+    /// Returns the attached monitor, if any.
+    #[cfg(feature = "std")]
+    pub fn soak_monitor(&self) -> Option<&SoakMonitor> {
+        self.soak.as_ref()
+    }
+
+    /// Detaches and returns the attached monitor, if any.
+    #[cfg(feature = "std")]
+    pub fn take_soak_monitor(&mut self) -> Option<SoakMonitor> {
+        self.soak.take()
+    }
+
+    /// Attaches a `Metrics` implementation, which is then passed the
+    /// statistics of every subsequent image. Replaces and returns the
+    /// previously attached one, if any. See the `metrics` module.
+    #[cfg(feature = "std")]
+    pub fn set_metrics<M: Metrics + 'static>(&mut self, metrics: M) -> Option<Box<dyn Metrics>> {
+        self.finish_frame();
+        self.metrics.replace(Recorder::new(Box::new(metrics))).map(Recorder::into_inner)
+    }
+
+    /// Detaches and returns the attached `Metrics` implementation, if any.
+    #[cfg(feature = "std")]
+    pub fn take_metrics(&mut self) -> Option<Box<dyn Metrics>> {
+        self.finish_frame();
+        self.metrics.take().map(Recorder::into_inner)
+    }
+
+    /// Reports the statistics of the last image to the attached `Metrics`,
+    /// unless they have already been reported.
+    #[cfg(feature = "std")]
+    fn finish_frame(&mut self) {
+        if let Some(ref mut metrics) = self.metrics {
+            metrics.finish_frame();
         }
     }
 
+    /// Does nothing, since metrics require `std`.
+    #[cfg(not(feature = "std"))]
+    fn finish_frame(&mut self) {}
+
+    /// Extracts and decodes the code at `index`, recording the time it
+    /// took and the outcome in the attached `Metrics`, if any.
+    #[cfg(feature = "std")]
+    fn decode_at(&mut self, index: c_int) -> CodeResult {
+        let start = Instant::now();
+        let result = decode_code(int_to_usize(index).unwrap_or_default(), self.extract_raw(index));
+
+        if let Some(ref mut metrics) = self.metrics {
+            metrics.record_code(start.elapsed(), result.as_ref().err().map(|e| e.error));
+        }
+
+        result
+    }
+
+    /// Extracts and decodes the code at `index`.
+    #[cfg(not(feature = "std"))]
+    fn decode_at(&mut self, index: c_int) -> CodeResult {
+        decode_code(int_to_usize(index).unwrap_or_default(), self.extract_raw(index))
+    }
+
     /// Return the version number of the `quirc` library, if possible.
+    #[cfg(feature = "std")]
     pub fn version() -> &'static str {
         let version_ptr = unsafe { quirc_version() };
 
@@ -44,102 +483,757 @@ impl Decoder {
     }
 
     /// Feeds image data to the decoder and returns the QR codes.
+    ///
+    /// If the rows of the image are padded (i.e. its stride is greater
+    /// than its width), they are copied into the decoder one by one.
     pub fn decode_image(&mut self, image: &Image) -> Result<Iter> {
-        let width = usize_to_int(image.width())?;
-        let height = usize_to_int(image.height())?;
-        let image_data = image.data();
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!(
+            "decode_image",
+            width = image.width(),
+            height = image.height(),
+            stride = image.stride(),
+        ).entered();
 
-        unsafe {
-            if quirc_resize(self.inner, width, height) != 0 {
-                return Err(Error::AllocFailed);
+        let size = Vec2D { x: image.width(), y: image.height() };
+        let buffer_size = self.config.bucketed_size(size)?;
+        let buf_ptr = self.begin_frame(size, buffer_size)?;
+        let num_pixels = size.x * size.y;
+        let buf = unsafe {
+            slice::from_raw_parts_mut(buf_ptr, buffer_size.x * buffer_size.y)
+        };
+        let zones = &self.config.exclusion_zones;
+
+        if size.x == 0 {
+            // nothing to copy, and `chunks_mut(0)` would panic
+        } else if zones.is_empty() && image.stride() == image.width() && buffer_size == size {
+            buf.copy_from_slice(&image.data()[..num_pixels]);
+        } else {
+            for (y, dst) in buf.chunks_mut(buffer_size.x).enumerate() {
+                if y < size.y {
+                    let (row, padding) = dst.split_at_mut(size.x);
+
+                    row.copy_from_slice(image.row(y));
+                    mask::blank_row(zones, y, row);
+                    fill_white(padding);
+                } else {
+                    fill_white(dst);
+                }
             }
+        }
 
-            let buf_ptr = quirc_begin(
-                self.inner,
-                ptr::null_mut(),
-                ptr::null_mut(),
-            );
-            assert!(!buf_ptr.is_null(), "quirc_begin() returned null pointer");
+        Ok(self.end_frame())
+    }
 
-            ptr::copy_nonoverlapping(
-                image_data.as_ptr(),
-                buf_ptr,
-                image_data.len(),
-            );
+    /// Like `decode_image()`, but extracts every code up front, so that
+    /// the results don't borrow the decoder, which can then proceed to the
+    /// next image while the results are being processed, possibly on
+    /// another thread.
+    pub fn decode_image_owned(&mut self, image: &Image) -> Result<Detections> {
+        let codes = self.decode_image(image)?.collect();
 
-            quirc_end(self.inner);
+        Ok(Detections { codes })
+    }
+
+    /// Like `decode_image_owned()`, but stores the codes in `detections`,
+    /// replacing its previous contents, so that its buffer is reused from
+    /// one image to the next, e.g. in a video loop, instead of allocating a
+    /// new one for every image. `detections` is emptied even if this fails.
+    pub fn decode_image_into(&mut self, image: &Image, detections: &mut Detections) -> Result<()> {
+        detections.codes.clear();
+        detections.codes.extend(self.decode_image(image)?);
+
+        Ok(())
+    }
+
+    /// Like `decode_image()`, but returns the codes as a `Scan`, which only
+    /// needs shared access to the decoder, so that it can be copied, passed
+    /// to functions, and iterated over several times. Detection statistics
+    /// are reported to the attached `Metrics` right away.
+    pub fn scan(&mut self, image: &Image) -> Result<Scan> {
+        self.decode_image(image)?;
+
+        Ok(Scan::new(self))
+    }
+
+    /// Returns the codes detected in the most recently processed image
+    /// again, without processing it anew, e.g. in order to draw their
+    /// outlines in one pass and to decode them in another one.
+    ///
+    /// Returns `None` if no image has been processed since the decoder was
+    /// created, or since a buffer was obtained from `begin()`.
+    pub fn last_scan(&self) -> Option<Scan> {
+        if self.thresholded {
+            Some(Scan::new(self))
+        } else {
+            None
+        }
+    }
+
+    /// Like `decode_image()`, but saves copying the pixels into the decoder
+    /// by taking over the buffer of `image`, in which they are thresholded
+    /// in place. In exchange, `image` receives a buffer of the same size,
+    /// with unspecified contents, which the decoder owned until then. It
+    /// can be filled with the next frame, so that the two buffers alternate
+    /// between the caller and the decoder without any copies or allocations.
+    ///
+    /// If `DecoderConfig::resize_bucket` calls for padding, the rows are
+    /// spread out within the buffer, which is reallocated if its capacity
+    /// doesn't suffice, so that the decoder isn't resized when this is
+    /// mixed with `decode_image()`.
+    pub fn decode_image_in_place(&mut self, image: &mut ImageBuf) -> Result<Iter> {
+        let size = Vec2D { x: image.width(), y: image.height() };
+        let buffer_size = self.config.bucketed_size(size)?;
+        let num_pixels = checked_mul(buffer_size.x, buffer_size.y)?;
+
+        self.begin_frame(size, buffer_size)?;
+
+        let mut pixels = mem::take(image).into_vec();
+        let zones = &self.config.exclusion_zones;
+
+        if size.x > 0 && !zones.is_empty() {
+            for (y, row) in pixels.chunks_mut(size.x).enumerate() {
+                mask::blank_row(zones, y, row);
+            }
+        }
+
+        if buffer_size != size {
+            pad_rows(&mut pixels, size, buffer_size);
+        }
+
+        mem::swap(&mut pixels, &mut self.image_buf);
+        pixels.resize(size.x * size.y, 0);
+        *image = ImageBuf::new(pixels, size)?;
+
+        // `image_buf` has exactly as many bytes as `quirc` has pixels, since
+        // the decoder was just resized to the bucketed size of `image`. It
+        // isn't touched until `restore_image()` installs the previous
+        // buffer again.
+        debug_assert_eq!(self.image_buf.len(), num_pixels);
+
+        let previous = unsafe {
+            quirs_swap_image(self.inner, self.image_buf.as_mut_ptr())
+        };
+        self.quirc_image = Some(previous);
+
+        Ok(self.end_frame())
+    }
+
+    /// Installs the image buffer allocated by `quirc` again if it was
+    /// replaced by `decode_image_in_place()`, because `quirc` frees it upon
+    /// resizing and destruction.
+    fn restore_image(&mut self) {
+        if let Some(previous) = self.quirc_image.take() {
+            unsafe {
+                quirs_swap_image(self.inner, previous);
+            }
         }
+    }
+
+    /// Prepares the decoder for an image of the given size, and returns
+    /// `quirc`'s internal image buffer, so that it can be filled in place
+    /// (e.g. while extracting the luma plane of a camera frame), saving a
+    /// copy of the entire image. Call `FrameBuffer::end()` once the buffer
+    /// has been filled in order to detect the QR codes in it.
+    ///
+    /// The contents of the buffer are unspecified upon return.
+    pub fn begin(&mut self, size: Vec2D) -> Result<FrameBuffer> {
+        let data = self.begin_frame(size, size)?;
 
-        Ok(Iter {
+        Ok(FrameBuffer {
             decoder: self,
-            index: 0,
+            data,
+            size,
         })
     }
+
+    /// Resizes the decoder for an image of size `size` in a buffer of size
+    /// `buffer_size` if necessary, and obtains its image buffer, which is
+    /// valid for `buffer_size.x * buffer_size.y` bytes.
+    fn begin_frame(&mut self, size: Vec2D, buffer_size: Vec2D) -> Result<*mut u8> {
+        if let Some(limit) = self.config.max_image_area {
+            let area = checked_mul(buffer_size.x, buffer_size.y)?;
+
+            if area > limit {
+                return Err(Error::ImageTooLarge { area, limit });
+            }
+        }
+
+        let width = usize_to_int(buffer_size.x)?;
+        let height = usize_to_int(buffer_size.y)?;
+
+        self.finish_frame();
+        self.restore_image();
+        self.thresholded = false;
+
+        // `quirc_resize()` may allocate new buffers and copy the old ones
+        // even if the size stays the same, so it's only called if it changes.
+        if self.buffer_size != Some(buffer_size) {
+            let resized = {
+                #[cfg(feature = "tracing")]
+                let _span = ::tracing::debug_span!("resize", width, height).entered();
+
+                unsafe { quirc_resize(self.inner, width, height) }
+            };
+
+            if resized != 0 {
+                self.size = Vec2D::default();
+                self.buffer_size = None;
+                return Err(Error::AllocFailed);
+            }
+
+            self.buffer_size = Some(buffer_size);
+        }
+
+        self.size = size;
+
+        let buf_ptr = {
+            #[cfg(feature = "tracing")]
+            let _span = ::tracing::debug_span!("begin").entered();
+
+            unsafe { quirc_begin(self.inner, ptr::null_mut(), ptr::null_mut()) }
+        };
+        assert!(!buf_ptr.is_null(), "quirc_begin() returned null pointer");
+
+        Ok(buf_ptr)
+    }
+
+    /// Detects the QR codes in the filled image buffer.
+    fn detect(&mut self) {
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::debug_span!("end", count = ::tracing::field::Empty).entered();
+
+        unsafe {
+            quirc_end(self.inner);
+        }
+
+        self.thresholded = true;
+
+        #[cfg(feature = "tracing")]
+        span.record("count", self.raw_count());
+    }
+
+    /// Processes the filled image buffer and returns the QR codes.
+    #[cfg(feature = "std")]
+    fn end_frame(&mut self) -> Iter {
+        let start = Instant::now();
+
+        self.detect();
+
+        let buffer_bytes = self.buffer_bytes();
+        let detect_time = start.elapsed();
+
+        if let Some(ref mut soak) = self.soak {
+            soak.record(buffer_bytes, detect_time);
+        }
+
+        if self.metrics.is_some() {
+            let count = self.count();
+            let size = self.size;
+
+            if let Some(ref mut metrics) = self.metrics {
+                metrics.start_frame(size, detect_time, count);
+            }
+        }
+
+        Iter::new(self)
+    }
+
+    /// Processes the filled image buffer and returns the QR codes.
+    #[cfg(not(feature = "std"))]
+    fn end_frame(&mut self) -> Iter {
+        self.detect();
+
+        Iter::new(self)
+    }
 }
 
+// The `quirc` object is allocated by and exclusively owned by the decoder,
+// and it points only to buffers which it or the decoder owns in turn. `quirc` keeps no
+// global or thread-local state, so its functions may be called on any
+// thread, as long as calls on the same object don't overlap, which the
+// borrow checker ensures by taking `&mut self` for every mutation.
+unsafe impl Send for Decoder {}
+
 impl Drop for Decoder {
     fn drop(&mut self) {
+        self.restore_image();
+
         unsafe {
             quirc_destroy(self.inner);
         }
     }
 }
 
+/// A view of the internal image buffer of a `Decoder`, obtained from
+/// `Decoder::begin()`. It dereferences to the pixels of the image, row by
+/// row, without padding.
+#[derive(Debug)]
+pub struct FrameBuffer<'a> {
+    /// The decoder whose buffer this is.
+    decoder: &'a mut Decoder,
+    /// Pointer to the first pixel of the buffer owned by `quirc`.
+    data: *mut u8,
+    /// The dimensions of the image.
+    size: Vec2D,
+}
+
+impl<'a> FrameBuffer<'a> {
+    /// Return the width of (number of columns in) the image.
+    pub fn width(&self) -> usize {
+        self.size.x
+    }
+
+    /// Return the height of (number of rows in) the image.
+    pub fn height(&self) -> usize {
+        self.size.y
+    }
+
+    /// Finishes filling the buffer, and returns the QR codes detected in it.
+    /// Exclusion zones configured on the decoder are blanked out first.
+    pub fn end(self) -> Iter<'a> {
+        let width = self.size.x;
+        let zones = &self.decoder.config.exclusion_zones;
+
+        if width > 0 && !zones.is_empty() {
+            // See the comment in `deref()` for why this is sound.
+            let buf = unsafe {
+                slice::from_raw_parts_mut(self.data, width * self.size.y)
+            };
+
+            for (y, row) in buf.chunks_mut(width).enumerate() {
+                mask::blank_row(zones, y, row);
+            }
+        }
+
+        self.decoder.end_frame()
+    }
+}
+
+impl<'a> Deref for FrameBuffer<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // The buffer is valid for this many bytes as per `quirc_resize()`,
+        // and the mutable borrow of the decoder prevents it from being
+        // resized or freed as long as `self` is alive.
+        unsafe {
+            slice::from_raw_parts(self.data, self.size.x * self.size.y)
+        }
+    }
+}
+
+impl<'a> DerefMut for FrameBuffer<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // See the comment in `deref()`.
+        unsafe {
+            slice::from_raw_parts_mut(self.data, self.size.x * self.size.y)
+        }
+    }
+}
+
+/// The QR codes detected in an image, obtained from `Decoder::scan()`.
+///
+/// Unlike `Iter`, a scan only borrows the decoder immutably, so it is
+/// `Copy`, and it can be passed around, queried and iterated over any
+/// number of times. The decoder can't process another image as long as the
+/// scan is alive, since `scan()` borrowed it mutably to begin with.
+#[derive(Debug, Clone, Copy)]
+pub struct Scan<'a> {
+    /// The decoder holding the results of detection.
+    decoder: &'a Decoder,
+    /// The number of codes detected.
+    count: c_int,
+}
+
+impl<'a> Scan<'a> {
+    /// Creates a scan of the codes detected in the last image.
+    fn new(decoder: &'a Decoder) -> Self {
+        Scan { decoder, count: decoder.raw_count() }
+    }
+
+    /// Returns the number of QR codes detected.
+    pub fn count(&self) -> usize {
+        int_to_usize(self.count).expect("quirc_count() was negative")
+    }
+
+    /// Returns `true` if no QR codes were detected.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Extracts the QR code at `index`, in the order in which `iter()`
+    /// yields them. Fails with `Error::InvalidArgument` if `index` is out
+    /// of bounds.
+    pub fn extract(&self, index: usize) -> Result<QrCode> {
+        self.decoder.extract(index)
+    }
+
+    /// Extracts and decodes the QR code at `index`. Unlike the codes
+    /// yielded by `Iter::decoded()`, these aren't recorded by the attached
+    /// `Metrics`, since that would require mutable access to the decoder.
+    pub fn decode(&self, index: usize) -> CodeResult {
+        decode_code(index, self.extract(index))
+    }
+
+    /// Iterates over the QR codes, extracting each one as it is yielded.
+    pub fn iter(&self) -> ScanIter<'a> {
+        ScanIter { scan: *self, index: 0, back: self.count }
+    }
+}
+
+impl<'a> IntoIterator for Scan<'a> {
+    type Item = Result<QrCode>;
+    type IntoIter = ScanIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &Scan<'a> {
+    type Item = Result<QrCode>;
+    type IntoIter = ScanIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the QR codes of a `Scan`.
+#[derive(Debug, Clone)]
+pub struct ScanIter<'a> {
+    /// The scan whose codes are yielded.
+    scan: Scan<'a>,
+    /// The index of the next code to extract from the front.
+    index: c_int,
+    /// One past the index of the next code to extract from the back.
+    back: c_int,
+}
+
+impl<'a> Iterator for ScanIter<'a> {
+    type Item = Result<QrCode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.back {
+            let code = self.scan.decoder.extract_raw(self.index);
+            self.index += 1;
+            Some(code)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // `index <= back` always holds, so this can't underflow.
+        let n = int_to_usize(self.back - self.index).unwrap_or_default();
+        (n, Some(n))
+    }
+}
+
+impl<'a> DoubleEndedIterator for ScanIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.index < self.back {
+            self.back -= 1;
+            Some(self.scan.decoder.extract_raw(self.back))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for ScanIter<'a> {}
+
+impl<'a> FusedIterator for ScanIter<'a> {}
+
 /// An iterator over QR codes in an image.
 #[derive(Debug)]
 pub struct Iter<'a> {
     /// A reference to the decoder where this iterator's contents come from.
     decoder: &'a mut Decoder,
-    /// The index of the next image to process.
+    /// The index of the next image to process from the front.
     index: c_int,
+    /// One past the index of the next image to process from the back.
+    back: c_int,
 }
 
 impl<'a> Iter<'a> {
-    /// Returns the total count and the current index,
-    /// ensuring that both are non-negative.
-    fn count_and_index(&self) -> (c_int, c_int) {
-        let count = unsafe {
-            quirc_count(self.decoder.inner)
-        };
-        let index = self.index;
+    /// Creates an iterator over all codes detected in the last image.
+    fn new(decoder: &'a mut Decoder) -> Self {
+        let back = decoder.raw_count();
 
-        assert!(count >= 0, "quirc_count() was negative");
-        assert!(index >= 0, "current index was negative");
+        Iter {
+            decoder,
+            index: 0,
+            back,
+        }
+    }
 
-        (count, index)
+    /// Decodes each code as it is extracted, yielding the code along with
+    /// the decoded information. Failures are reported per code, so the
+    /// remaining codes are still decoded after one of them fails.
+    pub fn decoded(self) -> Decoded<'a> {
+        Decoded { iter: self }
     }
+
 }
 
 impl<'a> Iterator for Iter<'a> {
     type Item = Result<QrCode>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (count, index) = self.count_and_index();
+        if self.index < self.back {
+            let code = self.decoder.extract_raw(self.index);
+            self.index += 1;
+            Some(code)
+        } else {
+            None
+        }
+    }
 
-        if index < count {
-            // This is not `mem::uninitialized` because `quirc_extract()`
-            // returns without writing anything to the `quirc_code` out argument
-            // if the index is OOB. Although we have a bounds check, I have
-            // trust issues with underlying C libraries, so this remains a 0.
-            let mut raw = quirc_code::default();
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // `index <= back` always holds, so this can't underflow.
+        let n = int_to_usize(self.back - self.index).unwrap_or_default();
+        (n, Some(n))
+    }
 
-            unsafe {
-                quirc_extract(self.decoder.inner, index, &mut raw);
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skips the codes without extracting them.
+        match usize_to_int(n) {
+            Ok(n) if n < self.back - self.index => {
+                self.index += n;
+                self.next()
             }
+            _ => {
+                self.index = self.back;
+                None
+            }
+        }
+    }
+}
 
-            self.index += 1;
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.index < self.back {
+            self.back -= 1;
+            Some(self.decoder.extract_raw(self.back))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+/// Completes the statistics of the image, see the `metrics` module.
+impl<'a> Drop for Iter<'a> {
+    fn drop(&mut self) {
+        self.decoder.finish_frame();
+    }
+}
+
+impl<'a> FusedIterator for Iter<'a> {}
+
+/// The stage of processing at which a code failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// The code couldn't be extracted from the detector.
+    Extraction,
+    /// The code was extracted, but its data couldn't be decoded.
+    Decoding,
+}
+
+/// An error which occurred while processing a single code of an image.
+#[derive(Debug, Clone, Copy)]
+pub struct CodeError {
+    /// The index of the code among the codes detected in the image.
+    pub index: usize,
+    /// The stage at which processing the code failed.
+    pub stage: Stage,
+    /// The error itself.
+    pub error: Error,
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stage = match self.stage {
+            Stage::Extraction => "extracting",
+            Stage::Decoding   => "decoding",
+        };
+
+        write!(f, "{} code #{} failed: {}", stage, self.index, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for CodeError {
+    fn description(&self) -> &str {
+        match self.stage {
+            Stage::Extraction => "extracting a code failed",
+            Stage::Decoding   => "decoding a code failed",
+        }
+    }
 
-            Some(QrCode::from_raw(raw))
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An iterator over the QR codes in an image and their decoded
+/// information, obtained from `Iter::decoded()`.
+#[derive(Debug)]
+pub struct Decoded<'a> {
+    /// The iterator over the undecoded codes.
+    iter: Iter<'a>,
+}
+
+impl<'a> Iterator for Decoded<'a> {
+    type Item = CodeResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.iter.index < self.iter.back {
+            let result = self.iter.decoder.decode_at(self.iter.index);
+            self.iter.index += 1;
+            Some(result)
         } else {
             None
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let (count, index) = self.count_and_index();
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Decoded<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.iter.index < self.iter.back {
+            self.iter.back -= 1;
+            Some(self.iter.decoder.decode_at(self.iter.back))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Decoded<'a> {}
+
+impl<'a> FusedIterator for Decoded<'a> {}
+
+/// Sets every pixel of `pixels` to white, i.e. to the background of codes.
+fn fill_white(pixels: &mut [u8]) {
+    for pixel in pixels {
+        *pixel = 0xff;
+    }
+}
+
+/// Spreads the rows of an image of `size`, stored without padding, out to
+/// the stride of a buffer of `buffer_size`, which must be at least as
+/// large in both dimensions, and pads them with white.
+fn pad_rows(pixels: &mut Vec<u8>, size: Vec2D, buffer_size: Vec2D) {
+    // The bytes past the image become the bottom padding.
+    pixels.resize(buffer_size.x * buffer_size.y, 0xff);
+
+    // Rows only move towards the end, so moving the last one first never
+    // overwrites a row which hasn't been moved yet.
+    for y in (0..size.y).rev() {
+        let start = y * buffer_size.x;
+
+        pixels.copy_within(y * size.x..(y + 1) * size.x, start);
+        fill_white(&mut pixels[start + size.x..start + buffer_size.x]);
+    }
+}
+
+/// A code along with the information decoded from it, or the reason why
+/// it couldn't be extracted or decoded.
+type CodeResult = ::std::result::Result<(QrCode, Info), CodeError>;
+
+/// Decodes the code which was extracted from `index`, attributing any
+/// failure to the stage at which it occurred.
+fn decode_code(index: usize, code: Result<QrCode>) -> CodeResult {
+    let error = |stage, error| CodeError { index, stage, error };
+
+    code.map_err(|e| error(Stage::Extraction, e))
+        .and_then(|code| match code.decode() {
+            Ok(info) => Ok((code, info)),
+            Err(e) => Err(error(Stage::Decoding, e)),
+        })
+}
+
+/// Decodes the codes on the threads of the global `rayon` pool.
+#[cfg(feature = "rayon")]
+fn decode_all(codes: &[Result<QrCode>]) -> Vec<Result<Info>> {
+    use rayon::prelude::*;
+
+    codes.par_iter().map(|&code| code.and_then(|code| code.decode())).collect()
+}
+
+/// Decodes the codes one after the other, on the calling thread.
+#[cfg(not(feature = "rayon"))]
+fn decode_all(codes: &[Result<QrCode>]) -> Vec<Result<Info>> {
+    codes.iter().map(|&code| code.and_then(|code| code.decode())).collect()
+}
+
+/// The QR codes detected in an image, owned independently of the
+/// `Decoder`. Obtained from `Decoder::decode_image_owned()`, or filled by
+/// `Decoder::decode_image_into()`.
+#[derive(Debug, Clone, Default)]
+pub struct Detections {
+    /// The result of extracting each code, in the order of detection.
+    codes: Vec<Result<QrCode>>,
+}
+
+impl Detections {
+    /// Returns the number of codes detected.
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Returns `true` if no codes were detected.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Returns the result of extracting the code at `index`, or `None`
+    /// if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Option<Result<QrCode>> {
+        self.codes.get(index).cloned()
+    }
+
+    /// Returns an iterator over the results of extracting the codes.
+    pub fn iter(&self) -> slice::Iter<Result<QrCode>> {
+        self.codes.iter()
+    }
+
+    /// Decodes every code, in parallel if the `rayon` feature is enabled,
+    /// and returns the results in the order of detection.
+    pub fn par_decode(&self) -> Vec<Result<Info>> {
+        decode_all(&self.codes)
+    }
+
+    /// Decodes each code, like `Iter::decoded()`.
+    pub fn decoded<'a>(&'a self) -> impl Iterator<Item = ::std::result::Result<(QrCode, Info), CodeError>> + 'a {
+        self.codes
+            .iter()
+            .enumerate()
+            .map(|(index, &code)| decode_code(index, code))
+    }
+}
+
+impl IntoIterator for Detections {
+    type Item = Result<QrCode>;
+    type IntoIter = vec::IntoIter<Result<QrCode>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.codes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Detections {
+    type Item = &'a Result<QrCode>;
+    type IntoIter = slice::Iter<'a, Result<QrCode>>;
 
-        int_to_usize(count - index)
-            .map(|n| (n, Some(n)))
-            .unwrap_or_default() // we don't know if it under- or overflowed
+    fn into_iter(self) -> Self::IntoIter {
+        self.codes.iter()
     }
 }
@@ -0,0 +1,87 @@
+//! Merging duplicate detections of the same physical code.
+//!
+//! The same code may be found more than once: by several passes over one
+//! image (e.g. the plain pass and a retry after suppressing glare), or in
+//! consecutive frames of a video. Two detections are considered duplicates
+//! if their payloads are identical, and their outlines overlap enough, as
+//! measured by the intersection over union (IoU) of the quadrilaterals
+//! spanned by their corners.
+
+use geom::{ QrCode, Quad, Vec2D };
+use info::Info;
+use retry::Decoded;
+use schema::CodeRecord;
+
+/// A reasonable minimal IoU of duplicates, which tolerates the jitter of
+/// the detected corners, but keeps identical codes side by side apart.
+pub const DEFAULT_MIN_IOU: f64 = 0.5;
+
+/// A decoded code which can be compared with others.
+pub trait Detection {
+    /// Returns the raw payload of the code.
+    fn payload(&self) -> &[u8];
+
+    /// Returns the corners of the code, clockwise from the top left one.
+    fn corners(&self) -> [Vec2D; 4];
+}
+
+impl Detection for (QrCode, Info) {
+    fn payload(&self) -> &[u8] {
+        self.1.payload()
+    }
+
+    fn corners(&self) -> [Vec2D; 4] {
+        self.0.corners()
+    }
+}
+
+impl Detection for Decoded {
+    fn payload(&self) -> &[u8] {
+        self.info.payload()
+    }
+
+    fn corners(&self) -> [Vec2D; 4] {
+        self.code.corners()
+    }
+}
+
+impl Detection for CodeRecord {
+    fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    fn corners(&self) -> [Vec2D; 4] {
+        self.corners
+    }
+}
+
+/// Returns whether two detections are of the same physical code, i.e.
+/// their payloads are identical, and the IoU of their outlines is at
+/// least `min_iou`.
+pub fn is_duplicate<A, B>(a: &A, b: &B, min_iou: f64) -> bool
+    where A: Detection,
+          B: Detection,
+{
+    a.payload() == b.payload() && iou(&a.corners(), &b.corners()) >= min_iou
+}
+
+/// Removes the duplicates from `detections`, keeping the first detection
+/// of every code, in the original order. Results of earlier passes should
+/// therefore come first.
+pub fn dedup<T: Detection>(detections: Vec<T>, min_iou: f64) -> Vec<T> {
+    let mut kept: Vec<T> = Vec::with_capacity(detections.len());
+
+    for detection in detections {
+        if !kept.iter().any(|other| is_duplicate(other, &detection, min_iou)) {
+            kept.push(detection);
+        }
+    }
+
+    kept
+}
+
+/// Returns the intersection over union of two quadrilaterals, in the range
+/// `[0, 1]`. See `Quad::iou()`.
+pub fn iou(a: &[Vec2D; 4], b: &[Vec2D; 4]) -> f64 {
+    Quad::from(*a).iou(&Quad::from(*b))
+}
@@ -0,0 +1,739 @@
+//! Generation of QR codes.
+//!
+//! The encoder picks the most compact of the numeric, alphanumeric and byte
+//! modes which can represent the whole payload, the smallest version in
+//! which the payload fits, and (unless fixed) the data mask with the lowest
+//! penalty score, as described in ISO/IEC 18004.
+
+use std::fmt;
+use std::vec::Vec;
+use geom::{ QrCode, Vec2D };
+use info::{ EccLevel, DataType };
+use error::{ Error, Result };
+use gf::{ rs_generator, rs_remainder };
+use spec;
+
+/// The characters of the alphanumeric mode, in the order of their values.
+const ALPHANUMERIC_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// The bytes appended alternately to fill up the data capacity.
+const PAD_BYTES: [u8; 2] = [0xec, 0x11];
+
+/// Penalty weights of the mask evaluation rules.
+const PENALTY_N1: usize = 3;
+/// See `PENALTY_N1`.
+const PENALTY_N2: usize = 3;
+/// See `PENALTY_N1`.
+const PENALTY_N3: usize = 40;
+/// See `PENALTY_N1`.
+const PENALTY_N4: usize = 10;
+
+/// The encoding of the payload of a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Mode {
+    /// Decimal digits, 3 per 10 bits.
+    Numeric,
+    /// Digits, uppercase letters and some punctuation, 2 per 11 bits.
+    Alphanumeric,
+    /// Arbitrary bytes, 8 bits each.
+    Byte,
+}
+
+impl Mode {
+    /// Returns the 4-bit mode indicator.
+    fn indicator(self) -> u32 {
+        match self {
+            Mode::Numeric => 0b0001,
+            Mode::Alphanumeric => 0b0010,
+            Mode::Byte => 0b0100,
+        }
+    }
+
+    /// Returns the width of the character count field in the given version.
+    fn char_count_bits(self, version: u8) -> usize {
+        let data_type = match self {
+            Mode::Numeric => DataType::Numeric,
+            Mode::Alphanumeric => DataType::Alphanumeric,
+            Mode::Byte => DataType::Byte,
+        };
+
+        spec::char_count_bits(version, data_type)
+    }
+
+    /// Returns the most compact mode which can represent all of `data`.
+    fn for_data(data: &[u8]) -> Self {
+        if data.iter().all(u8::is_ascii_digit) {
+            Mode::Numeric
+        } else if data.iter().all(|b| ALPHANUMERIC_CHARSET.contains(b)) {
+            Mode::Alphanumeric
+        } else {
+            Mode::Byte
+        }
+    }
+}
+
+/// A sequence of bits, most significant first.
+#[derive(Debug, Clone, Default)]
+struct BitBuffer {
+    /// The bits.
+    bits: Vec<bool>,
+}
+
+impl BitBuffer {
+    /// Appends the lowest `len` bits of `value`.
+    fn push(&mut self, value: u32, len: usize) {
+        debug_assert!(len <= 32 && (len == 32 || value >> len == 0));
+        self.bits.extend((0..len).rev().map(|i| (value >> i) & 1 != 0));
+    }
+
+    /// Returns the number of bits.
+    fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Packs the bits into bytes. The length must be a multiple of 8.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+            .collect()
+    }
+}
+
+/// A segment of the payload, encoded in a single mode.
+#[derive(Debug, Clone)]
+struct Segment {
+    /// The mode of the segment.
+    mode: Mode,
+    /// The number of characters (bytes, for the byte mode).
+    char_count: usize,
+    /// The encoded characters, without the mode indicator and the count.
+    data: BitBuffer,
+}
+
+impl Segment {
+    /// Encodes `data` in the most compact mode possible.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn new(data: &[u8]) -> Self {
+        let mode = Mode::for_data(data);
+        let mut bits = BitBuffer::default();
+
+        match mode {
+            Mode::Numeric => for chunk in data.chunks(3) {
+                let value = chunk.iter().fold(0, |acc, &d| acc * 10 + u32::from(d - b'0'));
+                bits.push(value, chunk.len() * 3 + 1);
+            },
+            Mode::Alphanumeric => for chunk in data.chunks(2) {
+                let value = chunk.iter().fold(0, |acc, c| {
+                    let index = ALPHANUMERIC_CHARSET.iter().position(|a| a == c).unwrap_or(0);
+                    acc * 45 + index as u32
+                });
+                bits.push(value, chunk.len() * 5 + 1);
+            },
+            Mode::Byte => for &byte in data {
+                bits.push(u32::from(byte), 8);
+            },
+        }
+
+        Segment { mode, char_count: data.len(), data: bits }
+    }
+
+    /// Returns the number of bits the segment occupies in the given
+    /// version, including the header, or `None` if the character count
+    /// doesn't fit in the count field.
+    fn encoded_len(&self, version: u8) -> Option<usize> {
+        let count_bits = self.mode.char_count_bits(version);
+
+        if self.char_count >> count_bits == 0 {
+            Some(4 + count_bits + self.data.len())
+        } else {
+            None
+        }
+    }
+}
+
+/// Generates QR codes with the configured parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Encoder {
+    /// The level of error correction.
+    pub ecc_level: EccLevel,
+    /// The smallest version to use. Defaults to 1.
+    pub min_version: u8,
+    /// The largest version to use. Defaults to 40.
+    pub max_version: u8,
+    /// The data mask to apply, from 0 to 7, or `None` for choosing the one
+    /// with the lowest penalty score. Defaults to `None`.
+    pub mask: Option<u8>,
+}
+
+impl Encoder {
+    /// Creates an encoder with the given ECC level, which chooses the
+    /// version and the data mask automatically.
+    pub fn new(ecc_level: EccLevel) -> Self {
+        Encoder {
+            ecc_level,
+            min_version: spec::MIN_VERSION,
+            max_version: spec::MAX_VERSION,
+            mask: None,
+        }
+    }
+
+    /// Encodes `data` into a QR code.
+    ///
+    /// Returns `Error::InvalidArgument` if the version range or the mask
+    /// is invalid, and `Error::DataTooLong` if the data doesn't fit into
+    /// the largest allowed version.
+    pub fn encode(&self, data: &[u8]) -> Result<EncodedQr> {
+        let valid_versions = spec::MIN_VERSION <= self.min_version
+            && self.min_version <= self.max_version
+            && self.max_version <= spec::MAX_VERSION;
+
+        if !valid_versions || self.mask.map_or(false, |mask| mask > 7) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let segment = Segment::new(data);
+        let (version, data_len) = (self.min_version..=self.max_version)
+            .filter_map(|version| {
+                let capacity = spec::data_codewords(version, self.ecc_level) * 8;
+                segment.encoded_len(version)
+                    .filter(|&len| len <= capacity)
+                    .map(|_| (version, capacity))
+            })
+            .next()
+            .ok_or(Error::DataTooLong)?;
+
+        let codewords = self.data_codewords(&segment, version, data_len);
+        let codewords = add_ecc_and_interleave(&codewords, version, self.ecc_level);
+        let mut matrix = Matrix::new(version);
+
+        matrix.draw_function_patterns();
+        matrix.draw_codewords(&codewords);
+
+        let mask = self.mask.unwrap_or_else(|| {
+            (0..8).min_by_key(|&mask| {
+                matrix.apply_mask(mask);
+                matrix.draw_format_bits(self.ecc_level, mask);
+                let penalty = matrix.penalty_score();
+                matrix.apply_mask(mask); // XOR is its own inverse
+                penalty
+            }).unwrap_or(0)
+        });
+
+        matrix.apply_mask(mask);
+        matrix.draw_format_bits(self.ecc_level, mask);
+
+        Ok(EncodedQr {
+            version,
+            ecc_level: self.ecc_level,
+            mask,
+            size: matrix.size,
+            bitmap: matrix.to_bitmap(),
+        })
+    }
+
+    /// Assembles the data codewords: the header and the data of the
+    /// segment, followed by the terminator and padding.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn data_codewords(&self, segment: &Segment, version: u8, capacity: usize) -> Vec<u8> {
+        let mut bits = BitBuffer::default();
+
+        bits.push(segment.mode.indicator(), 4);
+        bits.push(segment.char_count as u32, segment.mode.char_count_bits(version));
+        bits.bits.extend_from_slice(&segment.data.bits);
+
+        let terminator = (capacity - bits.len()).min(4);
+        bits.push(0, terminator);
+
+        let padding = (8 - bits.len() % 8) % 8;
+        bits.push(0, padding);
+
+        let mut codewords = bits.to_bytes();
+        let pad = PAD_BYTES.iter().cycle().take(capacity / 8 - codewords.len());
+        codewords.extend(pad);
+        codewords
+    }
+}
+
+/// Returns the coordinates of the data modules of a code of the given
+/// version, in the order in which codeword bits are placed.
+pub(crate) fn data_modules(version: u8) -> Vec<Vec2D> {
+    let mut matrix = Matrix::new(version);
+    matrix.draw_function_patterns();
+    matrix.data_module_order()
+}
+
+/// Splits the data codewords into blocks, computes the error correction
+/// codewords of each block, and interleaves the blocks.
+fn add_ecc_and_interleave(data: &[u8], version: u8, ecc_level: EccLevel) -> Vec<u8> {
+    let num_blocks = spec::ecc_blocks(version, ecc_level);
+    let ecc_len = spec::ecc_codewords_per_block(version, ecc_level);
+    let total = spec::total_codewords(version);
+    let num_short_blocks = num_blocks - total % num_blocks;
+    let short_block_len = total / num_blocks;
+    let generator = rs_generator(ecc_len);
+
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut offset = 0;
+
+    for i in 0..num_blocks {
+        let data_len = short_block_len - ecc_len + if i < num_short_blocks { 0 } else { 1 };
+        let block_data = &data[offset..offset + data_len];
+        let mut block = block_data.to_vec();
+
+        offset += data_len;
+
+        // Short blocks get a dummy byte so that all blocks can be
+        // interleaved by the same index; it is skipped below.
+        if i < num_short_blocks {
+            block.push(0);
+        }
+
+        block.extend(rs_remainder(block_data, &generator));
+        blocks.push(block);
+    }
+
+    let mut result = Vec::with_capacity(total);
+
+    for i in 0..=short_block_len {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_block_len - ecc_len || j >= num_short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+
+    result
+}
+
+/// The modules of a code under construction.
+#[derive(Debug, Clone)]
+struct Matrix {
+    /// The number of modules along each side.
+    size: usize,
+    /// The colors of the modules in row-major order; `true` is dark.
+    modules: Vec<bool>,
+    /// Whether each module belongs to a function pattern, or to the
+    /// format or version information, as opposed to the data.
+    function: Vec<bool>,
+}
+
+impl Matrix {
+    /// Creates a light matrix of the size corresponding to the version.
+    fn new(version: u8) -> Self {
+        let size = spec::size(version);
+
+        Matrix {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    /// Returns the version corresponding to the size.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn version(&self) -> u8 {
+        ((self.size - 17) / 4) as u8
+    }
+
+    /// Returns the color of the module at column `x` and row `y`.
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Sets the color of a module, and marks it as a function module.
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    /// Draws the finder, timing and alignment patterns, and reserves the
+    /// areas of the format and version information.
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        self.draw_finder_pattern(3, 3);
+        self.draw_finder_pattern(size - 4, 3);
+        self.draw_finder_pattern(3, size - 4);
+
+        let positions = spec::alignment_positions(self.version());
+        let last = positions.len().saturating_sub(1);
+
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Skip the three corners occupied by finder patterns.
+                let overlaps_finder = i == 0 && j == 0 || i == 0 && j == last || i == last && j == 0;
+
+                if !overlaps_finder {
+                    self.draw_alignment_pattern(x, y);
+                }
+            }
+        }
+
+        // Reserve the format information with a dummy mask.
+        self.draw_format_bits(EccLevel::L, 0);
+        self.draw_version_bits();
+    }
+
+    /// Draws a finder pattern and its separator around the given center.
+    /// Parts of the separator which would lie outside are omitted.
+    fn draw_finder_pattern(&mut self, cx: usize, cy: usize) {
+        let last = self.size - 1;
+
+        for y in cy.saturating_sub(4)..=(cy + 4).min(last) {
+            for x in cx.saturating_sub(4)..=(cx + 4).min(last) {
+                let distance = chebyshev_distance(x, y, cx, cy);
+                self.set_function(x, y, distance != 2 && distance != 4);
+            }
+        }
+    }
+
+    /// Draws an alignment pattern around the given center.
+    fn draw_alignment_pattern(&mut self, cx: usize, cy: usize) {
+        for y in cy - 2..=cy + 2 {
+            for x in cx - 2..=cx + 2 {
+                self.set_function(x, y, chebyshev_distance(x, y, cx, cy) != 1);
+            }
+        }
+    }
+
+    /// Draws both copies of the format information, and the dark module.
+    fn draw_format_bits(&mut self, ecc_level: EccLevel, mask: u8) {
+        let bits = spec::format_bits(ecc_level, mask);
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
+
+        // The copy around the top left finder pattern.
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        // The copy split between the other two finder patterns.
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Draws both copies of the version information, if the version has any.
+    fn draw_version_bits(&mut self) {
+        let version = self.version();
+
+        if version < 7 {
+            return;
+        }
+
+        let bits = spec::version_bits(version);
+
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 != 0;
+            let a = self.size - 11 + i % 3;
+            let b = i / 3;
+
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    /// Returns the coordinates of the data modules, in the zigzag order
+    /// going upwards and downwards in two-module wide columns, from the
+    /// right to the left. This is the order in which codewords are placed,
+    /// most significant bit first.
+    fn data_module_order(&self) -> Vec<Vec2D> {
+        let size = self.size;
+        let mut order = Vec::with_capacity(size * size);
+        let mut right = size - 1;
+
+        loop {
+            // The vertical timing pattern is skipped entirely.
+            if right == 6 {
+                right = 5;
+            }
+
+            let upward = (right + 1) & 2 == 0;
+
+            for vertical in 0..size {
+                let y = if upward { size - 1 - vertical } else { vertical };
+
+                for x in (right - 1..=right).rev() {
+                    if !self.function[y * size + x] {
+                        order.push(Vec2D { x, y });
+                    }
+                }
+            }
+
+            if right < 3 {
+                break;
+            }
+
+            right -= 2;
+        }
+
+        order
+    }
+
+    /// Places the codewords in the data modules. The remainder bits are
+    /// left light.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let order = self.data_module_order();
+
+        for (i, Vec2D { x, y }) in order.into_iter().take(codewords.len() * 8).enumerate() {
+            self.modules[y * self.size + x] = (codewords[i / 8] >> (7 - i % 8)) & 1 != 0;
+        }
+    }
+
+    /// Inverts the data modules selected by the mask pattern.
+    fn apply_mask(&mut self, mask: u8) {
+        let size = self.size;
+
+        for y in 0..size {
+            for x in 0..size {
+                if !self.function[y * size + x] && spec::mask_bit(mask, x, y) {
+                    self.modules[y * size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// Computes the penalty score of the masked matrix, which measures
+    /// how prone the code is to misdetection. Lower is better.
+    fn penalty_score(&self) -> usize {
+        let size = self.size;
+        let mut result = 0;
+
+        // Runs of the same color, and finder-like patterns, in rows and columns.
+        for transposed in &[false, true] {
+            for a in 0..size {
+                let mut history = FinderHistory::new(size);
+                let mut run_color = false;
+                let mut run_len = 0;
+
+                for b in 0..size {
+                    let color = if *transposed { self.get(a, b) } else { self.get(b, a) };
+
+                    if color == run_color {
+                        run_len += 1;
+
+                        if run_len == 5 {
+                            result += PENALTY_N1;
+                        } else if run_len > 5 {
+                            result += 1;
+                        }
+                    } else {
+                        history.push(run_len);
+
+                        if !run_color {
+                            result += history.count_patterns() * PENALTY_N3;
+                        }
+
+                        run_color = color;
+                        run_len = 1;
+                    }
+                }
+
+                result += history.terminate_and_count(run_color, run_len) * PENALTY_N3;
+            }
+        }
+
+        // 2x2 blocks of the same color.
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.get(x, y);
+
+                if color == self.get(x + 1, y)
+                    && color == self.get(x, y + 1)
+                    && color == self.get(x + 1, y + 1) {
+                    result += PENALTY_N2;
+                }
+            }
+        }
+
+        // Imbalance of dark and light modules, in steps of 5%.
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let total = size * size;
+        let deviation = (dark * 20).max(total * 10) - (dark * 20).min(total * 10);
+        let k = (deviation + total - 1) / total - 1;
+        result += k * PENALTY_N4;
+
+        result
+    }
+
+    /// Packs the modules into a bitmap in the format of `QrCode::bitmap()`.
+    fn to_bitmap(&self) -> Vec<u8> {
+        let mut bitmap = vec![0; (self.modules.len() + 7) / 8];
+
+        for (i, _) in self.modules.iter().enumerate().filter(|&(_, &dark)| dark) {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+
+        bitmap
+    }
+}
+
+/// Returns the distance of `(x1, y1)` and `(x2, y2)` along the axis
+/// in which they are farther apart.
+fn chebyshev_distance(x1: usize, y1: usize, x2: usize, y2: usize) -> usize {
+    let dx = if x1 > x2 { x1 - x2 } else { x2 - x1 };
+    let dy = if y1 > y2 { y1 - y2 } else { y2 - y1 };
+
+    dx.max(dy)
+}
+
+/// The lengths of the most recent runs of modules in a row or column,
+/// for detecting finder-like patterns (dark:light = 1:1:3:1:1 with 4
+/// light modules on either side).
+#[derive(Debug, Clone, Copy)]
+struct FinderHistory {
+    /// The size of the code, which is the length of the implicit light
+    /// border added at the beginning and at the end.
+    size: usize,
+    /// The run lengths, most recent first.
+    runs: [usize; 7],
+}
+
+impl FinderHistory {
+    /// Creates an empty history.
+    fn new(size: usize) -> Self {
+        FinderHistory { size, runs: [0; 7] }
+    }
+
+    /// Records the length of a run which just ended.
+    fn push(&mut self, mut run_len: usize) {
+        // The first run is light, and it is extended by the light border.
+        if self.runs[0] == 0 {
+            run_len += self.size;
+        }
+
+        for i in (0..self.runs.len() - 1).rev() {
+            self.runs[i + 1] = self.runs[i];
+        }
+
+        self.runs[0] = run_len;
+    }
+
+    /// Returns the number of finder-like patterns ending at the most
+    /// recent light run, counting each direction separately.
+    fn count_patterns(&self) -> usize {
+        let r = &self.runs;
+        let n = r[1];
+        let core = n > 0 && r[2] == n && r[3] == n * 3 && r[4] == n && r[5] == n;
+
+        usize::from(core && r[0] >= n * 4 && r[6] >= n)
+            + usize::from(core && r[6] >= n * 4 && r[0] >= n)
+    }
+
+    /// Ends the row or column, and counts the remaining patterns.
+    fn terminate_and_count(&mut self, run_color: bool, mut run_len: usize) -> usize {
+        if run_color {
+            self.push(run_len);
+            run_len = 0;
+        }
+
+        // The last run is light, and it is extended by the light border.
+        run_len += self.size;
+        self.push(run_len);
+        self.count_patterns()
+    }
+}
+
+/// A generated QR code.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct EncodedQr {
+    /// The version, from 1 to 40.
+    version: u8,
+    /// The level of error correction.
+    ecc_level: EccLevel,
+    /// The data mask, from 0 to 7.
+    mask: u8,
+    /// The number of modules along each side.
+    size: usize,
+    /// The modules, packed as in `QrCode::bitmap()`.
+    bitmap: Vec<u8>,
+}
+
+impl EncodedQr {
+    /// Returns the version of the code, from 1 to 40.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the error correction level of the code.
+    pub fn ecc_level(&self) -> EccLevel {
+        self.ecc_level
+    }
+
+    /// Returns the mask ID of the code, from 0 to 7.
+    pub fn mask_id(&self) -> u8 {
+        self.mask
+    }
+
+    /// The size (`width == height`) of the code, in modules, without
+    /// the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// A reference to the bitmap buffer, in the same format as
+    /// `QrCode::bitmap()`: the module at `(x, y)` is dark if bit
+    /// `i % 8` of byte `i / 8` is set, where `i = y * size + x`.
+    pub fn bitmap(&self) -> &[u8] {
+        &self.bitmap
+    }
+
+    /// Get the module at coordinates `(coord.x, coord.y)`,
+    /// performing a bounds check. Dark modules are `true`.
+    pub fn get(&self, coord: Vec2D) -> Option<bool> {
+        let Vec2D { x, y } = coord;
+
+        if x < self.size && y < self.size {
+            let i = y * self.size + x;
+            Some(self.bitmap[i / 8] >> (i % 8) & 1 != 0)
+        } else {
+            None
+        }
+    }
+
+    /// Get the module at coordinates `(coord.x, coord.y)`,
+    /// panicking upon an OOB condition.
+    pub fn bit_at(&self, coord: Vec2D) -> bool {
+        self.get(coord).unwrap_or_else(
+            || panic!("{:?} out of bounds for bitmap of size {}", coord, self.size)
+        )
+    }
+
+    /// Converts the code into a `QrCode`, as if it had been detected in an
+    /// image with one pixel per module, so that it can be decoded.
+    pub fn to_qr_code(&self) -> Result<QrCode> {
+        QrCode::from_bitmap(&self.bitmap, self.size)
+    }
+}
+
+impl fmt::Debug for EncodedQr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodedQr")
+            .field("version", &self.version)
+            .field("ecc_level", &self.ecc_level)
+            .field("mask", &self.mask)
+            .field("size", &self.size)
+            .field("bitmap", &self.bitmap)
+            .finish()
+    }
+}
@@ -1,40 +1,123 @@
 //! Errors that can happen during QR code detection and decoding.
 
 use std::fmt;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
 use std::error;
 use std::result;
+use std::str::Utf8Error;
 use quirc_sys::quirc_decode_error_t;
 
 /// An error that could happen while using the `quirc` library.
+///
+/// New variants may be added in the future, so matches on this type must
+/// include a wildcard arm.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub enum Error {
     /// Memory could not be allocated.
     AllocFailed,
-    /// The length of the data buffer doesn't match the dimensions of the image.
-    SizeMismatch,
+    /// The length of a buffer doesn't match the dimensions of the image,
+    /// or the sizes of two things which must agree differ.
+    SizeMismatch {
+        /// The expected length or size. Where a buffer may be longer than
+        /// necessary (e.g. because of row padding), this is the minimum.
+        expected: usize,
+        /// The actual length or size.
+        actual: usize,
+    },
     /// The size specified as a Rust `usize` can't be expressed in a C `int`
-    /// or vice versa.
-    IntOverflow,
+    /// or vice versa, or computing a size overflowed.
+    IntOverflow(Overflow),
     /// A decoding error occurred.
     DecodingFailed(DecodingErrorKind),
+    /// The contents of an image file are malformed or unsupported.
+    InvalidImage,
+    /// An I/O error occurred, e.g. while reading an image file.
+    #[cfg(feature = "std")]
+    Io(#[cfg_attr(feature = "serde", serde(with = "::serde_impls::io_error_kind"))] io::ErrorKind),
+    /// Data was recorded using an unknown version of the result schema.
+    UnsupportedSchemaVersion(u32),
+    /// The data to be encoded doesn't fit into a QR code of any of the
+    /// allowed versions.
+    DataTooLong,
+    /// An argument is outside of its valid range.
+    InvalidArgument,
+    /// An image has more pixels than the decoder is configured to accept.
+    ImageTooLarge {
+        /// The number of pixels in the image, including any padding.
+        area: usize,
+        /// The largest number of pixels allowed.
+        limit: usize,
+    },
+    /// Text was expected to be UTF-8, but it wasn't.
+    Utf8(#[cfg_attr(feature = "serde", serde(with = "::serde_impls::utf8_error"))] Utf8Error),
+    /// The operation isn't supported by the backend, e.g. extracting the
+    /// modules of a code from an engine which only reports payloads.
+    Unsupported,
+}
+
+impl Error {
+    /// Returns a human-readable description of the kind of the error.
+    fn to_str(&self) -> &'static str {
+        match *self {
+            Error::AllocFailed  => "memory allocation failed",
+            Error::SizeMismatch { .. } => "buffer size doesn't match image dimensions",
+            Error::IntOverflow(_) => "integer conversion or size computation would overflow",
+            Error::DecodingFailed(reason) => reason.to_str(),
+            Error::InvalidImage => "malformed or unsupported image file",
+            #[cfg(feature = "std")]
+            Error::Io(_)        => "I/O error",
+            Error::UnsupportedSchemaVersion(_) => "unsupported schema version",
+            Error::DataTooLong  => "data doesn't fit in a QR code of the allowed versions",
+            Error::InvalidArgument => "invalid argument",
+            Error::ImageTooLarge { .. } => "image exceeds the maximal area",
+            Error::Utf8(_)      => "invalid UTF-8",
+            Error::Unsupported  => "operation not supported by the backend",
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use std::error::Error;
-
-        self.description().fmt(f)
+        match *self {
+            Error::SizeMismatch { expected, actual } => write!(
+                f, "{}: expected {}, got {}", self.to_str(), expected, actual
+            ),
+            Error::IntOverflow(overflow) => write!(f, "{}: {}", self.to_str(), overflow),
+            #[cfg(feature = "std")]
+            Error::Io(kind) => write!(f, "{}: {:?}", self.to_str(), kind),
+            Error::UnsupportedSchemaVersion(version) => write!(
+                f, "{} {}", self.to_str(), version
+            ),
+            Error::ImageTooLarge { area, limit } => write!(
+                f, "{}: {} pixels, at most {} allowed", self.to_str(), area, limit
+            ),
+            Error::Utf8(error) => write!(f, "{}: {}", self.to_str(), error),
+            _ => self.to_str().fmt(f),
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl error::Error for Error {
     fn description(&self) -> &str {
-        match *self {
-            Error::AllocFailed  => "memory allocation failed",
-            Error::SizeMismatch => "buffer size doesn't match image dimensions",
-            Error::IntOverflow  => "usize <-> int conversion would overflow",
-            Error::DecodingFailed(reason) => reason.to_str(),
-        }
+        self.to_str()
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error.kind())
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(error: Utf8Error) -> Self {
+        Error::Utf8(error)
     }
 }
 
@@ -44,8 +127,31 @@ impl From<quirc_decode_error_t> for Error {
     }
 }
 
+/// The computation which caused an `Error::IntOverflow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Overflow {
+    /// The value doesn't fit into the integer type it was converted to.
+    Value(i128),
+    /// The product of the two values doesn't fit into a `usize`.
+    Product(usize, usize),
+    /// The sum of the two values doesn't fit into a `usize`.
+    Sum(usize, usize),
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Overflow::Value(value)   => write!(f, "value {} is out of range", value),
+            Overflow::Product(a, b)  => write!(f, "{} * {} is out of range", a, b),
+            Overflow::Sum(a, b)      => write!(f, "{} + {} is out of range", a, b),
+        }
+    }
+}
+
 /// A decoding error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DecodingErrorKind {
     /// An unknown error happened.
     Unknown,
@@ -107,5 +213,12 @@ impl fmt::Display for DecodingErrorKind {
     }
 }
 
+#[cfg(feature = "std")]
+impl error::Error for DecodingErrorKind {
+    fn description(&self) -> &str {
+        self.to_str()
+    }
+}
+
 /// A `Result` which may hold a Qui-RS `Error`.
 pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,36 @@
+//! The floating-point functions which `f64` only has with `std`,
+//! implemented with `libm` for `no_std` builds.
+
+/// Methods of `f64` missing from `core`. Inherent methods take precedence,
+/// so this trait is only imported without `std`, which unit tests always
+/// link, regardless of the features.
+pub trait F64Ext {
+    /// Returns the absolute value. It is in `core` since Rust 1.85, so
+    /// this one is only used by older compilers.
+    #[allow(dead_code)]
+    fn abs(self) -> Self;
+    /// Rounds towards positive infinity.
+    fn ceil(self) -> Self;
+    /// Computes the length of the hypotenuse of a right triangle.
+    fn hypot(self, other: Self) -> Self;
+    /// Computes the sine and the cosine at once.
+    fn sin_cos(self) -> (f64, f64);
+}
+
+impl F64Ext for f64 {
+    fn abs(self) -> Self {
+        libm::fabs(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        libm::hypot(self, other)
+    }
+
+    fn sin_cos(self) -> (f64, f64) {
+        (libm::sin(self), libm::cos(self))
+    }
+}
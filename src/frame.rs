@@ -0,0 +1,221 @@
+//! Conversions from the pixel formats delivered by cameras.
+//!
+//! `quirc` only ever looks at the brightness of the image, so color formats
+//! are converted by extracting the luma (Y) channel and discarding chroma
+//! information, and high dynamic range formats are mapped down to 8 bits.
+
+use std::cmp::Ordering;
+use geom::{ ImageBuf, Vec2D };
+use simd::{ rgb_to_luma, binarize };
+use util::{ checked_add, checked_mul };
+use error::{ Error, Result };
+
+/// Determines how high dynamic range pixel values are mapped to 8 bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Stretch linearly so that the darkest pixel of the image becomes 0,
+    /// and the brightest one becomes 255.
+    MinMax,
+    /// Stretch linearly so that the pixel at the `low` percentile becomes 0,
+    /// and the one at the `high` percentile becomes 255, clamping outliers.
+    /// Percentiles are in the range `0.0...100.0`. This is robust against
+    /// hot pixels and specular highlights, unlike `MinMax`.
+    Percentile {
+        /// The percentile mapped to black.
+        low: f64,
+        /// The percentile mapped to white.
+        high: f64,
+    },
+    /// Map the fixed range `[min, max]` to `[0, 255]`, clamping values
+    /// outside of it. Useful for keeping the mapping stable across frames.
+    Range {
+        /// The value mapped to black.
+        min: f64,
+        /// The value mapped to white.
+        max: f64,
+    },
+}
+
+impl ImageBuf {
+    /// Extracts the luma plane of an NV12 frame, i.e. a full-resolution
+    /// Y plane followed by a half-resolution plane of interleaved U and V.
+    ///
+    /// `stride` is the distance in bytes between the beginnings of two
+    /// consecutive rows of the Y plane. The chroma plane is never read, so
+    /// `data` may contain the entire frame or just its Y plane.
+    pub fn from_nv12(data: &[u8], size: Vec2D, stride: usize) -> Result<Self> {
+        extract_luma(data, size, stride, 1)
+    }
+
+    /// Extracts the luma plane of an I420 frame, i.e. a full-resolution
+    /// Y plane followed by half-resolution U and V planes.
+    ///
+    /// `stride` is the distance in bytes between the beginnings of two
+    /// consecutive rows of the Y plane. The chroma planes are never read, so
+    /// `data` may contain the entire frame or just its Y plane.
+    pub fn from_i420(data: &[u8], size: Vec2D, stride: usize) -> Result<Self> {
+        extract_luma(data, size, stride, 1)
+    }
+
+    /// Extracts the luma channel of a packed YUYV (a.k.a. YUY2) frame,
+    /// in which every 4 bytes `Y0 U Y1 V` describe two adjacent pixels.
+    ///
+    /// `stride` is the distance in bytes between the beginnings of two
+    /// consecutive rows, and it must be at least `2 * size.x`.
+    pub fn from_yuyv(data: &[u8], size: Vec2D, stride: usize) -> Result<Self> {
+        extract_luma(data, size, stride, 2)
+    }
+
+    /// Converts a packed RGB frame, 3 bytes per pixel, to grayscale, using
+    /// the Rec. 601 luma coefficients.
+    ///
+    /// `stride` is the distance in bytes between the beginnings of two
+    /// consecutive rows, and it must be at least `3 * size.x`.
+    pub fn from_rgb(data: &[u8], size: Vec2D, stride: usize) -> Result<Self> {
+        let row_len = checked_mul(size.x, 3)?;
+
+        check_rows(data, size, stride, row_len)?;
+
+        let mut luma = vec![0; size.x * size.y];
+
+        for (y, dst) in luma.chunks_mut(size.x.max(1)).enumerate() {
+            rgb_to_luma(&data[y * stride..y * stride + row_len], dst);
+        }
+
+        ImageBuf::new(luma, size)
+    }
+
+    /// Sets the pixels darker than `threshold` to black (0), and all others
+    /// to white (255), e.g. in order to inspect or fix up the thresholding
+    /// before detection.
+    pub fn binarize(&mut self, threshold: u8) {
+        binarize(self.data_mut(), threshold);
+    }
+
+    /// Converts a 16-bit grayscale image to 8 bits.
+    pub fn from_luma16(data: &[u16], size: Vec2D, normalization: Normalization) -> Result<Self> {
+        normalize(data, size, normalization, f64::from)
+    }
+
+    /// Converts a floating-point grayscale image to 8 bits.
+    /// Non-finite (NaN and infinite) pixels are ignored while computing
+    /// the mapping, and they become black.
+    pub fn from_luma_f32(data: &[f32], size: Vec2D, normalization: Normalization) -> Result<Self> {
+        normalize(data, size, normalization, f64::from)
+    }
+}
+
+/// Maps `data` to 8 bits as specified by `normalization`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_sign_loss))]
+fn normalize<T, F>(
+    data: &[T],
+    size: Vec2D,
+    normalization: Normalization,
+    to_f64: F,
+) -> Result<ImageBuf>
+    where T: Copy,
+          F: Fn(T) -> f64,
+{
+    let expected = checked_mul(size.x, size.y)?;
+
+    if data.len() != expected {
+        return Err(Error::SizeMismatch { expected, actual: data.len() });
+    }
+
+    let values = data.iter().map(|&px| to_f64(px));
+    let (low, high) = match normalization {
+        Normalization::MinMax => values
+            .filter(|v| v.is_finite())
+            .fold(None, |acc, v| match acc {
+                None => Some((v, v)),
+                Some((lo, hi)) => Some((v.min(lo), v.max(hi))),
+            })
+            .unwrap_or((0.0, 0.0)),
+        Normalization::Percentile { low, high } => {
+            let mut finite: Vec<f64> = values.filter(|v| v.is_finite()).collect();
+            (percentile(&mut finite, low), percentile(&mut finite, high))
+        }
+        Normalization::Range { min, max } => (min, max),
+    };
+    let scale = if high > low { 255.0 / (high - low) } else { 0.0 };
+    let luma = data
+        .iter()
+        .map(|&px| {
+            let v = to_f64(px);
+
+            if v.is_finite() {
+                ((v - low) * scale).round().clamp(0.0, 255.0) as u8
+            } else {
+                0
+            }
+        })
+        .collect();
+
+    ImageBuf::new(luma, size)
+}
+
+/// Returns the `p`th percentile (`0.0...100.0`) of `values`, which are
+/// reordered in the process. Returns 0 if `values` is empty.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss, cast_possible_truncation, cast_sign_loss))]
+fn percentile(values: &mut [f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let max_index = values.len() - 1;
+    let fraction = (p / 100.0).clamp(0.0, 1.0);
+    let index = ((max_index as f64 * fraction).round() as usize).min(max_index);
+    let (_, &mut value, _) = values.select_nth_unstable_by(index, |a, b| {
+        a.partial_cmp(b).unwrap_or(Ordering::Equal)
+    });
+
+    value
+}
+
+/// Copies every `step`th byte of each row, starting at the first byte,
+/// from a buffer of `size.y` rows that are `stride` bytes apart.
+fn extract_luma(data: &[u8], size: Vec2D, stride: usize, step: usize) -> Result<ImageBuf> {
+    let row_len = checked_mul(size.x, step)?;
+
+    check_rows(data, size, stride, row_len)?;
+
+    // Empty rows take up no bytes, so there would be nothing to stop a
+    // bogus height.
+    if size.x == 0 {
+        return ImageBuf::new(Vec::new(), size);
+    }
+
+    let mut luma = Vec::with_capacity(size.x * size.y);
+
+    for y in 0..size.y {
+        let row = &data[y * stride..y * stride + row_len];
+
+        if step == 1 {
+            luma.extend_from_slice(row);
+        } else {
+            luma.extend(row.iter().step_by(step));
+        }
+    }
+
+    ImageBuf::new(luma, size)
+}
+
+/// Checks that `data` holds `size.y` rows of `row_len` bytes that are
+/// `stride` bytes apart.
+fn check_rows(data: &[u8], size: Vec2D, stride: usize, row_len: usize) -> Result<()> {
+    if stride < row_len {
+        return Err(Error::SizeMismatch { expected: row_len, actual: stride });
+    }
+
+    // The last row doesn't need to be padded up to the full stride.
+    let required_len = match size.y {
+        0 => 0,
+        height => checked_add(checked_mul(stride, height - 1)?, row_len)?,
+    };
+
+    if data.len() < required_len {
+        return Err(Error::SizeMismatch { expected: required_len, actual: data.len() });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Entry points for fuzzers, which accept arbitrary bytes.
+//!
+//! Each function interprets its input as the input of one stage of the
+//! pipeline, runs that stage, and discards the results. Whatever the input,
+//! they must return normally; a panic, a crash or an out-of-bounds access
+//! (e.g. in `quirc`, under a sanitizer) is a bug. The `fuzz` directory of
+//! the repository contains `cargo fuzz` targets calling them.
+
+use std::vec::Vec;
+use geom::{ Image, QrCode, Vec2D };
+use decoder::Decoder;
+use backend::decode_in_rust;
+use error::Result;
+
+/// The largest size of the bitmaps built by `decode_bitmap_bytes()`: that
+/// of a version 40 code.
+const MAX_BITMAP_SIZE: usize = 177;
+
+/// Builds an image from `data`, and detects and decodes the codes in it.
+/// Returns the number of codes decoded.
+///
+/// The first two bytes are the width and the height of the image, minus
+/// one, so images are at most 256 pixels wide and high, and every input is
+/// processed quickly. The rest are the pixels, row by row; missing ones
+/// are white.
+pub fn decode_image_bytes(data: &[u8]) -> Result<usize> {
+    if data.len() < 2 {
+        return Ok(0);
+    }
+
+    let size = Vec2D { x: usize::from(data[0]) + 1, y: usize::from(data[1]) + 1 };
+    let mut buf = data[2..].to_vec();
+
+    buf.resize(size.x * size.y, 0xff);
+
+    let mut decoder = Decoder::new()?;
+    let decoded = decoder
+        .decode_image(&Image::new(&buf, size)?)?
+        .filter_map(|code| code.and_then(|code| code.decode()).ok())
+        .count();
+
+    Ok(decoded)
+}
+
+/// Builds the bitmap of a code from `data`, and decodes it both with
+/// `quirc` and in Rust, so that bitmaps which no detector would produce,
+/// e.g. with damaged format information, reach the decoders, too.
+///
+/// The first byte, modulo 178, is the size of the code, which needn't be
+/// that of a valid version. The rest is the bitmap, in the layout of
+/// `QrCode::bitmap()`; missing bytes are zero (light).
+pub fn decode_bitmap_bytes(data: &[u8]) -> Result<()> {
+    let (size, bytes) = match data.split_first() {
+        Some((&first, bytes)) => (usize::from(first) % (MAX_BITMAP_SIZE + 1), bytes),
+        None => return Ok(()),
+    };
+    let num_bytes = (size * size + 7) / 8;
+    let mut bitmap: Vec<u8> = bytes.iter().cloned().take(num_bytes).collect();
+
+    bitmap.resize(num_bytes, 0);
+
+    let code = QrCode::from_bitmap(&bitmap, size)?;
+    let _ = code.format_info();
+    let _ = code.decode();
+    let _ = decode_in_rust(&code);
+
+    Ok(())
+}
@@ -1,15 +1,24 @@
 //! Basic geometry types.
 
 use std::fmt;
-use util::int_to_usize;
-use info::Info;
-use quirc_sys::{ quirc_point, quirc_code, quirc_data };
+use std::mem;
+use std::vec::Vec;
+use std::ops::{ Add, Sub, Mul };
+use std::convert::TryFrom;
+#[cfg(not(any(feature = "std", test)))]
+use float::F64Ext;
+use util::{ int_to_usize, usize_to_int, checked_add, checked_mul };
+use info::{ Info, FormatInfo, DataType };
+use bitstream;
+use mask::polygon_contains;
+use quirc_sys::{ quirc_point, quirc_code, quirc_data, QUIRC_MAX_BITMAP };
 use quirc_sys::{ quirc_decode, quirc_decode_error_t };
-use error::{ Error, Result };
+use error::{ Error, Overflow, Result };
 use self::quirc_decode_error_t::QUIRC_SUCCESS;
 
 /// A size, offset, or point in the 2-dimensional plane.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vec2D {
     /// X coordinate, horizontal position, or width.
     pub x: usize,
@@ -23,6 +32,251 @@ impl Vec2D {
         let (x, y) = (int_to_usize(p.x)?, int_to_usize(p.y)?);
         Ok(Vec2D { x, y })
     }
+
+    /// Returns the Euclidean distance between two points.
+    pub fn distance_to(self, other: Vec2D) -> f64 {
+        Vec2Df::from(self).distance(other.into())
+    }
+}
+
+impl Add for Vec2D {
+    type Output = Vec2D;
+
+    fn add(self, other: Vec2D) -> Vec2D {
+        Vec2D { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+/// Panics if either coordinate of `other` is greater than that of `self`,
+/// like the subtraction of `usize`s.
+impl Sub for Vec2D {
+    type Output = Vec2D;
+
+    fn sub(self, other: Vec2D) -> Vec2D {
+        Vec2D { x: self.x - other.x, y: self.y - other.y }
+    }
+}
+
+impl Mul<usize> for Vec2D {
+    type Output = Vec2D;
+
+    fn mul(self, factor: usize) -> Vec2D {
+        Vec2D { x: self.x * factor, y: self.y * factor }
+    }
+}
+
+impl From<(usize, usize)> for Vec2D {
+    fn from((x, y): (usize, usize)) -> Self {
+        Vec2D { x, y }
+    }
+}
+
+impl From<[usize; 2]> for Vec2D {
+    fn from([x, y]: [usize; 2]) -> Self {
+        Vec2D { x, y }
+    }
+}
+
+/// Fails with `Error::IntOverflow` if either coordinate is negative.
+impl TryFrom<(i32, i32)> for Vec2D {
+    type Error = Error;
+
+    fn try_from((x, y): (i32, i32)) -> Result<Self> {
+        let convert = |n: i32| usize::try_from(n).map_err(
+            |_| Error::IntOverflow(Overflow::Value(n.into()))
+        );
+
+        Ok(Vec2D { x: convert(x)?, y: convert(y)? })
+    }
+}
+
+/// A point or vector in the 2-dimensional plane, with sub-pixel precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vec2Df {
+    /// X coordinate, or horizontal component.
+    pub x: f64,
+    /// Y coordinate, or vertical component.
+    pub y: f64,
+}
+
+impl Vec2Df {
+    /// Returns the Euclidean distance between two points.
+    pub fn distance(self, other: Vec2Df) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+impl From<Vec2D> for Vec2Df {
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn from(v: Vec2D) -> Self {
+        Vec2Df { x: v.x as f64, y: v.y as f64 }
+    }
+}
+
+/// A quadrilateral, e.g. the outline of a code in an image.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Quad {
+    /// The corners, in order along the outline. Those of a code are
+    /// clockwise from the top left one.
+    pub corners: [Vec2Df; 4],
+}
+
+impl Quad {
+    /// Creates a quadrilateral out of its corners, in order along the outline.
+    pub fn new(corners: [Vec2Df; 4]) -> Self {
+        Quad { corners }
+    }
+
+    /// The four edges, each one from a corner to the next one.
+    pub fn edges(&self) -> [(Vec2Df, Vec2Df); 4] {
+        let c = self.corners;
+        [(c[0], c[1]), (c[1], c[2]), (c[2], c[3]), (c[3], c[0])]
+    }
+
+    /// Whether the quadrilateral is convex, i.e. it turns the same way at
+    /// each corner. Degenerate ones, e.g. with three corners on a line,
+    /// aren't convex.
+    pub fn is_convex(&self) -> bool {
+        let edges = self.edges();
+        let turns: Vec<f64> = (0..4)
+            .map(|i| {
+                let (a, b) = edges[i];
+                cross(a, b, edges[(i + 1) % 4].1)
+            })
+            .collect();
+
+        turns.iter().all(|&t| t > 0.0) || turns.iter().all(|&t| t < 0.0)
+    }
+
+    /// The enclosed area, regardless of the orientation of the corners.
+    pub fn area(&self) -> f64 {
+        signed_area(&self.corners).abs()
+    }
+
+    /// The mean of the four corners.
+    pub fn centroid(&self) -> Vec2Df {
+        let c = self.corners;
+
+        Vec2Df {
+            x: (c[0].x + c[1].x + c[2].x + c[3].x) / 4.0,
+            y: (c[0].y + c[1].y + c[2].y + c[3].y) / 4.0,
+        }
+    }
+
+    /// Returns the intersection over union of two quadrilaterals, in the
+    /// range `[0, 1]`. They are assumed to be convex, which outlines of
+    /// codes are, barring misdetections. Returns 0 if both are degenerate.
+    pub fn iou(&self, other: &Quad) -> f64 {
+        let first = positive_polygon(&self.corners);
+        let second = positive_polygon(&other.corners);
+        let intersection = signed_area(&clip(&first, &second));
+        let union = signed_area(&first) + signed_area(&second) - intersection;
+
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the quadrilateral scaled by `factor` about its centroid,
+    /// e.g. to add a margin around a code before cropping it.
+    pub fn scaled(&self, factor: f64) -> Quad {
+        let center = self.centroid();
+        let scale = |p: Vec2Df| Vec2Df {
+            x: center.x + (p.x - center.x) * factor,
+            y: center.y + (p.y - center.y) * factor,
+        };
+        let c = self.corners;
+
+        Quad { corners: [scale(c[0]), scale(c[1]), scale(c[2]), scale(c[3])] }
+    }
+}
+
+impl From<[Vec2D; 4]> for Quad {
+    fn from(corners: [Vec2D; 4]) -> Self {
+        Quad {
+            corners: [
+                corners[0].into(),
+                corners[1].into(),
+                corners[2].into(),
+                corners[3].into(),
+            ],
+        }
+    }
+}
+
+/// Computes the signed area of a polygon with the shoelace formula. It is
+/// positive if the vertices are counterclockwise in a coordinate system
+/// whose y axis points up, i.e. clockwise in an image.
+fn signed_area(polygon: &[Vec2Df]) -> f64 {
+    let twice_area: f64 = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+
+    twice_area / 2.0
+}
+
+/// Returns the corners as a polygon with a positive signed area.
+fn positive_polygon(corners: &[Vec2Df; 4]) -> Vec<Vec2Df> {
+    let mut polygon = corners.to_vec();
+
+    if signed_area(&polygon) < 0.0 {
+        polygon.reverse();
+    }
+
+    polygon
+}
+
+/// Returns the z component of the cross product of `a - origin` and
+/// `b - origin`, which is positive if `b` lies to the left of the line
+/// from `origin` through `a`, given a positive orientation.
+fn cross(origin: Vec2Df, a: Vec2Df, b: Vec2Df) -> f64 {
+    (a.x - origin.x) * (b.y - origin.y) - (a.y - origin.y) * (b.x - origin.x)
+}
+
+/// Clips `subject` to the convex polygon `clipper` with the
+/// Sutherland-Hodgman algorithm. Both must have a positive orientation.
+fn clip(subject: &[Vec2Df], clipper: &[Vec2Df]) -> Vec<Vec2Df> {
+    let mut output = subject.to_vec();
+
+    for (&edge_start, &edge_end) in clipper.iter().zip(clipper.iter().cycle().skip(1)) {
+        let input = mem::take(&mut output);
+        let previous_vertices = input.iter().cycle().skip(input.len().saturating_sub(1));
+
+        for (&current, &previous) in input.iter().zip(previous_vertices) {
+            let current_inside = cross(edge_start, edge_end, current) >= 0.0;
+            let previous_inside = cross(edge_start, edge_end, previous) >= 0.0;
+
+            if current_inside != previous_inside {
+                output.push(crossing(previous, current, edge_start, edge_end));
+            }
+
+            if current_inside {
+                output.push(current);
+            }
+        }
+
+        if output.is_empty() {
+            break;
+        }
+    }
+
+    output
+}
+
+/// Returns the point where the segment from `p` to `q` crosses the line
+/// through `a` and `b`, given that `p` and `q` lie on different sides.
+fn crossing(p: Vec2Df, q: Vec2Df, a: Vec2Df, b: Vec2Df) -> Vec2Df {
+    let from_p = cross(a, b, p);
+    let from_q = cross(a, b, q);
+    let t = from_p / (from_p - from_q);
+
+    Vec2Df { x: p.x + t * (q.x - p.x), y: p.y + t * (q.y - p.y) }
 }
 
 /// Raw image data to be decoded.
@@ -32,20 +286,44 @@ pub struct Image<'a> {
     data: &'a [u8],
     /// The dimensions of the image.
     size: Vec2D,
+    /// The distance between the beginnings of consecutive rows, in bytes.
+    stride: usize,
 }
 
 impl<'a> Image<'a> {
     /// Creates a image out of a raw buffer of grayscale data,
     /// and the width and the height of the image.
     pub fn new(data: &'a [u8], size: Vec2D) -> Result<Self> {
-        if data.len() == size.x * size.y {
-            Ok(Image { data, size })
+        let expected = checked_mul(size.x, size.y)?;
+
+        if data.len() == expected {
+            Ok(Image { data, size, stride: size.x })
         } else {
-            Err(Error::SizeMismatch)
+            Err(Error::SizeMismatch { expected, actual: data.len() })
         }
     }
 
-    /// Return the raw data buffer.
+    /// Creates an image out of a raw buffer of grayscale data in which
+    /// consecutive rows begin `stride` bytes apart, e.g. because rows are
+    /// padded for alignment. The padding after the last row may be omitted.
+    pub fn with_stride(data: &'a [u8], size: Vec2D, stride: usize) -> Result<Self> {
+        if stride < size.x {
+            return Err(Error::SizeMismatch { expected: size.x, actual: stride });
+        }
+
+        let min_len = match size.y {
+            0 => 0,
+            height => checked_add(checked_mul(stride, height - 1)?, size.x)?,
+        };
+
+        if data.len() >= min_len {
+            Ok(Image { data, size, stride })
+        } else {
+            Err(Error::SizeMismatch { expected: min_len, actual: data.len() })
+        }
+    }
+
+    /// Return the raw data buffer, including any row padding.
     pub fn data(&self) -> &[u8] {
         self.data
     }
@@ -59,6 +337,76 @@ impl<'a> Image<'a> {
     pub fn height(&self) -> usize {
         self.size.y
     }
+
+    /// Return the distance between the beginnings of consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Return the pixels of row `y`, without padding.
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> &[u8] {
+        assert!(y < self.size.y, "row {} out of bounds for image of height {}", y, self.size.y);
+        let start = y * self.stride;
+        &self.data[start..start + self.size.x]
+    }
+}
+
+/// An owned buffer of 8-bit grayscale image data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ImageBuf {
+    /// The pixels, row by row, without padding.
+    data: Vec<u8>,
+    /// The dimensions of the image.
+    size: Vec2D,
+}
+
+impl ImageBuf {
+    /// Creates an image out of an owned buffer of grayscale data,
+    /// and the width and the height of the image.
+    pub fn new(data: Vec<u8>, size: Vec2D) -> Result<Self> {
+        let expected = checked_mul(size.x, size.y)?;
+
+        if data.len() == expected {
+            Ok(ImageBuf { data, size })
+        } else {
+            Err(Error::SizeMismatch { expected, actual: data.len() })
+        }
+    }
+
+    /// Borrows the buffer as an `Image` that can be passed to a `Decoder`.
+    pub fn as_image(&self) -> Image {
+        Image {
+            data: &self.data,
+            size: self.size,
+            stride: self.size.x,
+        }
+    }
+
+    /// Return the raw data buffer.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the raw data buffer for modification.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Return the width of (number of columns in) the image.
+    pub fn width(&self) -> usize {
+        self.size.x
+    }
+
+    /// Return the height of (number of rows in) the image.
+    pub fn height(&self) -> usize {
+        self.size.y
+    }
+
+    /// Consumes the image and returns the underlying buffer.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
 }
 
 /// Information about the location and raw data of a QR code within an `Image`.
@@ -69,13 +417,100 @@ impl QrCode {
     /// Attempts to create a new `QrCode` from a `quirc_code` FFI struct.
     #[doc(hidden)]
     pub fn from_raw(raw: quirc_code) -> Result<Self> {
-        let _ = int_to_usize(raw.size)?;
+        let code = QrCode(raw);
+
+        code.validate()?;
+
+        Ok(code)
+    }
+
+    /// Checks that the size and the corners of the code can be represented
+    /// as `usize`s, which `from_raw()` requires.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let _ = int_to_usize(self.0.size)?;
 
         for i in 0..4 {
-            let _ = Vec2D::from_raw(raw.corners[i])?;
+            let _ = Vec2D::from_raw(self.0.corners[i])?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the underlying FFI struct, so that `quirc` can overwrite
+    /// the code in place. The result must be checked with `validate()`.
+    pub(crate) fn as_raw_mut(&mut self) -> &mut quirc_code {
+        &mut self.0
+    }
+
+    /// Creates a code from a bitmap in the layout of `bitmap()`: the bit of
+    /// the module at `(x, y)` is bit `i % 8` of byte `i / 8`, where
+    /// `i = y * size + x`. The corners are those of a code detected in an
+    /// image with one pixel per module.
+    ///
+    /// Any `size` is accepted as long as the bitmap fits, even ones which
+    /// don't correspond to any version, so that arbitrary input can be fed
+    /// to `decode()`, e.g. by fuzzers. Fails with `Error::SizeMismatch` if
+    /// the length of `bitmap` isn't `(size * size + 7) / 8`, or if that is
+    /// larger than the bitmap of a version 40 code.
+    pub fn from_bitmap(bitmap: &[u8], size: usize) -> Result<Self> {
+        let mut code = QrCode::blank(size)?;
+        let num_bytes = code.bitmap().len();
+
+        if bitmap.len() != num_bytes {
+            return Err(Error::SizeMismatch { expected: num_bytes, actual: bitmap.len() });
+        }
+
+        code.0.cell_bitmap[..num_bytes].copy_from_slice(bitmap);
+
+        Ok(code)
+    }
+
+    /// Creates a code from its modules, row by row, `true` meaning dark,
+    /// e.g. as read by another detector, or from a rendered code. The
+    /// corners are those of a code detected in an image with one pixel per
+    /// module, just like for `from_bitmap()`.
+    ///
+    /// Fails with `Error::SizeMismatch` if the length of `modules` isn't
+    /// `size * size`, or if the code is larger than one of version 40.
+    pub fn from_modules(size: usize, modules: &[bool]) -> Result<Self> {
+        let mut code = QrCode::blank(size)?;
+        let num_modules = size * size;
+
+        if modules.len() != num_modules {
+            return Err(Error::SizeMismatch { expected: num_modules, actual: modules.len() });
+        }
+
+        for (i, _) in modules.iter().enumerate().filter(|&(_, &dark)| dark) {
+            code.0.cell_bitmap[i / 8] |= 1 << (i % 8);
         }
 
-        Ok(QrCode(raw))
+        Ok(code)
+    }
+
+    /// Creates a code of the given size with only light modules, with its
+    /// corners at those of a `size` by `size` image. Fails with
+    /// `Error::SizeMismatch` if its bitmap would be larger than that of a
+    /// version 40 code.
+    fn blank(size: usize) -> Result<Self> {
+        let num_bytes = checked_add(checked_mul(size, size)?, 7)? / 8;
+
+        if num_bytes > QUIRC_MAX_BITMAP {
+            return Err(Error::SizeMismatch { expected: QUIRC_MAX_BITMAP, actual: num_bytes });
+        }
+
+        let side = usize_to_int(size)?;
+        let raw = quirc_code {
+            corners: [
+                quirc_point { x: 0, y: 0 },
+                quirc_point { x: side, y: 0 },
+                quirc_point { x: side, y: side },
+                quirc_point { x: 0, y: side },
+            ],
+            size: side,
+            ..quirc_code::default()
+        };
+
+        QrCode::from_raw(raw)
     }
 
     /// Extracts the corner at the given index (0...3) as a `Vec2D`.
@@ -86,6 +521,16 @@ impl QrCode {
         Vec2D::from_raw(self.0.corners[i]).expect("invalid corner coordinates")
     }
 
+    /// The four corners of the QR code, clockwise from the top left one.
+    pub(crate) fn corners(&self) -> [Vec2D; 4] {
+        [
+            self.corner_at(0),
+            self.corner_at(1),
+            self.corner_at(2),
+            self.corner_at(3),
+        ]
+    }
+
     /// The coordinates of the top left corner of the QR code.
     pub fn top_left_corner(&self) -> Vec2D {
         self.corner_at(0)
@@ -106,6 +551,68 @@ impl QrCode {
         self.corner_at(3)
     }
 
+    /// The top left and bottom right corners of the smallest axis-aligned
+    /// rectangle containing the QR code.
+    pub fn bounding_box(&self) -> (Vec2D, Vec2D) {
+        let corners = self.corners();
+        let min = Vec2D {
+            x: corners.iter().map(|c| c.x).min().unwrap_or(0),
+            y: corners.iter().map(|c| c.y).min().unwrap_or(0),
+        };
+        let max = Vec2D {
+            x: corners.iter().map(|c| c.x).max().unwrap_or(0),
+            y: corners.iter().map(|c| c.y).max().unwrap_or(0),
+        };
+
+        (min, max)
+    }
+
+    /// The outline of the QR code, spanned by its four corners.
+    pub fn quad(&self) -> Quad {
+        Quad::from(self.corners())
+    }
+
+    /// The area enclosed by the four corners of the QR code, in square pixels.
+    pub fn area(&self) -> f64 {
+        self.quad().area()
+    }
+
+    /// The mean length of the four sides of the QR code, in pixels.
+    fn mean_side_length(&self) -> f64 {
+        let corners = self.corners();
+        let perimeter: f64 = (0..4)
+            .map(|i| Vec2Df::from(corners[i]).distance(corners[(i + 1) % 4].into()))
+            .sum();
+
+        perimeter / 4.0
+    }
+
+    /// The estimated size of a module (width == height) in the image,
+    /// in pixels, i.e. the mean length of the sides of the QR code divided
+    /// by the number of modules along each side.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn module_size_px(&self) -> f64 {
+        self.mean_side_length() / self.size() as f64
+    }
+
+    /// Estimates the distance of the QR code from the camera with the
+    /// pinhole camera model, given the focal length of the camera in pixels
+    /// and the physical side length of the code, excluding the quiet zone.
+    /// The result is in the same unit as `code_side_length`.
+    ///
+    /// This is only a rough estimate: it assumes that the code faces the
+    /// camera, and ignores lens distortion.
+    pub fn estimate_distance(&self, focal_length_px: f64, code_side_length: f64) -> f64 {
+        focal_length_px * code_side_length / self.mean_side_length()
+    }
+
+    /// Tests whether the pixel at `point` lies inside the quadrilateral
+    /// spanned by the four corners of the QR code.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn contains(&self, point: Vec2D) -> bool {
+        polygon_contains(&self.corners(), (point.x as f64 + 0.5, point.y as f64 + 0.5))
+    }
+
     /// The size (`width == height`) of the QR code bitmap.
     pub fn size(&self) -> usize {
         // This cannot panic because before the construction of the `QrCode`,
@@ -139,6 +646,24 @@ impl QrCode {
         }
     }
 
+    /// Set the bit at coordinates `(coord.x, coord.y)`,
+    /// panicking upon an OOB condition.
+    #[cfg(feature = "std")]
+    pub(crate) fn set_bit(&mut self, coord: Vec2D, value: bool) {
+        let size = self.size();
+        let Vec2D { x, y } = coord;
+        assert!(x < size && y < size, "{:?} out of bounds for bitmap of size {}", coord, size);
+
+        let i = y * size + x;
+        let mask = 1 << (i % 8);
+
+        if value {
+            self.0.cell_bitmap[i / 8] |= mask;
+        } else {
+            self.0.cell_bitmap[i / 8] &= !mask;
+        }
+    }
+
     /// Get the bit at coordinates `(coord.x, coord.y)`,
     /// panicking upon an OOB condition.
     pub fn bit_at(&self, coord: Vec2D) -> bool {
@@ -147,18 +672,89 @@ impl QrCode {
         )
     }
 
+    /// Reads the format information, i.e. the ECC level and the mask,
+    /// without decoding the data. This succeeds even for some codes whose
+    /// data is too damaged to be decoded.
+    pub fn format_info(&self) -> Result<FormatInfo> {
+        bitstream::read_format(self)
+    }
+
     /// Decode the raw data into higher-level information.
+    ///
+    /// The data codewords are only read in Rust where `quirc` can't fully
+    /// decode the symbol, so the accessors of the `Info` which need them,
+    /// such as `Info::segments()`, usually come up empty; use
+    /// `decode_with_codewords()` for those.
     pub fn decode(&self) -> Result<Info> {
+        self.decode_with(false)
+    }
+
+    /// Like `decode()`, but also reads and error-corrects the data
+    /// codewords in Rust, and keeps them in the `Info`, for inspecting
+    /// the bitstream, e.g. with `Info::segments()` or `Info::raw_bits()`.
+    pub fn decode_with_codewords(&self) -> Result<Info> {
+        self.decode_with(true)
+    }
+
+    /// Does the work of `decode()` and `decode_with_codewords()`.
+    fn decode_with(&self, keep_codewords: bool) -> Result<Info> {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!("decode", size = self.size()).entered();
+
+        let result = self.decode_untraced(keep_codewords);
+
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(ref info) => ::tracing::debug!(
+                version = info.version().number(),
+                payload_len = info.payload().len(),
+                "decoded",
+            ),
+            Err(ref error) => ::tracing::debug!(%error, "failed to decode"),
+        }
+
+        result
+    }
+
+    /// Does the actual work of `decode_with()`.
+    fn decode_untraced(&self, keep_codewords: bool) -> Result<Info> {
         let mut raw = quirc_data::default();
         let error_code = unsafe {
             quirc_decode(&self.0, &mut raw)
         };
 
-        if error_code == QUIRC_SUCCESS {
-            Ok(Info::from_raw(raw))
-        } else {
-            Err(error_code.into())
+        if error_code != QUIRC_SUCCESS {
+            return Err(error_code.into());
         }
+
+        let mut info = Info::from_raw(raw);
+
+        // `quirc` stops decoding at a structured append header, so such
+        // symbols appear to be empty, and it concatenates the segments,
+        // so Kanji can't be told apart from text in the ECI's character
+        // set; these need the bitstream to be read in Rust.
+        let is_incomplete = info.payload().is_empty()
+            || (info.data_type() == DataType::Kanji && info.eci().is_some());
+
+        if !keep_codewords && !is_incomplete {
+            return Ok(info);
+        }
+
+        if let Ok(codewords) = bitstream::read_codewords(self) {
+            if info.payload().is_empty() {
+                let payload = bitstream::decode_payload(&codewords.data, codewords.version);
+
+                if let Ok(ref payload) = payload {
+                    if payload.structured_append.is_some() {
+                        info.set_payload(payload)?;
+                    }
+                }
+            }
+
+            info.set_codewords(&codewords);
+        }
+
+        Ok(info)
     }
 }
 
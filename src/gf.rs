@@ -0,0 +1,355 @@
+//! Arithmetic in GF(2^8) and Reed-Solomon error correction codes, as
+//! used by QR codes: the field is generated by the primitive polynomial
+//! `x^8 + x^4 + x^3 + x^2 + 1`, and the generator element is 2.
+
+use std::vec::Vec;
+
+/// The primitive polynomial of the field, including the `x^8` term.
+const PRIMITIVE: u16 = 0x11d;
+
+/// The powers of the generator, `EXP[i] == 2^i`, repeated twice so that
+/// the sum of two logarithms can be looked up without reducing it.
+const EXP: [u8; 512] = exp_table();
+
+/// The discrete logarithms of the non-zero elements, `EXP[LOG[a]] == a`.
+const LOG: [u8; 256] = log_table();
+
+/// Computes `EXP` by repeated multiplication by the generator.
+const fn exp_table() -> [u8; 512] {
+    let mut table = [0; 512];
+    let mut x: u16 = 1;
+    let mut i = 0;
+
+    while i < table.len() {
+        table[i] = x.to_le_bytes()[0];
+        x <<= 1;
+
+        if x & 0x100 != 0 {
+            x ^= PRIMITIVE;
+        }
+
+        i += 1;
+    }
+
+    table
+}
+
+/// Computes `LOG` by inverting the first period of `EXP`. The logarithm
+/// of zero is undefined, and left at 0.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+const fn log_table() -> [u8; 256] {
+    let exp = exp_table();
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 255 {
+        table[exp[i] as usize] = i as u8;
+        i += 1;
+    }
+
+    table
+}
+
+/// Multiplies two elements of the field.
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        EXP[usize::from(LOG[usize::from(a)]) + usize::from(LOG[usize::from(b)])]
+    }
+}
+
+/// Returns the generator polynomial of the Reed-Solomon code with
+/// `degree` error correction codewords, i.e. `(x - 2^0)...(x - 2^(d-1))`.
+/// The coefficients are in order of decreasing powers, with the leading
+/// coefficient (which is always 1) omitted.
+pub fn rs_generator(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    let mut root = 1;
+
+    if let Some(last) = result.last_mut() {
+        *last = 1;
+    }
+
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = mul(result[j], root);
+
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+
+        root = mul(root, 2);
+    }
+
+    result
+}
+
+/// Returns the error correction codewords of `data`, i.e. the remainder
+/// of its polynomial division by `generator` (see `rs_generator()`).
+pub fn rs_remainder(data: &[u8], generator: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; generator.len()];
+
+    for &byte in data {
+        let factor = byte ^ result[0];
+
+        result.remove(0);
+        result.push(0);
+
+        for (r, &g) in result.iter_mut().zip(generator) {
+            *r ^= mul(g, factor);
+        }
+    }
+
+    result
+}
+
+/// Raises an element of the field to the given power.
+pub fn pow(a: u8, n: usize) -> u8 {
+    if n == 0 {
+        1
+    } else if a == 0 {
+        0
+    } else {
+        // The multiplicative group has 255 elements, so `a^255 == 1`.
+        EXP[usize::from(LOG[usize::from(a)]) * (n % 255) % 255]
+    }
+}
+
+/// Returns the multiplicative inverse of a non-zero element.
+pub fn inv(a: u8) -> u8 {
+    debug_assert!(a != 0, "zero has no inverse");
+    EXP[255 - usize::from(LOG[usize::from(a)])]
+}
+
+/// Evaluates a polynomial, given by its coefficients in order of
+/// increasing powers, at `x`.
+fn eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// Corrects errors in a Reed-Solomon block (data followed by `ecc_len`
+/// error correction codewords) in place, using the Berlekamp-Massey
+/// algorithm and Forney's formula.
+///
+/// Returns the number of corrected codewords, or `None` if the errors
+/// can't be corrected; the block is left unmodified in that case.
+pub fn rs_correct(block: &mut [u8], ecc_len: usize) -> Option<usize> {
+    let n = block.len();
+
+    // The codewords are the coefficients of the received polynomial in
+    // order of decreasing powers, so evaluate it in that direction.
+    let syndromes: Vec<u8> = (0..ecc_len)
+        .map(|i| {
+            let x = pow(2, i);
+            block.iter().fold(0, |acc, &c| mul(acc, x) ^ c)
+        })
+        .collect();
+
+    if syndromes.iter().all(|&s| s == 0) {
+        return Some(0);
+    }
+
+    // Berlekamp-Massey: find the error locator polynomial `sigma`,
+    // in order of increasing powers, with `sigma[0] == 1`.
+    let mut sigma = vec![1];
+    let mut prev = vec![1];
+    let mut num_errors = 0;
+    let mut shift = 1;
+    let mut prev_discrepancy = 1;
+
+    for k in 0..ecc_len {
+        let discrepancy = (1..=num_errors).fold(syndromes[k], |acc, i| {
+            acc ^ mul(sigma.get(i).cloned().unwrap_or(0), syndromes[k - i])
+        });
+
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let factor = mul(discrepancy, inv(prev_discrepancy));
+        let mut next = sigma.clone();
+
+        next.resize(next.len().max(prev.len() + shift), 0);
+
+        for (i, &p) in prev.iter().enumerate() {
+            next[i + shift] ^= mul(factor, p);
+        }
+
+        if 2 * num_errors <= k {
+            num_errors = k + 1 - num_errors;
+            prev = sigma;
+            prev_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+
+        sigma = next;
+    }
+
+    if 2 * num_errors > ecc_len {
+        return None;
+    }
+
+    // Chien search: the error at power `p` of the received polynomial
+    // has the locator `2^p`, which is the inverse of a root of `sigma`.
+    let positions: Vec<usize> = (0..n).filter(|&p| eval(&sigma, inv(pow(2, p))) == 0).collect();
+
+    if positions.len() != num_errors {
+        return None;
+    }
+
+    // Forney: the error evaluator is `omega = S * sigma mod x^ecc_len`.
+    let mut omega = vec![0; ecc_len];
+
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &c) in sigma.iter().enumerate().take(ecc_len - i) {
+            omega[i + j] ^= mul(s, c);
+        }
+    }
+
+    // The formal derivative of `sigma` keeps the odd powers only.
+    let derivative: Vec<u8> = sigma
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &c)| if i % 2 == 1 { c } else { 0 })
+        .collect();
+
+    let mut corrected = block.to_vec();
+
+    for &p in &positions {
+        let locator = pow(2, p);
+        let x_inv = inv(locator);
+        let denominator = eval(&derivative, x_inv);
+
+        if denominator == 0 {
+            return None;
+        }
+
+        let magnitude = mul(locator, mul(eval(&omega, x_inv), inv(denominator)));
+        corrected[n - 1 - p] ^= magnitude;
+    }
+
+    let is_valid = (0..ecc_len).all(|i| {
+        let x = pow(2, i);
+        corrected.iter().fold(0, |acc, &c| mul(acc, x) ^ c) == 0
+    });
+
+    if is_valid {
+        block.copy_from_slice(&corrected);
+        Some(num_errors)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    /// Multiplies by shifting and adding, reducing by the primitive
+    /// polynomial whenever the degree reaches 8.
+    fn mul_by_shifting(a: u8, b: u8) -> u8 {
+        let mut product = 0;
+        let mut a = u16::from(a);
+        let mut b = b;
+
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+
+            a <<= 1;
+
+            if a & 0x100 != 0 {
+                a ^= PRIMITIVE;
+            }
+
+            b >>= 1;
+        }
+
+        product.to_le_bytes()[0]
+    }
+
+    /// Returns a block of 20 data codewords followed by 10 error
+    /// correction codewords, which can correct up to 5 errors.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn block() -> Vec<u8> {
+        let mut block: Vec<u8> = (0..20).map(|i| (i * 37 + 11) as u8).collect();
+        let ecc = rs_remainder(&block, &rs_generator(10));
+
+        block.extend_from_slice(&ecc);
+        block
+    }
+
+    /// Flips bits of the codewords at every fifth position, data and error
+    /// correction alike, starting with the first one.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn corrupt(block: &mut [u8], num_errors: usize) {
+        for i in 0..num_errors {
+            block[i * 5] ^= (i * 73 + 1) as u8;
+        }
+    }
+
+    #[test]
+    fn tables_agree_with_shifting() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                assert_eq!(mul(a, b), mul_by_shifting(a, b), "{} * {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn powers_and_inverses() {
+        for a in 0..=255 {
+            let mut power = 1;
+
+            for n in 0..300 {
+                assert_eq!(pow(a, n), power, "{}^{}", a, n);
+                power = mul_by_shifting(power, a);
+            }
+        }
+
+        for a in 1..=255 {
+            assert_eq!(mul(a, inv(a)), 1, "inverse of {}", a);
+        }
+    }
+
+    #[test]
+    fn valid_block_has_no_errors() {
+        let mut block = block();
+
+        assert_eq!(rs_correct(&mut block, 10), Some(0));
+        assert_eq!(block, self::block());
+    }
+
+    #[test]
+    fn up_to_half_of_ecc_len_errors_are_corrected() {
+        for num_errors in 1..=5 {
+            let mut corrupted = block();
+
+            corrupt(&mut corrupted, num_errors);
+
+            assert_eq!(rs_correct(&mut corrupted, 10), Some(num_errors));
+            assert_eq!(corrupted, block(), "{} errors", num_errors);
+        }
+    }
+
+    #[test]
+    fn too_many_errors_are_rejected() {
+        let mut corrupted = block();
+
+        corrupt(&mut corrupted, 6);
+
+        let original = corrupted.clone();
+
+        assert_eq!(rs_correct(&mut corrupted, 10), None);
+        assert_eq!(corrupted, original, "the block was modified");
+    }
+}
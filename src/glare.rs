@@ -0,0 +1,271 @@
+//! Detection and suppression of specular highlights.
+//!
+//! Glossy surfaces (e.g. laminated badges) often reflect a light source
+//! right into the camera, producing a blown-out patch over part of the code.
+//! Besides destroying the modules underneath, such a patch also skews the
+//! adaptive threshold `quirc` computes from the running average brightness,
+//! so modules around it get misclassified too. Replacing the saturated
+//! pixels with values interpolated from their surroundings undoes the
+//! latter effect, and the error correction can often take care of the rest.
+
+use geom::{ Image, ImageBuf, QrCode, Vec2D };
+use decoder::Decoder;
+use retry::{ Pass, DecodePath, Decoded, DecodeOptions, Deadline, Undecoded };
+use mask::{ for_each_polygon_span, polygon_contains };
+use error::{ Error, Result };
+
+/// Parameters of glare detection and suppression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlareOptions {
+    /// Pixels at least this bright are considered to be blown out.
+    pub threshold: u8,
+    /// The minimal fraction of blown-out pixels within the outline of a code
+    /// that failed to decode for a retry to be attempted.
+    pub min_fraction: f64,
+}
+
+impl Default for GlareOptions {
+    fn default() -> Self {
+        GlareOptions {
+            threshold: 250,
+            min_fraction: 0.01,
+        }
+    }
+}
+
+/// Returns the fraction of the pixels within the outline of `code`
+/// that are at least as bright as `threshold`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+pub fn glare_fraction(image: &Image, code: &QrCode, threshold: u8) -> f64 {
+    let mut total = 0_usize;
+    let mut blown_out = 0_usize;
+
+    for_each_outline_span(image, code, |y, start, end| {
+        let row = image.row(y);
+
+        total += end - start;
+        blown_out += row[start..end].iter().filter(|&&px| px >= threshold).count();
+    });
+
+    if total == 0 {
+        0.0
+    } else {
+        blown_out as f64 / total as f64
+    }
+}
+
+/// Returns a copy of `image` in which every horizontal run of pixels at
+/// least as bright as `threshold` within the outline of `code` is replaced
+/// by a linear interpolation between the pixels on either side of the run.
+pub fn suppress_glare(image: &Image, code: &QrCode, threshold: u8) -> ImageBuf {
+    let mut data = copy_pixels(image);
+
+    inpaint_glare(&mut data, image, code, threshold);
+
+    ImageBuf::new(data, Vec2D { x: image.width(), y: image.height() })
+        .expect("copy of image has the same dimensions")
+}
+
+/// Copies the pixels of `image`, row by row, without padding.
+fn copy_pixels(image: &Image) -> Vec<u8> {
+    let mut data = Vec::with_capacity(image.width() * image.height());
+
+    for y in 0..image.height() {
+        data.extend_from_slice(image.row(y));
+    }
+
+    data
+}
+
+/// Replaces the runs of blown-out pixels within the outline of `code` in
+/// `data`, an unpadded copy of `image`, as described at `suppress_glare()`.
+fn inpaint_glare(data: &mut [u8], image: &Image, code: &QrCode, threshold: u8) {
+    let width = image.width();
+
+    for_each_outline_span(image, code, |y, start, end| {
+        let row = &mut data[y * width..(y + 1) * width];
+        let mut x = start;
+
+        while x < end {
+            if row[x] < threshold {
+                x += 1;
+                continue;
+            }
+
+            let run_start = x;
+
+            while x < end && row[x] >= threshold {
+                x += 1;
+            }
+
+            inpaint_run(row, run_start, x);
+        }
+    });
+}
+
+/// Calls `f(y, start, end)` for each span of pixels within the outline
+/// of `code`, clamped to the bounds of `image`.
+fn for_each_outline_span<F>(image: &Image, code: &QrCode, mut f: F)
+    where F: FnMut(usize, usize, usize)
+{
+    if image.height() == 0 {
+        return;
+    }
+
+    let corners = code.corners();
+    let min_y = corners.iter().map(|c| c.y).min().unwrap_or(0);
+    let max_y = corners.iter().map(|c| c.y).max().unwrap_or(0);
+
+    for y in min_y..=max_y.min(image.height() - 1) {
+        for_each_polygon_span(&corners, y, |start, end| {
+            let end = end.min(image.width());
+
+            if start < end {
+                f(y, start, end);
+            }
+        });
+    }
+}
+
+/// Overwrites `row[start..end]` with values linearly interpolated between
+/// the pixels immediately before and after the range.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss, cast_possible_truncation, cast_sign_loss))]
+fn inpaint_run(row: &mut [u8], start: usize, end: usize) {
+    let before = if start > 0 { Some(row[start - 1]) } else { None };
+    let after = row.get(end).cloned();
+    let (left, right) = match (before, after) {
+        (Some(l), Some(r)) => (l, r),
+        (Some(l), None) => (l, l),
+        (None, Some(r)) => (r, r),
+        (None, None) => return, // the whole row is blown out; nothing to go by
+    };
+    let len = (end - start + 1) as f64;
+
+    for (i, px) in row[start..end].iter_mut().enumerate() {
+        let t = (i + 1) as f64 / len;
+        *px = (f64::from(left) * (1.0 - t) + f64::from(right) * t).round() as u8;
+    }
+}
+
+/// Returns the centroid of the outline of `code`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+fn centroid(code: &QrCode) -> (f64, f64) {
+    let corners = code.corners();
+    let x = corners.iter().map(|c| c.x as f64).sum::<f64>() / 4.0;
+    let y = corners.iter().map(|c| c.y as f64).sum::<f64>() / 4.0;
+    (x, y)
+}
+
+impl Decoder {
+    /// Detects and decodes the QR codes in `image`, retrying the codes that
+    /// fail to decode after suppressing glare over them, if enough of their
+    /// pixels are blown out according to `options`. Glare is suppressed over
+    /// all such codes at once, so the image is only scanned again once.
+    ///
+    /// Failures to extract a code, as well as decoding failures that persist
+    /// or that aren't attributable to glare, are reported in the
+    /// corresponding element of the returned vector, and so is a failure to
+    /// scan the image with glare suppressed. The path of successful results
+    /// is either `[Plain]` or `[Plain, GlareSuppressed]`.
+    pub fn decode_with_glare_retry(
+        &mut self,
+        image: &Image,
+        options: &GlareOptions,
+    ) -> Result<Vec<Result<Decoded>>> {
+        let results = self.decode_with_glare_retry_options(image, options, &DecodeOptions::new())?;
+
+        Ok(results.into_iter().map(|result| result.map_err(Error::from)).collect())
+    }
+
+    /// Like `decode_with_glare_retry()`, but doesn't scan the image again
+    /// once the time budget of `decode_options` is used up by the plain
+    /// pass, in which case the path of the codes which would have been
+    /// retried reports `Pass::GlareSuppressed` as skipped.
+    pub fn decode_with_glare_retry_options(
+        &mut self,
+        image: &Image,
+        options: &GlareOptions,
+        decode_options: &DecodeOptions,
+    ) -> Result<Vec<::std::result::Result<Decoded, Undecoded>>> {
+        let deadline = Deadline::start(decode_options);
+        let codes: Vec<_> = self.decode_image(image)?.collect();
+        let mut results = Vec::with_capacity(codes.len());
+        let mut retries = Vec::new();
+
+        for code in codes {
+            let code = match code {
+                Ok(code) => code,
+                Err(error) => {
+                    results.push(Err(Undecoded { error, path: DecodePath::new() }));
+                    continue;
+                }
+            };
+            let mut path = DecodePath::new();
+
+            path.push(Pass::Plain);
+
+            match code.decode() {
+                Ok(info) => results.push(Ok(Decoded { code, info, path })),
+                Err(error) => {
+                    if glare_fraction(image, &code, options.threshold) >= options.min_fraction {
+                        retries.push((results.len(), code));
+                    }
+
+                    results.push(Err(Undecoded { error, path }));
+                }
+            }
+        }
+
+        if retries.is_empty() {
+            return Ok(results);
+        }
+
+        if deadline.has_passed() {
+            for &(index, _) in &retries {
+                if let Err(ref mut undecoded) = results[index] {
+                    undecoded.path.skip(Pass::GlareSuppressed);
+                }
+            }
+
+            return Ok(results);
+        }
+
+        let mut data = copy_pixels(image);
+
+        for (_, code) in &retries {
+            inpaint_glare(&mut data, image, code, options.threshold);
+        }
+
+        let retry_image = ImageBuf::new(data, Vec2D { x: image.width(), y: image.height() })
+            .expect("copy of image has the same dimensions");
+        let retried: ::std::result::Result<Vec<QrCode>, Error> = self
+            .decode_image(&retry_image.as_image())
+            .map(|iter| iter.filter_map(|c| c.ok()).collect());
+
+        for (index, code) in retries {
+            let outline = code.corners();
+            let decoded = retried.as_ref().map(|retried| {
+                retried
+                    .iter()
+                    .filter(|c| polygon_contains(&outline, centroid(c)))
+                    .filter_map(|c| c.decode().ok().map(|info| (*c, info)))
+                    .next()
+            });
+
+            if let Err(ref mut undecoded) = results[index] {
+                undecoded.path.push(Pass::GlareSuppressed);
+
+                match decoded {
+                    Ok(Some((code, info))) => {
+                        let path = undecoded.path.clone();
+                        results[index] = Ok(Decoded { code, info, path });
+                    }
+                    Ok(None) => {}
+                    Err(&error) => undecoded.error = error,
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
@@ -1,55 +1,168 @@
 //! High-level representation of the information contained in a QR code.
 
-use std::str::{ self, Utf8Error };
+use std::str::{ self, FromStr, Utf8Error };
 use std::cmp::{ min, max };
 use std::hash::{ Hash, Hasher };
+use std::vec::Vec;
+use std::boxed::Box;
+use std::borrow::Cow;
+use std::string::String;
+use std::fmt;
+use std::convert::TryFrom;
+use std::os::raw::c_int;
 use quirc_sys::{ quirc_data, QUIRC_MAX_PAYLOAD };
+use sequence::StructuredAppend;
+use bitstream::{ self, Codewords, Payload, RawData };
+use spec;
+use error::{ self, DecodingErrorKind };
 use quirc_sys::QuircEccLevel::*;
 use quirc_sys::QuircDataType::*;
 
 /// High-level representation of the information contained in a QR code.
-#[derive(Debug, Clone, Copy)]
-pub struct Info(quirc_data);
+///
+/// Only the valid part of the payload is kept, on the heap, so that large
+/// batches of results take up little memory, unlike the `quirc_data` they
+/// are decoded into, which has room for the largest possible payload.
+#[derive(Debug, Clone)]
+pub struct Info {
+    /// The version number, as reported by `quirc`.
+    version: c_int,
+    /// The error correction level, as reported by `quirc`.
+    ecc_level: c_int,
+    /// The mask ID, as reported by `quirc`.
+    mask: c_int,
+    /// The highest-valued data type, as reported by `quirc`.
+    data_type: c_int,
+    /// The ECI assignment number, 0 if absent.
+    eci: u32,
+    /// The payload.
+    payload: Vec<u8>,
+    /// The structured append header, which `quirc` doesn't decode.
+    structured_append: Option<StructuredAppend>,
+    /// The data codewords, if they were read in Rust. They are only needed
+    /// by a few accessors, so they are boxed to keep the rest small.
+    raw_data: Option<Box<RawData>>,
+}
 
 impl Info {
     /// Attempts to extract high-level information from the raw FFI `quirc_data`.
     #[doc(hidden)]
     pub fn from_raw(raw: quirc_data) -> Self {
-        Info(raw)
+        // This cast is safe because even if it over- or underflows,
+        // the result is still bounded by `[0, QUIRC_MAX_PAYLOAD]`.
+        let len = max(0, min(raw.payload_len as _, QUIRC_MAX_PAYLOAD));
+
+        Info {
+            version: raw.version,
+            ecc_level: raw.ecc_level,
+            mask: raw.mask,
+            data_type: raw.data_type,
+            eci: raw.eci,
+            payload: raw.payload[..len].to_vec(),
+            structured_append: None,
+            raw_data: None,
+        }
+    }
+
+    /// Builds the information entirely in Rust, from the data codewords and
+    /// the payload decoded from them, without calling `quirc_decode()`.
+    pub(crate) fn from_codewords(codewords: &Codewords, payload: &Payload) -> error::Result<Self> {
+        let mut info = Info {
+            version: c_int::from(codewords.version),
+            ecc_level: codewords.ecc_level as c_int,
+            mask: c_int::from(codewords.mask),
+            data_type: DataType::Byte as c_int,
+            eci: 0,
+            payload: Vec::new(),
+            structured_append: None,
+            raw_data: None,
+        };
+
+        info.set_codewords(codewords);
+        info.set_payload(payload)?;
+
+        Ok(info)
+    }
+
+    /// Stores the data codewords read in Rust, for parsing the bitstream
+    /// on demand.
+    pub(crate) fn set_codewords(&mut self, codewords: &Codewords) {
+        self.raw_data = Some(Box::new(RawData::new(codewords)));
+    }
+
+    /// Sets the structured append header, which `quirc` doesn't decode.
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_structured_append(&mut self, header: Option<StructuredAppend>) {
+        self.structured_append = header;
+    }
+
+    /// Replaces the payload with the one decoded in Rust, for symbols
+    /// which `quirc` can't fully decode. Fails if the payload is too long.
+    pub(crate) fn set_payload(&mut self, payload: &Payload) -> error::Result<()> {
+        let len = payload.bytes.len();
+
+        if len > QUIRC_MAX_PAYLOAD {
+            return Err(error::Error::DecodingFailed(DecodingErrorKind::DataOverflow));
+        }
+
+        self.payload.clone_from(&payload.bytes);
+        self.eci = payload.eci.unwrap_or(self.eci);
+        self.data_type = payload.data_type.map_or(self.data_type, |t| t as _);
+        self.structured_append = payload.structured_append;
+
+        Ok(())
+    }
+
+    /// Returns the version of the code.
+    pub fn version(&self) -> Version {
+        // This cannot panic because `quirc` only decodes codes of valid
+        // versions, and deserialization and decoding in Rust validate it.
+        u8::try_from(self.version).ok()
+            .and_then(|number| Version::new(number).ok())
+            .expect("invalid version")
     }
 
     /// Returns the version number of the code, in the range `1...40`.
-    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_possible_wrap))]
-    pub fn version(&self) -> u8 {
-        max(1, min(self.0.version, 40)) as _
+    #[deprecated(note = "use `version().number()` instead")]
+    pub fn version_number(&self) -> u8 {
+        self.version().number()
     }
 
     /// Returns the mask ID of the code, in the range `0...7`.
     #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_possible_wrap))]
     pub fn mask_id(&self) -> u8 {
-        max(0, min(self.0.mask, 7)) as _
+        max(0, min(self.mask, 7)) as _
     }
 
-    /// Returns the ECI assignment number, in the range `0...30`.
-    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_possible_wrap))]
-    pub fn eci(&self) -> u8 {
-        max(0, min(self.0.eci, 30)) as _
+    /// Returns the last ECI assignment of the code, or `None` if it has
+    /// none. Assignment number 0 can't be told apart from the absence of
+    /// an ECI, so it is reported as `None`, too.
+    pub fn eci(&self) -> Option<Eci> {
+        match self.eci {
+            0 => None,
+            number => Some(Eci::from(number)),
+        }
+    }
+
+    /// Returns the ECI assignment number, 0 if absent.
+    pub fn raw_eci(&self) -> u32 {
+        self.eci
     }
 
     /// Returns the error correction level of the code.
     pub fn ecc_level(&self) -> EccLevel {
-        let ecc = self.0.ecc_level;
+        let ecc = self.ecc_level;
 
         // Casting an `int` with a value that isn't valid for a Rust `enum` is
         // Undefined Behavior, so we must perform the conversion in the opposite
         // direction. Therefore, we can't use `match`.
-        if ecc == QUIRC_ECC_LEVEL_L as _ {
+        if ecc == QUIRC_ECC_LEVEL_L as c_int {
             EccLevel::L
-        } else if ecc == QUIRC_ECC_LEVEL_M as _ {
+        } else if ecc == QUIRC_ECC_LEVEL_M as c_int {
             EccLevel::M
-        } else if ecc == QUIRC_ECC_LEVEL_Q as _ {
+        } else if ecc == QUIRC_ECC_LEVEL_Q as c_int {
             EccLevel::Q
-        } else if ecc == QUIRC_ECC_LEVEL_H as _ {
+        } else if ecc == QUIRC_ECC_LEVEL_H as c_int {
             EccLevel::H
         } else {
             EccLevel::L // assume only the lowest level of ECC for robustness
@@ -60,15 +173,15 @@ impl Info {
     pub fn data_type(&self) -> DataType {
         // For the rationale behind this implementation,
         // see the comment in `ecc_level()` above.
-        let dtype = self.0.data_type;
+        let dtype = self.data_type;
 
-        if dtype == QUIRC_DATA_TYPE_NUMERIC as _ {
+        if dtype == QUIRC_DATA_TYPE_NUMERIC as c_int {
             DataType::Numeric
-        } else if dtype == QUIRC_DATA_TYPE_ALPHA as _ {
+        } else if dtype == QUIRC_DATA_TYPE_ALPHA as c_int {
             DataType::Alphanumeric
-        } else if dtype == QUIRC_DATA_TYPE_BYTE as _ {
+        } else if dtype == QUIRC_DATA_TYPE_BYTE as c_int {
             DataType::Byte
-        } else if dtype == QUIRC_DATA_TYPE_KANJI as _ {
+        } else if dtype == QUIRC_DATA_TYPE_KANJI as c_int {
             DataType::Kanji
         } else {
             DataType::Byte // assume uninterpreted raw bytes if type is unknown
@@ -77,16 +190,117 @@ impl Info {
 
     /// Returns the raw payload of the QR code.
     pub fn payload(&self) -> &[u8] {
-        // This cast is safe because even if it over- or underflows,
-        // the result is still bounded by `[0, QUIRC_MAX_PAYLOAD]`.
-        let len = max(0, min(self.0.payload_len as _, QUIRC_MAX_PAYLOAD));
-        &self.0.payload[..len]
+        &self.payload
+    }
+
+    /// Returns the structured append header if the code is one of a
+    /// sequence of symbols encoding a single message. The payload is then
+    /// only a part of the message; see `sequence::StructuredAppendAssembler`.
+    pub fn structured_append(&self) -> Option<StructuredAppend> {
+        self.structured_append
+    }
+
+    /// Returns the number of codewords which had to be corrected by the
+    /// Reed-Solomon error correction, across all blocks. This is a measure
+    /// of the quality of the scan.
+    ///
+    /// Returns `None` unless the codewords were kept while decoding, e.g.
+    /// by `QrCode::decode_with_codewords()`.
+    pub fn corrected_errors(&self) -> Option<usize> {
+        self.raw_data.as_ref().map(|raw| raw.corrected())
+    }
+
+    /// Returns the total number of codewords, data and error correction,
+    /// in a code of this version.
+    pub fn total_codewords(&self) -> usize {
+        spec::total_codewords(self.version().number())
+    }
+
+    /// Returns the fraction of the data capacity of the code occupied by
+    /// its segments, up to the terminator, between 0 and 1. The rest is
+    /// padding, which a smaller version or a higher ECC level could have
+    /// saved.
+    ///
+    /// Returns `None` unless the codewords were kept while decoding, e.g.
+    /// by `QrCode::decode_with_codewords()`, or if the bitstream can't be
+    /// parsed.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn capacity_used_fraction(&self) -> Option<f64> {
+        let raw = self.raw_data.as_ref()?;
+        let payload = bitstream::decode_payload(raw.data(), raw.version()).ok()?;
+        let capacity = raw.data().len() * 8;
+
+        if capacity == 0 {
+            return None;
+        }
+
+        Some(payload.bits_used as f64 / capacity as f64)
+    }
+
+    /// Returns the data codewords after error correction, i.e. the
+    /// bitstream before it is split into segments, in order.
+    ///
+    /// Returns `None` unless the codewords were kept while decoding, e.g.
+    /// by `QrCode::decode_with_codewords()`.
+    pub fn data_codewords(&self) -> Option<&[u8]> {
+        self.raw_data.as_ref().map(|raw| raw.data())
+    }
+
+    /// Returns the data bitstream, demasked, deinterleaved and error
+    /// corrected, up to the terminator, along with its length in bits.
+    /// The bits are packed most significant first, and the unused bits of
+    /// the last byte are zero. Unlike `data_codewords()`, this excludes the
+    /// terminator and the padding, so it is exactly what the segments
+    /// (and any custom binary format layered over them) occupy.
+    ///
+    /// Returns `None` unless the codewords were kept while decoding, e.g.
+    /// by `QrCode::decode_with_codewords()`, or if the bitstream can't be
+    /// parsed.
+    pub fn raw_bits(&self) -> Option<(Vec<u8>, usize)> {
+        let raw = self.raw_data.as_ref()?;
+        let payload = bitstream::decode_payload(raw.data(), raw.version()).ok()?;
+        let len = payload.bits_used;
+        let mut bytes = raw.data()[..(len + 7) / 8].to_vec();
+
+        if len % 8 != 0 {
+            if let Some(last) = bytes.last_mut() {
+                *last &= 0xff << (8 - len % 8);
+            }
+        }
+
+        Some((bytes, len))
+    }
+
+    /// Returns the data segments of the code, in order. Unlike `data_type()`,
+    /// this reveals how the encoder mixed the modes.
+    ///
+    /// The bitstream is re-read from the codewords of the symbol, so this
+    /// is empty unless they were kept while decoding, e.g. by
+    /// `QrCode::decode_with_codewords()`.
+    pub fn segments(&self) -> Vec<Segment> {
+        self.raw_data
+            .as_ref()
+            .and_then(|raw| bitstream::decode_payload(raw.data(), raw.version()).ok())
+            .map_or_else(Vec::new, |payload| payload.segments)
+    }
+
+    /// Returns the payload, consuming the information, without copying it.
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
     }
 
     /// Returns the payload as UTF-8 text if possible.
     pub fn as_str(&self) -> Result<&str, Utf8Error> {
         str::from_utf8(self.payload())
     }
+
+    /// Returns the payload as UTF-8 text, borrowed if it is valid, and
+    /// with invalid sequences replaced by U+FFFD otherwise, e.g. for
+    /// displaying it. The ECI is ignored; with the `std` feature,
+    /// `to_string_lossy()` decodes other character sets, too.
+    pub fn text_lossy(&self) -> Cow<str> {
+        String::from_utf8_lossy(self.payload())
+    }
 }
 
 impl PartialEq<Info> for Info {
@@ -96,7 +310,8 @@ impl PartialEq<Info> for Info {
         self.eci() == other.eci() &&
         self.ecc_level() == other.ecc_level() &&
         self.data_type() == other.data_type() &&
-        self.payload() == other.payload()
+        self.payload() == other.payload() &&
+        self.structured_append() == other.structured_append()
     }
 }
 
@@ -108,6 +323,189 @@ impl Hash for Info {
         self.ecc_level().hash(state);
         self.data_type().hash(state);
         self.payload().hash(state);
+        self.structured_append().hash(state);
+    }
+}
+
+/// The version of a QR code, which determines its size, in the range
+/// `1...40`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(u8);
+
+impl Version {
+    /// The smallest version.
+    pub const MIN: Version = Version(spec::MIN_VERSION);
+
+    /// The largest version.
+    pub const MAX: Version = Version(spec::MAX_VERSION);
+
+    /// Creates a version from its number. Fails with `Error::InvalidArgument`
+    /// if it isn't in the range `1...40`.
+    pub fn new(number: u8) -> error::Result<Self> {
+        if (spec::MIN_VERSION..=spec::MAX_VERSION).contains(&number) {
+            Ok(Version(number))
+        } else {
+            Err(error::Error::InvalidArgument)
+        }
+    }
+
+    /// Returns the version number, in the range `1...40`.
+    pub fn number(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the number of modules along each side of a code of this
+    /// version, without the quiet zone.
+    pub fn size_in_modules(self) -> usize {
+        spec::size(self.0)
+    }
+
+    /// Returns the coordinates of the centers of the alignment patterns
+    /// along either axis. See `spec::alignment_positions()`.
+    pub fn alignment_positions(self) -> Vec<usize> {
+        spec::alignment_positions(self.0)
+    }
+
+    /// Returns the largest number of characters of the given data type
+    /// that fit in a code of this version, in a single segment. See
+    /// `spec::capacity()`.
+    pub fn max_payload(self, ecc_level: EccLevel, data_type: DataType) -> usize {
+        spec::capacity(self.0, ecc_level, data_type)
+    }
+}
+
+impl TryFrom<u8> for Version {
+    type Error = error::Error;
+
+    fn try_from(number: u8) -> error::Result<Self> {
+        Version::new(number)
+    }
+}
+
+impl From<Version> for u8 {
+    fn from(version: Version) -> Self {
+        version.0
+    }
+}
+
+/// Formats the version number.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// An Extended Channel Interpretation assignment, which designates the
+/// character set (or other interpretation) of the data following it.
+///
+/// Only the canonical numbers of the assignments have their own variants;
+/// aliases, e.g. 0 and 1 for code page 437 and ISO-8859-1, and all other
+/// numbers are `Other`, so that `Eci::from(n).number() == n` for any `n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Eci {
+    /// Code page 437 (2).
+    Cp437,
+    /// The given part of ISO-8859, e.g. 1 for Latin-1 (3...18, except 14,
+    /// since there is no part 12).
+    Iso8859(u8),
+    /// Shift JIS (20).
+    ShiftJis,
+    /// Windows-1250, Central European (21).
+    Windows1250,
+    /// Windows-1251, Cyrillic (22).
+    Windows1251,
+    /// Windows-1252, Western European (23).
+    Windows1252,
+    /// Windows-1256, Arabic (24).
+    Windows1256,
+    /// UTF-16, big endian (25).
+    Utf16Be,
+    /// UTF-8 (26).
+    Utf8,
+    /// US-ASCII (27).
+    Ascii,
+    /// Big5 (28).
+    Big5,
+    /// GB 2312 (29).
+    Gb2312,
+    /// EUC-KR (30).
+    EucKr,
+    /// GBK (31).
+    Gbk,
+    /// GB 18030 (32).
+    Gb18030,
+    /// UTF-16, little endian (33).
+    Utf16Le,
+    /// UTF-32, big endian (34).
+    Utf32Be,
+    /// UTF-32, little endian (35).
+    Utf32Le,
+    /// Binary data, without a character set (899).
+    Binary,
+    /// Any other assignment number.
+    Other(u32),
+}
+
+impl Eci {
+    /// Returns the assignment number.
+    pub fn number(self) -> u32 {
+        match self {
+            Eci::Cp437 => 2,
+            Eci::Iso8859(part) => u32::from(part) + 2,
+            Eci::ShiftJis => 20,
+            Eci::Windows1250 => 21,
+            Eci::Windows1251 => 22,
+            Eci::Windows1252 => 23,
+            Eci::Windows1256 => 24,
+            Eci::Utf16Be => 25,
+            Eci::Utf8 => 26,
+            Eci::Ascii => 27,
+            Eci::Big5 => 28,
+            Eci::Gb2312 => 29,
+            Eci::EucKr => 30,
+            Eci::Gbk => 31,
+            Eci::Gb18030 => 32,
+            Eci::Utf16Le => 33,
+            Eci::Utf32Be => 34,
+            Eci::Utf32Le => 35,
+            Eci::Binary => 899,
+            Eci::Other(number) => number,
+        }
+    }
+}
+
+impl From<u32> for Eci {
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn from(number: u32) -> Self {
+        match number {
+            2 => Eci::Cp437,
+            3..=13 | 15..=18 => Eci::Iso8859((number - 2) as u8),
+            20 => Eci::ShiftJis,
+            21 => Eci::Windows1250,
+            22 => Eci::Windows1251,
+            23 => Eci::Windows1252,
+            24 => Eci::Windows1256,
+            25 => Eci::Utf16Be,
+            26 => Eci::Utf8,
+            27 => Eci::Ascii,
+            28 => Eci::Big5,
+            29 => Eci::Gb2312,
+            30 => Eci::EucKr,
+            31 => Eci::Gbk,
+            32 => Eci::Gb18030,
+            33 => Eci::Utf16Le,
+            34 => Eci::Utf32Be,
+            35 => Eci::Utf32Le,
+            899 => Eci::Binary,
+            _ => Eci::Other(number),
+        }
+    }
+}
+
+impl From<Eci> for u32 {
+    fn from(eci: Eci) -> Self {
+        eci.number()
     }
 }
 
@@ -118,6 +516,7 @@ impl Hash for Info {
 /// This is because `derive` takes into account the order in which the
 /// variants have been declared, and not their raw integer value.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum EccLevel {
     /// Low error correction: ~7% loss recoverable.
     L = QUIRC_ECC_LEVEL_L as _,
@@ -131,6 +530,7 @@ pub enum EccLevel {
 
 /// The highest-valued (most complex) data type found in the QR code.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DataType {
     /// Numeric (digits only).
     Numeric      = QUIRC_DATA_TYPE_NUMERIC as _,
@@ -141,3 +541,124 @@ pub enum DataType {
     /// Kanji characters.
     Kanji        = QUIRC_DATA_TYPE_KANJI   as _,
 }
+
+impl EccLevel {
+    /// Returns the letter of the level: `"L"`, `"M"`, `"Q"` or `"H"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EccLevel::L => "L",
+            EccLevel::M => "M",
+            EccLevel::Q => "Q",
+            EccLevel::H => "H",
+        }
+    }
+}
+
+impl fmt::Display for EccLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses the letter of the level, case-insensitively.
+impl FromStr for EccLevel {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        [EccLevel::L, EccLevel::M, EccLevel::Q, EccLevel::H]
+            .iter()
+            .cloned()
+            .find(|level| level.as_str().eq_ignore_ascii_case(s))
+            .ok_or(error::Error::InvalidArgument)
+    }
+}
+
+/// Converts the numeric value of the level, i.e. that of `level as u8`,
+/// which is the one used by `quirc` (and not the one in the format
+/// information).
+impl TryFrom<u8> for EccLevel {
+    type Error = error::Error;
+
+    fn try_from(value: u8) -> error::Result<Self> {
+        [EccLevel::L, EccLevel::M, EccLevel::Q, EccLevel::H]
+            .iter()
+            .cloned()
+            .find(|&level| level as u8 == value)
+            .ok_or(error::Error::InvalidArgument)
+    }
+}
+
+impl DataType {
+    /// Returns the name of the data type: `"numeric"`, `"alphanumeric"`,
+    /// `"byte"` or `"kanji"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataType::Numeric      => "numeric",
+            DataType::Alphanumeric => "alphanumeric",
+            DataType::Byte         => "byte",
+            DataType::Kanji        => "kanji",
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses the name of the data type, case-insensitively.
+impl FromStr for DataType {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        [DataType::Numeric, DataType::Alphanumeric, DataType::Byte, DataType::Kanji]
+            .iter()
+            .cloned()
+            .find(|data_type| data_type.as_str().eq_ignore_ascii_case(s))
+            .ok_or(error::Error::InvalidArgument)
+    }
+}
+
+/// Converts the numeric value of the data type, i.e. that of
+/// `data_type as u8`, which is its mode indicator.
+impl TryFrom<u8> for DataType {
+    type Error = error::Error;
+
+    fn try_from(value: u8) -> error::Result<Self> {
+        [DataType::Numeric, DataType::Alphanumeric, DataType::Byte, DataType::Kanji]
+            .iter()
+            .cloned()
+            .find(|&data_type| data_type as u8 == value)
+            .ok_or(error::Error::InvalidArgument)
+    }
+}
+
+/// The format information of a code, which is protected by its own
+/// error correction code, and is stored twice, independently of the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatInfo {
+    /// The error correction level of the data.
+    pub ecc_level: EccLevel,
+    /// The ID of the data mask, in the range `0...7`.
+    pub mask_id: u8,
+    /// The number of bits which differ from the valid format information,
+    /// in the copy with fewer errors. At most 3 errors can be corrected.
+    pub bit_errors: u32,
+}
+
+/// A segment of the data bitstream, encoded in a single mode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Segment {
+    /// The mode in which the segment is encoded.
+    pub data_type: DataType,
+    /// The number of characters, as given by the header of the segment.
+    /// Kanji characters are 2 bytes long, all others are 1 byte long.
+    pub char_count: usize,
+    /// The ECI assignment number in effect for the segment, if any.
+    pub eci: Option<u32>,
+    /// The decoded contents of the segment: ASCII for numeric and
+    /// alphanumeric segments, raw bytes for byte segments, and
+    /// Shift-JIS for Kanji segments.
+    pub bytes: Vec<u8>,
+}
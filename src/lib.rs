@@ -1,6 +1,12 @@
 //! Wrapper around the `quirc` QR code decoder library.
+//!
+//! Without the default `std` feature, the crate is `#![no_std]`, and only
+//! its core is available: `decoder`, `backend`, `fuzz`, `geom`, `modules`,
+//! `transform`, `info`, `spec`, `error`, `mask`, `encoder` and `sequence`.
+//! It then requires `alloc`, and the `libm` feature for floating-point math.
 
 #![crate_name="quirs"]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![doc(html_root_url = "https://docs.rs/quirs/0.1.1")]
 #![deny(missing_debug_implementations, missing_copy_implementations,
         trivial_casts, trivial_numeric_casts,
@@ -24,17 +30,172 @@
                  mutex_integer, mut_mut, items_after_statements,
                  print_stdout, mem_forget, maybe_infinite_iter))]
 
-extern crate libc;
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "libm")]
+extern crate libm;
+#[cfg(feature = "encoding_rs")]
+extern crate encoding_rs;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "v4l2")]
+extern crate v4l;
+#[cfg(feature = "gstreamer")]
+extern crate gst;
+#[cfg(feature = "gstreamer")]
+extern crate gst_app;
+#[cfg(feature = "gstreamer")]
+extern crate gst_video;
+// The code generated by the `pyo3` and `uniffi` macros refers to `::core`,
+// which only exists in the 2015 edition if it's declared.
+#[cfg(any(feature = "python", feature = "mobile"))]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "python")]
+extern crate numpy;
+#[cfg(feature = "mobile")]
+extern crate uniffi;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "opencv")]
+extern crate opencv;
+#[cfg(feature = "png")]
+extern crate png;
+#[cfg(feature = "jpeg-decoder")]
+extern crate jpeg_decoder;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "rqrr")]
+extern crate rqrr;
+#[cfg(feature = "bitvec")]
+extern crate bitvec;
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("without the `std` feature, the `libm` feature is required");
+
+/// The parts of the standard library which the core of the crate uses,
+/// assembled from `core` and `alloc`, so that `use std::...` works the same
+/// with and without `std`.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::*;
+    pub use alloc::vec;
+    pub use alloc::boxed;
+    pub use alloc::borrow;
+    pub use alloc::string;
+
+    /// Platform-specific definitions.
+    pub mod os {
+        /// The C types of the `quirc` API.
+        pub mod raw {
+            /// The C `int` type, which is 32 bits wide on every target
+            /// `quirc` supports.
+            #[allow(non_camel_case_types)]
+            pub type c_int = i32;
+        }
+    }
+}
 
 mod quirc_sys;
+#[cfg(feature = "zbar")]
+mod zbar_sys;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm_libc;
+#[cfg(not(any(feature = "std", test)))]
+mod float;
 mod util;
+#[cfg(feature = "std")]
+mod simd;
+#[cfg(feature = "std")]
+mod pnm;
+#[cfg(feature = "std")]
+mod load;
+mod gf;
+mod bitstream;
+#[cfg(feature = "serde")]
+mod serde_impls;
 
 pub mod decoder;
+pub mod backend;
+pub mod fuzz;
+#[cfg(feature = "zbar")]
+pub mod zbar;
 pub mod info;
+pub mod spec;
 pub mod geom;
+pub mod modules;
+pub mod transform;
 pub mod error;
+pub mod mask;
+#[cfg(feature = "std")]
+pub mod glare;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod retry;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "std")]
+pub mod capabilities;
+#[cfg(feature = "std")]
+pub mod content;
+#[cfg(feature = "std")]
+pub mod soak;
+#[cfg(feature = "std")]
+pub mod metrics;
+pub mod encoder;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod annotate;
+pub mod sequence;
+#[cfg(feature = "std")]
+pub mod text;
+#[cfg(feature = "std")]
+pub mod perspective;
+#[cfg(feature = "std")]
+pub mod resample;
+#[cfg(feature = "std")]
+pub mod confidence;
+#[cfg(feature = "test-util")]
+pub mod testutil;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod asynch;
+#[cfg(feature = "std")]
+pub mod video;
+#[cfg(feature = "std")]
+pub mod dedup;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "mobile")]
+pub mod mobile;
+#[cfg(feature = "ndarray")]
+pub mod array;
+#[cfg(feature = "opencv")]
+pub mod cv;
 
-pub use decoder::Decoder;
+pub use decoder::{ Decoder, DecoderConfig, FrameBuffer };
+pub use encoder::{ Encoder, EncodedQr };
 pub use error::Error;
-pub use geom::{ Image, Vec2D, QrCode };
+pub use geom::{ Image, ImageBuf, Vec2D, Vec2Df, QrCode };
 pub use info::Info;
+pub use mask::ExclusionZone;
+
+// The UniFFI scaffolding has to be set up in the crate root.
+#[cfg(feature = "mobile")]
+uniffi::setup_scaffolding!("quirs");
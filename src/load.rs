@@ -0,0 +1,134 @@
+//! Loading images from files, whatever their format.
+//!
+//! The format is recognized by the signature at the beginning of the file.
+//! Netpbm images are always supported (see the `pnm` module), while PNG and
+//! JPEG images require the `png` and `jpeg-decoder` features, respectively.
+//! Color images are converted to grayscale, and transparent pixels are
+//! composited onto white, since the light modules of codes rendered with a
+//! transparent background are usually transparent black.
+
+use std::fs;
+use std::path::Path;
+use geom::ImageBuf;
+#[cfg(any(feature = "png", feature = "jpeg-decoder"))]
+use geom::Vec2D;
+#[cfg(feature = "png")]
+use simd::luma;
+#[cfg(feature = "jpeg-decoder")]
+use simd::rgb_to_luma;
+#[cfg(feature = "png")]
+use util::u32_to_usize;
+use error::{ Error, Result };
+
+/// The signature of PNG files.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The start of image marker, which JPEG files begin with.
+const JPEG_SIGNATURE: &[u8] = b"\xff\xd8\xff";
+
+impl ImageBuf {
+    /// Parses the contents of an image file in any supported format.
+    /// Fails with `Error::InvalidImage` if the format isn't recognized, or
+    /// if support for it isn't compiled in.
+    pub fn from_file_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(PNG_SIGNATURE) {
+            Self::from_png_bytes(bytes)
+        } else if bytes.starts_with(JPEG_SIGNATURE) {
+            Self::from_jpeg_bytes(bytes)
+        } else {
+            Self::from_pgm_bytes(bytes)
+        }
+    }
+
+    /// Reads and parses an image file. See `from_file_bytes()` for details.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::from_file_bytes(&bytes)
+    }
+
+    /// Parses the contents of a PNG file of any color type and bit depth.
+    #[cfg(feature = "png")]
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self> {
+        use png::{ ColorType, Decoder, Transformations };
+
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+        let mut reader = decoder.read_info().map_err(|_| Error::InvalidImage)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf).map_err(|_| Error::InvalidImage)?;
+        let size = Vec2D {
+            x: u32_to_usize(frame.width)?,
+            y: u32_to_usize(frame.height)?,
+        };
+        let channels = match frame.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+            ColorType::Indexed => return Err(Error::InvalidImage),
+        };
+        let data = buf[..frame.buffer_size()]
+            .chunks(frame.line_size)
+            .flat_map(|row| row[..size.x * channels].chunks(channels))
+            .map(|pixel| match *pixel {
+                [gray] => gray,
+                [gray, alpha] => over_white(gray, alpha),
+                [r, g, b] => luma(r, g, b),
+                [r, g, b, alpha] => over_white(luma(r, g, b), alpha),
+                _ => 0xff,
+            })
+            .collect();
+
+        ImageBuf::new(data, size)
+    }
+
+    /// Fails with `Error::InvalidImage`, because PNG support isn't compiled in.
+    #[cfg(not(feature = "png"))]
+    pub fn from_png_bytes(_bytes: &[u8]) -> Result<Self> {
+        Err(Error::InvalidImage)
+    }
+
+    /// Parses the contents of a baseline or progressive JPEG file in
+    /// grayscale or RGB.
+    #[cfg(feature = "jpeg-decoder")]
+    pub fn from_jpeg_bytes(bytes: &[u8]) -> Result<Self> {
+        use jpeg_decoder::{ Decoder, PixelFormat };
+
+        let mut decoder = Decoder::new(bytes);
+        let pixels = decoder.decode().map_err(|_| Error::InvalidImage)?;
+        let info = decoder.info().ok_or(Error::InvalidImage)?;
+        let size = Vec2D {
+            x: usize::from(info.width),
+            y: usize::from(info.height),
+        };
+        let data = match info.pixel_format {
+            PixelFormat::L8 => pixels,
+            // The samples are big endian, so the first byte is the significant one.
+            PixelFormat::L16 => pixels.chunks(2).map(|sample| sample[0]).collect(),
+            PixelFormat::RGB24 => {
+                let mut luma = vec![0; pixels.len() / 3];
+                rgb_to_luma(&pixels[..luma.len() * 3], &mut luma);
+                luma
+            }
+            PixelFormat::CMYK32 => return Err(Error::InvalidImage),
+        };
+
+        ImageBuf::new(data, size)
+    }
+
+    /// Fails with `Error::InvalidImage`, because JPEG support isn't compiled in.
+    #[cfg(not(feature = "jpeg-decoder"))]
+    pub fn from_jpeg_bytes(_bytes: &[u8]) -> Result<Self> {
+        Err(Error::InvalidImage)
+    }
+}
+
+/// Composites a gray pixel with the given opacity onto a white background.
+#[cfg(feature = "png")]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn over_white(gray: u8, alpha: u8) -> u8 {
+    let (value, opacity) = (u32::from(gray), u32::from(alpha));
+
+    ((value * opacity + 0xff * (0xff - opacity) + 0x7f) / 0xff) as u8
+}
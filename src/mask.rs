@@ -0,0 +1,129 @@
+//! Exclusion zones: parts of the image that are hidden from the detector.
+
+use std::cmp::Ordering;
+use std::vec::Vec;
+#[cfg(not(any(feature = "std", test)))]
+use float::F64Ext;
+use geom::Vec2D;
+
+/// The value written over excluded pixels. White is used because `quirc`
+/// looks for dark finder patterns on a light background, so blanked-out
+/// areas can never give rise to (or be mistaken for) part of a code.
+const BLANK: u8 = 0xff;
+
+/// A region of the image which is excluded from QR code detection,
+/// e.g. the reflection of a kiosk's own display in the camera image.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExclusionZone {
+    /// An axis-aligned rectangle.
+    Rect {
+        /// The coordinates of the top left corner of the rectangle.
+        origin: Vec2D,
+        /// The width and the height of the rectangle.
+        size: Vec2D,
+    },
+    /// A simple polygon, given by its vertices in either winding order.
+    /// The polygon is implicitly closed, i.e. its last vertex is connected
+    /// to the first one. Pixels whose center lies inside are excluded.
+    Polygon(Vec<Vec2D>),
+}
+
+impl ExclusionZone {
+    /// Blanks out the pixels of row `y` covered by this zone.
+    fn blank_row(&self, y: usize, row: &mut [u8]) {
+        match *self {
+            ExclusionZone::Rect { origin, size } => {
+                if y >= origin.y && y - origin.y < size.y {
+                    fill_span(row, origin.x, origin.x.saturating_add(size.x));
+                }
+            }
+            ExclusionZone::Polygon(ref vertices) => {
+                blank_polygon_row(vertices, y, row);
+            }
+        }
+    }
+}
+
+/// Blanks out the pixels of row `y` covered by any of the exclusion `zones`.
+pub(crate) fn blank_row(zones: &[ExclusionZone], y: usize, row: &mut [u8]) {
+    for zone in zones {
+        zone.blank_row(y, row);
+    }
+}
+
+/// Fills the pixels in the half-open range `[start, end)` of `row`,
+/// clamping the range to the bounds of the row.
+fn fill_span(row: &mut [u8], start: usize, end: usize) {
+    let end = end.min(row.len());
+
+    if start < end {
+        for px in &mut row[start..end] {
+            *px = BLANK;
+        }
+    }
+}
+
+/// Blanks out the pixels of row `y` inside a polygon.
+fn blank_polygon_row(vertices: &[Vec2D], y: usize, row: &mut [u8]) {
+    for_each_polygon_span(vertices, y, |start, end| fill_span(row, start, end));
+}
+
+/// Scan-converts one row of a polygon using the even-odd rule, calling `f`
+/// with the half-open range `[start, end)` of each horizontal span of pixels
+/// whose centers lie inside. The spans are not clamped to any image width.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss, cast_possible_truncation, cast_sign_loss))]
+pub(crate) fn for_each_polygon_span<F>(vertices: &[Vec2D], y: usize, mut f: F)
+    where F: FnMut(usize, usize)
+{
+    if vertices.len() < 3 {
+        return;
+    }
+
+    // Sample at the center of the pixels in this row.
+    let scan_y = y as f64 + 0.5;
+    let mut crossings = Vec::new();
+
+    for (i, a) in vertices.iter().enumerate() {
+        let b = &vertices[(i + 1) % vertices.len()];
+        let (ax, ay) = (a.x as f64, a.y as f64);
+        let (bx, by) = (b.x as f64, b.y as f64);
+
+        // Half-open test so that vertices on the scan line are counted once.
+        if (ay <= scan_y) != (by <= scan_y) {
+            crossings.push(ax + (scan_y - ay) / (by - ay) * (bx - ax));
+        }
+    }
+
+    crossings.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal));
+
+    for pair in crossings.chunks(2) {
+        if let [x0, x1] = *pair {
+            // Pixel `x` is inside iff its center `x + 0.5` is in `[x0, x1)`.
+            let start = (x0 - 0.5).ceil().max(0.0) as usize;
+            let end = (x1 - 0.5).ceil().max(0.0) as usize;
+
+            if start < end {
+                f(start, end);
+            }
+        }
+    }
+}
+
+/// Tests whether `point` lies inside a polygon, using the even-odd rule.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+pub(crate) fn polygon_contains(vertices: &[Vec2D], point: (f64, f64)) -> bool {
+    let (px, py) = point;
+    let mut inside = false;
+
+    for (i, a) in vertices.iter().enumerate() {
+        let b = &vertices[(i + 1) % vertices.len()];
+        let (ax, ay) = (a.x as f64, a.y as f64);
+        let (bx, by) = (b.x as f64, b.y as f64);
+
+        if (ay <= py) != (by <= py) && px < ax + (py - ay) / (by - ay) * (bx - ax) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
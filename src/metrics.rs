@@ -0,0 +1,288 @@
+//! Per-frame statistics of a decoder, for monitoring.
+//!
+//! A `Metrics` implementation attached to a `Decoder` with
+//! `Decoder::set_metrics()` is passed a `FrameMetrics` for every image:
+//! how long detection and decoding took, how many codes were detected and
+//! decoded, and the errors that occurred. The statistics of an image are
+//! complete once the iterator returned by `Decoder::decode_image()` (or
+//! `FrameBuffer::end()`) is dropped, and they are reported then.
+//!
+//! Only codes decoded through `Iter::decoded()` are timed and counted as
+//! decoded; codes which are extracted and then decoded separately, e.g.
+//! with `QrCode::decode()`, are only counted as detected.
+//!
+//! Closures taking a `&FrameMetrics` implement `Metrics`, so statistics
+//! can be forwarded to any monitoring system. With the `prometheus`
+//! feature, `PrometheusRecorder` aggregates them into counters and
+//! histograms, and renders them in the Prometheus text exposition format.
+
+use std::fmt;
+use std::time::Duration;
+#[cfg(feature = "prometheus")]
+use std::fmt::Write;
+#[cfg(feature = "prometheus")]
+use std::collections::BTreeMap;
+#[cfg(feature = "prometheus")]
+use std::sync::{ Arc, Mutex, PoisonError };
+use geom::Vec2D;
+use error::{ Error, DecodingErrorKind };
+
+/// The upper bounds of the buckets of the latency histograms, in seconds.
+#[cfg(feature = "prometheus")]
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// The statistics of a single image.
+#[derive(Debug, Clone, Default)]
+pub struct FrameMetrics {
+    /// The width and height of the image.
+    pub image_size: Vec2D,
+    /// The time spent detecting the codes, i.e. in `quirc_end()`.
+    pub detect_time: Duration,
+    /// The total time spent extracting and decoding the codes.
+    pub decode_time: Duration,
+    /// The number of codes detected.
+    pub codes_detected: usize,
+    /// The number of codes decoded successfully.
+    pub codes_decoded: usize,
+    /// The errors which prevented codes from being extracted or decoded.
+    pub errors: Vec<Error>,
+}
+
+/// Receives the statistics of every image processed by a `Decoder`.
+pub trait Metrics: Send {
+    /// Called once the processing of an image is complete.
+    fn record_frame(&mut self, frame: &FrameMetrics);
+}
+
+impl<F> Metrics for F where F: FnMut(&FrameMetrics) + Send {
+    fn record_frame(&mut self, frame: &FrameMetrics) {
+        self(frame)
+    }
+}
+
+/// A `Metrics` implementation attached to a `Decoder`, along with the
+/// statistics of the image being processed.
+pub(crate) struct Recorder {
+    /// Receives the statistics.
+    metrics: Box<dyn Metrics>,
+    /// The statistics of the current image, until they are complete.
+    frame: Option<FrameMetrics>,
+}
+
+impl Recorder {
+    /// Wraps `metrics`.
+    pub(crate) fn new(metrics: Box<dyn Metrics>) -> Self {
+        Recorder { metrics, frame: None }
+    }
+
+    /// Unwraps the `Metrics` implementation, dropping the statistics of
+    /// the current image, if any.
+    pub(crate) fn into_inner(self) -> Box<dyn Metrics> {
+        self.metrics
+    }
+
+    /// Starts collecting the statistics of an image in which codes have
+    /// been detected.
+    pub(crate) fn start_frame(&mut self, image_size: Vec2D, detect_time: Duration, codes_detected: usize) {
+        self.finish_frame();
+        self.frame = Some(FrameMetrics {
+            image_size,
+            detect_time,
+            codes_detected,
+            ..FrameMetrics::default()
+        });
+    }
+
+    /// Records the time it took to extract and decode a code, and the
+    /// error that occurred, if any.
+    pub(crate) fn record_code(&mut self, time: Duration, error: Option<Error>) {
+        if let Some(ref mut frame) = self.frame {
+            frame.decode_time += time;
+
+            match error {
+                Some(error) => frame.errors.push(error),
+                None => frame.codes_decoded += 1,
+            }
+        }
+    }
+
+    /// Reports the statistics of the current image, if any.
+    pub(crate) fn finish_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.metrics.record_frame(&frame);
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
+
+/// Returns a short, stable, `snake_case` name of the kind of an error,
+/// suitable as a metric label.
+pub fn error_kind(error: &Error) -> &'static str {
+    match *error {
+        Error::AllocFailed => "alloc_failed",
+        Error::SizeMismatch { .. } => "size_mismatch",
+        Error::IntOverflow(_) => "int_overflow",
+        Error::DecodingFailed(kind) => match kind {
+            DecodingErrorKind::Unknown => "decoding_failed",
+            DecodingErrorKind::InvalidGridSize => "invalid_grid_size",
+            DecodingErrorKind::InvalidVersion => "invalid_version",
+            DecodingErrorKind::FormatEcc => "format_ecc",
+            DecodingErrorKind::DataEcc => "data_ecc",
+            DecodingErrorKind::UnknownDataType => "unknown_data_type",
+            DecodingErrorKind::DataOverflow => "data_overflow",
+            DecodingErrorKind::DataUnderflow => "data_underflow",
+        },
+        Error::InvalidImage => "invalid_image",
+        Error::Io(_) => "io",
+        Error::UnsupportedSchemaVersion(_) => "unsupported_schema_version",
+        Error::DataTooLong => "data_too_long",
+        Error::InvalidArgument => "invalid_argument",
+        Error::ImageTooLarge { .. } => "image_too_large",
+        Error::Utf8(_) => "utf8",
+        Error::Unsupported => "unsupported",
+    }
+}
+
+/// A histogram of latencies with the buckets of `LATENCY_BUCKETS`.
+#[cfg(feature = "prometheus")]
+#[derive(Debug, Clone, Copy, Default)]
+struct Histogram {
+    /// The number of samples in each bucket, not cumulative.
+    buckets: [u64; 10],
+    /// The number of samples, including ones beyond the last bucket.
+    count: u64,
+    /// The sum of the samples, in seconds.
+    sum: f64,
+}
+
+#[cfg(feature = "prometheus")]
+impl Histogram {
+    /// Adds a sample.
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Appends the histogram in the text exposition format to `out`.
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+
+        let mut cumulative = 0;
+
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+/// The values aggregated by a `PrometheusRecorder`.
+#[cfg(feature = "prometheus")]
+#[derive(Debug, Clone, Default)]
+struct Aggregate {
+    /// The number of frames processed.
+    frames: u64,
+    /// The number of frames in which at least one code was decoded.
+    frames_with_codes: u64,
+    /// The number of codes detected.
+    codes_detected: u64,
+    /// The number of codes decoded.
+    codes_decoded: u64,
+    /// The number of errors, by kind.
+    errors: BTreeMap<&'static str, u64>,
+    /// The distribution of the detection time.
+    detect_time: Histogram,
+    /// The distribution of the decoding time.
+    decode_time: Histogram,
+}
+
+/// Aggregates the statistics of frames into Prometheus metrics.
+///
+/// Clones share the same metrics, so one clone can be attached to a
+/// decoder (or to several), while another one renders the metrics, e.g.
+/// in the handler of a `/metrics` endpoint.
+#[cfg(feature = "prometheus")]
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusRecorder {
+    /// The metrics, shared between clones.
+    aggregate: Arc<Mutex<Aggregate>>,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusRecorder {
+    /// Creates a recorder with all metrics at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    /// (version 0.0.4). Every metric name is prefixed with `quirs_`.
+    pub fn render(&self) -> String {
+        let aggregate = self.aggregate.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let mut out = String::new();
+        let counters = [
+            ("quirs_frames_total", "Images processed.", aggregate.frames),
+            ("quirs_frames_with_codes_total", "Images in which at least one code was decoded.", aggregate.frames_with_codes),
+            ("quirs_codes_detected_total", "Codes detected.", aggregate.codes_detected),
+            ("quirs_codes_decoded_total", "Codes decoded successfully.", aggregate.codes_decoded),
+        ];
+
+        for &(name, help, value) in &counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        out.push_str("# HELP quirs_errors_total Codes which couldn't be extracted or decoded, by kind of error.\n");
+        out.push_str("# TYPE quirs_errors_total counter\n");
+
+        for (kind, count) in &aggregate.errors {
+            let _ = writeln!(out, "quirs_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        aggregate.detect_time.render(&mut out, "quirs_detect_seconds", "Time spent detecting codes in an image.");
+        aggregate.decode_time.render(&mut out, "quirs_decode_seconds", "Time spent decoding the codes of an image.");
+
+        out
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Metrics for PrometheusRecorder {
+    fn record_frame(&mut self, frame: &FrameMetrics) {
+        let mut aggregate = self.aggregate.lock().unwrap_or_else(PoisonError::into_inner);
+
+        aggregate.frames += 1;
+        aggregate.codes_detected += frame.codes_detected as u64;
+        aggregate.codes_decoded += frame.codes_decoded as u64;
+
+        if frame.codes_decoded > 0 {
+            aggregate.frames_with_codes += 1;
+        }
+
+        for error in &frame.errors {
+            *aggregate.errors.entry(error_kind(error)).or_insert(0) += 1;
+        }
+
+        aggregate.detect_time.observe(frame.detect_time);
+        aggregate.decode_time.observe(frame.decode_time);
+    }
+}
@@ -0,0 +1,321 @@
+//! Bindings for Android and iOS apps, generated by UniFFI.
+//!
+//! The interface is defined by the annotated items of this module: a
+//! `CodeScanner` object decoding grayscale camera frames, and the
+//! `ScannedCode` records it returns, whose payloads are classified by the
+//! `content` module. The Kotlin and Swift bindings are generated from the
+//! metadata embedded in the library:
+//!
+//! ```shell
+//! cargo rustc --release --features mobile --crate-type cdylib
+//! uniffi-bindgen generate --library target/release/libquirs.so \
+//!     --language kotlin --out-dir bindings
+//! ```
+//!
+//! Every type is owned and copied across the boundary, and errors are
+//! reported as `ScanError` exceptions rather than status codes.
+
+use std::fmt;
+use std::error;
+use std::sync::{ Arc, Mutex, PoisonError };
+use decoder::Decoder;
+use geom::{ Image, Vec2D };
+use info::Info;
+use content::{ self, Content, VCard };
+use util::{ u32_to_usize, u64_to_usize, usize_to_u64 };
+use error::Error;
+
+/// An error reported to the app.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, uniffi::Error)]
+pub enum ScanError {
+    /// The image buffer doesn't match its dimensions, or it is too large.
+    InvalidImage {
+        /// Details of the problem.
+        message: String,
+    },
+    /// Memory could not be allocated.
+    OutOfMemory,
+    /// An internal error occurred.
+    Internal {
+        /// Details of the problem.
+        message: String,
+    },
+}
+
+impl From<Error> for ScanError {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+
+        match error {
+            Error::AllocFailed => ScanError::OutOfMemory,
+            Error::SizeMismatch { .. }
+            | Error::IntOverflow(_)
+            | Error::InvalidImage
+            | Error::ImageTooLarge { .. } => ScanError::InvalidImage { message },
+            _ => ScanError::Internal { message },
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScanError::InvalidImage { ref message } => write!(f, "invalid image: {}", message),
+            ScanError::OutOfMemory => f.write_str("memory allocation failed"),
+            ScanError::Internal { ref message } => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl error::Error for ScanError {}
+
+/// A point in an image, in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, uniffi::Record)]
+pub struct Point {
+    /// The column of the point.
+    pub x: u64,
+    /// The row of the point.
+    pub y: u64,
+}
+
+impl From<Vec2D> for Point {
+    fn from(point: Vec2D) -> Self {
+        Point { x: usize_to_u64(point.x), y: usize_to_u64(point.y) }
+    }
+}
+
+/// The structure of a payload, as recognized by `content::classify()`.
+/// Formats without a variant of their own are reported as `Other`.
+#[derive(Debug, Clone, PartialEq, uniffi::Enum)]
+pub enum ScannedContent {
+    /// An `http` or `https` URL.
+    Url {
+        /// The URL, verbatim.
+        url: String,
+    },
+    /// Credentials for joining a wireless network.
+    Wifi {
+        /// The name of the network.
+        ssid: String,
+        /// The password of the network, unless it is open.
+        password: Option<String>,
+        /// The authentication type, e.g. `WPA` or `nopass`.
+        security: String,
+        /// Whether the network doesn't broadcast its SSID.
+        hidden: bool,
+    },
+    /// A contact, from a vCard or a MeCard.
+    Contact {
+        /// The name of the contact.
+        name: Option<String>,
+        /// Phone numbers.
+        phones: Vec<String>,
+        /// E-mail addresses.
+        emails: Vec<String>,
+        /// Web sites.
+        urls: Vec<String>,
+    },
+    /// A geographic location.
+    Geo {
+        /// Latitude in degrees.
+        latitude: f64,
+        /// Longitude in degrees.
+        longitude: f64,
+        /// Altitude in meters, if specified.
+        altitude: Option<f64>,
+    },
+    /// A telephone number.
+    Tel {
+        /// The number, verbatim.
+        number: String,
+    },
+    /// A text message.
+    Sms {
+        /// The phone number of the recipient.
+        number: String,
+        /// The text of the message, if specified.
+        body: Option<String>,
+    },
+    /// An e-mail message.
+    Email {
+        /// The address of the recipient.
+        to: String,
+        /// The subject of the message, if specified.
+        subject: Option<String>,
+        /// The text of the message, if specified.
+        body: Option<String>,
+    },
+    /// A recognized format without a variant of its own, e.g. a payment
+    /// or a calendar event, which the app may parse itself.
+    Other {
+        /// The name of the format: `epc`, `otpauth`, `gs1`, `event`,
+        /// `emv` or `crypto`.
+        kind: String,
+        /// The payload, decoded as UTF-8 (lossily for EPC payments, which
+        /// may be in a legacy character set).
+        text: String,
+    },
+    /// Any other UTF-8 text.
+    Text {
+        /// The text.
+        text: String,
+    },
+    /// A payload that isn't valid UTF-8.
+    Binary {
+        /// The raw payload.
+        bytes: Vec<u8>,
+    },
+}
+
+impl ScannedContent {
+    /// Converts the result of `content::classify(payload)`.
+    fn new(content: Content, payload: &[u8]) -> Self {
+        let other = |kind: &str| ScannedContent::Other {
+            kind: kind.to_owned(),
+            text: String::from_utf8_lossy(payload).into_owned(),
+        };
+
+        match content {
+            Content::Url(url) => ScannedContent::Url { url },
+            Content::WifiConfig(config) => ScannedContent::Wifi {
+                security: config.security.as_str().to_owned(),
+                ssid: config.ssid,
+                password: config.password,
+                hidden: config.hidden,
+            },
+            Content::VCard(VCard { formatted_name, name, phones, emails, urls, .. }) => {
+                ScannedContent::Contact {
+                    name: formatted_name.or_else(|| {
+                        name.map(|parts| format!("{} {}", parts.given, parts.family).trim().to_owned())
+                    }),
+                    phones: phones.into_iter().map(|phone| phone.value).collect(),
+                    emails: emails.into_iter().map(|email| email.value).collect(),
+                    urls,
+                }
+            }
+            Content::MeCard(card) => ScannedContent::Contact {
+                name: card.name,
+                phones: card.phones,
+                emails: card.emails,
+                urls: card.urls,
+            },
+            Content::EpcPayment(_) => other("epc"),
+            Content::OtpAuth(_) => other("otpauth"),
+            Content::Gs1(_) => other("gs1"),
+            Content::Event(_) => other("event"),
+            Content::EmvQr(_) => other("emv"),
+            Content::CryptoPayment(_) => other("crypto"),
+            Content::Geo { latitude, longitude, altitude } => {
+                ScannedContent::Geo { latitude, longitude, altitude }
+            }
+            Content::Tel(number) => ScannedContent::Tel { number },
+            Content::Sms { number, body } => ScannedContent::Sms { number, body },
+            Content::Email { to, subject, body } => ScannedContent::Email { to, subject, body },
+            Content::Text(text) => ScannedContent::Text { text },
+            Content::Binary(bytes) => ScannedContent::Binary { bytes },
+        }
+    }
+}
+
+/// A decoded QR code.
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct ScannedCode {
+    /// The corners of the code, clockwise from the top left one.
+    pub corners: Vec<Point>,
+    /// The version of the code, from 1 to 40.
+    pub version: u8,
+    /// The error correction level: `L`, `M`, `Q` or `H`.
+    pub ecc_level: String,
+    /// The mask ID, from 0 to 7.
+    pub mask_id: u8,
+    /// The highest-valued data type: `numeric`, `alphanumeric`, `byte`
+    /// or `kanji`.
+    pub data_type: String,
+    /// The ECI assignment number, or 0 if there is none.
+    pub eci: u32,
+    /// The raw payload.
+    pub payload: Vec<u8>,
+    /// The payload, if it is valid UTF-8.
+    pub text: Option<String>,
+    /// The structure of the payload.
+    pub content: ScannedContent,
+}
+
+impl ScannedCode {
+    /// Collects the information decoded from the code with the given corners.
+    fn new(corners: [Vec2D; 4], info: &Info) -> Self {
+        let payload = info.payload();
+
+        ScannedCode {
+            corners: corners.iter().cloned().map(Point::from).collect(),
+            version: info.version().number(),
+            ecc_level: info.ecc_level().as_str().to_owned(),
+            mask_id: info.mask_id(),
+            data_type: info.data_type().as_str().to_owned(),
+            eci: info.raw_eci(),
+            payload: payload.to_vec(),
+            text: info.as_str().ok().map(str::to_owned),
+            content: ScannedContent::new(content::classify(payload), payload),
+        }
+    }
+}
+
+/// Detects and decodes QR codes in camera frames. It may be shared
+/// between threads, but frames are decoded one at a time.
+#[derive(Debug, uniffi::Object)]
+pub struct CodeScanner {
+    /// The underlying decoder.
+    decoder: Mutex<Decoder>,
+}
+
+#[uniffi::export]
+impl CodeScanner {
+    /// Creates a scanner.
+    #[uniffi::constructor]
+    pub fn new() -> Result<Arc<Self>, ScanError> {
+        let decoder = Decoder::new()?;
+
+        Ok(Arc::new(CodeScanner { decoder: Mutex::new(decoder) }))
+    }
+
+    /// Rejects frames with more than `limit` pixels, or lifts the limit if
+    /// `limit` is 0.
+    pub fn set_max_image_area(&self, limit: u64) -> Result<(), ScanError> {
+        let limit = u64_to_usize(limit)?;
+        let mut decoder = self.decoder.lock().unwrap_or_else(PoisonError::into_inner);
+
+        decoder.config_mut().max_image_area = if limit == 0 { None } else { Some(limit) };
+
+        Ok(())
+    }
+
+    /// Decodes the QR codes in an 8-bit grayscale frame, e.g. the Y plane
+    /// of an NV21 or YUV_420_888 camera image, whose rows are `stride`
+    /// bytes apart. Codes which can't be decoded are left out.
+    pub fn scan_gray(
+        &self,
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+        stride: u32,
+    ) -> Result<Vec<ScannedCode>, ScanError> {
+        let size = Vec2D { x: u32_to_usize(width)?, y: u32_to_usize(height)? };
+        let image = Image::with_stride(&data, size, u32_to_usize(stride)?)?;
+        let mut decoder = self.decoder.lock().unwrap_or_else(PoisonError::into_inner);
+        let codes = decoder
+            .decode_image(&image)?
+            .decoded()
+            .filter_map(Result::ok)
+            .map(|(code, info)| ScannedCode::new(code.corners(), &info))
+            .collect();
+
+        Ok(codes)
+    }
+}
+
+/// Recognizes the structure of a payload, e.g. one read by another
+/// scanner or typed in by the user.
+#[uniffi::export]
+pub fn parse_content(payload: Vec<u8>) -> ScannedContent {
+    ScannedContent::new(content::classify(&payload), &payload)
+}
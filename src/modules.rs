@@ -0,0 +1,159 @@
+//! The modules of a code as a matrix of booleans.
+//!
+//! `QrCode::bitmap()` packs 8 modules into each byte, least significant bit
+//! first, and the rows aren't padded to whole bytes, so they usually start
+//! in the middle of one. `Modules` hides the packing: it is indexed by
+//! `(x, y)`, iterates over rows, and converts into a `Vec<bool>`. With the
+//! `bitvec` feature, it is also available as a `BitSlice`, without copying.
+//! It implements `render::Modules`, too, so it can be rendered like a code.
+//!
+//! Dark modules are `true`.
+
+use std::ops::Index;
+use std::iter::FusedIterator;
+use std::vec::Vec;
+use geom::QrCode;
+use encoder::EncodedQr;
+#[cfg(feature = "bitvec")]
+use bitvec::{ slice::BitSlice, order::Lsb0 };
+
+/// A borrowed view of the modules of a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modules<'a> {
+    /// The modules, packed as in `QrCode::bitmap()`.
+    bitmap: &'a [u8],
+    /// The number of modules along each side.
+    size: usize,
+}
+
+impl<'a> Modules<'a> {
+    /// The number of modules along each side of the code.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the module at `(x, y)`, or `None` if it's out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> Option<bool> {
+        if x < self.size && y < self.size {
+            let i = y * self.size + x;
+            Some(self.bitmap[i / 8] >> (i % 8) & 1 != 0)
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the rows of the code, from top to bottom.
+    pub fn iter_rows(&self) -> Rows<'a> {
+        Rows { modules: *self, y: 0 }
+    }
+
+    /// Returns the modules row by row, i.e. the module at `(x, y)` is at
+    /// index `y * size + x`, as accepted by `QrCode::from_modules()`.
+    pub fn to_vec_bool(&self) -> Vec<bool> {
+        self.iter_rows().flatten().collect()
+    }
+
+    /// Returns the modules row by row as a bit slice, in the same order as
+    /// `to_vec_bool()`, without copying them.
+    #[cfg(feature = "bitvec")]
+    pub fn as_bitslice(&self) -> &'a BitSlice<u8, Lsb0> {
+        &BitSlice::from_slice(self.bitmap)[..self.size * self.size]
+    }
+}
+
+/// Returns the module at `(x, y)`, panicking if it's out of bounds.
+impl<'a> Index<(usize, usize)> for Modules<'a> {
+    type Output = bool;
+
+    fn index(&self, (x, y): (usize, usize)) -> &bool {
+        match self.get(x, y) {
+            Some(true) => &true,
+            Some(false) => &false,
+            None => panic!("({}, {}) out of bounds for bitmap of size {}", x, y, self.size),
+        }
+    }
+}
+
+/// An iterator over the rows of a code. Created by `Modules::iter_rows()`.
+#[derive(Debug, Clone)]
+pub struct Rows<'a> {
+    /// The modules of the code.
+    modules: Modules<'a>,
+    /// The index of the next row.
+    y: usize,
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = Row<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y < self.modules.size {
+            let row = Row { modules: self.modules, y: self.y, x: 0 };
+            self.y += 1;
+            Some(row)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.modules.size - self.y;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Rows<'a> {}
+
+impl<'a> FusedIterator for Rows<'a> {}
+
+/// An iterator over the modules of a row, from left to right.
+#[derive(Debug, Clone)]
+pub struct Row<'a> {
+    /// The modules of the code.
+    modules: Modules<'a>,
+    /// The index of the row.
+    y: usize,
+    /// The index of the next module within the row.
+    x: usize,
+}
+
+impl<'a> Row<'a> {
+    /// The index of the row, from the top.
+    pub fn y(&self) -> usize {
+        self.y
+    }
+}
+
+impl<'a> Iterator for Row<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let module = self.modules.get(self.x, self.y)?;
+        self.x += 1;
+        Some(module)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.modules.size - self.x;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Row<'a> {}
+
+impl<'a> FusedIterator for Row<'a> {}
+
+impl QrCode {
+    /// Returns the modules of the code as a matrix of booleans, which is
+    /// easier to use correctly than the packed `bitmap()`.
+    pub fn modules(&self) -> Modules {
+        Modules { bitmap: self.bitmap(), size: self.size() }
+    }
+}
+
+impl EncodedQr {
+    /// Returns the modules of the code as a matrix of booleans.
+    pub fn modules(&self) -> Modules {
+        Modules { bitmap: self.bitmap(), size: self.size() }
+    }
+}
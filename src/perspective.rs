@@ -0,0 +1,325 @@
+//! The perspective transform between the module grid of a code and the
+//! image it was detected in.
+//!
+//! `quirc` fits a homography to each code, and reports the corners of the
+//! code as the images of the corners of the grid under that homography.
+//! Four point correspondences determine a homography, so it can be
+//! reconstructed exactly, up to the rounding of the corner coordinates.
+
+use geom::{ Image, QrCode, Vec2D, Vec2Df };
+
+/// The number of intervals into which each search line of
+/// `QrCode::refined_corners()` is divided for sampling.
+const EDGE_SAMPLES: usize = 32;
+
+/// The distance, in modules, searched for an edge on either side of its
+/// estimated position.
+const EDGE_SEARCH_RADIUS: f64 = 1.0;
+
+/// The smallest difference between the lightest and the darkest sample
+/// along a search line for an edge to be located on it.
+const EDGE_MIN_CONTRAST: f64 = 24.0;
+
+/// A projective transformation of the plane, given by a 3x3 matrix
+/// acting on homogeneous coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Homography {
+    /// The matrix, in row-major order.
+    matrix: [[f64; 3]; 3],
+}
+
+impl Homography {
+    /// Returns the homography mapping the square `[0, size] x [0, size]`
+    /// onto the quadrilateral with the given vertices, which correspond
+    /// to the corners of the square clockwise from `(0, 0)`.
+    ///
+    /// If the quadrilateral is degenerate (e.g. three of its vertices are
+    /// collinear), the result maps points to non-finite coordinates.
+    pub fn from_square(size: f64, corners: [(f64, f64); 4]) -> Self {
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = corners;
+        let sx = x0 - x1 + x2 - x3;
+        let sy = y0 - y1 + y2 - y3;
+
+        // The mapping of the unit square (Heckbert, 1989).
+        let (g, h) = if sx.abs() < ::std::f64::EPSILON && sy.abs() < ::std::f64::EPSILON {
+            (0.0, 0.0)
+        } else {
+            let (dx1, dx2) = (x1 - x2, x3 - x2);
+            let (dy1, dy2) = (y1 - y2, y3 - y2);
+            let den = dx1 * dy2 - dx2 * dy1;
+
+            ((sx * dy2 - dx2 * sy) / den, (dx1 * sy - sx * dy1) / den)
+        };
+
+        let mut matrix = [
+            [x1 - x0 + g * x1, x3 - x0 + h * x3, x0],
+            [y1 - y0 + g * y1, y3 - y0 + h * y3, y0],
+            [g, h, 1.0],
+        ];
+
+        // Scale the input by `1 / size` first.
+        for row in &mut matrix {
+            row[0] /= size;
+            row[1] /= size;
+        }
+
+        Homography { matrix }
+    }
+
+    /// Maps a point.
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        let m = &self.matrix;
+        let w = m[2][0] * x + m[2][1] * y + m[2][2];
+
+        (
+            (m[0][0] * x + m[0][1] * y + m[0][2]) / w,
+            (m[1][0] * x + m[1][1] * y + m[1][2]) / w,
+        )
+    }
+
+    /// Returns the inverse transformation, or `None` if it is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.matrix;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+
+        // The adjugate, i.e. the transposed matrix of cofactors.
+        let adjugate = [
+            [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+        ];
+        let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+
+        if determinant.abs() < ::std::f64::EPSILON || !determinant.is_finite() {
+            return None;
+        }
+
+        // Homogeneous matrices are defined up to scale, so the adjugate
+        // would do; normalizing keeps the coefficients well-conditioned.
+        let scale = 1.0 / determinant;
+        let mut matrix = adjugate;
+
+        for value in matrix.iter_mut().flat_map(|row| row.iter_mut()) {
+            *value *= scale;
+        }
+
+        Some(Homography { matrix })
+    }
+
+    /// Returns the matrix of the transformation, in row-major order.
+    pub fn matrix(&self) -> [[f64; 3]; 3] {
+        self.matrix
+    }
+}
+
+impl QrCode {
+    /// Returns the homography mapping grid coordinates, in which each
+    /// module is a unit square and the code spans `[0, size()]` along
+    /// both axes, to image coordinates, in pixels.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn homography(&self) -> Homography {
+        let corners = self.corners();
+        let point = |i: usize| (corners[i].x as f64, corners[i].y as f64);
+
+        Homography::from_square(
+            self.size() as f64,
+            [point(0), point(1), point(2), point(3)],
+        )
+    }
+
+    /// Returns the center of the code in the image, i.e. the image of the
+    /// center of the grid, which accounts for perspective distortion.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn center(&self) -> Vec2Df {
+        let half = self.size() as f64 / 2.0;
+        let (x, y) = self.homography().map(half, half);
+
+        Vec2Df { x, y }
+    }
+
+    /// Returns the angle of the top edge of the code, from its top left
+    /// corner to its top right corner, relative to the X axis of the image.
+    /// Since the Y axis of images points down, positive angles mean
+    /// clockwise rotation. The result is in the range `(-pi, pi]`.
+    pub fn orientation_radians(&self) -> f64 {
+        let top_left = Vec2Df::from(self.top_left_corner());
+        let top_right = Vec2Df::from(self.top_right_corner());
+
+        (top_right.y - top_left.y).atan2(top_right.x - top_left.x)
+    }
+
+    /// Estimates the corners of the code with sub-pixel precision, clockwise
+    /// from the top left one, by locating the outer edges of the finder
+    /// patterns in `image`, which must be the image the code was detected
+    /// in, fitting lines to them, and intersecting the lines.
+    ///
+    /// Corners which can't be refined, e.g. because the edges are outside
+    /// the image or lack contrast, are returned as detected.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn refined_corners(&self, image: &Image) -> [Vec2Df; 4] {
+        let size = self.size() as f64;
+        let homography = self.homography();
+
+        // Sample the 7 modules wide finder patterns, away from their corners.
+        let near: Vec<f64> = (2..13).map(|i| f64::from(i) * 0.5).collect();
+        let far: Vec<f64> = near.iter().map(|t| size - t).collect();
+        let both: Vec<f64> = near.iter().chain(&far).cloned().collect();
+
+        let edge = |along: &[f64], horizontal: bool, at: f64, outward: f64| {
+            let points: Vec<Vec2Df> = along
+                .iter()
+                .filter_map(|&t| {
+                    let grid = |offset: f64| if horizontal { (t, at + offset) } else { (at + offset, t) };
+                    let (outer, inner) = (grid(outward * EDGE_SEARCH_RADIUS), grid(-outward * EDGE_SEARCH_RADIUS));
+                    let (ox, oy) = homography.map(outer.0, outer.1);
+                    let (ix, iy) = homography.map(inner.0, inner.1);
+
+                    find_edge(image, Vec2Df { x: ox, y: oy }, Vec2Df { x: ix, y: iy })
+                })
+                .collect();
+
+            fit_line(&points)
+        };
+
+        let top = edge(&both, true, 0.0, -1.0);
+        let left = edge(&both, false, 0.0, -1.0);
+        let right = edge(&near, false, size, 1.0);
+        let bottom = edge(&near, true, size, 1.0);
+
+        let detected = [
+            self.top_left_corner(),
+            self.top_right_corner(),
+            self.bottom_right_corner(),
+            self.bottom_left_corner(),
+        ];
+        let lines = [(top, left), (top, right), (right, bottom), (bottom, left)];
+        let mut corners = [Vec2Df::default(); 4];
+
+        for (corner, (&(a, b), &fallback)) in corners.iter_mut().zip(lines.iter().zip(&detected)) {
+            *corner = match (a, b) {
+                (Some(a), Some(b)) => intersect(a, b),
+                _ => None,
+            }.unwrap_or_else(|| fallback.into());
+        }
+
+        corners
+    }
+
+    /// Maps the center of a module to image coordinates, in pixels.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn grid_to_image(&self, coord: Vec2D) -> (f64, f64) {
+        self.homography().map(coord.x as f64 + 0.5, coord.y as f64 + 0.5)
+    }
+
+    /// Maps image coordinates, in pixels, to grid coordinates. The module
+    /// at `(x, y)` covers grid coordinates from `(x, y)` to `(x + 1, y + 1)`,
+    /// so the integer parts of the result identify the module containing
+    /// the point. Returns `None` if the code is degenerate.
+    pub fn image_to_grid(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        self.homography().inverse().map(|inverse| inverse.map(x, y))
+    }
+}
+
+/// A line, given by a point on it and its direction.
+type Line = (Vec2Df, Vec2Df);
+
+/// Samples `image` at sub-pixel coordinates, with bilinear interpolation.
+/// Pixel centers lie at half-integer coordinates.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_sign_loss, cast_precision_loss))]
+pub(crate) fn sample(image: &Image, point: Vec2Df) -> Option<f64> {
+    let (x, y) = (point.x - 0.5, point.y - 0.5);
+    let max_x = image.width() as f64 - 1.0;
+    let max_y = image.height() as f64 - 1.0;
+
+    if !(0.0 <= x && x <= max_x && 0.0 <= y && y <= max_y) {
+        return None;
+    }
+
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let x1 = (x0 + 1).min(image.width() - 1);
+    let y1 = (y0 + 1).min(image.height() - 1);
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let pixel = |px: usize, py: usize| f64::from(image.row(py)[px]);
+
+    let top = pixel(x0, y0) * (1.0 - fx) + pixel(x1, y0) * fx;
+    let bottom = pixel(x0, y1) * (1.0 - fx) + pixel(x1, y1) * fx;
+
+    Some(top * (1.0 - fy) + bottom * fy)
+}
+
+/// Locates the first transition from light to dark along the segment
+/// from `outer` to `inner`, with sub-pixel precision.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+fn find_edge(image: &Image, outer: Vec2Df, inner: Vec2Df) -> Option<Vec2Df> {
+    let at = |t: f64| Vec2Df {
+        x: outer.x + (inner.x - outer.x) * t,
+        y: outer.y + (inner.y - outer.y) * t,
+    };
+    let samples: Option<Vec<f64>> = (0..=EDGE_SAMPLES)
+        .map(|i| sample(image, at(i as f64 / EDGE_SAMPLES as f64)))
+        .collect();
+    let samples = samples?;
+
+    let min = samples.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+    let max = samples.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+
+    if max - min < EDGE_MIN_CONTRAST {
+        return None;
+    }
+
+    let threshold = (min + max) / 2.0;
+
+    samples
+        .windows(2)
+        .position(|pair| pair[0] >= threshold && pair[1] < threshold)
+        .map(|i| {
+            let fraction = (samples[i] - threshold) / (samples[i] - samples[i + 1]);
+            at((i as f64 + fraction) / EDGE_SAMPLES as f64)
+        })
+}
+
+/// Fits a line to the points by total least squares. Requires at least
+/// two distinct points.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+fn fit_line(points: &[Vec2Df]) -> Option<Line> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.y).sum::<f64>() / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+
+    for p in points {
+        let (dx, dy) = (p.x - mean_x, p.y - mean_y);
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+
+    if sxx + syy < ::std::f64::EPSILON {
+        return None;
+    }
+
+    // The direction of the largest variance.
+    let angle = (2.0 * sxy).atan2(sxx - syy) / 2.0;
+
+    Some((Vec2Df { x: mean_x, y: mean_y }, Vec2Df { x: angle.cos(), y: angle.sin() }))
+}
+
+/// Returns the intersection of two lines, or `None` if they are parallel.
+fn intersect(a: Line, b: Line) -> Option<Vec2Df> {
+    let ((p, d), (q, e)) = (a, b);
+    let cross = d.x * e.y - d.y * e.x;
+
+    if cross.abs() < 1e-9 {
+        return None;
+    }
+
+    let t = ((q.x - p.x) * e.y - (q.y - p.y) * e.x) / cross;
+
+    Some(Vec2Df { x: p.x + d.x * t, y: p.y + d.y * t })
+}
@@ -0,0 +1,167 @@
+//! Decoding a stream of frames on a pool of threads.
+//!
+//! A `DecoderPool` owns a number of worker threads, each with its own
+//! `Decoder`. Frames are submitted through a bounded queue, so producers
+//! (e.g. camera capture loops) are slowed down instead of letting the
+//! backlog grow without bounds when decoding can't keep up. Results are
+//! emitted in the order in which the frames finish decoding, which may
+//! differ from the order of submission; they carry the ID of the frame
+//! for reassociation.
+
+use std::thread::{ self, JoinHandle };
+use std::sync::{ Arc, Mutex, PoisonError };
+use std::sync::mpsc::{ self, Receiver, SyncSender, Sender, TrySendError };
+use decoder::Decoder;
+use geom::ImageBuf;
+use info::Info;
+use error::{ Error, Result };
+
+/// A frame waiting to be decoded: its ID and its pixels.
+type Job = (u64, ImageBuf);
+
+/// The codes decoded from a single frame.
+#[derive(Debug, Clone)]
+pub struct FrameResult {
+    /// The ID of the frame, as passed to `DecoderPool::submit()`.
+    pub frame_id: u64,
+    /// The information decoded from the codes in the frame, in the order
+    /// of detection, or the error which prevented detection altogether.
+    /// Codes which were detected but couldn't be decoded are omitted.
+    pub infos: Result<Vec<Info>>,
+}
+
+/// A pool of decoders running on their own threads.
+#[derive(Debug)]
+pub struct DecoderPool {
+    /// The sending half of the queue of frames; `None` once shut down.
+    jobs: Option<SyncSender<Job>>,
+    /// The receiving half of the queue of results.
+    results: Receiver<FrameResult>,
+    /// The worker threads.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl DecoderPool {
+    /// Starts `num_decoders` worker threads, each with its own decoder.
+    /// At most `queue_len` frames wait to be decoded at any time; further
+    /// submissions block until a worker becomes free.
+    ///
+    /// Fails with `Error::InvalidArgument` if `num_decoders` is 0, or if
+    /// a decoder couldn't be created.
+    pub fn new(num_decoders: usize, queue_len: usize) -> Result<Self> {
+        if num_decoders == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let decoders = (0..num_decoders)
+            .map(|_| Decoder::new())
+            .collect::<Result<Vec<_>>>()?;
+
+        let (job_tx, job_rx) = mpsc::sync_channel(queue_len);
+        let (result_tx, result_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        let workers = decoders
+            .into_iter()
+            .map(|decoder| {
+                let jobs = Arc::clone(&job_rx);
+                let results = result_tx.clone();
+
+                thread::spawn(move || work(decoder, &jobs, &results))
+            })
+            .collect();
+
+        Ok(DecoderPool {
+            jobs: Some(job_tx),
+            results: result_rx,
+            workers,
+        })
+    }
+
+    /// Returns the number of worker threads.
+    pub fn num_decoders(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Submits a frame for decoding, blocking while the queue is full.
+    /// Fails with `Error::InvalidArgument` if the pool has shut down.
+    pub fn submit(&self, frame_id: u64, image: ImageBuf) -> Result<()> {
+        self.jobs
+            .as_ref()
+            .ok_or(Error::InvalidArgument)?
+            .send((frame_id, image))
+            .map_err(|_| Error::InvalidArgument)
+    }
+
+    /// Submits a frame for decoding if there is room in the queue.
+    /// Otherwise, returns the image, so that the caller can decide whether
+    /// to drop the frame or to retry later. Fails with
+    /// `Error::InvalidArgument` if the pool has shut down.
+    pub fn try_submit(&self, frame_id: u64, image: ImageBuf) -> Result<Option<ImageBuf>> {
+        let jobs = self.jobs.as_ref().ok_or(Error::InvalidArgument)?;
+
+        match jobs.try_send((frame_id, image)) {
+            Ok(()) => Ok(None),
+            Err(TrySendError::Full((_, image))) => Ok(Some(image)),
+            Err(TrySendError::Disconnected(_)) => Err(Error::InvalidArgument),
+        }
+    }
+
+    /// Waits for the next result. Returns `None` if every submitted frame
+    /// has been decoded and the pool has shut down.
+    pub fn recv(&self) -> Option<FrameResult> {
+        self.results.recv().ok()
+    }
+
+    /// Returns the next result if one is available, without blocking.
+    pub fn try_recv(&self) -> Option<FrameResult> {
+        self.results.try_recv().ok()
+    }
+
+    /// Stops accepting frames, waits for the frames already submitted to
+    /// be decoded, and returns the results which haven't been received yet.
+    pub fn shutdown(mut self) -> Vec<FrameResult> {
+        self.close();
+        self.results.try_iter().collect()
+    }
+
+    /// Closes the queue of frames and joins the worker threads.
+    fn close(&mut self) {
+        // Dropping the sender makes the workers exit once the queue is empty.
+        self.jobs = None;
+
+        for worker in self.workers.drain(..) {
+            // A worker can only panic if decoding panicked, and that panic
+            // has already been reported on the worker thread.
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for DecoderPool {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// The body of a worker thread: decodes frames until the queue is closed.
+fn work(mut decoder: Decoder, jobs: &Mutex<Receiver<Job>>, results: &Sender<FrameResult>) {
+    loop {
+        // The lock is released as soon as a job has been received, so that
+        // the other workers can receive the next one while this one works.
+        let job = jobs.lock().unwrap_or_else(PoisonError::into_inner).recv();
+        let (frame_id, image) = match job {
+            Ok(job) => job,
+            Err(_) => return, // the queue is closed and empty
+        };
+        let infos = decoder.decode_image(&image.as_image()).map(|codes| {
+            codes
+                .filter_map(|code| code.and_then(|code| code.decode()).ok())
+                .collect()
+        });
+
+        if results.send(FrameResult { frame_id, infos }).is_err() {
+            return; // nobody is listening anymore
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Loading images in the Netpbm formats (PBM, PGM and PPM).
+//!
+//! These are the formats of the test corpus of `quirc` itself, and they
+//! are simple enough not to warrant depending on an image decoding crate.
+
+use std::fs;
+use std::path::Path;
+use geom::{ ImageBuf, Vec2D };
+use simd::rgb_to_luma;
+use util::{ checked_add, checked_mul };
+use error::{ Error, Result };
+
+impl ImageBuf {
+    /// Parses the contents of a Netpbm file, i.e. a PGM (grayscale) image,
+    /// or a PBM (bitmap) or PPM (color) image, which is converted to
+    /// grayscale. Both the binary and the plain (ASCII) variants are
+    /// supported, with any maximal sample value up to 65535.
+    pub fn from_pgm_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut parser = Parser { bytes, pos: 0 };
+
+        if parser.next_byte()? != b'P' {
+            return Err(Error::InvalidImage);
+        }
+
+        let format = parser.next_byte()?;
+        let (plain, channels) = match format {
+            b'1' | b'2' => (true, 1),
+            b'4' | b'5' => (false, 1),
+            b'3' => (true, 3),
+            b'6' => (false, 3),
+            _ => return Err(Error::InvalidImage),
+        };
+        let width = parser.number()?;
+        let height = parser.number()?;
+        let size = Vec2D { x: width, y: height };
+        let num_pixels = checked_mul(width, height)?;
+
+        if format == b'1' || format == b'4' {
+            parser.skip_single_whitespace(plain)?;
+
+            let data = if plain {
+                parser.plain_bits(num_pixels)?
+            } else {
+                parser.packed_bits(size)?
+            };
+
+            return ImageBuf::new(data, size);
+        }
+
+        let max_value = parser.number()?;
+
+        if max_value == 0 || max_value > 0xffff {
+            return Err(Error::InvalidImage);
+        }
+
+        parser.skip_single_whitespace(plain)?;
+
+        // Each sample takes up at least one byte, so the header can't make
+        // us reserve more memory than the size of the file justifies.
+        let num_samples = checked_mul(num_pixels, channels)?;
+        let mut samples = Vec::with_capacity(num_samples.min(parser.remaining()));
+
+        for _ in 0..num_samples {
+            let sample = if plain {
+                parser.number()?
+            } else if max_value < 0x100 {
+                usize::from(parser.next_byte()?)
+            } else {
+                usize::from(parser.next_byte()?) << 8 | usize::from(parser.next_byte()?)
+            };
+
+            samples.push(scale(sample, max_value)?);
+        }
+
+        let data = if channels == 1 {
+            samples
+        } else {
+            let mut luma = vec![0; num_pixels];
+            rgb_to_luma(&samples, &mut luma);
+            luma
+        };
+
+        ImageBuf::new(data, size)
+    }
+
+    /// Reads and parses a Netpbm file. See `from_pgm_bytes()` for details.
+    pub fn load_pgm<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::from_pgm_bytes(&bytes)
+    }
+}
+
+/// Maps a sample in the range `0...max_value` to `0...255`.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn scale(sample: usize, max_value: usize) -> Result<u8> {
+    if sample > max_value {
+        Err(Error::InvalidImage)
+    } else if max_value == 0xff {
+        Ok(sample as u8)
+    } else {
+        Ok(((sample * 0xff + max_value / 2) / max_value) as u8)
+    }
+}
+
+/// A cursor over the contents of a Netpbm file.
+#[derive(Debug)]
+struct Parser<'a> {
+    /// The entire file.
+    bytes: &'a [u8],
+    /// The index of the next byte to be read.
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Reads a single byte.
+    fn next_byte(&mut self) -> Result<u8> {
+        let byte = *self.bytes.get(self.pos).ok_or(Error::InvalidImage)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// The number of bytes not read yet.
+    fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.pos)
+    }
+
+    /// Skips whitespace and comments, which extend to the end of the line.
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            if byte == b'#' {
+                while self.bytes.get(self.pos).map_or(false, |&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Skips the single whitespace character separating the header from
+    /// the raster of binary files. Plain files may contain any amount of
+    /// whitespace there, which is skipped when reading the next sample.
+    fn skip_single_whitespace(&mut self, plain: bool) -> Result<()> {
+        if plain || self.next_byte()?.is_ascii_whitespace() {
+            Ok(())
+        } else {
+            Err(Error::InvalidImage)
+        }
+    }
+
+    /// Reads a decimal number preceded by optional whitespace.
+    fn number(&mut self) -> Result<usize> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        let mut value: usize = 0;
+
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            if !byte.is_ascii_digit() {
+                break;
+            }
+
+            value = checked_add(checked_mul(value, 10)?, usize::from(byte - b'0'))?;
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            Err(Error::InvalidImage)
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Reads the raster of a plain PBM file, in which pixels are
+    /// represented by the characters `0` (white) and `1` (black),
+    /// optionally separated by whitespace.
+    fn plain_bits(&mut self, num_pixels: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(num_pixels.min(self.remaining()));
+
+        while data.len() < num_pixels {
+            self.skip_whitespace();
+
+            data.push(match self.next_byte()? {
+                b'0' => 0xff,
+                b'1' => 0x00,
+                _ => return Err(Error::InvalidImage),
+            });
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the raster of a binary PBM file, in which each row is packed
+    /// into whole bytes, most significant bit first, 1 meaning black.
+    fn packed_bits(&mut self, size: Vec2D) -> Result<Vec<u8>> {
+        // Rows of zero width take up no bytes, so there would be nothing
+        // to stop a bogus height.
+        if size.x == 0 {
+            return Ok(Vec::new());
+        }
+
+        let row_len = (size.x + 7) / 8;
+        let mut data = Vec::with_capacity((size.x * size.y).min(self.remaining().saturating_mul(8)));
+
+        for _ in 0..size.y {
+            let row = self.bytes
+                .get(self.pos..self.pos + row_len)
+                .ok_or(Error::InvalidImage)?;
+
+            data.extend((0..size.x).map(|x| {
+                if row[x / 8] & 0x80 >> (x % 8) == 0 { 0xff } else { 0x00 }
+            }));
+            self.pos += row_len;
+        }
+
+        Ok(data)
+    }
+}
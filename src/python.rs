@@ -0,0 +1,250 @@
+//! Python bindings, built as an extension module named `quirs`.
+//!
+//! The module exposes `Decoder`, `Image` and `Info`. Images are copied
+//! from 2-dimensional `uint8` NumPy arrays, or from any object supporting
+//! the buffer protocol, and the GIL is released while they are decoded,
+//! so several decoders may run in parallel threads. It is built with:
+//!
+//! ```shell
+//! cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib
+//! cp target/release/libquirs.so quirs.so
+//! ```
+//!
+//! ```python
+//! import numpy as np, quirs
+//!
+//! decoder = quirs.Decoder()
+//! for info in decoder.decode(np.asarray(gray_frame, dtype=np.uint8)):
+//!     print(info.corners, info.text or info.payload)
+//! ```
+
+use std::sync::{ Mutex, PoisonError };
+use pyo3::prelude::*;
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{ PyMemoryError, PyOverflowError, PyRuntimeError, PyValueError };
+use pyo3::types::PyBytes;
+use numpy::PyReadonlyArray2;
+use decoder::Decoder;
+use geom::{ Image, ImageBuf, Vec2D };
+use info::Info;
+use error::Error;
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+
+        match error {
+            Error::AllocFailed => PyMemoryError::new_err(message),
+            Error::IntOverflow(_) => PyOverflowError::new_err(message),
+            Error::SizeMismatch { .. }
+            | Error::InvalidImage
+            | Error::InvalidArgument
+            | Error::ImageTooLarge { .. } => PyValueError::new_err(message),
+            _ => PyRuntimeError::new_err(message),
+        }
+    }
+}
+
+/// A grayscale image, one byte per pixel.
+#[pyclass(name = "Image", module = "quirs", frozen)]
+#[derive(Debug)]
+pub struct PyImage {
+    /// The pixels, with rows not padded.
+    buf: ImageBuf,
+}
+
+#[pymethods]
+impl PyImage {
+    /// Copies a 2-dimensional `uint8` array of shape `(height, width)`,
+    /// which needn't be contiguous.
+    #[new]
+    fn new(array: PyReadonlyArray2<u8>) -> PyResult<Self> {
+        let view = array.as_array();
+        let (height, width) = view.dim();
+        let data = view.iter().cloned().collect();
+        let buf = ImageBuf::new(data, Vec2D { x: width, y: height })?;
+
+        Ok(PyImage { buf })
+    }
+
+    /// Copies the bytes of an object supporting the buffer protocol, whose
+    /// rows are `stride` bytes apart, or `width` bytes if not given.
+    #[staticmethod]
+    #[pyo3(signature = (data, width, height, stride = None))]
+    fn from_buffer(
+        py: Python,
+        data: PyBuffer<u8>,
+        width: usize,
+        height: usize,
+        stride: Option<usize>,
+    ) -> PyResult<Self> {
+        let bytes = data.to_vec(py)?;
+        let size = Vec2D { x: width, y: height };
+        let image = Image::with_stride(&bytes, size, stride.unwrap_or(width))?;
+        let pixels = (0..height).flat_map(|y| image.row(y)).cloned().collect();
+        let buf = ImageBuf::new(pixels, size)?;
+
+        Ok(PyImage { buf })
+    }
+
+    /// The width of the image in pixels.
+    #[getter]
+    fn width(&self) -> usize {
+        self.buf.width()
+    }
+
+    /// The height of the image in pixels.
+    #[getter]
+    fn height(&self) -> usize {
+        self.buf.height()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Image(width={}, height={})", self.buf.width(), self.buf.height())
+    }
+}
+
+/// The information decoded from a QR code.
+#[pyclass(name = "Info", module = "quirs", frozen)]
+#[derive(Debug)]
+pub struct PyInfo {
+    /// The corners of the code, clockwise from the top left one.
+    corners: [Vec2D; 4],
+    /// The decoded information.
+    info: Info,
+}
+
+#[pymethods]
+impl PyInfo {
+    /// The corners of the code in the image, as `(x, y)` tuples clockwise
+    /// from the top left one.
+    #[getter]
+    fn corners(&self) -> Vec<(usize, usize)> {
+        self.corners.iter().map(|corner| (corner.x, corner.y)).collect()
+    }
+
+    /// The version of the code, from 1 to 40.
+    #[getter]
+    fn version(&self) -> u8 {
+        self.info.version().number()
+    }
+
+    /// The error correction level: `"L"`, `"M"`, `"Q"` or `"H"`.
+    #[getter]
+    fn ecc_level(&self) -> &'static str {
+        self.info.ecc_level().as_str()
+    }
+
+    /// The mask ID, from 0 to 7.
+    #[getter]
+    fn mask_id(&self) -> u8 {
+        self.info.mask_id()
+    }
+
+    /// The highest-valued data type: `"numeric"`, `"alphanumeric"`,
+    /// `"byte"` or `"kanji"`.
+    #[getter]
+    fn data_type(&self) -> &'static str {
+        self.info.data_type().as_str()
+    }
+
+    /// The ECI designator, or 0 if there is none.
+    #[getter]
+    fn eci(&self) -> u32 {
+        self.info.raw_eci()
+    }
+
+    /// The raw payload.
+    #[getter]
+    fn payload<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.info.payload())
+    }
+
+    /// The payload as a string, or `None` if it isn't valid UTF-8.
+    #[getter]
+    fn text(&self) -> Option<&str> {
+        self.info.as_str().ok()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Info(version={}, ecc_level={:?}, payload={:?})",
+            self.info.version(),
+            self.info.ecc_level().as_str(),
+            String::from_utf8_lossy(self.info.payload()),
+        )
+    }
+}
+
+/// A QR code detector and decoder.
+#[pyclass(name = "Decoder", module = "quirs")]
+#[derive(Debug)]
+pub struct PyDecoder {
+    /// The underlying decoder. Python objects must be `Sync`, but the
+    /// decoder is only `Send`, and is only ever accessed mutably anyway.
+    decoder: Mutex<Decoder>,
+    /// The number of codes detected in the last image.
+    detected: usize,
+}
+
+#[pymethods]
+impl PyDecoder {
+    /// Creates a decoder.
+    #[new]
+    fn new() -> PyResult<Self> {
+        Ok(PyDecoder { decoder: Mutex::new(Decoder::new()?), detected: 0 })
+    }
+
+    /// Decodes the QR codes in an `Image` or a 2-dimensional `uint8` array,
+    /// returning a list of `Info`. Codes which are detected, but can't be
+    /// decoded are left out; see `detected`.
+    fn decode(&mut self, py: Python, image: &Bound<PyAny>) -> PyResult<Vec<PyInfo>> {
+        let converted;
+        let image: &PyImage = match image.cast::<PyImage>() {
+            Ok(image) => image.get(),
+            Err(_) => {
+                converted = PyImage::new(image.extract()?)?;
+                &converted
+            }
+        };
+        let decoder = self.decoder.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let (detected, infos) = py.detach(|| -> Result<_, Error> {
+            let codes = decoder.decode_image(&image.buf.as_image())?;
+            let detected = codes.len();
+            let infos: Vec<_> = codes
+                .decoded()
+                .filter_map(Result::ok)
+                .map(|(code, info)| PyInfo { corners: code.corners(), info })
+                .collect();
+
+            Ok((detected, infos))
+        })?;
+
+        self.detected = detected;
+
+        Ok(infos)
+    }
+
+    /// The number of codes detected in the last image, including those
+    /// which couldn't be decoded.
+    #[getter]
+    fn detected(&self) -> usize {
+        self.detected
+    }
+
+    /// The version of the underlying `quirc` library.
+    #[staticmethod]
+    fn quirc_version() -> &'static str {
+        Decoder::version()
+    }
+}
+
+/// The `quirs` extension module.
+#[pymodule]
+fn quirs(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_class::<PyDecoder>()?;
+    module.add_class::<PyImage>()?;
+    module.add_class::<PyInfo>()?;
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 //! Raw FFI bindings for the `quirc` C API.
 
 use std::fmt;
-use libc::{ c_int, c_char };
+use std::os::raw::c_int;
+#[cfg(feature = "std")]
+use std::os::raw::c_char;
 
 /// Opaque type manipulated by the `quirc` C API.
 #[allow(non_camel_case_types)]
@@ -182,6 +184,7 @@ pub const QUIRC_MAX_PAYLOAD: usize = 8896;
 
 extern {
     /// Obtain the library version string.
+    #[cfg(feature = "std")]
     pub fn quirc_version() -> *const c_char;
 
     /// Construct a new QR-code recognizer. This function will return NULL
@@ -218,4 +221,41 @@ extern {
     /// Decode a QR-code, returning the payload data.
     pub fn quirc_decode(code: *const quirc_code,
                         data: *mut quirc_data) -> quirc_decode_error_t;
+
+    /// Copy the thresholded and region-labeled pixels of the last processed
+    /// image into `out`, which must hold as many bytes as it has pixels.
+    /// Defined by `shim/debug.c`, not by `quirc` itself.
+    pub fn quirs_debug_pixels(q: *const quirc, out: *mut u8);
+
+    /// Return the number of regions examined as candidate finder patterns
+    /// in the last processed image. Defined by `shim/debug.c`.
+    pub fn quirs_debug_num_regions(q: *const quirc) -> c_int;
+
+    /// Return the number of capstones (finder patterns) found in the last
+    /// processed image. Defined by `shim/debug.c`.
+    pub fn quirs_debug_num_capstones(q: *const quirc) -> c_int;
+
+    /// Copy the corners and the center of the capstone at `index`, and
+    /// return the index of the grid it belongs to, or -1 if none.
+    /// Defined by `shim/debug.c`.
+    pub fn quirs_debug_capstone(q: *const quirc,
+                                index: c_int,
+                                corners: *mut quirc_point,
+                                center: *mut quirc_point) -> c_int;
+
+    /// Install `image`, which must hold as many bytes as the decoder has
+    /// pixels, as the image buffer, and return the previous one, which
+    /// must be installed again before resizing or destroying the decoder.
+    /// Defined by `shim/image.c`.
+    pub fn quirs_swap_image(q: *mut quirc, image: *mut u8) -> *mut u8;
+
+    /// Return the number of bytes allocated by the decoder, including its
+    /// image and pixel buffers and the work area of the flood fill.
+    /// Defined by `shim/memory.c`.
+    pub fn quirs_memory_usage(q: *const quirc) -> usize;
 }
+
+/// The value of light pixels after thresholding.
+pub const QUIRC_PIXEL_WHITE: u8 = 0;
+/// The value of dark pixels after thresholding, unless they belong to a region.
+pub const QUIRC_PIXEL_BLACK: u8 = 1;
@@ -0,0 +1,532 @@
+//! Rendering the modules of QR codes as PNG, PBM and SVG images, and as text.
+//!
+//! Both detected (`QrCode`) and generated (`EncodedQr`) codes can be
+//! rendered, as can anything else implementing `Modules`. The PNG encoder
+//! is minimal: it emits uncompressed (stored) deflate blocks, which is
+//! perfectly adequate for the small images QR codes make.
+//!
+//! Rendering a detected code shows exactly which modules were extracted
+//! from the image, whether or not they could be decoded, so it is a compact
+//! way of archiving what a photo yielded (`QrCode::save_pbm()`).
+//!
+//! `RgbImage` holds arbitrary color images, such as the annotated copies
+//! of scanned images made by `Report::annotate()`, and shares that encoder.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::io::Write;
+use geom::{ Image, ImageBuf, QrCode, Vec2D };
+use encoder::EncodedQr;
+use modules;
+use simd::luma;
+use util::{ checked_add, checked_mul, usize_to_u32 };
+use error::Result;
+
+/// The signature at the beginning of every PNG file.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The largest amount of data a stored deflate block can hold.
+const MAX_STORED_BLOCK_LEN: usize = 0xffff;
+
+/// The width of the quiet zone of `to_terminal_string()` methods, in modules.
+const TERMINAL_QUIET_ZONE: usize = 2;
+
+/// Characters depicting a pair of vertically adjacent modules, indexed by
+/// `top_is_dark | bottom_is_dark << 1`: a space, an upper half block, a
+/// lower half block, and a full block.
+const HALF_BLOCKS: [char; 4] = [' ', '\u{2580}', '\u{2584}', '\u{2588}'];
+
+/// A square matrix of light and dark modules.
+pub trait Modules {
+    /// The number of modules along each side.
+    fn size(&self) -> usize;
+
+    /// Returns whether the module at column `x` and row `y` is dark.
+    /// Both coordinates must be less than `size()`.
+    fn is_dark(&self, x: usize, y: usize) -> bool;
+}
+
+impl Modules for QrCode {
+    fn size(&self) -> usize {
+        QrCode::size(self)
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.bit_at(Vec2D { x, y })
+    }
+}
+
+impl Modules for EncodedQr {
+    fn size(&self) -> usize {
+        EncodedQr::size(self)
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.bit_at(Vec2D { x, y })
+    }
+}
+
+impl<'a> Modules for modules::Modules<'a> {
+    fn size(&self) -> usize {
+        modules::Modules::size(self)
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self[(x, y)]
+    }
+}
+
+/// A color with 8 bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    /// The red component.
+    pub r: u8,
+    /// The green component.
+    pub g: u8,
+    /// The blue component.
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Black, the default color of dark modules.
+    pub const BLACK: Rgb = Rgb { r: 0x00, g: 0x00, b: 0x00 };
+
+    /// White, the default color of light modules.
+    pub const WHITE: Rgb = Rgb { r: 0xff, g: 0xff, b: 0xff };
+
+    /// The luma of the color, using the Rec. 601 coefficients.
+    pub fn luma(self) -> u8 {
+        luma(self.r, self.g, self.b)
+    }
+}
+
+/// Formats the color in the CSS hexadecimal notation, e.g. `#ff8000`.
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Parameters of rendering a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderOptions {
+    /// The width and height of each module, in pixels. Defaults to 4.
+    pub module_size: usize,
+    /// The width of the light border around the code, in modules.
+    /// Defaults to 4, the minimum required by the specification.
+    pub quiet_zone: usize,
+    /// The color of dark modules. Defaults to black.
+    pub dark: Rgb,
+    /// The color of light modules and the quiet zone. Defaults to white.
+    pub light: Rgb,
+}
+
+impl RenderOptions {
+    /// Returns the width (and height) of the rendered image, in modules.
+    fn modules_across<M: Modules + ?Sized>(&self, code: &M) -> Result<usize> {
+        checked_add(checked_mul(self.quiet_zone, 2)?, code.size())
+    }
+
+    /// Returns the width (and height) of the rendered image, in pixels.
+    fn pixels_across<M: Modules + ?Sized>(&self, code: &M) -> Result<usize> {
+        checked_mul(self.modules_across(code)?, self.module_size)
+    }
+
+    /// Returns whether the pixel at column `x` and row `y` of the image
+    /// belongs to a dark module.
+    fn is_dark_pixel<M: Modules + ?Sized>(&self, code: &M, x: usize, y: usize) -> bool {
+        let mx = (x / self.module_size).wrapping_sub(self.quiet_zone);
+        let my = (y / self.module_size).wrapping_sub(self.quiet_zone);
+
+        // Coordinates in the quiet zone wrap around to huge values.
+        mx < code.size() && my < code.size() && code.is_dark(mx, my)
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            module_size: 4,
+            quiet_zone: 4,
+            dark: Rgb::BLACK,
+            light: Rgb::WHITE,
+        }
+    }
+}
+
+/// Renders the code as a standalone SVG document. Dark modules are drawn
+/// as a single path over a rectangle filling the background, and the
+/// coordinate system is scaled so that each module is a unit square.
+pub fn to_svg<M: Modules + ?Sized>(code: &M, options: &RenderOptions) -> Result<String> {
+    let modules = options.modules_across(code)?;
+    let pixels = options.pixels_across(code)?;
+    let mut svg = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" "#,
+            r#"width="{pixels}" height="{pixels}" viewBox="0 0 {modules} {modules}" "#,
+            r#"shape-rendering="crispEdges">"#, "\n",
+            r#"<rect width="100%" height="100%" fill="{light}"/>"#, "\n",
+            r#"<path fill="{dark}" d=""#,
+        ),
+        pixels = pixels,
+        modules = modules,
+        light = options.light,
+        dark = options.dark,
+    );
+
+    for y in 0..code.size() {
+        for x in (0..code.size()).filter(|&x| code.is_dark(x, y)) {
+            if !svg.ends_with('"') {
+                svg.push(' ');
+            }
+
+            svg += &format!(
+                "M{},{}h1v1h-1z",
+                x + options.quiet_zone,
+                y + options.quiet_zone,
+            );
+        }
+    }
+
+    svg += "\"/>\n</svg>\n";
+
+    Ok(svg)
+}
+
+/// Renders the code as text, using Unicode half block characters so that
+/// each character represents two vertically adjacent modules. Dark modules
+/// are drawn with blocks, so on terminals with a light foreground color
+/// and a dark background, the code appears inverted. Each row of text,
+/// including the last one, ends with a newline.
+pub fn to_terminal_string<M: Modules + ?Sized>(code: &M, quiet_zone: usize) -> String {
+    let across = code.size() + 2 * quiet_zone;
+    let is_dark = |x: usize, y: usize| {
+        let mx = x.wrapping_sub(quiet_zone);
+        let my = y.wrapping_sub(quiet_zone);
+        mx < code.size() && my < code.size() && code.is_dark(mx, my)
+    };
+    let mut text = String::with_capacity((across + 1) * (across + 1) / 2 * 3);
+
+    for y in (0..across).step_by(2) {
+        for x in 0..across {
+            let top = usize::from(is_dark(x, y));
+            let bottom = usize::from(is_dark(x, y + 1));
+            text.push(HALF_BLOCKS[top | bottom << 1]);
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+impl QrCode {
+    /// Renders the module matrix as text with a narrow quiet zone, for
+    /// debugging. See `render::to_terminal_string()` for details.
+    pub fn to_terminal_string(&self) -> String {
+        to_terminal_string(self, TERMINAL_QUIET_ZONE)
+    }
+}
+
+impl EncodedQr {
+    /// Renders the module matrix as text with a narrow quiet zone, for
+    /// debugging. See `render::to_terminal_string()` for details.
+    pub fn to_terminal_string(&self) -> String {
+        to_terminal_string(self, TERMINAL_QUIET_ZONE)
+    }
+}
+
+/// Renders the code as an 8-bit grayscale image, using the luma of the
+/// colors in `options`.
+pub fn to_image_buf<M: Modules + ?Sized>(code: &M, options: &RenderOptions) -> Result<ImageBuf> {
+    let pixels = options.pixels_across(code)?;
+    let (dark, light) = (options.dark.luma(), options.light.luma());
+    let mut data = Vec::with_capacity(checked_mul(pixels, pixels)?);
+
+    for y in 0..pixels {
+        data.extend((0..pixels).map(|x| {
+            if options.is_dark_pixel(code, x, y) { dark } else { light }
+        }));
+    }
+
+    ImageBuf::new(data, Vec2D { x: pixels, y: pixels })
+}
+
+/// Renders the code as an RGB PNG image.
+pub fn to_png<M: Modules + ?Sized>(code: &M, options: &RenderOptions) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    write_png(code, options, &mut png)?;
+    Ok(png)
+}
+
+/// Renders the code as an RGB PNG image into `writer`.
+pub fn write_png<M, W>(code: &M, options: &RenderOptions, mut writer: W) -> Result<()>
+    where M: Modules + ?Sized,
+          W: Write
+{
+    let pixels = options.pixels_across(code)?;
+
+    // Each row is preceded by its filter type, which is always 0 (none).
+    let row_len = checked_add(checked_mul(pixels, 3)?, 1)?;
+    let mut raw = Vec::with_capacity(checked_mul(row_len, pixels)?);
+
+    for y in 0..pixels {
+        raw.push(0);
+
+        for x in 0..pixels {
+            let color = if options.is_dark_pixel(code, x, y) { options.dark } else { options.light };
+            raw.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    write_rgb_png(&mut writer, Vec2D { x: pixels, y: pixels }, &raw)
+}
+
+/// Renders the code as a binary (`P4`) PBM image, in which dark modules
+/// are black. The colors in `options` are ignored.
+pub fn to_pbm<M: Modules + ?Sized>(code: &M, options: &RenderOptions) -> Result<Vec<u8>> {
+    let mut pbm = Vec::new();
+    write_pbm(code, options, &mut pbm)?;
+    Ok(pbm)
+}
+
+/// Renders the code as a binary (`P4`) PBM image into `writer`.
+pub fn write_pbm<M, W>(code: &M, options: &RenderOptions, mut writer: W) -> Result<()>
+    where M: Modules + ?Sized,
+          W: Write
+{
+    let pixels = options.pixels_across(code)?;
+
+    // Each row is packed into bytes, most significant bit first, and padded.
+    let row_len = checked_add(pixels, 7)? / 8;
+    let mut raster = Vec::with_capacity(checked_mul(row_len, pixels)?);
+
+    for y in 0..pixels {
+        let mut row = vec![0_u8; row_len];
+
+        for x in (0..pixels).filter(|&x| options.is_dark_pixel(code, x, y)) {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+
+        raster.extend_from_slice(&row);
+    }
+
+    write!(writer, "P4\n{} {}\n", pixels, pixels)?;
+    writer.write_all(&raster)?;
+
+    Ok(())
+}
+
+impl QrCode {
+    /// Renders the extracted modules as a PBM image. See `render::to_pbm()`.
+    pub fn to_pbm_bytes(&self, options: &RenderOptions) -> Result<Vec<u8>> {
+        to_pbm(self, options)
+    }
+
+    /// Renders the extracted modules as a PBM image, and writes it to a
+    /// file, e.g. in order to archive what was extracted from an image.
+    pub fn save_pbm<P: AsRef<Path>>(&self, path: P, options: &RenderOptions) -> Result<()> {
+        fs::write(path, self.to_pbm_bytes(options)?)?;
+        Ok(())
+    }
+
+    /// Renders the extracted modules as a PNG image. See `render::to_png()`.
+    pub fn to_png_bytes(&self, options: &RenderOptions) -> Result<Vec<u8>> {
+        to_png(self, options)
+    }
+
+    /// Renders the extracted modules as a PNG image, and writes it to a file.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, options: &RenderOptions) -> Result<()> {
+        fs::write(path, self.to_png_bytes(options)?)?;
+        Ok(())
+    }
+}
+
+/// An owned buffer of 8-bit RGB image data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RgbImage {
+    /// The pixels, row by row, 3 bytes each, without padding.
+    data: Vec<u8>,
+    /// The dimensions of the image.
+    size: Vec2D,
+}
+
+impl RgbImage {
+    /// Creates an image of the given size filled with a single color.
+    pub fn new(size: Vec2D, fill: Rgb) -> Result<Self> {
+        let num_pixels = checked_mul(size.x, size.y)?;
+        let mut data = Vec::with_capacity(checked_mul(num_pixels, 3)?);
+
+        for _ in 0..num_pixels {
+            data.extend_from_slice(&[fill.r, fill.g, fill.b]);
+        }
+
+        Ok(RgbImage { data, size })
+    }
+
+    /// Copies a grayscale image, e.g. in order to draw on it in color.
+    pub fn from_gray(image: &Image) -> Result<Self> {
+        let num_pixels = checked_mul(image.width(), image.height())?;
+        let mut data = Vec::with_capacity(checked_mul(num_pixels, 3)?);
+
+        for y in 0..image.height() {
+            for &gray in image.row(y) {
+                data.extend_from_slice(&[gray, gray, gray]);
+            }
+        }
+
+        Ok(RgbImage {
+            data,
+            size: Vec2D { x: image.width(), y: image.height() },
+        })
+    }
+
+    /// Return the raw data buffer, 3 bytes per pixel.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the width of (number of columns in) the image.
+    pub fn width(&self) -> usize {
+        self.size.x
+    }
+
+    /// Return the height of (number of rows in) the image.
+    pub fn height(&self) -> usize {
+        self.size.y
+    }
+
+    /// Returns the color of the pixel at column `x` and row `y`, or `None`
+    /// if it is outside the image.
+    pub fn get(&self, x: usize, y: usize) -> Option<Rgb> {
+        self.offset(x, y).map(|i| Rgb {
+            r: self.data[i],
+            g: self.data[i + 1],
+            b: self.data[i + 2],
+        })
+    }
+
+    /// Sets the color of the pixel at column `x` and row `y`. Pixels
+    /// outside the image are ignored, so that shapes can be drawn partly
+    /// off the image.
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if let Some(i) = self.offset(x, y) {
+            self.data[i..i + 3].copy_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    /// Encodes the image as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut png = Vec::new();
+        self.write_png(&mut png)?;
+        Ok(png)
+    }
+
+    /// Encodes the image as a PNG file into `writer`.
+    pub fn write_png<W: Write>(&self, mut writer: W) -> Result<()> {
+        let row_len = checked_mul(self.size.x, 3)?;
+        let mut raw = Vec::with_capacity(checked_mul(checked_add(row_len, 1)?, self.size.y)?);
+
+        // Rows are empty if the image is, and `chunks()` rejects length 0.
+        for row in self.data.chunks(row_len.max(1)) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        write_rgb_png(&mut writer, self.size, &raw)
+    }
+
+    /// Returns the index of the first byte of the pixel at column `x` and
+    /// row `y`, or `None` if it is outside the image.
+    fn offset(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.size.x && y < self.size.y {
+            Some((y * self.size.x + x) * 3)
+        } else {
+            None
+        }
+    }
+}
+
+/// Writes an RGB PNG image of the given size, whose rows in `raw` are
+/// already preceded by their filter type.
+fn write_rgb_png<W: Write>(writer: &mut W, size: Vec2D, raw: &[u8]) -> Result<()> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&usize_to_u32(size.x)?.to_be_bytes());
+    header.extend_from_slice(&usize_to_u32(size.y)?.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression and filtering, no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    writer.write_all(&PNG_SIGNATURE)?;
+    write_png_chunk(writer, b"IHDR", &header)?;
+    write_png_chunk(writer, b"IDAT", &zlib_stored(raw))?;
+    write_png_chunk(writer, b"IEND", &[])?;
+
+    Ok(())
+}
+
+/// Writes a PNG chunk: its length, type, data and CRC.
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    let crc = crc32(kind.iter().chain(data));
+
+    writer.write_all(&usize_to_u32(data.len())?.to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.to_be_bytes())?;
+
+    Ok(())
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let num_blocks = data.len() / MAX_STORED_BLOCK_LEN + 1;
+    let mut stream = Vec::with_capacity(data.len() + num_blocks * 5 + 6);
+
+    // Deflate with a 32 kB window, no preset dictionary, fastest level.
+    stream.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+
+    // Empty data still needs a single (final) block.
+    if chunks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0x00, 0x00, 0xff, 0xff]);
+    }
+
+    while let Some(chunk) = chunks.next() {
+        let is_final = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+
+        stream.push(u8::from(is_final));
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(chunk);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Computes the CRC-32 (ISO-HDLC) checksum of `bytes`, as used by PNG.
+fn crc32<'a, I: IntoIterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let remainder = bytes.into_iter().fold(!0_u32, |acc, &byte| {
+        (0..8).fold(acc ^ u32::from(byte), |r, _| {
+            if r & 1 != 0 { (r >> 1) ^ 0xedb8_8320 } else { r >> 1 }
+        })
+    });
+
+    !remainder
+}
+
+/// Computes the Adler-32 checksum of `bytes`, as used by zlib.
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+
+    let (a, b) = bytes.iter().fold((1_u32, 0_u32), |(sum, sum_of_sums), &byte| {
+        let next = (sum + u32::from(byte)) % MODULUS;
+        (next, (sum_of_sums + next) % MODULUS)
+    });
+
+    b << 16 | a
+}
@@ -0,0 +1,154 @@
+//! Self-describing JSON reports of decode runs.
+//!
+//! A `Report` records everything found in a single image: its dimensions,
+//! and for each detected code either the decoded information or the reason
+//! it couldn't be decoded. Codes are recorded as `schema::CodeRecord`s, so
+//! reports follow the same versioned schema as scan events:
+//!
+//! ```text
+//! {
+//!   "schema_version": 1,
+//!   "image": { "width": 640, "height": 480 },
+//!   "codes": [
+//!     {
+//!       "status": "decoded",
+//!       "code": { "corners": [[10, 10], ...], "version": 1, ..., "text": "hello" }
+//!     },
+//!     {
+//!       "status": "failed",
+//!       "corners": [[200, 40], [260, 42], [258, 101], [199, 99]],
+//!       "error": "decoding failed because the data failed ECC check"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The corners of a code which couldn't even be extracted are `null`.
+
+use std::fmt::Write;
+use decoder::Decoder;
+use geom::{ Image, QrCode, Vec2D };
+use schema::{ self, CodeRecord };
+use util::write_json_str;
+use error::{ Error, Result };
+
+/// What became of a code detected in the image.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// The code was decoded successfully.
+    Decoded(CodeRecord),
+    /// The code was detected, but it couldn't be extracted or decoded.
+    Failed {
+        /// The corners of the code, clockwise from the top left one,
+        /// or `None` if the code couldn't be extracted.
+        corners: Option<[Vec2D; 4]>,
+        /// The reason of the failure.
+        error: Error,
+    },
+}
+
+impl Outcome {
+    /// Appends the JSON representation of the outcome to `out`.
+    fn write_json(&self, out: &mut String) {
+        match *self {
+            Outcome::Decoded(ref code) => {
+                out.push_str("{\"status\":\"decoded\",\"code\":");
+                code.write_json(out);
+            }
+            Outcome::Failed { corners, error } => {
+                out.push_str("{\"status\":\"failed\",\"corners\":");
+
+                match corners {
+                    Some(ref corners) => schema::write_corners_json(out, corners),
+                    None => out.push_str("null"),
+                }
+
+                out.push_str(",\"error\":");
+                write_json_str(out, &error.to_string());
+            }
+        }
+
+        out.push('}');
+    }
+}
+
+/// The results of decoding a single image.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The width and height of the image.
+    pub image_size: Vec2D,
+    /// The codes detected in the image, in the order of detection.
+    pub codes: Vec<Outcome>,
+}
+
+impl Report {
+    /// Creates an empty report for an image of the given size.
+    pub fn new(image_size: Vec2D) -> Self {
+        Report { image_size, codes: Vec::new() }
+    }
+
+    /// Decodes every code in `image` with `decoder`, and records the
+    /// results. Fails only if the image can't be fed to the decoder;
+    /// codes which fail to decode are recorded as such.
+    pub fn scan(decoder: &mut Decoder, image: &Image) -> Result<Self> {
+        let mut report = Report::new(Vec2D { x: image.width(), y: image.height() });
+
+        for code in decoder.decode_image(image)? {
+            report.push(code);
+        }
+
+        Ok(report)
+    }
+
+    /// Decodes and records a code as returned by the iterator of a decoder.
+    pub fn push(&mut self, code: Result<QrCode>) {
+        let outcome = match code {
+            Ok(code) => match code.decode() {
+                Ok(info) => Outcome::Decoded(CodeRecord::new(&code, &info)),
+                Err(error) => Outcome::Failed { corners: Some(code.corners()), error },
+            },
+            Err(error) => Outcome::Failed { corners: None, error },
+        };
+
+        self.codes.push(outcome);
+    }
+
+    /// Returns the number of codes which were decoded successfully.
+    pub fn num_decoded(&self) -> usize {
+        self.codes
+            .iter()
+            .filter(|outcome| match **outcome {
+                Outcome::Decoded(_) => true,
+                Outcome::Failed { .. } => false,
+            })
+            .count()
+    }
+
+    /// Returns the compact JSON representation of the report.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    /// Appends the compact JSON representation of the report to `out`.
+    pub fn write_json(&self, out: &mut String) {
+        let _ = write!(
+            out,
+            "{{\"schema_version\":{},\"image\":{{\"width\":{},\"height\":{}}},\"codes\":[",
+            schema::VERSION,
+            self.image_size.x,
+            self.image_size.y,
+        );
+
+        for (i, outcome) in self.codes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            outcome.write_json(out);
+        }
+
+        out.push_str("]}");
+    }
+}
@@ -0,0 +1,358 @@
+//! Reading the modules of a detected code again, from the original image.
+//!
+//! `quirc` reads each module from a single pixel of an image thresholded
+//! with a running average, which occasionally gets a few modules wrong,
+//! e.g. near shadows, or when the code has little contrast. Since the
+//! corners of a code determine the perspective transform between its grid
+//! and the image, the modules can be re-read from the image with another
+//! threshold, and the code decoded again.
+//!
+//! `QrCode::decode_with_recovery()` climbs a ladder of such retries, from
+//! the cheapest to the most speculative one, each with a bounded number of
+//! attempts, and reports which one succeeded. `decode_with_options()` does
+//! the same within a time budget, skipping the passes it leaves no time for.
+
+use std::vec::Vec;
+use std::iter;
+use std::cmp::Ordering;
+use geom::{ Image, QrCode, Vec2D, Vec2Df };
+use perspective::sample;
+use encoder::data_modules;
+use bitstream::version_from_size;
+use retry::{ Pass, DecodePath, Decoded, DecodeOptions, Deadline, Undecoded };
+use error::{ Error, Result };
+
+/// The offsets from the center of a module, in modules, at which it is
+/// sampled along each axis. Staying well inside the module makes the
+/// samples robust against small errors of the corners.
+const SAMPLE_OFFSETS: [f64; 3] = [-0.25, 0.0, 0.25];
+
+/// The thresholds tried by `QrCode::decode_with_recovery()`, in order.
+const RECOVERY_THRESHOLDS: [Threshold; 4] = [
+    Threshold::Otsu,
+    Threshold::Local { radius: 2 },
+    Threshold::Local { radius: 4 },
+    Threshold::Local { radius: 8 },
+];
+
+/// The threshold of the code whose ambiguous modules are inverted by
+/// `QrCode::decode_with_recovery()`.
+const TOGGLE_THRESHOLD: Threshold = Threshold::Local { radius: 4 };
+
+/// The number of data modules closest to the threshold which are inverted,
+/// one at a time and in pairs, by `QrCode::decode_with_recovery()`. This
+/// bounds the number of attempts to `n + n * (n - 1) / 2`.
+const MAX_AMBIGUOUS_MODULES: usize = 8;
+
+/// How to tell dark modules from light ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Threshold {
+    /// Modules darker than the given brightness are dark.
+    Fixed(u8),
+    /// A single threshold for the whole code, separating the brightness
+    /// of its modules into two classes as well as possible (Otsu's method).
+    Otsu,
+    /// Each module is compared to the mean brightness of the modules at
+    /// most `radius` modules away from it, which copes with shadows and
+    /// uneven lighting.
+    Local {
+        /// The half width of the square of modules averaged, in modules.
+        radius: usize,
+    },
+}
+
+/// The brightness of each module of a code in an image.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Samples {
+    /// The number of modules along each side of the code.
+    size: usize,
+    /// The brightness of each module, row by row, or `None` if the module
+    /// lies outside the image.
+    values: Vec<Option<f64>>,
+}
+
+impl Samples {
+    /// Samples each module of `code` in `image`, averaging several points
+    /// around its center.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub(crate) fn new(code: &QrCode, image: &Image) -> Self {
+        let size = code.size();
+        let homography = code.homography();
+        let mut values = Vec::with_capacity(size * size);
+
+        for y in 0..size {
+            for x in 0..size {
+                let mut sum = 0.0;
+                let mut count = 0.0;
+
+                for &dy in &SAMPLE_OFFSETS {
+                    for &dx in &SAMPLE_OFFSETS {
+                        let (px, py) = homography.map(x as f64 + 0.5 + dx, y as f64 + 0.5 + dy);
+
+                        if let Some(value) = sample(image, Vec2Df { x: px, y: py }) {
+                            sum += value;
+                            count += 1.0;
+                        }
+                    }
+                }
+
+                values.push(if count > 0.0 { Some(sum / count) } else { None });
+            }
+        }
+
+        Samples { size, values }
+    }
+
+    /// The brightness of the module at `(x, y)`, or `None` if it lies
+    /// outside the image.
+    fn get(&self, x: usize, y: usize) -> Option<f64> {
+        self.values[y * self.size + x]
+    }
+
+    /// Computes the threshold each module is compared to, row by row, or
+    /// `None` for modules outside the image, and if no threshold can be
+    /// computed at all.
+    fn thresholds(&self, strategy: Threshold) -> Vec<Option<f64>> {
+        match strategy {
+            Threshold::Fixed(value) => self.uniform(Some(f64::from(value))),
+            Threshold::Otsu => self.uniform(self.otsu()),
+            Threshold::Local { radius } => self.local(radius),
+        }
+    }
+
+    /// Returns a copy of `code` with the modules inside the image set by
+    /// comparing their brightness to `strategy`.
+    pub(crate) fn apply(&self, code: &QrCode, strategy: Threshold) -> QrCode {
+        let thresholds = self.thresholds(strategy);
+        let mut result = *code;
+
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if let (Some(value), Some(threshold)) = (self.get(x, y), thresholds[y * self.size + x]) {
+                    result.set_bit(Vec2D { x, y }, value < threshold);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns copies of `code`, resampled with `strategy`, in which the
+    /// data modules closest to the threshold are inverted: first each of
+    /// them alone, then each pair of them, the most ambiguous ones first.
+    /// Returns no codes if the size of `code` isn't that of a version.
+    pub(crate) fn toggle_candidates(&self, code: &QrCode, strategy: Threshold) -> Vec<QrCode> {
+        let modules = match version_from_size(self.size) {
+            Ok(version) => data_modules(version),
+            Err(_) => return Vec::new(),
+        };
+        let thresholds = self.thresholds(strategy);
+        let base = self.apply(code, strategy);
+        let mut ambiguous: Vec<(f64, Vec2D)> = modules
+            .into_iter()
+            .filter_map(|coord| {
+                let value = self.get(coord.x, coord.y)?;
+                let threshold = thresholds[coord.y * self.size + coord.x]?;
+                Some(((value - threshold).abs(), coord))
+            })
+            .collect();
+
+        ambiguous.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        ambiguous.truncate(MAX_AMBIGUOUS_MODULES);
+
+        let toggled = |coords: &[Vec2D]| {
+            let mut candidate = base;
+
+            for &coord in coords {
+                candidate.set_bit(coord, !base.bit_at(coord));
+            }
+
+            candidate
+        };
+        let mut candidates: Vec<QrCode> = ambiguous.iter().map(|&(_, coord)| toggled(&[coord])).collect();
+
+        for (i, &(_, first)) in ambiguous.iter().enumerate() {
+            for &(_, second) in &ambiguous[i + 1..] {
+                candidates.push(toggled(&[first, second]));
+            }
+        }
+
+        candidates
+    }
+
+    /// The same threshold for each module inside the image.
+    fn uniform(&self, threshold: Option<f64>) -> Vec<Option<f64>> {
+        self.values.iter().map(|value| value.and(threshold)).collect()
+    }
+
+    /// The threshold maximizing the variance between the classes of light
+    /// and dark modules, or `None` if all modules are equally bright.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_sign_loss, cast_precision_loss))]
+    fn otsu(&self) -> Option<f64> {
+        let mut histogram = [0_usize; 256];
+
+        for &value in self.values.iter().flatten() {
+            histogram[value.round().max(0.0).min(255.0) as usize] += 1;
+        }
+
+        let total: usize = histogram.iter().sum();
+        let total_sum: f64 = histogram.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+        let mut below = 0_usize;
+        let mut below_sum = 0.0;
+        let mut best: Option<(f64, usize)> = None;
+
+        for (i, &n) in histogram.iter().enumerate() {
+            below += n;
+            below_sum += i as f64 * n as f64;
+
+            if below == 0 || below == total {
+                continue;
+            }
+
+            let above = total - below;
+            let mean_below = below_sum / below as f64;
+            let mean_above = (total_sum - below_sum) / above as f64;
+            let difference = mean_above - mean_below;
+            let variance = below as f64 * above as f64 * difference * difference;
+
+            if best.map_or(true, |(max, _)| variance > max) {
+                best = Some((variance, i));
+            }
+        }
+
+        // Values up to and including the best bin are dark.
+        best.map(|(_, i)| i as f64 + 0.5)
+    }
+
+    /// The mean brightness of the modules around each module.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn local(&self, radius: usize) -> Vec<Option<f64>> {
+        let size = self.size;
+        let mut thresholds = Vec::with_capacity(self.values.len());
+
+        for y in 0..size {
+            for x in 0..size {
+                if self.get(x, y).is_none() {
+                    thresholds.push(None);
+                    continue;
+                }
+
+                let rows = y.saturating_sub(radius)..(y + radius + 1).min(size);
+                let (sum, count) = rows.fold((0.0, 0.0), |acc, wy| {
+                    let columns = x.saturating_sub(radius)..(x + radius + 1).min(size);
+
+                    columns.filter_map(|wx| self.get(wx, wy)).fold(acc, |(sum, count), value| {
+                        (sum + value, count + 1.0)
+                    })
+                });
+
+                thresholds.push(Some(sum / count));
+            }
+        }
+
+        thresholds
+    }
+}
+
+impl QrCode {
+    /// Reads the modules of the code again from `image`, which must be the
+    /// image the code was detected in, using the perspective transform
+    /// given by its corners, and telling dark modules from light ones with
+    /// `threshold`. The result can be decoded like the original code.
+    ///
+    /// Modules outside the image, and all of them if the threshold can't be
+    /// computed, e.g. because the code has no contrast at all, keep their
+    /// original values.
+    pub fn resample_from(&self, image: &Image, threshold: Threshold) -> QrCode {
+        Samples::new(self, image).apply(self, threshold)
+    }
+
+    /// Decodes the code, retrying with increasingly speculative strategies
+    /// if that fails:
+    ///
+    /// 1. decoding the code as extracted (`Pass::Plain`),
+    /// 2. reading its modules again from `image` with each of a few
+    ///    thresholds (`Pass::Resampled`), see `resample_from()`, and
+    /// 3. inverting the data modules whose brightness is closest to a local
+    ///    threshold, one at a time and in pairs (`Pass::ModulesToggled`).
+    ///
+    /// `image` must be the image the code was detected in. The path of the
+    /// result lists the passes tried, the last one being the one that
+    /// succeeded. If every pass fails, the error of the plain decoding is
+    /// returned, since the others only reflect failed guesses.
+    pub fn decode_with_recovery(&self, image: &Image) -> Result<Decoded> {
+        self.decode_with_options(image, &DecodeOptions::new()).map_err(Error::from)
+    }
+
+    /// Decodes the code like `decode_with_recovery()`, but starts no further
+    /// passes once the time budget of `options` is used up, and stops
+    /// inverting modules between attempts, in which case that pass counts
+    /// as skipped. The plain pass is always tried.
+    ///
+    /// If every pass tried fails, the error of the plain decoding is
+    /// returned along with the path, whose `skipped()` passes tell whether
+    /// the code might have been decoded given more time.
+    pub fn decode_with_options(
+        &self,
+        image: &Image,
+        options: &DecodeOptions,
+    ) -> ::std::result::Result<Decoded, Undecoded> {
+        let deadline = Deadline::start(options);
+        let mut path = DecodePath::new();
+
+        path.push(Pass::Plain);
+
+        let error = match self.decode() {
+            Ok(info) => return Ok(Decoded { code: *self, info, path }),
+            Err(error) => error,
+        };
+        let passes = RECOVERY_THRESHOLDS
+            .iter()
+            .map(|&threshold| Pass::Resampled(threshold))
+            .chain(iter::once(Pass::ModulesToggled));
+        let mut samples = None;
+
+        for pass in passes {
+            if deadline.has_passed() {
+                path.skip(pass);
+                continue;
+            }
+
+            let sampled = samples.get_or_insert_with(|| Samples::new(self, image));
+            let mut decoded = None;
+            let mut completed = true;
+
+            match pass {
+                Pass::Resampled(threshold) => {
+                    let code = sampled.apply(self, threshold);
+                    decoded = code.decode().ok().map(|info| (code, info));
+                }
+                _ => {
+                    for code in sampled.toggle_candidates(self, TOGGLE_THRESHOLD) {
+                        if deadline.has_passed() {
+                            completed = false;
+                            break;
+                        }
+
+                        if let Ok(info) = code.decode() {
+                            decoded = Some((code, info));
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if completed {
+                path.push(pass);
+            } else {
+                path.skip(pass);
+            }
+
+            if let Some((code, info)) = decoded {
+                return Ok(Decoded { code, info, path });
+            }
+        }
+
+        Err(Undecoded { error, path })
+    }
+}
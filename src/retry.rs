@@ -0,0 +1,188 @@
+//! Bookkeeping of the fallback strategies used while decoding.
+//!
+//! Every result produced by a decoding method that may retry carries a
+//! `DecodePath`, so that it can be told (e.g. in telemetry) which of the
+//! strategies were actually needed in order to read a given code.
+//!
+//! Retrying takes time, which real-time applications may not have: with a
+//! time budget set in `DecodeOptions`, no further passes are started once
+//! it is used up, and the path lists the passes skipped for lack of time.
+
+use std::fmt;
+use std::time::{ Duration, Instant };
+use geom::QrCode;
+use info::Info;
+use resample::Threshold;
+use error::Error;
+
+/// A strategy tried in order to detect or decode a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pass {
+    /// Detection and decoding of the image as supplied by the caller.
+    Plain,
+    /// Detection and decoding after suppressing glare over the code.
+    GlareSuppressed,
+    /// Decoding after reading the modules again from the image with the
+    /// given threshold.
+    Resampled(Threshold),
+    /// Decoding after inverting a few modules of a resampled code whose
+    /// brightness is closest to the threshold.
+    ModulesToggled,
+}
+
+impl Pass {
+    /// Returns a stable, machine-readable identifier of the pass.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Pass::Plain           => "plain",
+            Pass::GlareSuppressed => "glare_suppressed",
+            Pass::Resampled(_)    => "resampled",
+            Pass::ModulesToggled  => "modules_toggled",
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Options of the decoding methods that may retry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    /// The time after which no further passes are started.
+    time_budget: Option<Duration>,
+}
+
+impl DecodeOptions {
+    /// Creates options without a time budget.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops starting new passes once `budget` has elapsed since decoding
+    /// began. The plain pass is always tried, and a pass that is already
+    /// running may stop early between attempts, so the budget is a soft
+    /// limit, exceeded by at most the duration of a single attempt. Passes
+    /// stopped early are reported as skipped.
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Returns the time budget, if any.
+    pub fn budget(&self) -> Option<Duration> {
+        self.time_budget
+    }
+}
+
+/// The point in time after which no further passes are started.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Starts the clock of the time budget of `options`, if any.
+    pub(crate) fn start(options: &DecodeOptions) -> Self {
+        Deadline(options.time_budget.and_then(|budget| Instant::now().checked_add(budget)))
+    }
+
+    /// Returns `true` if the time budget is used up.
+    pub(crate) fn has_passed(&self) -> bool {
+        self.0.map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
+
+/// The passes that led to a result, in the order they were tried.
+/// The last one is the pass that actually produced the result.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DecodePath {
+    /// The passes tried so far.
+    passes: Vec<Pass>,
+    /// The passes not tried, or not completed, because the time budget was
+    /// used up.
+    skipped: Vec<Pass>,
+}
+
+impl DecodePath {
+    /// Creates an empty path.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a pass to the path.
+    pub fn push(&mut self, pass: Pass) {
+        self.passes.push(pass);
+    }
+
+    /// Records a pass that wasn't tried, or was stopped early, because the
+    /// time budget was used up.
+    pub fn skip(&mut self, pass: Pass) {
+        self.skipped.push(pass);
+    }
+
+    /// Returns the passes in the order they were tried.
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Returns the passes that weren't tried, or were stopped early,
+    /// because the time budget was used up, in the order they would have
+    /// been tried.
+    pub fn skipped(&self) -> &[Pass] {
+        &self.skipped
+    }
+
+    /// Returns the pass that produced the result, if any.
+    pub fn succeeded_with(&self) -> Option<Pass> {
+        self.passes.last().cloned()
+    }
+
+    /// Returns `true` if the result didn't need any fallback strategies.
+    pub fn is_plain(&self) -> bool {
+        self.passes == [Pass::Plain]
+    }
+}
+
+impl fmt::Display for DecodePath {
+    /// Formats the path as a comma-separated list of pass identifiers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, pass) in self.passes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+
+            f.write_str(pass.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A successfully decoded QR code, along with how it was obtained.
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    /// The location and the raw bitmap of the code.
+    pub code: QrCode,
+    /// The information decoded from the code.
+    pub info: Info,
+    /// The strategies that were needed in order to decode the code.
+    pub path: DecodePath,
+}
+
+/// A code that none of the passes tried could decode.
+#[derive(Debug, Clone)]
+pub struct Undecoded {
+    /// The error of the plain pass, since the others only reflect failed
+    /// guesses, unless a retry failed for another reason, or the code
+    /// couldn't even be extracted, in which case the path is empty.
+    pub error: Error,
+    /// The passes tried, all of which failed, and those skipped.
+    pub path: DecodePath,
+}
+
+impl From<Undecoded> for Error {
+    fn from(undecoded: Undecoded) -> Self {
+        undecoded.error
+    }
+}
@@ -0,0 +1,189 @@
+//! The stable, versioned representation of scan results for interchange.
+//!
+//! Everything that exports results out of the process (the JSON output of
+//! tools, logs, event sinks, etc.) should go through `ScanEvent` in order to
+//! agree on a single shape. The JSON form of an event looks like this:
+//!
+//! ```text
+//! {
+//!   "schema_version": 1,
+//!   "kind": "detected",
+//!   "frame": 0,
+//!   "code": {
+//!     "corners": [[10, 10], [110, 10], [110, 110], [10, 110]],
+//!     "version": 1,
+//!     "ecc_level": "M",
+//!     "mask_id": 3,
+//!     "data_type": "byte",
+//!     "eci": 0,
+//!     "payload_base64": "aGVsbG8=",
+//!     "text": "hello"
+//!   }
+//! }
+//! ```
+//!
+//! Within one version of the schema, fields are never removed, renamed or
+//! reinterpreted. Any such change bumps `VERSION`, and `ScanEvent::upgrade()`
+//! learns to convert events recorded with the previous versions.
+
+use std::fmt::Write;
+use geom::{ QrCode, Vec2D };
+use info::{ Info, EccLevel, DataType };
+use util::{ write_json_str, base64_encode };
+use error::{ Error, Result };
+
+/// The current version of the schema.
+pub const VERSION: u32 = 1;
+
+/// What happened to a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// The code was found in a still image.
+    Detected,
+    /// The code entered the field of view in a sequence of frames.
+    Appeared,
+    /// The code is still in view, but it has moved or its content changed.
+    Updated,
+    /// The code has left the field of view.
+    Disappeared,
+}
+
+impl EventKind {
+    /// Returns the identifier of the kind used in serialized events.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Detected    => "detected",
+            EventKind::Appeared    => "appeared",
+            EventKind::Updated     => "updated",
+            EventKind::Disappeared => "disappeared",
+        }
+    }
+}
+
+/// A decoded code, as recorded in a `ScanEvent`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CodeRecord {
+    /// The corners of the code, clockwise from the top left one.
+    pub corners: [Vec2D; 4],
+    /// The version of the code.
+    pub version: u8,
+    /// The error correction level of the code.
+    pub ecc_level: EccLevel,
+    /// The mask ID of the code.
+    pub mask_id: u8,
+    /// The highest-valued data type found in the code.
+    pub data_type: DataType,
+    /// The ECI assignment number, 0 if absent.
+    pub eci: u32,
+    /// The raw payload of the code.
+    pub payload: Vec<u8>,
+}
+
+impl CodeRecord {
+    /// Records the location of a code and the information decoded from it.
+    pub fn new(code: &QrCode, info: &Info) -> Self {
+        CodeRecord {
+            corners: code.corners(),
+            version: info.version().number(),
+            ecc_level: info.ecc_level(),
+            mask_id: info.mask_id(),
+            data_type: info.data_type(),
+            eci: info.raw_eci(),
+            payload: info.payload().to_vec(),
+        }
+    }
+
+    /// Appends the JSON representation of the record to `out`.
+    pub(crate) fn write_json(&self, out: &mut String) {
+        out.push_str("{\"corners\":");
+        write_corners_json(out, &self.corners);
+
+        let _ = write!(
+            out,
+            ",\"version\":{},\"ecc_level\":\"{}\",\"mask_id\":{},\"data_type\":\"{}\",\"eci\":{}",
+            self.version,
+            self.ecc_level,
+            self.mask_id,
+            self.data_type,
+            self.eci,
+        );
+
+        out.push_str(",\"payload_base64\":");
+        write_json_str(out, &base64_encode(&self.payload));
+        out.push_str(",\"text\":");
+        write_json_str(out, &String::from_utf8_lossy(&self.payload));
+        out.push('}');
+    }
+}
+
+/// Something that happened to a code in a still image or a video.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScanEvent {
+    /// The version of the schema the event was recorded with.
+    pub schema_version: u32,
+    /// What happened to the code.
+    pub kind: EventKind,
+    /// The index of the frame in which it happened (0 for still images).
+    pub frame: u64,
+    /// The code itself.
+    pub code: CodeRecord,
+}
+
+impl ScanEvent {
+    /// Creates an event using the current version of the schema.
+    pub fn new(kind: EventKind, frame: u64, code: CodeRecord) -> Self {
+        ScanEvent {
+            schema_version: VERSION,
+            kind,
+            frame,
+            code,
+        }
+    }
+
+    /// Converts an event recorded with any supported version of the schema
+    /// to the current version. Versions newer than `VERSION`, and ones that
+    /// never existed, result in `Error::UnsupportedSchemaVersion`.
+    pub fn upgrade(self) -> Result<Self> {
+        // Each older version gets an arm here converting it to its
+        // successor, and then recursing until `VERSION` is reached.
+        match self.schema_version {
+            VERSION => Ok(self),
+            version => Err(Error::UnsupportedSchemaVersion(version)),
+        }
+    }
+
+    /// Returns the compact JSON representation of the event.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    /// Appends the compact JSON representation of the event to `out`.
+    pub fn write_json(&self, out: &mut String) {
+        let _ = write!(
+            out,
+            "{{\"schema_version\":{},\"kind\":\"{}\",\"frame\":{},\"code\":",
+            self.schema_version,
+            self.kind.as_str(),
+            self.frame,
+        );
+        self.code.write_json(out);
+        out.push('}');
+    }
+}
+
+/// Appends the corners of a code to `out` as a JSON array of `[x, y]` pairs.
+pub(crate) fn write_corners_json(out: &mut String, corners: &[Vec2D; 4]) {
+    out.push('[');
+
+    for (i, corner) in corners.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(out, "[{},{}]", corner.x, corner.y);
+    }
+
+    out.push(']');
+}
@@ -0,0 +1,166 @@
+//! Structured append: messages split across a sequence of up to 16 symbols.
+//!
+//! Each symbol of a sequence starts with a header containing its position,
+//! the number of symbols, and a parity byte, which is the XOR of all bytes
+//! of the whole message and identifies the sequence.
+//! `StructuredAppendAssembler` collects the symbols, possibly from many
+//! frames and in any order, and verifies the parity of the reassembled
+//! message.
+
+use std::fmt;
+#[cfg(feature = "std")]
+use std::error;
+use std::vec::Vec;
+use info::Info;
+
+/// The structured append header of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StructuredAppend {
+    /// The zero-based position of the symbol in the sequence.
+    pub index: u8,
+    /// The number of symbols in the sequence, from 1 to 16.
+    pub total: u8,
+    /// The XOR of all bytes of the whole message.
+    pub parity: u8,
+}
+
+/// An error which can occur while reassembling a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssemblyError {
+    /// The symbol has no structured append header.
+    NotStructured,
+    /// The number of symbols or the parity of the symbol differs from
+    /// those of the symbols seen before, i.e. it belongs to another message.
+    ForeignSymbol,
+    /// A symbol at the same position, but with a different payload, has
+    /// already been seen.
+    Conflict,
+    /// Not all symbols of the sequence have been seen yet.
+    Incomplete,
+    /// The parity of the reassembled message is incorrect.
+    ParityMismatch,
+}
+
+impl AssemblyError {
+    /// Returns a human-readable error message.
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AssemblyError::NotStructured  => "symbol has no structured append header",
+            AssemblyError::ForeignSymbol  => "symbol belongs to a different sequence",
+            AssemblyError::Conflict       => "conflicting symbols at the same position",
+            AssemblyError::Incomplete     => "not all symbols of the sequence were seen",
+            AssemblyError::ParityMismatch => "parity of the reassembled message is incorrect",
+        }
+    }
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for AssemblyError {
+    fn description(&self) -> &str {
+        self.to_str()
+    }
+}
+
+/// Collects the symbols of a structured append sequence, and reassembles
+/// the message they encode.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct StructuredAppendAssembler {
+    /// The header of the first symbol seen, which every other symbol
+    /// must agree with (apart from its index).
+    header: Option<StructuredAppend>,
+    /// The payloads of the symbols, indexed by their position.
+    parts: Vec<Option<Vec<u8>>>,
+}
+
+impl StructuredAppendAssembler {
+    /// Creates an assembler which hasn't seen any symbols yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a decoded symbol to the sequence. Returns `true` if it wasn't
+    /// seen before, and `false` if it is a duplicate, which is common when
+    /// scanning a video stream.
+    pub fn push(&mut self, info: &Info) -> Result<bool, AssemblyError> {
+        let header = info.structured_append().ok_or(AssemblyError::NotStructured)?;
+
+        if header.index >= header.total {
+            return Err(AssemblyError::ForeignSymbol);
+        }
+
+        match self.header {
+            Some(first) if first.total != header.total || first.parity != header.parity => {
+                return Err(AssemblyError::ForeignSymbol);
+            }
+            Some(_) => {}
+            None => {
+                self.header = Some(header);
+                self.parts = vec![None; usize::from(header.total)];
+            }
+        }
+
+        let part = &mut self.parts[usize::from(header.index)];
+
+        match *part {
+            Some(ref payload) if payload.as_slice() == info.payload() => Ok(false),
+            Some(_) => Err(AssemblyError::Conflict),
+            None => {
+                *part = Some(info.payload().to_vec());
+                Ok(true)
+            }
+        }
+    }
+
+    /// Returns the header shared by the symbols of the sequence, with the
+    /// index of the first symbol seen, or `None` if no symbols were seen.
+    pub fn header(&self) -> Option<StructuredAppend> {
+        self.header
+    }
+
+    /// Returns the positions of the symbols which haven't been seen yet,
+    /// in increasing order. Empty if no symbols were seen at all.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    pub fn missing(&self) -> Vec<u8> {
+        self.parts
+            .iter()
+            .enumerate()
+            .filter(|&(_, part)| part.is_none())
+            .map(|(i, _)| i as u8)
+            .collect()
+    }
+
+    /// Returns whether all symbols of the sequence have been seen.
+    pub fn is_complete(&self) -> bool {
+        self.header.is_some() && self.parts.iter().all(Option::is_some)
+    }
+
+    /// Returns the reassembled message, after verifying its parity.
+    pub fn payload(&self) -> Result<Vec<u8>, AssemblyError> {
+        let header = match self.header {
+            Some(header) if self.is_complete() => header,
+            _ => return Err(AssemblyError::Incomplete),
+        };
+
+        let payload: Vec<u8> = self.parts.iter().flatten().flatten().cloned().collect();
+        let parity = payload.iter().fold(0, |acc, &byte| acc ^ byte);
+
+        if parity == header.parity {
+            Ok(payload)
+        } else {
+            Err(AssemblyError::ParityMismatch)
+        }
+    }
+
+    /// Forgets all symbols, so that a new sequence can be assembled.
+    pub fn clear(&mut self) {
+        self.header = None;
+        self.parts.clear();
+    }
+}
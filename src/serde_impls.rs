@@ -0,0 +1,240 @@
+//! `Serialize` and `Deserialize` implementations for types which can't
+//! simply derive them, because they wrap FFI structs or foreign types.
+//!
+//! `Info` and `QrCode` are (de)serialized as plain records of their
+//! high-level properties. Deserialized values are validated just like the
+//! ones obtained from `quirc`, so they are indistinguishable from those.
+
+#[cfg(feature = "std")]
+use std::io;
+use std::vec::Vec;
+use std::str::{ self, Utf8Error };
+use serde::{ Serialize, Serializer, Deserialize, Deserializer };
+use serde::de::Error as DeError;
+use geom::{ QrCode, Vec2D };
+use info::{ Info, EccLevel, DataType };
+use sequence::StructuredAppend;
+use quirc_sys::{ quirc_code, quirc_data, quirc_point };
+use quirc_sys::{ QUIRC_MAX_BITMAP, QUIRC_MAX_PAYLOAD };
+use util::usize_to_int;
+
+/// The serialized form of `Info`.
+#[derive(Serialize, Deserialize)]
+struct InfoRecord {
+    /// The version number.
+    version: u8,
+    /// The error correction level.
+    ecc_level: EccLevel,
+    /// The ID of the data mask.
+    mask_id: u8,
+    /// The highest-valued data type.
+    data_type: DataType,
+    /// The ECI assignment number, 0 if absent.
+    eci: u32,
+    /// The raw payload.
+    payload: Vec<u8>,
+    /// The structured append header, if any.
+    structured_append: Option<StructuredAppend>,
+}
+
+impl Serialize for Info {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InfoRecord {
+            version: self.version().number(),
+            ecc_level: self.ecc_level(),
+            mask_id: self.mask_id(),
+            data_type: self.data_type(),
+            eci: self.raw_eci(),
+            payload: self.payload().to_vec(),
+            structured_append: self.structured_append(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Info {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = InfoRecord::deserialize(deserializer)?;
+        let len = record.payload.len();
+
+        if record.version < 1 || record.version > 40 {
+            return Err(D::Error::custom("version must be in the range 1...40"));
+        }
+        if record.mask_id > 7 {
+            return Err(D::Error::custom("mask ID must be in the range 0...7"));
+        }
+        if len > QUIRC_MAX_PAYLOAD {
+            return Err(D::Error::custom("payload is too long"));
+        }
+
+        let mut raw = quirc_data::default();
+
+        raw.version = record.version.into();
+        raw.ecc_level = record.ecc_level as _;
+        raw.mask = record.mask_id.into();
+        raw.data_type = record.data_type as _;
+        raw.payload[..len].copy_from_slice(&record.payload);
+        raw.payload_len = usize_to_int(len).map_err(D::Error::custom)?;
+        raw.eci = record.eci;
+
+        let mut info = Info::from_raw(raw);
+        info.set_structured_append(record.structured_append);
+
+        Ok(info)
+    }
+}
+
+/// The serialized form of `QrCode`.
+#[derive(Serialize, Deserialize)]
+struct QrCodeRecord {
+    /// The corners, clockwise from the top left one.
+    corners: [Vec2D; 4],
+    /// The number of modules along each side.
+    size: usize,
+    /// The bitmap, packed as in `QrCode::bitmap()`.
+    bitmap: Vec<u8>,
+}
+
+impl Serialize for QrCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        QrCodeRecord {
+            corners: [
+                self.top_left_corner(),
+                self.top_right_corner(),
+                self.bottom_right_corner(),
+                self.bottom_left_corner(),
+            ],
+            size: self.size(),
+            bitmap: self.bitmap().to_vec(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for QrCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = QrCodeRecord::deserialize(deserializer)?;
+        let num_bytes = record.size
+            .checked_mul(record.size)
+            .map(|num_bits| (num_bits + 7) / 8)
+            .ok_or_else(|| D::Error::custom("code size is too large"))?;
+
+        if num_bytes > QUIRC_MAX_BITMAP {
+            return Err(D::Error::custom("code size is too large"));
+        }
+        if record.bitmap.len() != num_bytes {
+            return Err(D::Error::custom("bitmap length doesn't match code size"));
+        }
+
+        let mut raw = quirc_code::default();
+
+        for (point, corner) in raw.corners.iter_mut().zip(&record.corners) {
+            *point = quirc_point {
+                x: usize_to_int(corner.x).map_err(D::Error::custom)?,
+                y: usize_to_int(corner.y).map_err(D::Error::custom)?,
+            };
+        }
+
+        raw.size = usize_to_int(record.size).map_err(D::Error::custom)?;
+        raw.cell_bitmap[..num_bytes].copy_from_slice(&record.bitmap);
+
+        QrCode::from_raw(raw).map_err(D::Error::custom)
+    }
+}
+
+/// (De)serializes `io::ErrorKind` by the name of the variant, for use
+/// with `#[serde(with)]`. Kinds which aren't known by name become `Other`.
+#[cfg(feature = "std")]
+pub mod io_error_kind {
+    use super::*;
+
+    /// The serializable kinds of I/O errors, and their names.
+    const KINDS: &[(io::ErrorKind, &str)] = &[
+        (io::ErrorKind::NotFound,          "NotFound"),
+        (io::ErrorKind::PermissionDenied,  "PermissionDenied"),
+        (io::ErrorKind::ConnectionRefused, "ConnectionRefused"),
+        (io::ErrorKind::ConnectionReset,   "ConnectionReset"),
+        (io::ErrorKind::ConnectionAborted, "ConnectionAborted"),
+        (io::ErrorKind::NotConnected,      "NotConnected"),
+        (io::ErrorKind::AddrInUse,         "AddrInUse"),
+        (io::ErrorKind::AddrNotAvailable,  "AddrNotAvailable"),
+        (io::ErrorKind::BrokenPipe,        "BrokenPipe"),
+        (io::ErrorKind::AlreadyExists,     "AlreadyExists"),
+        (io::ErrorKind::WouldBlock,        "WouldBlock"),
+        (io::ErrorKind::InvalidInput,      "InvalidInput"),
+        (io::ErrorKind::InvalidData,       "InvalidData"),
+        (io::ErrorKind::TimedOut,          "TimedOut"),
+        (io::ErrorKind::WriteZero,         "WriteZero"),
+        (io::ErrorKind::Interrupted,       "Interrupted"),
+        (io::ErrorKind::UnexpectedEof,     "UnexpectedEof"),
+        (io::ErrorKind::Other,             "Other"),
+    ];
+
+    /// Serializes the name of the kind.
+    pub fn serialize<S: Serializer>(kind: &io::ErrorKind, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = KINDS
+            .iter()
+            .find(|&&(known, _)| known == *kind)
+            .map_or("Other", |&(_, name)| name);
+
+        serializer.serialize_str(name)
+    }
+
+    /// Deserializes a kind from its name.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<io::ErrorKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Ok(KINDS
+            .iter()
+            .find(|&&(_, known)| known == name)
+            .map_or(io::ErrorKind::Other, |&(kind, _)| kind))
+    }
+}
+
+/// (De)serializes `Utf8Error` by its position and the length of the
+/// invalid sequence, for use with `#[serde(with)]`.
+pub mod utf8_error {
+    use super::*;
+
+    /// The serialized form of `Utf8Error`.
+    #[derive(Serialize, Deserialize)]
+    struct Utf8ErrorRecord {
+        /// The length of the valid prefix of the input.
+        valid_up_to: usize,
+        /// The length of the invalid sequence, or `None` if the input
+        /// ended in the middle of a sequence.
+        error_len: Option<usize>,
+    }
+
+    /// Serializes the position and the length of the invalid sequence.
+    pub fn serialize<S: Serializer>(error: &Utf8Error, serializer: S) -> Result<S::Ok, S::Error> {
+        Utf8ErrorRecord {
+            valid_up_to: error.valid_up_to(),
+            error_len: error.error_len(),
+        }.serialize(serializer)
+    }
+
+    /// Deserializes an error, which can't be constructed directly, by
+    /// reproducing it with an input of the same shape. The only text the
+    /// crate validates comes from payloads, so longer valid prefixes are
+    /// rejected rather than allocated.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Utf8Error, D::Error> {
+        let record = Utf8ErrorRecord::deserialize(deserializer)?;
+
+        if record.valid_up_to > QUIRC_MAX_PAYLOAD {
+            return Err(D::Error::custom("valid prefix is longer than any payload"));
+        }
+
+        let invalid: &[u8] = match record.error_len {
+            None    => b"\xe2\x82",
+            Some(1) => b"\xff",
+            Some(2) => b"\xe2\x82(",
+            Some(3) => b"\xf0\x90\x80(",
+            Some(_) => return Err(D::Error::custom("invalid UTF-8 sequence length")),
+        };
+        let mut input = vec![b'0'; record.valid_up_to];
+        input.extend_from_slice(invalid);
+
+        str::from_utf8(&input)
+            .err()
+            .ok_or_else(|| D::Error::custom("input was expected to be invalid UTF-8"))
+    }
+}
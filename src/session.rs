@@ -0,0 +1,234 @@
+//! Reconstruction of codes that are occluded differently across frames.
+//!
+//! When e.g. a finger covers one corner of a code in one frame and another
+//! corner in the next one, neither frame may be decodable on its own, even
+//! though together they contain every module. A `Session` collects the
+//! sampled module grids of one physical code over several frames, and tries
+//! to combine them into a grid that survives error correction.
+
+use std::collections::VecDeque;
+use geom::{ QrCode, Vec2D };
+use info::Info;
+use error::{ Error, DecodingErrorKind, Result };
+
+/// The number of frames retained by a session created using `new()`.
+const DEFAULT_CAPACITY: usize = 8;
+/// The maximal number of regions of disagreement between two frames for
+/// which every combination is tried. There are `2^MAX_REGIONS` of them.
+const MAX_REGIONS: usize = 6;
+/// Regions of disagreement smaller than this are considered to be noise
+/// rather than occlusion, and are left to the error correction.
+const MIN_REGION_MODULES: usize = 4;
+/// Modules of disagreement at most this far apart (in Chebyshev distance)
+/// are attributed to the same occlusion.
+const REGION_RADIUS: usize = 2;
+
+/// Module-level samples of the same physical code across several frames.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The codes added so far, oldest first.
+    frames: VecDeque<QrCode>,
+    /// The number of frames after which the oldest ones are discarded.
+    capacity: usize,
+}
+
+impl Session {
+    /// Creates an empty session retaining a reasonable number of frames.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates an empty session retaining at most `capacity` frames.
+    /// A capacity of 0 is treated as 1.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+
+        Session {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds the code extracted from the next frame, discarding the oldest
+    /// frame if the session is full. The grid size of every code in a
+    /// session must be the same, otherwise `Error::SizeMismatch` is returned.
+    pub fn add(&mut self, code: QrCode) -> Result<()> {
+        if let Some(first) = self.frames.front() {
+            if first.size() != code.size() {
+                return Err(Error::SizeMismatch { expected: first.size(), actual: code.size() });
+            }
+        }
+
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(code);
+
+        Ok(())
+    }
+
+    /// Returns the number of frames currently in the session.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns `true` if no frames were added since the last `clear()`.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Removes all frames from the session.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Attempts to decode the code, using the frames in the session.
+    ///
+    /// Every frame is tried on its own first, newest first. If there are at
+    /// least 3 frames, each module is then decided by majority vote. Finally,
+    /// the newest frame is combined with each older one in turn: the modules
+    /// on which they disagree are grouped into spatially coherent regions,
+    /// and every assignment of these regions to either frame is tried.
+    ///
+    /// If everything fails, the error of decoding the newest frame is
+    /// returned, or `DecodingFailed(Unknown)` if the session is empty.
+    pub fn decode(&self) -> Result<Info> {
+        let newest = match self.frames.back() {
+            Some(code) => code,
+            None => return Err(Error::DecodingFailed(DecodingErrorKind::Unknown)),
+        };
+        let error = match newest.decode() {
+            Ok(info) => return Ok(info),
+            Err(error) => error,
+        };
+
+        for code in self.frames.iter().rev().skip(1) {
+            if let Ok(info) = code.decode() {
+                return Ok(info);
+            }
+        }
+
+        if self.frames.len() >= 3 {
+            if let Ok(info) = self.majority_vote().decode() {
+                return Ok(info);
+            }
+        }
+
+        for older in self.frames.iter().rev().skip(1) {
+            if let Some(info) = combine_pair(newest, older) {
+                return Ok(info);
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Decides each module by majority vote, breaking ties in favor of the
+    /// newest frame. Must not be called on an empty session.
+    fn majority_vote(&self) -> QrCode {
+        let newest = *self.frames.back().expect("empty session");
+        let size = newest.size();
+        let mut code = newest;
+
+        for y in 0..size {
+            for x in 0..size {
+                let coord = Vec2D { x, y };
+                let dark = self.frames.iter().filter(|c| c.bit_at(coord)).count();
+                let light = self.frames.len() - dark;
+
+                if dark != light {
+                    code.set_bit(coord, dark > light);
+                }
+            }
+        }
+
+        code
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tries every combination of taking each region of disagreement between
+/// `base` and `other` from either of them.
+fn combine_pair(base: &QrCode, other: &QrCode) -> Option<Info> {
+    let regions = disagreement_regions(base, other);
+
+    // With 0 or 1 regions, every combination is one of the inputs,
+    // and with too many of them, trying all combinations is too slow.
+    if regions.len() < 2 || regions.len() > MAX_REGIONS {
+        return None;
+    }
+
+    // Skip the two combinations that take everything from either input.
+    for combination in 1_usize..(1 << regions.len()) - 1 {
+        let mut code = *base;
+
+        for (i, region) in regions.iter().enumerate() {
+            if combination & 1 << i != 0 {
+                for &coord in region {
+                    code.set_bit(coord, other.bit_at(coord));
+                }
+            }
+        }
+
+        if let Ok(info) = code.decode() {
+            return Some(info);
+        }
+    }
+
+    None
+}
+
+/// Groups the modules on which `a` and `b` disagree into regions of
+/// nearby modules, dropping the regions which are too small to matter.
+fn disagreement_regions(a: &QrCode, b: &QrCode) -> Vec<Vec<Vec2D>> {
+    let size = a.size();
+    let mut pending: Vec<bool> = (0..size * size)
+        .map(|i| {
+            let coord = Vec2D { x: i % size, y: i / size };
+            a.bit_at(coord) != b.bit_at(coord)
+        })
+        .collect();
+    let mut regions = Vec::new();
+
+    for start in 0..pending.len() {
+        if !pending[start] {
+            continue;
+        }
+
+        pending[start] = false;
+
+        let mut region = Vec::new();
+        let mut stack = vec![start];
+
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % size, i / size);
+            let x_range = x.saturating_sub(REGION_RADIUS)..(x + REGION_RADIUS + 1).min(size);
+            let y_range = y.saturating_sub(REGION_RADIUS)..(y + REGION_RADIUS + 1).min(size);
+
+            region.push(Vec2D { x, y });
+
+            for ny in y_range {
+                for nx in x_range.clone() {
+                    let j = ny * size + nx;
+
+                    if pending[j] {
+                        pending[j] = false;
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+
+        if region.len() >= MIN_REGION_MODULES {
+            regions.push(region);
+        }
+    }
+
+    regions
+}
@@ -0,0 +1,269 @@
+//! Per-pixel preprocessing, vectorized where the CPU allows.
+//!
+//! These loops run over every pixel of every frame, so on x86 and x86-64
+//! they process 16 pixels at a time with SSE2 and SSSE3, which are detected
+//! at runtime. Other targets, and the pixels left over at the end of a row,
+//! use the scalar code, which computes exactly the same results. (NEON
+//! intrinsics would require a newer compiler than the crate supports.)
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// The Rec. 601 luma coefficients of red, green and blue, in units of
+/// `1 / LUMA_DENOMINATOR`. Each fits into an `i16`, as required by
+/// `_mm_madd_epi16()`.
+const LUMA_COEFFICIENTS: [i32; 3] = [299, 587, 114];
+
+/// The sum of `LUMA_COEFFICIENTS`.
+const LUMA_DENOMINATOR: i32 = 1000;
+
+/// Dividing the weighted sum of the channels, rounding term included, by
+/// `LUMA_DENOMINATOR` is the same as dividing it by 8, which brings it below
+/// `1 << 15`, then multiplying it by `DIVISION_MULTIPLIER` and dropping the
+/// lowest `16 + DIVISION_SHIFT` bits, for every possible sum.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const DIVISION_MULTIPLIER: u16 = 33555;
+
+/// See `DIVISION_MULTIPLIER`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const DIVISION_SHIFT: i32 = 6;
+
+/// Computes the luma of an RGB pixel, using the Rec. 601 coefficients.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_sign_loss))]
+pub(crate) fn luma(r: u8, g: u8, b: u8) -> u8 {
+    let [cr, cg, cb] = LUMA_COEFFICIENTS;
+    let sum = cr * i32::from(r) + cg * i32::from(g) + cb * i32::from(b);
+    ((sum + LUMA_DENOMINATOR / 2) / LUMA_DENOMINATOR) as u8
+}
+
+/// Converts packed RGB pixels (3 bytes each) to luma. `luma` must have one
+/// byte for each pixel of `rgb`.
+pub fn rgb_to_luma(rgb: &[u8], luma: &mut [u8]) {
+    assert_eq!(rgb.len(), luma.len() * 3, "RGB and luma buffers differ in length");
+
+    #[cfg_attr(not(any(target_arch = "x86", target_arch = "x86_64")), allow(unused_mut))]
+    let mut done = 0;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("ssse3") {
+            // The feature was just detected.
+            done = unsafe { rgb_to_luma_ssse3(rgb, luma) };
+        }
+    }
+
+    rgb_to_luma_scalar(&rgb[done * 3..], &mut luma[done..]);
+}
+
+/// Converts packed RGB pixels to luma one at a time, like `rgb_to_luma()`
+/// does on CPUs without SSSE3.
+pub fn rgb_to_luma_scalar(rgb: &[u8], luma: &mut [u8]) {
+    assert_eq!(rgb.len(), luma.len() * 3, "RGB and luma buffers differ in length");
+
+    for (dst, src) in luma.iter_mut().zip(rgb.chunks(3)) {
+        *dst = self::luma(src[0], src[1], src[2]);
+    }
+}
+
+/// Sets the pixels darker than `threshold` to black (0), and all others to
+/// white (255).
+pub fn binarize(pixels: &mut [u8], threshold: u8) {
+    #[cfg_attr(not(any(target_arch = "x86", target_arch = "x86_64")), allow(unused_mut))]
+    let mut done = 0;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("sse2") {
+            // The feature was just detected.
+            done = unsafe { binarize_sse2(pixels, threshold) };
+        }
+    }
+
+    binarize_scalar(&mut pixels[done..], threshold);
+}
+
+/// Binarizes pixels one at a time, like `binarize()` does on CPUs without
+/// SSE2.
+pub fn binarize_scalar(pixels: &mut [u8], threshold: u8) {
+    for pixel in pixels {
+        *pixel = if *pixel < threshold { 0 } else { 255 };
+    }
+}
+
+/// Converts the leading pixels of `rgb` to luma in blocks of 16, and returns
+/// the number of pixels converted. The caller must ensure that the CPU
+/// supports SSSE3.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_ptr_alignment, cast_possible_wrap))]
+unsafe fn rgb_to_luma_ssse3(rgb: &[u8], luma: &mut [u8]) -> usize {
+    // Gather the red, green and blue bytes of 16 pixels spread over three
+    // 16-byte blocks; -1 zeroes the byte.
+    let red = [
+        _mm_setr_epi8(0, 3, 6, 9, 12, 15, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, -1, 2, 5, 8, 11, 14, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 4, 7, 10, 13),
+    ];
+    let green = [
+        _mm_setr_epi8(1, 4, 7, 10, 13, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, 0, 3, 6, 9, 12, 15, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 2, 5, 8, 11, 14),
+    ];
+    let blue = [
+        _mm_setr_epi8(2, 5, 8, 11, 14, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, 1, 4, 7, 10, 13, -1, -1, -1, -1, -1, -1),
+        _mm_setr_epi8(-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 3, 6, 9, 12, 15),
+    ];
+    let [cr, cg, cb] = LUMA_COEFFICIENTS;
+    // `_mm_madd_epi16()` multiplies pairs of (red, green), and of (blue, 1),
+    // so the latter adds the rounding term.
+    let rg_coefficients = _mm_set1_epi32(cg << 16 | cr);
+    let b1_coefficients = _mm_set1_epi32((LUMA_DENOMINATOR / 2) << 16 | cb);
+    let zero = _mm_setzero_si128();
+    let blocks = luma.len() / 16;
+
+    for i in 0..blocks {
+        let src = rgb.as_ptr().add(i * 48) as *const __m128i;
+        let v = [_mm_loadu_si128(src), _mm_loadu_si128(src.add(1)), _mm_loadu_si128(src.add(2))];
+        let (r, g, b) = (gather(&v, &red), gather(&v, &green), gather(&v, &blue));
+        let low = luma8(
+            [_mm_unpacklo_epi8(r, zero), _mm_unpacklo_epi8(g, zero), _mm_unpacklo_epi8(b, zero)],
+            rg_coefficients,
+            b1_coefficients,
+        );
+        let high = luma8(
+            [_mm_unpackhi_epi8(r, zero), _mm_unpackhi_epi8(g, zero), _mm_unpackhi_epi8(b, zero)],
+            rg_coefficients,
+            b1_coefficients,
+        );
+
+        _mm_storeu_si128(luma.as_mut_ptr().add(i * 16) as *mut __m128i, _mm_packus_epi16(low, high));
+    }
+
+    blocks * 16
+}
+
+/// Picks the bytes selected by `masks` out of each of the three blocks.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn gather(blocks: &[__m128i; 3], masks: &[__m128i; 3]) -> __m128i {
+    _mm_or_si128(
+        _mm_or_si128(_mm_shuffle_epi8(blocks[0], masks[0]), _mm_shuffle_epi8(blocks[1], masks[1])),
+        _mm_shuffle_epi8(blocks[2], masks[2]),
+    )
+}
+
+/// Computes the luma of 8 pixels, whose red, green and blue values are
+/// given in 16-bit lanes, and returns it in 16-bit lanes.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+unsafe fn luma8(rgb: [__m128i; 3], rg_coefficients: __m128i, b1_coefficients: __m128i) -> __m128i {
+    let [r, g, b] = rgb;
+    let one = _mm_set1_epi16(1);
+    let low = _mm_add_epi32(
+        _mm_madd_epi16(_mm_unpacklo_epi16(r, g), rg_coefficients),
+        _mm_madd_epi16(_mm_unpacklo_epi16(b, one), b1_coefficients),
+    );
+    let high = _mm_add_epi32(
+        _mm_madd_epi16(_mm_unpackhi_epi16(r, g), rg_coefficients),
+        _mm_madd_epi16(_mm_unpackhi_epi16(b, one), b1_coefficients),
+    );
+
+    // The sums are below `1 << 18`, so their eighths fit into 16-bit lanes.
+    let eighths = _mm_packs_epi32(_mm_srli_epi32(low, 3), _mm_srli_epi32(high, 3));
+    let multiplier = _mm_set1_epi16(DIVISION_MULTIPLIER as i16);
+
+    _mm_srli_epi16(_mm_mulhi_epu16(eighths, multiplier), DIVISION_SHIFT)
+}
+
+/// Binarizes the leading pixels in blocks of 16, and returns the number of
+/// pixels processed. The caller must ensure that the CPU supports SSE2.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_ptr_alignment, cast_possible_wrap))]
+unsafe fn binarize_sse2(pixels: &mut [u8], threshold: u8) -> usize {
+    let threshold = _mm_set1_epi8(threshold as i8);
+    let blocks = pixels.len() / 16;
+
+    for i in 0..blocks {
+        let ptr = pixels.as_mut_ptr().add(i * 16) as *mut __m128i;
+        let v = _mm_loadu_si128(ptr);
+        // `max(v, threshold) == v` if and only if `v >= threshold`, and
+        // the comparison yields all ones (255) or all zeros.
+        let white = _mm_cmpeq_epi8(_mm_max_epu8(v, threshold), v);
+
+        _mm_storeu_si128(ptr, white);
+    }
+
+    blocks * 16
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    /// The lengths, in pixels, around the multiples of the 16 pixels which
+    /// the vectorized loops process at a time.
+    const LENGTHS: &[usize] = &[0, 15, 16, 17, 47, 48, 49];
+
+    /// Returns `len` bytes covering every value, in an irregular order.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 167 + i / 256 * 13) as u8).collect()
+    }
+
+    /// Checks that `rgb_to_luma()` agrees with `rgb_to_luma_scalar()`.
+    fn check_luma(rgb: &[u8]) {
+        let mut luma = vec![0; rgb.len() / 3];
+        let mut expected = vec![0; rgb.len() / 3];
+
+        rgb_to_luma(rgb, &mut luma);
+        rgb_to_luma_scalar(rgb, &mut expected);
+
+        assert_eq!(luma, expected);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn luma_matches_scalar_for_every_color() {
+        let mut rgb = Vec::with_capacity(3 << 16);
+
+        for r in 0..=255 {
+            rgb.clear();
+
+            for gb in 0..=0xffff_u32 {
+                rgb.extend_from_slice(&[r, (gb >> 8) as u8, gb as u8]);
+            }
+
+            check_luma(&rgb);
+        }
+    }
+
+    #[test]
+    fn luma_matches_scalar_for_every_length() {
+        for &len in LENGTHS {
+            check_luma(&pattern(len * 3));
+        }
+    }
+
+    #[test]
+    fn binarize_matches_scalar() {
+        for &threshold in &[0, 1, 128, 255] {
+            for &len in LENGTHS.iter().chain(&[256]) {
+                let mut pixels = pattern(len);
+                let mut expected = pixels.clone();
+
+                binarize(&mut pixels, threshold);
+                binarize_scalar(&mut expected, threshold);
+
+                assert_eq!(pixels, expected, "threshold {}, {} pixels", threshold, len);
+            }
+        }
+    }
+}
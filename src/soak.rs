@@ -0,0 +1,194 @@
+//! Leak and drift detection for decoders running over very many frames.
+//!
+//! Kiosks and other unattended scanners run for months, so slow growth of
+//! the memory held by the decoder, or a gradual slowdown of detection, can
+//! go unnoticed until it becomes a problem. A `SoakMonitor` attached to a
+//! `Decoder` keeps track of these, and calls back when they cross the
+//! configured thresholds. Memory is measured inside `quirc`, by summing the
+//! sizes of the buffers it allocated, so growth of its work areas shows up
+//! even if the size of the images stays the same.
+
+use std::fmt;
+use std::time::Duration;
+
+/// The weight of the newest sample in the moving average of the latency.
+const LATENCY_SMOOTHING: f64 = 0.01;
+
+/// The limits beyond which a `SoakMonitor` raises an alert.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoakThresholds {
+    /// The maximal number of bytes held in the buffers of the decoder.
+    pub max_buffer_bytes: usize,
+    /// The maximal ratio of the moving average of the detection latency
+    /// to the baseline latency measured over the first frames.
+    pub max_latency_ratio: f64,
+    /// The number of frames over which the baseline latency is measured.
+    pub baseline_frames: u64,
+}
+
+impl Default for SoakThresholds {
+    fn default() -> Self {
+        SoakThresholds {
+            max_buffer_bytes: 64 << 20,
+            max_latency_ratio: 2.0,
+            baseline_frames: 100,
+        }
+    }
+}
+
+/// A threshold crossed by the monitored decoder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoakAlert {
+    /// The buffers of the decoder grew beyond the limit.
+    BufferGrowth {
+        /// The number of bytes currently held by the decoder.
+        bytes: usize,
+        /// The configured limit.
+        limit: usize,
+    },
+    /// Detection became slower than the baseline by more than the limit.
+    LatencyDrift {
+        /// The average latency over the first frames.
+        baseline: Duration,
+        /// The moving average of the latency.
+        current: Duration,
+    },
+}
+
+/// The statistics collected by a `SoakMonitor`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SoakStats {
+    /// The number of frames processed.
+    pub frames: u64,
+    /// The number of bytes allocated by the decoder after the last frame,
+    /// as reported by `Decoder::buffer_bytes()`.
+    pub buffer_bytes: usize,
+    /// The largest value of `buffer_bytes` seen so far.
+    pub peak_buffer_bytes: usize,
+    /// The average latency over the first frames, once they are processed.
+    pub baseline_latency: Option<Duration>,
+    /// The exponential moving average of the latency.
+    pub average_latency: Duration,
+    /// The number of alerts raised so far.
+    pub alerts: u64,
+}
+
+/// Monitors a decoder for memory growth and latency drift.
+///
+/// Each alert is raised once when its threshold is crossed, and it is only
+/// raised again after the monitored value has fallen below the threshold.
+pub struct SoakMonitor {
+    /// The limits beyond which an alert is raised.
+    thresholds: SoakThresholds,
+    /// The statistics collected so far.
+    stats: SoakStats,
+    /// The sum of the latencies of the first frames, in seconds.
+    baseline_sum: f64,
+    /// Whether the buffer growth alert is currently raised.
+    buffer_alert_raised: bool,
+    /// Whether the latency drift alert is currently raised.
+    latency_alert_raised: bool,
+    /// Called with every alert raised.
+    callback: Option<Box<dyn FnMut(&SoakAlert) + Send>>,
+}
+
+impl SoakMonitor {
+    /// Creates a monitor with the given thresholds and no callback.
+    pub fn new(thresholds: SoakThresholds) -> Self {
+        SoakMonitor {
+            thresholds,
+            stats: SoakStats::default(),
+            baseline_sum: 0.0,
+            buffer_alert_raised: false,
+            latency_alert_raised: false,
+            callback: None,
+        }
+    }
+
+    /// Sets the function called with every alert raised.
+    pub fn set_callback<F>(&mut self, callback: F)
+        where F: FnMut(&SoakAlert) + Send + 'static
+    {
+        self.callback = Some(Box::new(callback));
+    }
+
+    /// Returns the configured thresholds.
+    pub fn thresholds(&self) -> &SoakThresholds {
+        &self.thresholds
+    }
+
+    /// Returns the statistics collected so far.
+    pub fn stats(&self) -> &SoakStats {
+        &self.stats
+    }
+
+    /// Records the buffer size and the latency of a processed frame,
+    /// raising alerts as necessary. This is called by the `Decoder` the
+    /// monitor is attached to, but it can be fed manually, too.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn record(&mut self, buffer_bytes: usize, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+
+        self.stats.frames += 1;
+        self.stats.buffer_bytes = buffer_bytes;
+        self.stats.peak_buffer_bytes = self.stats.peak_buffer_bytes.max(buffer_bytes);
+
+        let average = if self.stats.frames == 1 {
+            seconds
+        } else {
+            let previous = self.stats.average_latency.as_secs_f64();
+            previous + LATENCY_SMOOTHING * (seconds - previous)
+        };
+
+        self.stats.average_latency = Duration::from_secs_f64(average);
+
+        if self.stats.frames <= self.thresholds.baseline_frames {
+            self.baseline_sum += seconds;
+
+            if self.stats.frames == self.thresholds.baseline_frames {
+                let baseline = self.baseline_sum / self.stats.frames as f64;
+                self.stats.baseline_latency = Some(Duration::from_secs_f64(baseline));
+            }
+        }
+
+        let limit = self.thresholds.max_buffer_bytes;
+        let over_limit = buffer_bytes > limit;
+
+        if over_limit && !self.buffer_alert_raised {
+            self.raise(SoakAlert::BufferGrowth { bytes: buffer_bytes, limit });
+        }
+
+        self.buffer_alert_raised = over_limit;
+
+        if let Some(baseline) = self.stats.baseline_latency.filter(|b| b.as_secs_f64() > 0.0) {
+            let ratio = average / baseline.as_secs_f64();
+            let drifted = ratio > self.thresholds.max_latency_ratio;
+
+            if drifted && !self.latency_alert_raised {
+                let current = self.stats.average_latency;
+                self.raise(SoakAlert::LatencyDrift { baseline, current });
+            }
+
+            self.latency_alert_raised = drifted;
+        }
+    }
+
+    /// Counts an alert and passes it to the callback, if any.
+    fn raise(&mut self, alert: SoakAlert) {
+        self.stats.alerts += 1;
+
+        if let Some(ref mut callback) = self.callback {
+            callback(&alert);
+        }
+    }
+}
+
+impl fmt::Debug for SoakMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SoakMonitor")
+            .field("thresholds", &self.thresholds)
+            .field("stats", &self.stats)
+            .field("has_callback", &self.callback.is_some())
+            .finish()
+    }
+}
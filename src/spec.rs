@@ -0,0 +1,208 @@
+//! Tables and formulas from the QR code specification (ISO/IEC 18004).
+//!
+//! These describe the layout and the capacity of each version, e.g. for
+//! telling how full a decoded code is (`Info::capacity_used_fraction()`),
+//! or which version a payload needs. Functions taking a version expect it
+//! to be in the range `MIN_VERSION...MAX_VERSION`, and panic for larger
+//! ones.
+
+use std::vec::Vec;
+use info::{ EccLevel, DataType };
+
+/// The smallest version.
+pub const MIN_VERSION: u8 = 1;
+
+/// The largest version.
+pub const MAX_VERSION: u8 = 40;
+
+/// The number of error correction codewords per block, indexed by
+/// the ECC level (L, M, Q, H) and the version. Index 0 is unused.
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28,
+     28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26,
+     26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30,
+     28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28,
+     30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/// The number of error correction blocks, indexed by the ECC level
+/// (L, M, Q, H) and the version. Index 0 is unused.
+const ECC_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8,
+     8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16,
+     17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20,
+     23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25,
+     25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+/// Returns the index of the ECC level in the tables above.
+fn ecc_index(ecc_level: EccLevel) -> usize {
+    match ecc_level {
+        EccLevel::L => 0,
+        EccLevel::M => 1,
+        EccLevel::Q => 2,
+        EccLevel::H => 3,
+    }
+}
+
+/// Returns the 2-bit value encoding the ECC level in the format information.
+pub fn ecc_format_bits(ecc_level: EccLevel) -> u16 {
+    match ecc_level {
+        EccLevel::L => 1,
+        EccLevel::M => 0,
+        EccLevel::Q => 3,
+        EccLevel::H => 2,
+    }
+}
+
+/// Returns the number of modules along each side of a code.
+pub fn size(version: u8) -> usize {
+    usize::from(version) * 4 + 17
+}
+
+/// Returns the number of error correction codewords in each block.
+pub fn ecc_codewords_per_block(version: u8, ecc_level: EccLevel) -> usize {
+    usize::from(ECC_CODEWORDS_PER_BLOCK[ecc_index(ecc_level)][usize::from(version)])
+}
+
+/// Returns the number of error correction blocks.
+pub fn ecc_blocks(version: u8, ecc_level: EccLevel) -> usize {
+    usize::from(ECC_BLOCKS[ecc_index(ecc_level)][usize::from(version)])
+}
+
+/// Returns the number of modules available for data and error correction
+/// codewords, including the remainder bits, i.e. the modules which aren't
+/// occupied by function patterns or format and version information.
+pub fn raw_data_modules(version: u8) -> usize {
+    let v = usize::from(version);
+    let mut result = (16 * v + 128) * v + 64;
+
+    if version >= 2 {
+        let alignments = v / 7 + 2;
+        result -= (25 * alignments - 10) * alignments - 55;
+
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+
+    result
+}
+
+/// Returns the total number of codewords, data and error correction.
+pub fn total_codewords(version: u8) -> usize {
+    raw_data_modules(version) / 8
+}
+
+/// Returns the number of data codewords.
+pub fn data_codewords(version: u8, ecc_level: EccLevel) -> usize {
+    total_codewords(version)
+        - ecc_codewords_per_block(version, ecc_level) * ecc_blocks(version, ecc_level)
+}
+
+/// Returns the width of the character count field of a segment of the
+/// given data type.
+pub fn char_count_bits(version: u8, data_type: DataType) -> usize {
+    let widths = match data_type {
+        DataType::Numeric => [10, 12, 14],
+        DataType::Alphanumeric => [9, 11, 13],
+        DataType::Byte => [8, 16, 16],
+        DataType::Kanji => [8, 10, 12],
+    };
+
+    match version {
+        1..=9 => widths[0],
+        10..=26 => widths[1],
+        _ => widths[2],
+    }
+}
+
+/// Returns the largest number of characters of the given data type (digits,
+/// alphanumeric characters, bytes or Kanji characters, respectively) that
+/// fit in a code, in a single segment.
+pub fn capacity(version: u8, ecc_level: EccLevel, data_type: DataType) -> usize {
+    let count_bits = char_count_bits(version, data_type);
+    let bits = (data_codewords(version, ecc_level) * 8).saturating_sub(4 + count_bits);
+    let chars = match data_type {
+        DataType::Numeric => bits / 10 * 3 + match bits % 10 {
+            0..=3 => 0,
+            4..=6 => 1,
+            _ => 2,
+        },
+        DataType::Alphanumeric => bits / 11 * 2 + usize::from(bits % 11 >= 6),
+        DataType::Byte => bits / 8,
+        DataType::Kanji => bits / 13,
+    };
+
+    // The character count field limits the length of a segment, too.
+    chars.min((1 << count_bits) - 1)
+}
+
+/// Returns the coordinates of the centers of the alignment patterns
+/// along either axis, in ascending order. Patterns at all combinations
+/// of these are present, except where they would overlap a finder.
+pub fn alignment_positions(version: u8) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+
+    let v = usize::from(version);
+    let count = v / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (v * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+
+    let mut result: Vec<usize> = (0..count - 1).map(|i| size(version) - 7 - i * step).collect();
+    result.push(6);
+    result.reverse();
+    result
+}
+
+/// Returns the 15-bit format information, including its BCH error
+/// correction bits and the XOR mask.
+pub fn format_bits(ecc_level: EccLevel, mask: u8) -> u16 {
+    let data = ecc_format_bits(ecc_level) << 3 | u16::from(mask);
+    let mut remainder = data;
+
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+
+    (data << 10 | remainder) ^ 0x5412
+}
+
+/// Returns the 18-bit version information, including its BCH error
+/// correction bits. Only versions 7 and up carry version information.
+pub fn version_bits(version: u8) -> u32 {
+    let mut remainder = u32::from(version);
+
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+    }
+
+    u32::from(version) << 12 | remainder
+}
+
+/// Returns whether the module of a data mask at column `x` and row `y`
+/// is inverted. Panics if `mask` is greater than 7.
+pub fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y) % 2 == 0,
+        1 => y % 2 == 0,
+        2 => x % 3 == 0,
+        3 => (x + y) % 3 == 0,
+        4 => (x / 3 + y / 2) % 2 == 0,
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3) % 2 == 0,
+        7 => ((x + y) % 2 + x * y % 3) % 2 == 0,
+        _ => panic!("invalid mask pattern {}", mask),
+    }
+}
@@ -0,0 +1,406 @@
+//! A regression harness replaying labeled corpora of images.
+//!
+//! A corpus is a directory of images, searched recursively, each of which
+//! is labeled by a text file next to it, with the same name and the `.txt`
+//! extension: `tickets/001.png` is labeled by `tickets/001.txt`. The label
+//! file lists the payloads of the codes expected in the image, one per
+//! line, in any order. Lines starting with `hex:` are hexadecimal bytes,
+//! for payloads which aren't text or contain line breaks; empty lines are
+//! ignored, so an empty label file marks an image without codes. Images
+//! without a label file aren't part of the corpus.
+//!
+//! `Corpus::run()` decodes every image and compares the payloads with the
+//! labels. `Corpus::run_with()` modifies the images before decoding them,
+//! e.g. to inject faults like noise or occlusion, and measure how robust
+//! decoding is against them. Loading and decoding errors are recorded as
+//! failures of the samples concerned, so that a run always covers the
+//! whole corpus.
+//!
+//! `roundtrip()` checks the whole pipeline without any images on disk: it
+//! encodes a payload, rasterizes the code at a configurable scale, rotation
+//! and noise level, and decodes it again. It is meant to be driven by
+//! property-based tests or fuzzers, within this crate or downstream.
+//!
+//! The per-pixel preprocessing functions are exported along with their
+//! scalar counterparts, so that benchmarks can compare the vectorized code
+//! with the portable one.
+
+pub use simd::{ rgb_to_luma, rgb_to_luma_scalar, binarize, binarize_scalar };
+
+use std::fs;
+use std::io;
+use std::path::{ Path, PathBuf };
+use geom::{ ImageBuf, Vec2D };
+use decoder::Decoder;
+use encoder::{ Encoder, EncodedQr };
+use info::EccLevel;
+use report::{ Report, Outcome };
+use error::{ Error, Result };
+
+/// The extensions of the files considered to be images, in lowercase.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "pgm", "pbm", "ppm", "pnm"];
+
+/// The prefix of the lines of label files which are hexadecimal bytes.
+const HEX_PREFIX: &str = "hex:";
+
+/// An image, along with the payloads of the codes expected in it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sample {
+    /// The path of the image file.
+    pub image: PathBuf,
+    /// The expected payloads, in the order of the label file.
+    pub expected: Vec<Vec<u8>>,
+}
+
+/// A set of labeled images.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Corpus {
+    /// The samples, in the order of their paths.
+    pub samples: Vec<Sample>,
+}
+
+impl Corpus {
+    /// Loads the labels of the images within `dir` and its subdirectories.
+    /// The images themselves are only loaded when the corpus is run.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut samples = Vec::new();
+
+        walk(dir.as_ref(), &mut samples)?;
+
+        Ok(Corpus { samples })
+    }
+
+    /// Decodes every image with `decoder`, and compares the payloads with
+    /// the labels.
+    pub fn run(&self, decoder: &mut Decoder) -> CorpusStats {
+        self.run_with(decoder, |_| {})
+    }
+
+    /// Passes every image to `modify` before decoding it with `decoder`,
+    /// and compares the payloads with the labels.
+    pub fn run_with<F>(&self, decoder: &mut Decoder, mut modify: F) -> CorpusStats
+        where F: FnMut(&mut ImageBuf)
+    {
+        let results = self.samples
+            .iter()
+            .map(|sample| {
+                let report = ImageBuf::load(&sample.image).and_then(|mut image| {
+                    modify(&mut image);
+                    Report::scan(decoder, &image.as_image())
+                });
+
+                match report {
+                    Ok(report) => SampleResult::compare(sample, &report),
+                    Err(error) => SampleResult::failed(sample, error),
+                }
+            })
+            .collect();
+
+        CorpusStats { results }
+    }
+}
+
+/// The outcome of decoding a single sample.
+#[derive(Debug, Clone)]
+pub struct SampleResult {
+    /// The path of the image file.
+    pub image: PathBuf,
+    /// The number of codes expected in the image.
+    pub num_expected: usize,
+    /// The expected payloads which weren't decoded.
+    pub missing: Vec<Vec<u8>>,
+    /// The decoded payloads which weren't expected, or which were decoded
+    /// more often than expected.
+    pub unexpected: Vec<Vec<u8>>,
+    /// The errors of the codes which were detected but couldn't be decoded.
+    pub decode_errors: Vec<Error>,
+    /// The error which prevented the image from being loaded or decoded.
+    pub error: Option<Error>,
+}
+
+impl SampleResult {
+    /// Compares the payloads decoded from a sample with its labels.
+    fn compare(sample: &Sample, report: &Report) -> Self {
+        let mut missing = sample.expected.clone();
+        let mut unexpected = Vec::new();
+        let mut decode_errors = Vec::new();
+
+        for outcome in &report.codes {
+            match *outcome {
+                Outcome::Decoded(ref code) => {
+                    match missing.iter().position(|payload| *payload == code.payload) {
+                        Some(index) => { missing.remove(index); }
+                        None => unexpected.push(code.payload.clone()),
+                    }
+                }
+                Outcome::Failed { error, .. } => decode_errors.push(error),
+            }
+        }
+
+        SampleResult {
+            image: sample.image.clone(),
+            num_expected: sample.expected.len(),
+            missing,
+            unexpected,
+            decode_errors,
+            error: None,
+        }
+    }
+
+    /// Records a sample whose image couldn't be loaded or decoded.
+    fn failed(sample: &Sample, error: Error) -> Self {
+        SampleResult {
+            image: sample.image.clone(),
+            num_expected: sample.expected.len(),
+            missing: sample.expected.clone(),
+            unexpected: Vec::new(),
+            decode_errors: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Returns whether exactly the expected payloads were decoded. Codes
+    /// which were detected but couldn't be decoded don't count against
+    /// the sample, as long as every expected payload was found.
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+
+    /// Returns the number of expected payloads which were decoded.
+    pub fn num_found(&self) -> usize {
+        self.num_expected - self.missing.len()
+    }
+}
+
+/// The results of running a corpus.
+#[derive(Debug, Clone, Default)]
+pub struct CorpusStats {
+    /// The results of the samples, in the order of the corpus.
+    pub results: Vec<SampleResult>,
+}
+
+impl CorpusStats {
+    /// Returns the number of samples which passed.
+    pub fn num_passed(&self) -> usize {
+        self.results.iter().filter(|result| result.passed()).count()
+    }
+
+    /// Returns the number of samples which failed.
+    pub fn num_failed(&self) -> usize {
+        self.results.len() - self.num_passed()
+    }
+
+    /// Returns the samples which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SampleResult> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+
+    /// Returns the fraction of the samples which passed, or 1 for an
+    /// empty corpus.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn pass_rate(&self) -> f64 {
+        if self.results.is_empty() {
+            1.0
+        } else {
+            self.num_passed() as f64 / self.results.len() as f64
+        }
+    }
+
+    /// Returns the fraction of the expected payloads which were decoded,
+    /// across all samples, or 1 if no codes were expected.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn recall(&self) -> f64 {
+        let expected: usize = self.results.iter().map(|result| result.num_expected).sum();
+        let found: usize = self.results.iter().map(SampleResult::num_found).sum();
+
+        if expected == 0 {
+            1.0
+        } else {
+            found as f64 / expected as f64
+        }
+    }
+}
+
+/// Parses the contents of a label file into the expected payloads.
+/// Fails with `Error::InvalidArgument` if a `hex:` line is malformed.
+pub fn parse_labels(text: &str) -> Result<Vec<Vec<u8>>> {
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| if line.starts_with(HEX_PREFIX) {
+            parse_hex(line[HEX_PREFIX.len()..].trim())
+        } else {
+            Ok(line.as_bytes().to_vec())
+        })
+        .collect()
+}
+
+/// Parses pairs of hexadecimal digits into bytes.
+fn parse_hex(digits: &str) -> Result<Vec<u8>> {
+    if digits.len() % 2 != 0 {
+        return Err(Error::InvalidArgument);
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or(Error::InvalidArgument)
+        })
+        .collect()
+}
+
+/// Appends the labeled images within `dir` and its subdirectories to
+/// `samples`, in sorted order.
+fn walk(dir: &Path, samples: &mut Vec<Sample>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, samples)?;
+        } else if is_image(&path) {
+            let labels = path.with_extension("txt");
+
+            if labels.is_file() {
+                let expected = parse_labels(&fs::read_to_string(&labels)?)?;
+                samples.push(Sample { image: path, expected });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the extension of `path` is that of a supported format.
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            IMAGE_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// How a code is rasterized by `rasterize()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Raster {
+    /// The width and height of each module, in pixels. Defaults to 4.
+    pub scale: f64,
+    /// The clockwise rotation of the code around the center of the image,
+    /// in radians. Defaults to 0.
+    pub rotation: f64,
+    /// The width of the light border around the code, in modules.
+    /// Defaults to 4.
+    pub quiet_zone: usize,
+    /// The largest amount by which the brightness of each pixel is changed
+    /// in either direction, at random. Defaults to 0.
+    pub noise: u8,
+    /// The seed of the pseudo-random noise, so that failures can be
+    /// reproduced. Defaults to 1.
+    pub seed: u64,
+}
+
+impl Default for Raster {
+    fn default() -> Self {
+        Raster {
+            scale: 4.0,
+            rotation: 0.0,
+            quiet_zone: 4,
+            noise: 0,
+            seed: 1,
+        }
+    }
+}
+
+/// Renders `code` as a grayscale image with black modules on a white
+/// background, transformed as specified by `raster`. The image is large
+/// enough to hold the rotated code along with its quiet zone.
+/// Fails with `Error::InvalidArgument` if the scale isn't positive.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_sign_loss, cast_precision_loss))]
+pub fn rasterize(code: &EncodedQr, raster: &Raster) -> Result<ImageBuf> {
+    if !(raster.scale > 0.0) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let modules = (code.size() + 2 * raster.quiet_zone) as f64;
+    let (sin, cos) = raster.rotation.sin_cos();
+    let pixels = (modules * raster.scale * (sin.abs() + cos.abs())).ceil() as usize;
+    let center = pixels as f64 / 2.0;
+    let mut noise = XorShift(raster.seed.max(1));
+    let mut data = Vec::with_capacity(pixels * pixels);
+
+    for y in 0..pixels {
+        for x in 0..pixels {
+            // Rotate the center of the pixel back, and map it to modules.
+            let (dx, dy) = (x as f64 + 0.5 - center, y as f64 + 0.5 - center);
+            let mx = (dx * cos + dy * sin) / raster.scale + modules / 2.0 - raster.quiet_zone as f64;
+            let my = (dy * cos - dx * sin) / raster.scale + modules / 2.0 - raster.quiet_zone as f64;
+            let dark = mx >= 0.0 && my >= 0.0 && code.get(Vec2D { x: mx as usize, y: my as usize }) == Some(true);
+            let value = if dark { 0 } else { 255 };
+
+            data.push(noise.perturb(value, raster.noise));
+        }
+    }
+
+    ImageBuf::new(data, Vec2D { x: pixels, y: pixels })
+}
+
+/// Encodes `payload` into a code of the given ECC level and version,
+/// rasterizes it with the default `Raster`, decodes it, and returns
+/// whether the payload was recovered. Also returns `false` if the payload
+/// doesn't fit into the version; see `roundtrip_with()` to tell apart.
+pub fn roundtrip(payload: &[u8], ecc: EccLevel, version: u8) -> bool {
+    roundtrip_with(payload, ecc, version, &Raster::default()).unwrap_or(false)
+}
+
+/// Like `roundtrip()`, but rasterizes the code as specified by `raster`.
+/// Fails if the payload can't be encoded into the version, or the code
+/// can't be rasterized, so that inputs which can't make a round trip can
+/// be told apart from the ones which fail to.
+pub fn roundtrip_with(payload: &[u8], ecc: EccLevel, version: u8, raster: &Raster) -> Result<bool> {
+    let encoder = Encoder {
+        min_version: version,
+        max_version: version,
+        ..Encoder::new(ecc)
+    };
+    let code = encoder.encode(payload)?;
+    let image = rasterize(&code, raster)?;
+    let mut decoder = Decoder::new()?;
+    let recovered = decoder
+        .decode_image(&image.as_image())?
+        .filter_map(|code| code.and_then(|code| code.decode()).ok())
+        .any(|info| info.payload() == payload);
+
+    Ok(recovered)
+}
+
+/// A xorshift64 pseudo-random number generator, which is plenty for noise.
+#[derive(Debug, Clone, Copy)]
+struct XorShift(u64);
+
+impl XorShift {
+    /// Returns the next pseudo-random number.
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Changes `value` by a pseudo-random amount of at most `amplitude` in
+    /// either direction, saturating at black and white.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn perturb(&mut self, value: u8, amplitude: u8) -> u8 {
+        if amplitude == 0 {
+            return value;
+        }
+
+        let range = 2 * u64::from(amplitude) + 1;
+        let offset = (self.next() % range) as i16 - i16::from(amplitude);
+
+        (i16::from(value) + offset).max(0).min(255) as u8
+    }
+}
@@ -0,0 +1,312 @@
+//! Decoding payloads to text according to their ECI designators.
+//!
+//! The Extended Channel Interpretation (ECI) assignment number of a code
+//! specifies the character set of its payload. UTF-8, UTF-16, UTF-32,
+//! ISO-8859-1 and ASCII are always supported; the other character sets,
+//! including the Shift JIS of Kanji segments, require the `encoding_rs`
+//! feature.
+
+use std::fmt;
+use std::error;
+use info::{ Info, DataType };
+
+#[cfg(feature = "encoding_rs")]
+use encoding_rs::Encoding;
+
+/// A character set which an ECI assignment number can designate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Charset {
+    /// IBM code page 437.
+    Cp437,
+    /// A part of ISO/IEC 8859, from 1 to 16. Part 1 is Latin-1, the
+    /// default character set of QR codes.
+    Iso8859(u8),
+    /// Shift JIS.
+    ShiftJis,
+    /// A Windows code page: 1250, 1251, 1252 or 1256.
+    Windows(u16),
+    /// UTF-8.
+    Utf8,
+    /// UTF-16, big-endian.
+    Utf16Be,
+    /// UTF-16, little-endian.
+    Utf16Le,
+    /// UTF-32, big-endian.
+    Utf32Be,
+    /// UTF-32, little-endian.
+    Utf32Le,
+    /// US-ASCII.
+    Ascii,
+    /// Big5.
+    Big5,
+    /// GB 2312, GBK or GB 18030, which are decoded as GB 18030, their
+    /// common superset.
+    Gb18030,
+    /// EUC-KR.
+    EucKr,
+}
+
+impl Charset {
+    /// Returns the character set designated by an ECI assignment number,
+    /// or `None` if the number doesn't designate a known character set.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    pub fn from_eci(eci: u32) -> Option<Self> {
+        let charset = match eci {
+            0 | 2 => Charset::Cp437,
+            1 | 3 => Charset::Iso8859(1),
+            // There is no ISO-8859-12, so neither is there an ECI 14.
+            4..=13 | 15..=18 => Charset::Iso8859((eci - 2) as u8),
+            20 => Charset::ShiftJis,
+            21 => Charset::Windows(1250),
+            22 => Charset::Windows(1251),
+            23 => Charset::Windows(1252),
+            24 => Charset::Windows(1256),
+            25 => Charset::Utf16Be,
+            26 => Charset::Utf8,
+            27 | 170 => Charset::Ascii,
+            28 => Charset::Big5,
+            29 | 31 | 32 => Charset::Gb18030,
+            30 => Charset::EucKr,
+            33 => Charset::Utf16Le,
+            34 => Charset::Utf32Be,
+            35 => Charset::Utf32Le,
+            _ => return None,
+        };
+
+        Some(charset)
+    }
+
+    /// Returns the preferred label of the character set, e.g. `ISO-8859-2`.
+    pub fn name(self) -> String {
+        match self {
+            Charset::Cp437    => "IBM437".to_owned(),
+            Charset::Iso8859(part) => format!("ISO-8859-{}", part),
+            Charset::ShiftJis => "Shift_JIS".to_owned(),
+            Charset::Windows(page) => format!("windows-{}", page),
+            Charset::Utf8     => "UTF-8".to_owned(),
+            Charset::Utf16Be  => "UTF-16BE".to_owned(),
+            Charset::Utf16Le  => "UTF-16LE".to_owned(),
+            Charset::Utf32Be  => "UTF-32BE".to_owned(),
+            Charset::Utf32Le  => "UTF-32LE".to_owned(),
+            Charset::Ascii    => "US-ASCII".to_owned(),
+            Charset::Big5     => "Big5".to_owned(),
+            Charset::Gb18030  => "GB18030".to_owned(),
+            Charset::EucKr    => "EUC-KR".to_owned(),
+        }
+    }
+
+    /// Decodes `bytes`, failing if they are malformed in this character set.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, CharsetError> {
+        self.decode_with(bytes, false)
+    }
+
+    /// Decodes `bytes`, replacing malformed sequences with U+FFFD.
+    /// Fails only if the character set isn't supported.
+    pub fn decode_lossy(self, bytes: &[u8]) -> Result<String, CharsetError> {
+        self.decode_with(bytes, true)
+    }
+
+    /// Decodes `bytes`, either replacing malformed sequences or failing.
+    fn decode_with(self, bytes: &[u8], lossy: bool) -> Result<String, CharsetError> {
+        let text = match self {
+            Charset::Iso8859(1) => Some(bytes.iter().map(|&b| char::from(b)).collect()),
+            Charset::Ascii => if lossy {
+                Some(bytes.iter().map(|&b| if b.is_ascii() { char::from(b) } else { REPLACEMENT }).collect())
+            } else if bytes.is_ascii() {
+                Some(bytes.iter().map(|&b| char::from(b)).collect())
+            } else {
+                None
+            },
+            Charset::Utf8 => if lossy {
+                Some(String::from_utf8_lossy(bytes).into_owned())
+            } else {
+                String::from_utf8(bytes.to_vec()).ok()
+            },
+            Charset::Utf16Be => decode_utf16(bytes, u16::from_be_bytes, lossy),
+            Charset::Utf16Le => decode_utf16(bytes, u16::from_le_bytes, lossy),
+            Charset::Utf32Be => decode_utf32(bytes, u32::from_be_bytes, lossy),
+            Charset::Utf32Le => decode_utf32(bytes, u32::from_le_bytes, lossy),
+            _ => return self.decode_external(bytes, lossy),
+        };
+
+        text.ok_or(CharsetError::Malformed(self))
+    }
+
+    /// Decodes `bytes` in a character set which isn't built in.
+    #[cfg(feature = "encoding_rs")]
+    fn decode_external(self, bytes: &[u8], lossy: bool) -> Result<String, CharsetError> {
+        let encoding = match self {
+            // Not part of the WHATWG Encoding Standard.
+            Charset::Cp437 => None,
+            _ => Encoding::for_label(self.name().as_bytes()),
+        }.ok_or(CharsetError::Unsupported(self))?;
+
+        if lossy {
+            Ok(encoding.decode_without_bom_handling(bytes).0.into_owned())
+        } else {
+            encoding
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(|text| text.into_owned())
+                .ok_or(CharsetError::Malformed(self))
+        }
+    }
+
+    /// Decodes `bytes` in a character set which isn't built in.
+    #[cfg(not(feature = "encoding_rs"))]
+    fn decode_external(self, _bytes: &[u8], _lossy: bool) -> Result<String, CharsetError> {
+        Err(CharsetError::Unsupported(self))
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+/// The replacement character, substituted for malformed sequences.
+const REPLACEMENT: char = '\u{fffd}';
+
+/// Decodes UTF-16 with the given byte order.
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16, lossy: bool) -> Option<String> {
+    if bytes.len() % 2 != 0 && !lossy {
+        return None;
+    }
+
+    let units = bytes.chunks(2).filter(|c| c.len() == 2).map(|c| from_bytes([c[0], c[1]]));
+    let chars = ::std::char::decode_utf16(units);
+    let mut text: String = if lossy {
+        chars.map(|c| c.unwrap_or(REPLACEMENT)).collect()
+    } else {
+        chars.collect::<Result<_, _>>().ok()?
+    };
+
+    // A trailing odd byte is an incomplete code unit.
+    if bytes.len() % 2 != 0 {
+        text.push(REPLACEMENT);
+    }
+
+    Some(text)
+}
+
+/// Decodes UTF-32 with the given byte order.
+fn decode_utf32(bytes: &[u8], from_bytes: fn([u8; 4]) -> u32, lossy: bool) -> Option<String> {
+    if bytes.len() % 4 != 0 && !lossy {
+        return None;
+    }
+
+    let mut text = String::with_capacity(bytes.len() / 4);
+
+    for chunk in bytes.chunks(4) {
+        let decoded = if chunk.len() == 4 {
+            ::std::char::from_u32(from_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        } else {
+            None
+        };
+
+        match decoded {
+            Some(c) => text.push(c),
+            None if lossy => text.push(REPLACEMENT),
+            None => return None,
+        }
+    }
+
+    Some(text)
+}
+
+/// An error which can occur while decoding a payload to text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharsetError {
+    /// The ECI assignment number doesn't designate a known character set.
+    UnknownEci(u32),
+    /// The character set isn't supported, e.g. because it requires the
+    /// `encoding_rs` feature.
+    Unsupported(Charset),
+    /// The payload is malformed in the character set.
+    Malformed(Charset),
+}
+
+impl fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CharsetError::UnknownEci(eci) => write!(f, "unknown ECI assignment number {}", eci),
+            CharsetError::Unsupported(charset) => write!(f, "unsupported character set {}", charset),
+            CharsetError::Malformed(charset) => write!(f, "payload is malformed in {}", charset),
+        }
+    }
+}
+
+impl error::Error for CharsetError {
+    fn description(&self) -> &str {
+        match *self {
+            CharsetError::UnknownEci(_)  => "unknown ECI assignment number",
+            CharsetError::Unsupported(_) => "unsupported character set",
+            CharsetError::Malformed(_)   => "payload is malformed in its character set",
+        }
+    }
+}
+
+impl Info {
+    /// Returns the character set of the payload, as designated by the ECI
+    /// assignment number. Returns `Ok(None)` if the code has no ECI.
+    pub fn charset(&self) -> Result<Option<Charset>, CharsetError> {
+        match self.raw_eci() {
+            0 => Ok(None),
+            eci => Charset::from_eci(eci).map(Some).ok_or(CharsetError::UnknownEci(eci)),
+        }
+    }
+
+    /// Decodes the payload to text in the character set designated by
+    /// the ECI assignment number.
+    ///
+    /// Without an ECI, the specification mandates ISO-8859-1, but many
+    /// encoders emit UTF-8 instead, so a payload which is valid UTF-8 is
+    /// decoded as such, and any other payload as ISO-8859-1. Codes with
+    /// Kanji segments but without an ECI are decoded as Shift JIS.
+    ///
+    /// Kanji segments are always Shift JIS, even if the ECI designates
+    /// another character set; this requires the `encoding_rs` feature.
+    pub fn decode_text(&self) -> Result<String, CharsetError> {
+        self.decode_text_with(false)
+    }
+
+    /// Like `decode_text()`, but replaces malformed sequences with U+FFFD.
+    /// If the character set is unknown or unsupported, the payload is
+    /// decoded as UTF-8.
+    pub fn to_string_lossy(&self) -> String {
+        self.decode_text_with(true)
+            .unwrap_or_else(|_| String::from_utf8_lossy(self.payload()).into_owned())
+    }
+
+    /// Decodes the payload, either replacing malformed sequences or failing.
+    fn decode_text_with(&self, lossy: bool) -> Result<String, CharsetError> {
+        let decode = |charset: Charset, bytes: &[u8]| charset.decode_with(bytes, lossy);
+
+        let charset = match self.charset()? {
+            Some(charset) => charset,
+            // Codes containing Kanji usually encode the rest of the
+            // Japanese text in byte segments as Shift JIS, too.
+            None if self.data_type() == DataType::Kanji => Charset::ShiftJis,
+            None => return decode(Charset::Utf8, self.payload())
+                .or_else(|_| decode(Charset::Iso8859(1), self.payload())),
+        };
+
+        let segments = self.segments();
+
+        // Kanji segments are Shift JIS regardless of the ECI.
+        if charset != Charset::ShiftJis && segments.iter().any(|s| s.data_type == DataType::Kanji) {
+            return segments
+                .iter()
+                .map(|segment| match segment.data_type {
+                    DataType::Kanji => decode(Charset::ShiftJis, &segment.bytes),
+                    _ => {
+                        let eci_charset = segment.eci.and_then(Charset::from_eci);
+                        decode(eci_charset.unwrap_or(charset), &segment.bytes)
+                    }
+                })
+                .collect();
+        }
+
+        decode(charset, self.payload())
+    }
+}
@@ -0,0 +1,285 @@
+//! Mapping coordinates between an image and a preprocessed copy of it.
+//!
+//! Codes are often detected in an image which was cropped, downscaled,
+//! rotated or flipped first, e.g. to speed up detection, or to compensate
+//! for the orientation of a camera. Their corners then refer to the
+//! preprocessed image. A `Transform` records how the coordinates of the
+//! original image were mapped, and `Preprocessed` composes it automatically
+//! while applying the preprocessing steps, so that the corners of detected
+//! codes can be mapped back to the original image.
+
+use std::vec::Vec;
+#[cfg(not(any(feature = "std", test)))]
+use float::F64Ext;
+use geom::{ Image, ImageBuf, QrCode, Quad, Vec2D, Vec2Df };
+use util::checked_add;
+use error::{ Error, Result };
+
+/// An affine transformation of the plane, mapping the coordinates of an
+/// image to those of a preprocessed copy of it. Coordinates are continuous:
+/// the pixel at `(x, y)` covers the square from `(x, y)` to `(x + 1, y + 1)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// The coefficients `[a, b, c, d, e, f]`, which map `(x, y)` to
+    /// `(a * x + b * y + c, d * x + e * y + f)`.
+    matrix: [f64; 6],
+}
+
+impl Transform {
+    /// The transformation which leaves every point in place.
+    pub fn identity() -> Self {
+        Transform { matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0] }
+    }
+
+    /// Scales the coordinates by `sx` horizontally and `sy` vertically,
+    /// about the origin.
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Transform { matrix: [sx, 0.0, 0.0, 0.0, sy, 0.0] }
+    }
+
+    /// Moves every point by `(dx, dy)`.
+    pub fn translate(dx: f64, dy: f64) -> Self {
+        Transform { matrix: [1.0, 0.0, dx, 0.0, 1.0, dy] }
+    }
+
+    /// Rotates about the origin by `angle` radians, clockwise as seen in an
+    /// image, whose y axis points down.
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform { matrix: [cos, -sin, 0.0, sin, cos, 0.0] }
+    }
+
+    /// Mirrors an image of the given width, left to right.
+    pub fn flip_horizontal(width: f64) -> Self {
+        Transform { matrix: [-1.0, 0.0, width, 0.0, 1.0, 0.0] }
+    }
+
+    /// Mirrors an image of the given height, top to bottom.
+    pub fn flip_vertical(height: f64) -> Self {
+        Transform { matrix: [1.0, 0.0, 0.0, 0.0, -1.0, height] }
+    }
+
+    /// Returns the transformation which applies `self` first, then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        let [a, b, c, d, e, f] = self.matrix;
+        let [na, nb, nc, nd, ne, nf] = next.matrix;
+
+        Transform {
+            matrix: [
+                na * a + nb * d,
+                na * b + nb * e,
+                na * c + nb * f + nc,
+                nd * a + ne * d,
+                nd * b + ne * e,
+                nd * c + ne * f + nf,
+            ],
+        }
+    }
+
+    /// Returns the transformation undoing `self`, or `None` if it collapses
+    /// the plane onto a line or a point, e.g. a scaling by 0.
+    pub fn inverse(&self) -> Option<Transform> {
+        let [a, b, c, d, e, f] = self.matrix;
+        let det = a * e - b * d;
+
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let (ia, ib, id, ie) = (e / det, -b / det, -d / det, a / det);
+
+        Some(Transform {
+            matrix: [ia, ib, -(ia * c + ib * f), id, ie, -(id * c + ie * f)],
+        })
+    }
+
+    /// Maps a point.
+    pub fn apply(&self, point: Vec2Df) -> Vec2Df {
+        let [a, b, c, d, e, f] = self.matrix;
+
+        Vec2Df {
+            x: a * point.x + b * point.y + c,
+            y: d * point.x + e * point.y + f,
+        }
+    }
+
+    /// Maps each corner of a quadrilateral.
+    pub fn apply_quad(&self, quad: &Quad) -> Quad {
+        let c = quad.corners;
+        Quad::new([self.apply(c[0]), self.apply(c[1]), self.apply(c[2]), self.apply(c[3])])
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl QrCode {
+    /// Returns the outline of the code in the original image, given the
+    /// transformation from the original image to the one the code was
+    /// detected in. Returns `None` if `transform` can't be undone.
+    pub fn quad_in_original(&self, transform: &Transform) -> Option<Quad> {
+        transform.inverse().map(|inverse| inverse.apply_quad(&self.quad()))
+    }
+}
+
+/// An image which went through preprocessing steps, along with the
+/// transformation of coordinates they amount to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preprocessed {
+    /// The preprocessed image.
+    image: ImageBuf,
+    /// Maps the coordinates of the original image to those of `image`.
+    transform: Transform,
+}
+
+impl Preprocessed {
+    /// Starts preprocessing an image.
+    pub fn new(image: ImageBuf) -> Self {
+        Preprocessed { image, transform: Transform::identity() }
+    }
+
+    /// Borrows the preprocessed image, e.g. for a `Decoder`.
+    pub fn as_image(&self) -> Image {
+        self.image.as_image()
+    }
+
+    /// Maps the coordinates of the original image to those of the
+    /// preprocessed one.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Returns the preprocessed image and the transformation.
+    pub fn into_parts(self) -> (ImageBuf, Transform) {
+        (self.image, self.transform)
+    }
+
+    /// Returns the outline, in the original image, of a code detected in
+    /// the preprocessed one.
+    pub fn quad_in_original(&self, code: &QrCode) -> Quad {
+        // This cannot panic because each preprocessing step is a bijection
+        // of the plane, and so is their composition.
+        code.quad_in_original(&self.transform).expect("preprocessing is not invertible")
+    }
+
+    /// Keeps only the rectangle of the given size whose top left corner is
+    /// at `origin`. Fails with `Error::InvalidArgument` if the rectangle
+    /// doesn't lie within the image.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn crop(self, origin: Vec2D, size: Vec2D) -> Result<Self> {
+        let (width, height) = (self.image.width(), self.image.height());
+
+        if checked_add(origin.x, size.x)? > width || checked_add(origin.y, size.y)? > height {
+            return Err(Error::InvalidArgument);
+        }
+
+        let data = self.image.data();
+        let mut pixels = Vec::with_capacity(size.x * size.y);
+
+        for y in origin.y..origin.y + size.y {
+            let start = y * width + origin.x;
+            pixels.extend_from_slice(&data[start..start + size.x]);
+        }
+
+        let step = Transform::translate(-(origin.x as f64), -(origin.y as f64));
+        self.with(pixels, size, &step)
+    }
+
+    /// Shrinks the image by an integer factor along both axes, averaging
+    /// each square of `factor` by `factor` pixels. Pixels at the right and
+    /// bottom edges which don't fill a whole square are dropped. Fails with
+    /// `Error::InvalidArgument` if `factor` is 0.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss, cast_possible_truncation))]
+    pub fn downscale(self, factor: usize) -> Result<Self> {
+        if factor == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let width = self.image.width();
+        let size = Vec2D { x: width / factor, y: self.image.height() / factor };
+        let data = self.image.data();
+        let area = (factor * factor) as u64;
+        let mut pixels = Vec::with_capacity(size.x * size.y);
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let sum: u64 = (y * factor..(y + 1) * factor)
+                    .flat_map(|sy| &data[sy * width + x * factor..sy * width + (x + 1) * factor])
+                    .map(|&value| u64::from(value))
+                    .sum();
+
+                pixels.push(((sum + area / 2) / area) as u8);
+            }
+        }
+
+        let scale = 1.0 / factor as f64;
+        self.with(pixels, size, &Transform::scale(scale, scale))
+    }
+
+    /// Rotates the image by a quarter turn clockwise.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn rotate_cw(self) -> Result<Self> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let data = self.image.data();
+        let pixels = (0..width)
+            .flat_map(|y| (0..height).map(move |x| data[(height - 1 - x) * width + y]))
+            .collect();
+        let step = Transform { matrix: [0.0, -1.0, height as f64, 1.0, 0.0, 0.0] };
+
+        self.with(pixels, Vec2D { x: height, y: width }, &step)
+    }
+
+    /// Rotates the image by a quarter turn counterclockwise.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn rotate_ccw(self) -> Result<Self> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let data = self.image.data();
+        let pixels = (0..width)
+            .flat_map(|y| (0..height).map(move |x| data[x * width + width - 1 - y]))
+            .collect();
+        let step = Transform { matrix: [0.0, 1.0, 0.0, -1.0, 0.0, width as f64] };
+
+        self.with(pixels, Vec2D { x: height, y: width }, &step)
+    }
+
+    /// Mirrors the image left to right.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn flip_horizontal(self) -> Result<Self> {
+        let width = self.image.width();
+        let pixels = self.image
+            .data()
+            .chunks(width.max(1))
+            .flat_map(|row| row.iter().rev().cloned())
+            .collect();
+        let size = Vec2D { x: width, y: self.image.height() };
+
+        self.with(pixels, size, &Transform::flip_horizontal(width as f64))
+    }
+
+    /// Mirrors the image top to bottom.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    pub fn flip_vertical(self) -> Result<Self> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let pixels = self.image
+            .data()
+            .chunks(width.max(1))
+            .rev()
+            .flat_map(|row| row.iter().cloned())
+            .collect();
+        let size = Vec2D { x: width, y: height };
+
+        self.with(pixels, size, &Transform::flip_vertical(height as f64))
+    }
+
+    /// Replaces the image by the result of a preprocessing step, and
+    /// appends the step to the transformation.
+    fn with(&self, pixels: Vec<u8>, size: Vec2D, step: &Transform) -> Result<Self> {
+        Ok(Preprocessed {
+            image: ImageBuf::new(pixels, size)?,
+            transform: self.transform.then(step),
+        })
+    }
+}
@@ -2,18 +2,18 @@
 
 use std::usize;
 use std::mem::size_of;
-use libc::{ c_int, INT_MAX };
-use error::{ Error, Result };
+use std::os::raw::c_int;
+use error::{ Error, Overflow, Result };
 
 /// Attempts to convert a `usize` to an `int` without overflow.
 #[cfg_attr(feature = "cargo-clippy", allow(if_same_then_else, cast_possible_truncation, cast_possible_wrap))]
 pub fn usize_to_int(n: usize) -> Result<c_int> {
     if size_of::<usize>() < size_of::<c_int>() {
         Ok(n as c_int)
-    } else if n <= INT_MAX as usize {
+    } else if n <= c_int::max_value() as usize {
         Ok(n as c_int)
     } else {
-        Err(Error::IntOverflow)
+        Err(Error::IntOverflow(Overflow::Value(n as i128)))
     }
 }
 
@@ -21,12 +21,109 @@ pub fn usize_to_int(n: usize) -> Result<c_int> {
 #[cfg_attr(feature = "cargo-clippy", allow(if_same_then_else, cast_possible_truncation, cast_possible_wrap))]
 pub fn int_to_usize(n: c_int) -> Result<usize> {
     if n < 0 {
-        Err(Error::IntOverflow)
+        Err(Error::IntOverflow(Overflow::Value(n.into())))
     } else if size_of::<c_int>() <= size_of::<usize>() {
         Ok(n as usize)
     } else if n <= usize::MAX as c_int {
         Ok(n as usize)
     } else {
-        Err(Error::IntOverflow)
+        Err(Error::IntOverflow(Overflow::Value(n.into())))
     }
 }
+
+/// Attempts to convert a `usize` to a `u32` without overflow.
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, cast_possible_wrap))]
+pub fn usize_to_u32(n: usize) -> Result<u32> {
+    if n as u64 <= u64::from(u32::max_value()) {
+        Ok(n as u32)
+    } else {
+        Err(Error::IntOverflow(Overflow::Value(n as i128)))
+    }
+}
+
+/// Attempts to convert a `u32` to a `usize` without overflow. Only some
+/// optional features use it, hence no complaints when none is enabled.
+#[allow(dead_code)]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+pub fn u32_to_usize(n: u32) -> Result<usize> {
+    if u64::from(n) <= usize::MAX as u64 {
+        Ok(n as usize)
+    } else {
+        Err(Error::IntOverflow(Overflow::Value(n.into())))
+    }
+}
+
+/// Attempts to convert a `u64` to a `usize` without overflow.
+#[cfg(feature = "mobile")]
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+pub fn u64_to_usize(n: u64) -> Result<usize> {
+    if n <= usize::MAX as u64 {
+        Ok(n as usize)
+    } else {
+        Err(Error::IntOverflow(Overflow::Value(n.into())))
+    }
+}
+
+/// Converts a `usize` to a `u64`, which is at least as wide on every
+/// supported platform.
+#[cfg(feature = "mobile")]
+pub fn usize_to_u64(n: usize) -> u64 {
+    n as u64
+}
+
+/// Multiplies two sizes, failing with the operands if the product overflows.
+pub fn checked_mul(a: usize, b: usize) -> Result<usize> {
+    a.checked_mul(b).ok_or(Error::IntOverflow(Overflow::Product(a, b)))
+}
+
+/// Adds two sizes, failing with the operands if the sum overflows.
+pub fn checked_add(a: usize, b: usize) -> Result<usize> {
+    a.checked_add(b).ok_or(Error::IntOverflow(Overflow::Sum(a, b)))
+}
+
+/// Appends `s` to `out` as a JSON string literal, including the quotes.
+#[cfg(feature = "std")]
+pub fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
+
+/// Encodes `bytes` using the standard, padded Base64 alphabet.
+#[cfg(feature = "std")]
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
@@ -0,0 +1,836 @@
+//! Scanning sequences of frames, e.g. from a camera.
+//!
+//! A `Scanner` decodes successive frames, and follows each physical code
+//! from frame to frame by its payload and its approximate location. Rather
+//! than reporting every code in every frame, it emits a `ScanEvent` when a
+//! code appears, moves, or disappears. Appearance and disappearance are
+//! debounced, so that a code which is missed in a frame or two (because of
+//! motion blur, say) doesn't flicker in and out of existence.
+//!
+//! The center of each code is smoothed and extrapolated with an alpha-beta
+//! filter, so that moving codes are matched against where they are expected
+//! to be, and so that their position can still be estimated in frames in
+//! which they are missed (see `Scanner::tracked()`).
+//!
+//! When decoding can't keep up with the camera, `Scanner::scan_at()` skips
+//! frames which are already too old to be worth decoding, according to
+//! `ScannerOptions::max_latency` and `ScannerOptions::skip_strategy`, so
+//! that frames don't pile up in the capture queue. `Scanner::stats()`
+//! reports how many frames were processed and dropped.
+//!
+//! With the `v4l2` feature, `CameraSource` captures frames from a Video4Linux
+//! device on Linux, ready to be passed to a `Scanner`. With the `gstreamer`
+//! feature, `AppSinkScanner` scans the frames arriving at a GStreamer
+//! `appsink`, at the end of an arbitrary pipeline.
+
+use std::mem;
+use std::cmp::Ordering;
+use std::time::{ Duration, Instant };
+use decoder::Decoder;
+use geom::{ Image, QrCode, Vec2Df };
+use info::Info;
+use schema::{ CodeRecord, EventKind, ScanEvent };
+use error::Result;
+
+#[cfg(feature = "v4l2")]
+use std::{ fmt, io };
+#[cfg(feature = "v4l2")]
+use std::path::Path;
+#[cfg(feature = "v4l2")]
+use v4l::{ Device, FourCC };
+#[cfg(feature = "v4l2")]
+use v4l::buffer::Type;
+#[cfg(feature = "v4l2")]
+use v4l::video::Capture;
+#[cfg(feature = "v4l2")]
+use v4l::io::mmap::Stream;
+#[cfg(feature = "v4l2")]
+use v4l::io::traits::CaptureStream;
+#[cfg(feature = "v4l2")]
+use util::usize_to_u32;
+#[cfg(feature = "gstreamer")]
+use std::collections::VecDeque;
+#[cfg(feature = "gstreamer")]
+use gst::Sample;
+#[cfg(feature = "gstreamer")]
+use gst_app::AppSink;
+#[cfg(feature = "gstreamer")]
+use gst_video::{ VideoCapsBuilder, VideoFormat, VideoInfo };
+#[cfg(feature = "gstreamer")]
+use util::int_to_usize;
+#[cfg(any(feature = "v4l2", feature = "gstreamer"))]
+use geom::{ ImageBuf, Vec2D };
+#[cfg(any(feature = "v4l2", feature = "gstreamer"))]
+use util::u32_to_usize;
+#[cfg(any(feature = "v4l2", feature = "gstreamer"))]
+use error::Error;
+
+/// Parameters of tracking codes across frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScannerOptions {
+    /// The number of consecutive frames in which a code must be found
+    /// before it is reported as having appeared.
+    pub appear_after: u32,
+    /// The number of consecutive frames in which a code must be missing
+    /// before it is reported as having disappeared.
+    pub disappear_after: u32,
+    /// How far the center of a code may move between two frames in which
+    /// it is found, relative to its side length, for it to be considered
+    /// the same physical code.
+    pub max_displacement: f64,
+    /// How far the center of a code must move from where it was last
+    /// reported, relative to its side length, for an update to be reported.
+    pub update_threshold: f64,
+    /// The alpha gain of the motion filter, in the range `(0, 1]`: how much
+    /// the estimated position follows the measured one rather than the
+    /// prediction. 1 disables smoothing.
+    pub position_gain: f64,
+    /// The beta gain of the motion filter, in the range `[0, 2)`: how fast
+    /// the estimated velocity adapts. 0 disables motion prediction.
+    pub velocity_gain: f64,
+    /// How old a frame may be by the time it has been decoded, counting
+    /// from its capture.
+    pub max_latency: Duration,
+    /// How frames which would exceed `max_latency` are recognized.
+    pub skip_strategy: SkipStrategy,
+}
+
+impl Default for ScannerOptions {
+    fn default() -> Self {
+        ScannerOptions {
+            appear_after: 2,
+            disappear_after: 5,
+            max_displacement: 1.0,
+            update_threshold: 0.1,
+            position_gain: 0.85,
+            velocity_gain: 0.3,
+            max_latency: Duration::from_millis(100),
+            skip_strategy: SkipStrategy::DropStale,
+        }
+    }
+}
+
+/// Determines which frames a `Scanner` skips in order to keep latency low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipStrategy {
+    /// Decode every frame, however late.
+    Never,
+    /// Skip the frames which are older than `max_latency` when they are
+    /// passed to the scanner.
+    DropStale,
+    /// Also skip the frames which, judging by how long recent frames took
+    /// to decode, would be older than `max_latency` by the time they have
+    /// been decoded. If decoding takes longer than `max_latency` even for
+    /// a fresh frame, only stale frames are skipped.
+    DropPredicted,
+}
+
+impl SkipStrategy {
+    /// Returns whether a frame of the given age should be skipped, given
+    /// the expected time it takes to decode a frame.
+    fn skips(self, age: Duration, decode_time: Option<Duration>, max_latency: Duration) -> bool {
+        match self {
+            SkipStrategy::Never => false,
+            SkipStrategy::DropStale => age > max_latency,
+            SkipStrategy::DropPredicted => {
+                let expected = decode_time.unwrap_or_default();
+
+                age > max_latency || (expected <= max_latency && age + expected > max_latency)
+            }
+        }
+    }
+}
+
+/// The number of frames a `Scanner` processed and dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScanStats {
+    /// The number of frames decoded since the scanner was created.
+    pub frames_processed: u64,
+    /// The number of frames skipped since the scanner was created.
+    pub frames_dropped: u64,
+    /// The number of frames decoded per second, over the last complete
+    /// one-second window.
+    pub processed_per_second: f64,
+    /// The number of frames skipped per second, over the last complete
+    /// one-second window.
+    pub dropped_per_second: f64,
+}
+
+/// Counts the frames processed and dropped, and measures their rates over
+/// one-second windows.
+#[derive(Debug, Clone, Copy)]
+struct Throughput {
+    /// The totals, and the rates over the last complete window.
+    stats: ScanStats,
+    /// The beginning of the current window.
+    window_start: Instant,
+    /// The number of frames processed in the current window.
+    window_processed: u64,
+    /// The number of frames dropped in the current window.
+    window_dropped: u64,
+}
+
+impl Throughput {
+    /// The length of the windows over which rates are measured.
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    /// Starts counting.
+    fn new() -> Self {
+        Throughput {
+            stats: ScanStats::default(),
+            window_start: Instant::now(),
+            window_processed: 0,
+            window_dropped: 0,
+        }
+    }
+
+    /// Counts a frame which was either processed or dropped at `now`.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn record(&mut self, processed: bool, now: Instant) {
+        if processed {
+            self.stats.frames_processed += 1;
+            self.window_processed += 1;
+        } else {
+            self.stats.frames_dropped += 1;
+            self.window_dropped += 1;
+        }
+
+        let elapsed = now.checked_duration_since(self.window_start).unwrap_or_default();
+
+        if elapsed >= Self::WINDOW {
+            let seconds = elapsed.as_secs_f64();
+
+            self.stats.processed_per_second = self.window_processed as f64 / seconds;
+            self.stats.dropped_per_second = self.window_dropped as f64 / seconds;
+            self.window_start = now;
+            self.window_processed = 0;
+            self.window_dropped = 0;
+        }
+    }
+}
+
+/// A physical code followed across frames.
+#[derive(Debug, Clone)]
+struct Track {
+    /// The code as last found.
+    record: CodeRecord,
+    /// The center of the code, as last found.
+    measured: Vec2Df,
+    /// The estimated center of the code in the current frame.
+    center: Vec2Df,
+    /// The estimated velocity of the center, in pixels per frame.
+    velocity: Vec2Df,
+    /// The mean side length of the code, as last found, in pixels.
+    side: f64,
+    /// The center of the code when it was last reported, if it has been
+    /// reported as having appeared.
+    reported_center: Option<Vec2Df>,
+    /// The number of consecutive frames in which the code was found.
+    hits: u32,
+    /// The number of consecutive frames in which the code was missing.
+    misses: u32,
+}
+
+impl Track {
+    /// Starts following a code found in the current frame.
+    fn new(record: CodeRecord, code: &QrCode) -> Self {
+        Track {
+            record,
+            measured: code.center(),
+            center: code.center(),
+            velocity: Vec2Df::default(),
+            side: side_length(code),
+            reported_center: None,
+            hits: 1,
+            misses: 0,
+        }
+    }
+
+    /// Returns whether `code` with the given payload may be this code in
+    /// the current frame, and if so, how far it moved.
+    fn distance_to(&self, payload: &[u8], code: &QrCode, max_displacement: f64) -> Option<f64> {
+        let distance = self.center.distance(code.center());
+
+        if self.record.payload == payload && distance <= self.side * max_displacement {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    /// Advances the estimated center to the next frame.
+    fn predict(&mut self) {
+        self.center.x += self.velocity.x;
+        self.center.y += self.velocity.y;
+    }
+
+    /// Corrects the predicted center and the velocity by the center
+    /// measured in the current frame.
+    fn correct(&mut self, measured: Vec2Df, options: &ScannerOptions) {
+        let residual = Vec2Df {
+            x: measured.x - self.center.x,
+            y: measured.y - self.center.y,
+        };
+
+        self.measured = measured;
+        self.center.x += options.position_gain * residual.x;
+        self.center.y += options.position_gain * residual.y;
+        self.velocity.x += options.velocity_gain * residual.x;
+        self.velocity.y += options.velocity_gain * residual.y;
+    }
+
+    /// Returns the corners as last found, moved along with the center.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+    fn corners(&self) -> [Vec2Df; 4] {
+        let mut corners = [Vec2Df::default(); 4];
+
+        for (corner, found) in corners.iter_mut().zip(&self.record.corners) {
+            corner.x = found.x as f64 + self.center.x - self.measured.x;
+            corner.y = found.y as f64 + self.center.y - self.measured.y;
+        }
+
+        corners
+    }
+}
+
+/// The estimated state of a code which is being tracked.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackedCode<'a> {
+    /// The code as last found.
+    pub record: &'a CodeRecord,
+    /// The estimated center of the code in the current frame.
+    pub center: Vec2Df,
+    /// The estimated corners of the code in the current frame, clockwise
+    /// from the top left one: the corners as last found, moved along with
+    /// the center.
+    pub corners: [Vec2Df; 4],
+    /// The estimated velocity of the center, in pixels per frame.
+    pub velocity: Vec2Df,
+    /// The number of consecutive frames, up to and including the current
+    /// one, in which the code was missed, and its position was predicted.
+    pub frames_missed: u32,
+}
+
+/// Returns the mean length of the sides of a code, in pixels.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+fn side_length(code: &QrCode) -> f64 {
+    code.module_size_px() * code.size() as f64
+}
+
+/// Decodes successive frames and reports the codes appearing, moving and
+/// disappearing.
+#[derive(Debug)]
+pub struct Scanner {
+    /// The decoder used for every frame.
+    decoder: Decoder,
+    /// The parameters of tracking.
+    options: ScannerOptions,
+    /// The codes currently followed, whether reported or not.
+    tracks: Vec<Track>,
+    /// The index of the next frame.
+    frame: u64,
+    /// The running average of the time it took to decode a frame.
+    decode_time: Option<Duration>,
+    /// The frames processed and dropped.
+    throughput: Throughput,
+}
+
+impl Scanner {
+    /// Creates a scanner with the default options.
+    pub fn new() -> Result<Self> {
+        Self::with_options(ScannerOptions::default())
+    }
+
+    /// Creates a scanner with the given options.
+    pub fn with_options(options: ScannerOptions) -> Result<Self> {
+        Ok(Scanner {
+            decoder: Decoder::new()?,
+            options,
+            tracks: Vec::new(),
+            frame: 0,
+            decode_time: None,
+            throughput: Throughput::new(),
+        })
+    }
+
+    /// Returns the options of the scanner.
+    pub fn options(&self) -> &ScannerOptions {
+        &self.options
+    }
+
+    /// Returns the index of the next frame, i.e. the number of frames
+    /// scanned so far.
+    pub fn frame_index(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns the number of frames processed and dropped.
+    pub fn stats(&self) -> ScanStats {
+        self.throughput.stats
+    }
+
+    /// Returns the running average of the time it took to decode a frame,
+    /// or `None` if no frame has been decoded yet.
+    pub fn decode_time(&self) -> Option<Duration> {
+        self.decode_time
+    }
+
+    /// Returns the codes which have been reported as having appeared, and
+    /// haven't been reported as having disappeared yet, as last found.
+    pub fn visible(&self) -> Vec<&CodeRecord> {
+        self.tracks
+            .iter()
+            .filter(|track| track.reported_center.is_some())
+            .map(|track| &track.record)
+            .collect()
+    }
+
+    /// Returns the estimated state of the visible codes in the current
+    /// frame, including the ones which were missed in the last few frames.
+    /// This is what overlays should be drawn from.
+    pub fn tracked(&self) -> Vec<TrackedCode> {
+        self.tracks
+            .iter()
+            .filter(|track| track.reported_center.is_some())
+            .map(|track| TrackedCode {
+                record: &track.record,
+                center: track.center,
+                corners: track.corners(),
+                velocity: track.velocity,
+                frames_missed: track.misses,
+            })
+            .collect()
+    }
+
+    /// Scans the next frame, and returns the events it gave rise to.
+    /// Codes which are detected but can't be decoded are ignored.
+    ///
+    /// The frame is considered to have just been captured, so it is only
+    /// ever skipped by `SkipStrategy::DropPredicted`, in which case no
+    /// events are returned.
+    pub fn scan(&mut self, image: &Image) -> Result<Vec<ScanEvent>> {
+        self.scan_at(image, Instant::now()).map(Option::unwrap_or_default)
+    }
+
+    /// Scans the next frame, which was captured at `captured`, and returns
+    /// the events it gave rise to, or `None` if the frame was skipped
+    /// because it was too old.
+    ///
+    /// A skipped frame still counts as a frame: the estimated positions of
+    /// the codes move on, but the codes aren't considered missed in it.
+    pub fn scan_at(&mut self, image: &Image, captured: Instant) -> Result<Option<Vec<ScanEvent>>> {
+        let start = Instant::now();
+        let age = start.checked_duration_since(captured).unwrap_or_default();
+        let options = self.options;
+
+        if options.skip_strategy.skips(age, self.decode_time, options.max_latency) {
+            self.skip();
+            self.throughput.record(false, start);
+            return Ok(None);
+        }
+
+        let found: Vec<(QrCode, Info)> = self.decoder
+            .decode_image(image)?
+            .decoded()
+            .filter_map(|code| code.ok())
+            .collect();
+
+        let end = Instant::now();
+        let elapsed = end.duration_since(start);
+
+        self.decode_time = Some(match self.decode_time {
+            Some(average) => (average * 7 + elapsed) / 8,
+            None => elapsed,
+        });
+        self.throughput.record(true, end);
+
+        Ok(Some(self.update(&found)))
+    }
+
+    /// Reports every visible code as having disappeared, and forgets all
+    /// codes, e.g. when the camera is switched off. The frame index keeps
+    /// counting.
+    pub fn finish(&mut self) -> Vec<ScanEvent> {
+        let frame = self.frame;
+
+        mem::replace(&mut self.tracks, Vec::new())
+            .into_iter()
+            .filter(|track| track.reported_center.is_some())
+            .map(|track| ScanEvent::new(EventKind::Disappeared, frame, track.record))
+            .collect()
+    }
+
+    /// Advances to the next frame without looking at it.
+    fn skip(&mut self) {
+        self.frame += 1;
+
+        for track in &mut self.tracks {
+            track.predict();
+        }
+    }
+
+    /// Matches the codes found in the current frame to the tracks, updates
+    /// the tracks, and returns the resulting events.
+    fn update(&mut self, found: &[(QrCode, Info)]) -> Vec<ScanEvent> {
+        let frame = self.frame;
+        let options = self.options;
+        let mut matched = vec![false; self.tracks.len()];
+        let mut events = Vec::new();
+        let mut new_tracks = Vec::new();
+
+        self.frame += 1;
+
+        for track in &mut self.tracks {
+            track.predict();
+        }
+
+        for &(ref code, ref info) in found {
+            let record = CodeRecord::new(code, info);
+            let closest = self.tracks
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| !matched[i])
+                .filter_map(|(i, track)| {
+                    track.distance_to(info.payload(), code, options.max_displacement).map(|d| (i, d))
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+            let index = match closest {
+                Some((index, _)) => index,
+                None => {
+                    new_tracks.push(Track::new(record, code));
+                    continue;
+                }
+            };
+            let track = &mut self.tracks[index];
+
+            matched[index] = true;
+            track.record = record;
+            track.correct(code.center(), &options);
+            track.side = side_length(code);
+            track.hits = track.hits.saturating_add(1);
+            track.misses = 0;
+
+            match track.reported_center {
+                Some(reported) => {
+                    if reported.distance(track.center) > track.side * options.update_threshold {
+                        track.reported_center = Some(track.center);
+                        events.push(ScanEvent::new(EventKind::Updated, frame, track.record.clone()));
+                    }
+                }
+                None => {
+                    if track.hits >= options.appear_after {
+                        track.reported_center = Some(track.center);
+                        events.push(ScanEvent::new(EventKind::Appeared, frame, track.record.clone()));
+                    }
+                }
+            }
+        }
+
+        let mut kept = Vec::with_capacity(self.tracks.len() + new_tracks.len());
+
+        for (mut track, found_now) in mem::replace(&mut self.tracks, Vec::new()).into_iter().zip(matched) {
+            if !found_now {
+                track.hits = 0;
+                track.misses = track.misses.saturating_add(1);
+
+                if track.misses >= options.disappear_after {
+                    if track.reported_center.is_some() {
+                        events.push(ScanEvent::new(EventKind::Disappeared, frame, track.record));
+                    }
+                    continue;
+                }
+
+                // A code which was never reported disappears silently as
+                // soon as it is missed, since it was likely a misdetection.
+                if track.reported_center.is_none() {
+                    continue;
+                }
+            }
+
+            kept.push(track);
+        }
+
+        // Codes found for the first time may be reported right away if
+        // a single frame suffices.
+        for mut track in new_tracks {
+            if track.hits >= options.appear_after {
+                track.reported_center = Some(track.center);
+                events.push(ScanEvent::new(EventKind::Appeared, frame, track.record.clone()));
+            }
+
+            kept.push(track);
+        }
+
+        self.tracks = kept;
+
+        events
+    }
+}
+
+/// The number of buffers shared with the driver by a `CameraSource`.
+#[cfg(feature = "v4l2")]
+const CAMERA_BUFFERS: u32 = 4;
+
+/// A pixel format which a `CameraSource` can convert to grayscale.
+#[cfg(feature = "v4l2")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CameraFormat {
+    /// Packed 4:2:2 YUV, as delivered by most webcams.
+    Yuyv,
+    /// Semi-planar 4:2:0 YUV, as delivered by many embedded cameras.
+    Nv12,
+}
+
+#[cfg(feature = "v4l2")]
+impl CameraFormat {
+    /// The formats in order of preference.
+    const ALL: [CameraFormat; 2] = [CameraFormat::Yuyv, CameraFormat::Nv12];
+
+    /// Returns the V4L2 pixel format code.
+    fn fourcc(self) -> FourCC {
+        match self {
+            CameraFormat::Yuyv => FourCC::new(b"YUYV"),
+            CameraFormat::Nv12 => FourCC::new(b"NV12"),
+        }
+    }
+}
+
+/// Captures grayscale frames from a Video4Linux device, e.g. `/dev/video0`.
+/// Iterating over it yields the frames as they arrive, forever.
+#[cfg(feature = "v4l2")]
+pub struct CameraSource {
+    /// The memory-mapped buffers shared with the driver. It holds its own
+    /// handle to the device, which stays open for as long as it lives.
+    stream: Stream<'static>,
+    /// The negotiated pixel format.
+    format: CameraFormat,
+    /// The negotiated size of the frames.
+    size: Vec2D,
+    /// The distance between the beginnings of consecutive rows, in bytes.
+    stride: usize,
+}
+
+#[cfg(feature = "v4l2")]
+impl CameraSource {
+    /// Opens the device at `path`, and asks for frames of the given size
+    /// in a format which can be converted to grayscale. The driver may
+    /// choose a different size; see `size()`.
+    ///
+    /// Fails with `Error::Io(InvalidInput)` if the device supports none of
+    /// the formats which can be converted.
+    pub fn open<P: AsRef<Path>>(path: P, size: Vec2D) -> Result<Self> {
+        let device = Device::with_path(path)?;
+        let mut requested = device.format()?;
+
+        requested.width = usize_to_u32(size.x)?;
+        requested.height = usize_to_u32(size.y)?;
+
+        for &format in &CameraFormat::ALL {
+            requested.fourcc = format.fourcc();
+
+            let actual = device.set_format(&requested)?;
+
+            if actual.fourcc != requested.fourcc {
+                continue;
+            }
+
+            let stream = Stream::with_buffers(&device, Type::VideoCapture, CAMERA_BUFFERS)?;
+            let width = u32_to_usize(actual.width)?;
+            let height = u32_to_usize(actual.height)?;
+            let stride = u32_to_usize(actual.stride)?;
+
+            return Ok(CameraSource {
+                stream,
+                format,
+                size: Vec2D { x: width, y: height },
+                stride,
+            });
+        }
+
+        Err(Error::Io(io::ErrorKind::InvalidInput))
+    }
+
+    /// Returns the size of the frames.
+    pub fn size(&self) -> Vec2D {
+        self.size
+    }
+
+    /// Waits for the next frame, and returns its luma channel.
+    pub fn next_frame(&mut self) -> Result<ImageBuf> {
+        let (buffer, meta) = CaptureStream::next(&mut self.stream)?;
+        let len = u32_to_usize(meta.bytesused)?.min(buffer.len());
+        let data = &buffer[..len];
+
+        match self.format {
+            CameraFormat::Yuyv => ImageBuf::from_yuyv(data, self.size, self.stride),
+            CameraFormat::Nv12 => ImageBuf::from_nv12(data, self.size, self.stride),
+        }
+    }
+}
+
+#[cfg(feature = "v4l2")]
+impl Iterator for CameraSource {
+    type Item = Result<ImageBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_frame())
+    }
+}
+
+#[cfg(feature = "v4l2")]
+impl fmt::Debug for CameraSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CameraSource")
+            .field("format", &self.format)
+            .field("size", &self.size)
+            .field("stride", &self.stride)
+            .finish()
+    }
+}
+
+/// The raw video formats which an `AppSinkScanner` accepts.
+#[cfg(feature = "gstreamer")]
+const APPSINK_FORMATS: [VideoFormat; 4] = [
+    VideoFormat::Gray8,
+    VideoFormat::Nv12,
+    VideoFormat::I420,
+    VideoFormat::Yuy2,
+];
+
+/// Scans the frames arriving at a GStreamer `appsink` for codes.
+///
+/// Iterating over it waits for frames, and yields the events of each one
+/// in turn. At the end of the stream, or once the pipeline is stopped,
+/// the codes still in view are reported as having disappeared, and the
+/// iteration ends. GStreamer must have been initialized with `gst::init()`.
+#[cfg(feature = "gstreamer")]
+#[derive(Debug)]
+pub struct AppSinkScanner {
+    /// The sink the frames are pulled from.
+    sink: AppSink,
+    /// Tracks the codes across frames.
+    scanner: Scanner,
+    /// The events of the last frame which haven't been yielded yet.
+    pending: VecDeque<ScanEvent>,
+    /// Whether the sink has run out of frames.
+    finished: bool,
+}
+
+#[cfg(feature = "gstreamer")]
+impl AppSinkScanner {
+    /// Wraps `sink`, scanning its frames with the default options.
+    pub fn new(sink: AppSink) -> Result<Self> {
+        Scanner::new().map(|scanner| Self::with_scanner(sink, scanner))
+    }
+
+    /// Wraps `sink`, scanning its frames with `scanner`.
+    ///
+    /// The caps of the sink are restricted to raw GRAY8, NV12, I420 and
+    /// YUY2 video, so it should be wrapped before the pipeline starts
+    /// playing, and upstream may need a `videoconvert` element.
+    pub fn with_scanner(sink: AppSink, scanner: Scanner) -> Self {
+        let caps = VideoCapsBuilder::new()
+            .format_list(APPSINK_FORMATS.iter().cloned())
+            .build();
+
+        sink.set_caps(Some(&caps));
+
+        AppSinkScanner {
+            sink,
+            scanner,
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Returns the wrapped sink.
+    pub fn sink(&self) -> &AppSink {
+        &self.sink
+    }
+
+    /// Returns the scanner the frames are passed to.
+    pub fn scanner(&self) -> &Scanner {
+        &self.scanner
+    }
+
+    /// Waits for the next frame, and returns its luma channel without
+    /// scanning it. Returns `None` at the end of the stream.
+    pub fn next_frame(&mut self) -> Option<Result<ImageBuf>> {
+        self.sink.pull_sample().ok().map(|sample| sample_to_image(&sample))
+    }
+
+    /// Consumes the adapter, and yields the payloads of the codes as they
+    /// come into view. A code is yielded again only if it disappears and
+    /// then reappears.
+    pub fn payloads(self) -> impl Iterator<Item = Result<Vec<u8>>> {
+        self.filter_map(|event| match event {
+            Ok(event) => if event.kind == EventKind::Appeared {
+                Some(Ok(event.code.payload))
+            } else {
+                None
+            },
+            Err(error) => Some(Err(error)),
+        })
+    }
+}
+
+#[cfg(feature = "gstreamer")]
+impl Iterator for AppSinkScanner {
+    type Item = Result<ScanEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            // Fails at the end of the stream, and when the pipeline stops.
+            match self.sink.pull_sample() {
+                Ok(sample) => {
+                    let image = match sample_to_image(&sample) {
+                        Ok(image) => image,
+                        Err(error) => return Some(Err(error)),
+                    };
+
+                    match self.scanner.scan(&image.as_image()) {
+                        Ok(events) => self.pending.extend(events),
+                        Err(error) => return Some(Err(error)),
+                    }
+                }
+                Err(_) => {
+                    self.finished = true;
+                    self.pending.extend(self.scanner.finish());
+                }
+            }
+        }
+    }
+}
+
+/// Extracts the luma channel of a raw video frame pulled from an `appsink`.
+#[cfg(feature = "gstreamer")]
+fn sample_to_image(sample: &Sample) -> Result<ImageBuf> {
+    let caps = sample.caps().ok_or(Error::InvalidImage)?;
+    let info = VideoInfo::from_caps(caps).map_err(|_| Error::InvalidImage)?;
+    let buffer = sample.buffer().ok_or(Error::InvalidImage)?;
+    let map = buffer.map_readable().map_err(|_| Error::InvalidImage)?;
+    let size = Vec2D {
+        x: u32_to_usize(info.width())?,
+        y: u32_to_usize(info.height())?,
+    };
+    // Only the first plane is read, which is the Y plane of every format.
+    let stride = int_to_usize(info.stride()[0])?;
+    let data = map.as_slice().get(info.offset()[0]..).ok_or(Error::InvalidImage)?;
+
+    match info.format() {
+        // A GRAY8 frame is a lone Y plane, just like the first plane of NV12.
+        VideoFormat::Gray8 | VideoFormat::Nv12 => ImageBuf::from_nv12(data, size, stride),
+        VideoFormat::I420 => ImageBuf::from_i420(data, size, stride),
+        VideoFormat::Yuy2 => ImageBuf::from_yuyv(data, size, stride),
+        _ => Err(Error::InvalidImage),
+    }
+}
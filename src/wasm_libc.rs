@@ -0,0 +1,115 @@
+//! The parts of the C standard library used by `quirc`, which has no C
+//! library to link against on `wasm32-unknown-unknown`. They are declared
+//! by the headers in `wasm/include`. `memcpy()` and friends come from the
+//! compiler builtins of the Rust standard library.
+
+use std::ptr;
+use std::alloc::{ self, Layout };
+use std::os::raw::{ c_int, c_void };
+
+/// The alignment of every allocation, that of `max_align_t`. The size of
+/// an allocation is stored in this many bytes in front of it, since
+/// `free()` isn't told the size, but the Rust allocator needs it.
+const ALIGN: usize = 16;
+
+/// Returns the layout of an allocation of `size` bytes plus its header.
+fn layout(size: usize) -> Option<Layout> {
+    size.checked_add(ALIGN).and_then(|total| Layout::from_size_align(total, ALIGN).ok())
+}
+
+/// Returns the beginning of the allocation `ptr` points into, and its layout.
+unsafe fn block(ptr: *mut c_void) -> (*mut u8, Layout) {
+    let base = (ptr as *mut u8).sub(ALIGN);
+    let size = (base as *const usize).read();
+
+    (base, Layout::from_size_align_unchecked(size + ALIGN, ALIGN))
+}
+
+/// Stores `size` in the header of the allocation at `base`, and returns
+/// the pointer handed out to C, or null if `base` is null.
+unsafe fn finish(base: *mut u8, size: usize) -> *mut c_void {
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+
+    (base as *mut usize).write(size);
+    base.add(ALIGN) as *mut c_void
+}
+
+/// Allocates `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    match layout(size) {
+        Some(layout) => finish(alloc::alloc(layout), size),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Allocates `count * size` zeroed bytes.
+#[no_mangle]
+pub unsafe extern "C" fn calloc(count: usize, size: usize) -> *mut c_void {
+    match count.checked_mul(size).and_then(|total| layout(total).map(|l| (total, l))) {
+        Some((total, layout)) => finish(alloc::alloc_zeroed(layout), total),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Resizes the allocation `ptr` points to, which may be null.
+#[no_mangle]
+pub unsafe extern "C" fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return malloc(size);
+    }
+
+    let (base, old_layout) = block(ptr);
+
+    match layout(size) {
+        Some(new_layout) => finish(alloc::realloc(base, old_layout, new_layout.size()), size),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Frees the allocation `ptr` points to, which may be null.
+#[no_mangle]
+pub unsafe extern "C" fn free(ptr: *mut c_void) {
+    if !ptr.is_null() {
+        let (base, layout) = block(ptr);
+        alloc::dealloc(base, layout);
+    }
+}
+
+/// Returns the absolute value of `n`.
+#[no_mangle]
+pub extern "C" fn abs(n: c_int) -> c_int {
+    n.wrapping_abs()
+}
+
+/// Returns the square root of `x`.
+#[no_mangle]
+pub extern "C" fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+/// Returns the absolute value of `x`.
+#[no_mangle]
+pub extern "C" fn fabs(x: f64) -> f64 {
+    x.abs()
+}
+
+/// Rounds `x` towards negative infinity.
+#[no_mangle]
+pub extern "C" fn floor(x: f64) -> f64 {
+    x.floor()
+}
+
+/// Rounds `x` to the nearest integer, and halfway cases to even, unlike
+/// `f64::round()`, which rounds them away from zero.
+#[no_mangle]
+#[cfg_attr(feature = "cargo-clippy", allow(float_cmp))]
+pub extern "C" fn rint(x: f64) -> f64 {
+    if (x - x.trunc()).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        x.round()
+    }
+}
@@ -0,0 +1,173 @@
+//! ZBar as a `Backend`, for comparing `quirc` against another engine.
+//!
+//! ZBar only reports the payloads and the locations of the codes it finds,
+//! not their modules, so `ZbarBackend` can't extract or decode `QrCode`s.
+//! It is meant to be used through `Backend::detections()`, typically as one
+//! side of a `backend::CompareDecoder`. Payloads are reported as encoded,
+//! without conversion to UTF-8, if the library is at least version 0.23.
+//!
+//! The system `zbar` library is linked when the feature is enabled.
+
+use std::slice;
+use std::convert::TryFrom;
+use std::os::raw::{ c_uint, c_ulong, c_void };
+use geom::{ Image, QrCode, Vec2D };
+use info::Info;
+use backend::{ Backend, Detection };
+use util::{ usize_to_u32, u32_to_usize, int_to_usize };
+use error::{ Error, Overflow, Result };
+use zbar_sys::*;
+
+/// A detector and decoder of QR codes backed by the ZBar library.
+#[derive(Debug)]
+pub struct ZbarBackend {
+    /// The image scanner, configured to look for QR codes only.
+    scanner: *mut zbar_image_scanner_t,
+    /// The codes found in the last image.
+    detections: Vec<Detection>,
+}
+
+impl ZbarBackend {
+    /// Creates a scanner which looks for QR codes only.
+    pub fn new() -> Result<Self> {
+        let scanner = unsafe {
+            zbar_image_scanner_create()
+        };
+
+        if scanner.is_null() {
+            return Err(Error::AllocFailed);
+        }
+
+        unsafe {
+            zbar_image_scanner_set_config(scanner, ZBAR_NONE, ZBAR_CFG_ENABLE, 0);
+            zbar_image_scanner_set_config(scanner, ZBAR_QRCODE, ZBAR_CFG_ENABLE, 1);
+            // Versions before 0.23 reject this option, and convert payloads
+            // to UTF-8 according to the character set they guess.
+            zbar_image_scanner_set_config(scanner, ZBAR_QRCODE, ZBAR_CFG_BINARY, 1);
+        }
+
+        Ok(ZbarBackend { scanner, detections: Vec::new() })
+    }
+
+    /// Scans `image`, and returns the QR codes found in it.
+    fn scan_image(&mut self, image: &Image) -> Result<Vec<Detection>> {
+        let pixels: Vec<u8> = (0..image.height()).flat_map(|y| image.row(y)).cloned().collect();
+        let width: c_uint = usize_to_u32(image.width())?;
+        let height: c_uint = usize_to_u32(image.height())?;
+        let len = c_ulong::try_from(pixels.len()).map_err(
+            |_| Error::IntOverflow(Overflow::Value(pixels.len() as i128))
+        )?;
+        let raw = unsafe {
+            zbar_image_create()
+        };
+
+        if raw.is_null() {
+            return Err(Error::AllocFailed);
+        }
+
+        // ZBar only borrows the pixels, since there is no cleanup handler,
+        // and they outlive the image, which is destroyed below.
+        let count = unsafe {
+            zbar_image_set_format(raw, FOURCC_Y800);
+            zbar_image_set_size(raw, width, height);
+            zbar_image_set_data(raw, pixels.as_ptr() as *const c_void, len, None);
+            zbar_scan_image(self.scanner, raw)
+        };
+        let detections = if count < 0 {
+            Err(Error::InvalidImage)
+        } else {
+            Ok(unsafe { read_symbols(raw) })
+        };
+
+        unsafe {
+            zbar_image_destroy(raw);
+        }
+
+        detections
+    }
+}
+
+// The scanner is only ever used through `&mut self`, and ZBar keeps no
+// global state, so it may be moved to another thread.
+unsafe impl Send for ZbarBackend {}
+
+impl Drop for ZbarBackend {
+    fn drop(&mut self) {
+        unsafe {
+            zbar_image_scanner_destroy(self.scanner);
+        }
+    }
+}
+
+impl Backend for ZbarBackend {
+    fn name(&self) -> &'static str {
+        "zbar"
+    }
+
+    fn detect(&mut self, image: &Image) -> Result<usize> {
+        self.detections = self.scan_image(image)?;
+        Ok(self.detections.len())
+    }
+
+    /// Fails with `Error::Unsupported` for every code that was detected,
+    /// since ZBar doesn't report the modules.
+    fn extract(&self, index: usize) -> Result<QrCode> {
+        if index < self.detections.len() {
+            Err(Error::Unsupported)
+        } else {
+            Err(Error::InvalidArgument)
+        }
+    }
+
+    /// Always fails with `Error::Unsupported`, since ZBar only decodes the
+    /// codes it detects itself.
+    fn decode(&self, _code: &QrCode) -> Result<Info> {
+        Err(Error::Unsupported)
+    }
+
+    fn detections(&mut self, image: &Image) -> Result<Vec<Detection>> {
+        self.detect(image)?;
+        Ok(self.detections.clone())
+    }
+}
+
+/// Collects the QR codes among the symbols found in `image`.
+unsafe fn read_symbols(image: *const zbar_image_t) -> Vec<Detection> {
+    let mut detections = Vec::new();
+    let mut symbol = zbar_image_first_symbol(image);
+
+    while !symbol.is_null() {
+        if zbar_symbol_get_type(symbol) == ZBAR_QRCODE {
+            detections.push(read_symbol(symbol));
+        }
+
+        symbol = zbar_symbol_next(symbol);
+    }
+
+    detections
+}
+
+/// Converts a QR code found by ZBar into a `Detection`.
+unsafe fn read_symbol(symbol: *const zbar_symbol_t) -> Detection {
+    let data = zbar_symbol_get_data(symbol);
+    let len = u32_to_usize(zbar_symbol_get_data_length(symbol));
+    let payload = match len {
+        Ok(len) if !data.is_null() => Ok(slice::from_raw_parts(data as *const u8, len).to_vec()),
+        Ok(_) => Ok(Vec::new()),
+        Err(error) => Err(error),
+    };
+    let mut corners = [Vec2D::default(); 4];
+
+    // ZBar lists the corners counter-clockwise from the top left one.
+    // Extrapolated corners may lie outside the image, so they are clamped.
+    if zbar_symbol_get_loc_size(symbol) == 4 {
+        for (corner, &index) in corners.iter_mut().zip(&[0, 3, 2, 1]) {
+            *corner = Vec2D {
+                x: int_to_usize(zbar_symbol_get_loc_x(symbol, index).max(0)).unwrap_or(0),
+                y: int_to_usize(zbar_symbol_get_loc_y(symbol, index).max(0)).unwrap_or(0),
+            };
+        }
+    }
+
+    Detection { corners, payload }
+}
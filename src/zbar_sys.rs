@@ -0,0 +1,121 @@
+//! Raw FFI bindings for the parts of the `zbar` C API used by `ZbarBackend`.
+
+use std::os::raw::{ c_char, c_int, c_uint, c_ulong, c_void };
+
+/// Opaque scanner of images, which finds and decodes symbols.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug)]
+pub struct zbar_image_scanner_t {
+    /// Guard against instantiating the opaque type from Rust.
+    _dummy: [u8; 0],
+}
+
+/// Opaque image, along with the symbols found in it.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug)]
+pub struct zbar_image_t {
+    /// Guard against instantiating the opaque type from Rust.
+    _dummy: [u8; 0],
+}
+
+/// Opaque symbol found in an image.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug)]
+pub struct zbar_symbol_t {
+    /// Guard against instantiating the opaque type from Rust.
+    _dummy: [u8; 0],
+}
+
+/// The type of a symbol, `zbar_symbol_type_t`.
+#[allow(non_camel_case_types)]
+pub type zbar_symbol_type_t = c_int;
+
+/// A configuration option of a decoder, `zbar_config_t`.
+#[allow(non_camel_case_types)]
+pub type zbar_config_t = c_int;
+
+/// All symbol types, when configuring the scanner.
+pub const ZBAR_NONE: zbar_symbol_type_t = 0;
+/// QR codes.
+pub const ZBAR_QRCODE: zbar_symbol_type_t = 64;
+
+/// Enables or disables decoding a symbol type.
+pub const ZBAR_CFG_ENABLE: zbar_config_t = 0;
+/// Reports the payload as it was encoded, instead of converting it to
+/// UTF-8 according to a guessed character set. Since ZBar 0.23.
+pub const ZBAR_CFG_BINARY: zbar_config_t = 4;
+
+/// The FourCC of 8-bit grayscale images, `"Y800"`.
+pub const FOURCC_Y800: c_ulong = 0x3030_3859;
+
+extern {
+    /// Creates an image scanner. Returns NULL if memory couldn't be allocated.
+    pub fn zbar_image_scanner_create() -> *mut zbar_image_scanner_t;
+
+    /// Destroys an image scanner.
+    pub fn zbar_image_scanner_destroy(scanner: *mut zbar_image_scanner_t);
+
+    /// Sets a configuration option for a symbol type, or for all of them
+    /// with `ZBAR_NONE`. Returns 0 on success.
+    pub fn zbar_image_scanner_set_config(
+        scanner: *mut zbar_image_scanner_t,
+        symbology: zbar_symbol_type_t,
+        config: zbar_config_t,
+        value: c_int,
+    ) -> c_int;
+
+    /// Scans an image for symbols, and attaches the results to it.
+    /// Returns the number of symbols found, or -1 on error.
+    pub fn zbar_scan_image(scanner: *mut zbar_image_scanner_t, image: *mut zbar_image_t) -> c_int;
+
+    /// Creates an empty image. Returns NULL if memory couldn't be allocated.
+    pub fn zbar_image_create() -> *mut zbar_image_t;
+
+    /// Destroys an image, along with its results. The data is only freed
+    /// if a cleanup handler was set.
+    pub fn zbar_image_destroy(image: *mut zbar_image_t);
+
+    /// Sets the pixel format of an image, as a FourCC.
+    pub fn zbar_image_set_format(image: *mut zbar_image_t, format: c_ulong);
+
+    /// Sets the width and height of an image, in pixels.
+    pub fn zbar_image_set_size(image: *mut zbar_image_t, width: c_uint, height: c_uint);
+
+    /// Sets the pixels of an image, which are borrowed until the image is
+    /// destroyed, and then passed to `cleanup` unless it is NULL.
+    pub fn zbar_image_set_data(
+        image: *mut zbar_image_t,
+        data: *const c_void,
+        len: c_ulong,
+        cleanup: Option<unsafe extern "C" fn(image: *mut zbar_image_t)>,
+    );
+
+    /// Returns the first symbol found in an image, or NULL if there are none.
+    pub fn zbar_image_first_symbol(image: *const zbar_image_t) -> *const zbar_symbol_t;
+
+    /// Returns the symbol after `symbol`, or NULL if it was the last one.
+    pub fn zbar_symbol_next(symbol: *const zbar_symbol_t) -> *const zbar_symbol_t;
+
+    /// Returns the type of a symbol.
+    pub fn zbar_symbol_get_type(symbol: *const zbar_symbol_t) -> zbar_symbol_type_t;
+
+    /// Returns the payload of a symbol, which is valid as long as the image.
+    pub fn zbar_symbol_get_data(symbol: *const zbar_symbol_t) -> *const c_char;
+
+    /// Returns the length of the payload of a symbol, in bytes.
+    pub fn zbar_symbol_get_data_length(symbol: *const zbar_symbol_t) -> c_uint;
+
+    /// Returns the number of points in the location polygon of a symbol.
+    pub fn zbar_symbol_get_loc_size(symbol: *const zbar_symbol_t) -> c_uint;
+
+    /// Returns the column of a point of the location polygon, or -1 if
+    /// `index` is out of bounds.
+    pub fn zbar_symbol_get_loc_x(symbol: *const zbar_symbol_t, index: c_uint) -> c_int;
+
+    /// Returns the row of a point of the location polygon, or -1 if
+    /// `index` is out of bounds.
+    pub fn zbar_symbol_get_loc_y(symbol: *const zbar_symbol_t, index: c_uint) -> c_int;
+}
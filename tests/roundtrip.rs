@@ -0,0 +1,74 @@
+extern crate quirs;
+
+use quirs::{ Encoder, QrCode };
+use quirs::info::{ EccLevel, DataType };
+use quirs::backend::decode_in_rust;
+use quirs::testutil;
+
+/// The smallest version, the smallest one with version information, and
+/// the largest one, which has blocks of two different lengths.
+const VERSIONS: &[u8] = &[1, 7, 40];
+
+/// Every error correction level.
+const ECC_LEVELS: &[EccLevel] = &[EccLevel::L, EccLevel::M, EccLevel::Q, EccLevel::H];
+
+/// A payload for each mode the encoder chooses, along with that mode.
+/// Each of them fits into a version 1 code at level H.
+const PAYLOADS: &[(&[u8], DataType)] = &[
+    (b"0123456789", DataType::Numeric),
+    (b"QR-CODE $%", DataType::Alphanumeric),
+    (b"h\xc3\xa9llo", DataType::Byte),
+];
+
+/// Encodes `payload` with exactly the given parameters.
+fn encode(payload: &[u8], ecc_level: EccLevel, version: u8, mask: u8) -> QrCode {
+    let encoder = Encoder {
+        min_version: version,
+        max_version: version,
+        mask: Some(mask),
+        ..Encoder::new(ecc_level)
+    };
+
+    encoder
+        .encode(payload)
+        .and_then(|code| code.to_qr_code())
+        .expect("encoding failed")
+}
+
+#[test]
+fn encoded_codes_decode() {
+    for &version in VERSIONS {
+        for &ecc_level in ECC_LEVELS {
+            for mask in 0..8 {
+                for &(payload, data_type) in PAYLOADS {
+                    let code = encode(payload, ecc_level, version, mask);
+                    let context = format!("version {}, {:?}, mask {}, {:?}", version, ecc_level, mask, data_type);
+
+                    for result in [code.decode(), decode_in_rust(&code)] {
+                        let info = result.unwrap_or_else(|error| panic!("{}: {}", context, error));
+
+                        assert_eq!(info.payload(), payload, "{}", context);
+                        assert_eq!(info.version().number(), version, "{}", context);
+                        assert_eq!(info.ecc_level(), ecc_level, "{}", context);
+                        assert_eq!(info.mask_id(), mask, "{}", context);
+                        assert_eq!(info.data_type(), data_type, "{}", context);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rasterized_codes_decode() {
+    for &version in VERSIONS {
+        for &ecc_level in ECC_LEVELS {
+            for &(payload, data_type) in PAYLOADS {
+                assert!(
+                    testutil::roundtrip(payload, ecc_level, version),
+                    "version {}, {:?}, {:?}", version, ecc_level, data_type,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+extern crate quirs;
+
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use quirs::{ Decoder, Encoder, ImageBuf };
+use quirs::info::EccLevel;
+use quirs::render::{ to_image_buf, RenderOptions };
+
+/// The payload of the code decoded by the tests.
+const PAYLOAD: &str = "https://example.com/";
+
+/// Renders a code containing `PAYLOAD`.
+fn code_image() -> ImageBuf {
+    let code = Encoder::new(EccLevel::M).encode(PAYLOAD.as_bytes()).expect("encoding failed");
+
+    to_image_buf(&code, &RenderOptions::default()).expect("rendering failed")
+}
+
+/// Decodes the codes in `image` and returns their payloads.
+fn payloads(decoder: &mut Decoder, image: &ImageBuf) -> Vec<Vec<u8>> {
+    decoder
+        .decode_image(&image.as_image())
+        .expect("detection failed")
+        .map(|code| {
+            let info = code.expect("extraction failed").decode().expect("decoding failed");
+            info.payload().to_vec()
+        })
+        .collect()
+}
+
+#[test]
+fn decoder_can_be_moved_to_another_thread() {
+    let mut decoder = Decoder::new().expect("can't create decoder");
+    let image = code_image();
+    let handle = thread::spawn(move || payloads(&mut decoder, &image));
+
+    assert_eq!(handle.join().expect("thread panicked"), vec![PAYLOAD.as_bytes().to_vec()]);
+}
+
+#[test]
+fn decoder_can_be_shared_behind_a_mutex() {
+    let decoder = Arc::new(Mutex::new(Decoder::new().expect("can't create decoder")));
+    let image = Arc::new(code_image());
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let shared_decoder = Arc::clone(&decoder);
+            let shared_image = Arc::clone(&image);
+
+            thread::spawn(move || {
+                let mut guard = shared_decoder.lock().expect("mutex poisoned");
+                payloads(&mut guard, &shared_image)
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(handle.join().expect("thread panicked"), vec![PAYLOAD.as_bytes().to_vec()]);
+    }
+}
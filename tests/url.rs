@@ -0,0 +1,17 @@
+extern crate quirs;
+
+use quirs::content::Url;
+
+#[test]
+fn non_ascii_host_labels_are_not_punycode() {
+    let url = Url::parse("https://aé€.com/").expect("non-ASCII host was rejected");
+
+    assert_eq!(url.unicode_host(), "aé€.com");
+}
+
+#[test]
+fn punycode_labels_are_decoded() {
+    let url = Url::parse("https://XN--bcher-kva.example/").expect("Punycode host was rejected");
+
+    assert_eq!(url.unicode_host(), "bücher.example");
+}
@@ -0,0 +1,11 @@
+/* The subset of <math.h> used by quirc on wasm32-unknown-unknown,
+ * implemented by src/wasm_libc.rs. */
+#ifndef QUIRS_WASM_MATH_H
+#define QUIRS_WASM_MATH_H
+
+double sqrt(double x);
+double fabs(double x);
+double floor(double x);
+double rint(double x);
+
+#endif
@@ -0,0 +1,14 @@
+/* The subset of <stdlib.h> used by quirc on wasm32-unknown-unknown,
+ * implemented by src/wasm_libc.rs. */
+#ifndef QUIRS_WASM_STDLIB_H
+#define QUIRS_WASM_STDLIB_H
+
+#include <stddef.h>
+
+void *malloc(size_t size);
+void *calloc(size_t count, size_t size);
+void *realloc(void *ptr, size_t size);
+void free(void *ptr);
+int abs(int n);
+
+#endif
@@ -0,0 +1,13 @@
+/* The subset of <string.h> used by quirc on wasm32-unknown-unknown,
+ * provided by the compiler-builtins crate of the Rust standard library. */
+#ifndef QUIRS_WASM_STRING_H
+#define QUIRS_WASM_STRING_H
+
+#include <stddef.h>
+
+void *memcpy(void *dst, const void *src, size_t n);
+void *memmove(void *dst, const void *src, size_t n);
+void *memset(void *dst, int c, size_t n);
+int memcmp(const void *a, const void *b, size_t n);
+
+#endif